@@ -0,0 +1,350 @@
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use crate::audit::sink::{AuditSink, AuditSinkConfig};
+use crate::config::units::deserialize_optional_duration;
+use crate::executor::authorizer::principal;
+use crate::executor::budget::over_budget;
+use crate::executor::sampling::is_recorded;
+use crate::flow::matcher::matched_route;
+use crate::handler::api_key::client_identity;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::traceability::CORRELATION_ID_ATTACHMENT_KEY;
+use crate::handler::LambdaExchange;
+
+pub mod sink;
+
+const AUDIT_EVENTS_ATTACHMENT_KEY: &str = "audit_events";
+const DEFAULT_BATCH_SIZE: usize = 25;
+
+fn epoch_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+    Denied,
+}
+
+/// Something a handler did that needs to be accounted for, like letting a caller in or
+/// changing a resource.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    pub handler: String,
+    pub action: String,
+    pub outcome: AuditOutcome,
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, Value>,
+}
+
+impl AuditEvent {
+    pub fn new(handler: &str, action: &str, outcome: AuditOutcome) -> Self {
+        Self {
+            handler: handler.to_string(),
+            action: action.to_string(),
+            outcome,
+            timestamp: epoch_millis(),
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn detail(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.details.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// The audit events of one request, as handed to the sinks.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    /* the client of the request's API key */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub status_code: i64,
+    /* what went over the flow's latency budget */
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub over_budget: Vec<String>,
+    pub events: Vec<AuditEvent>,
+}
+
+/// Appends an event to the audit record of the exchange.
+pub fn record_audit_event(exchange: &mut LambdaExchange, event: AuditEvent) {
+    let attachments = exchange.attachments_mut();
+    match attachments.get_mut::<Vec<AuditEvent>>(AUDIT_EVENTS_ATTACHMENT_KEY) {
+        Some(events) => events.push(event),
+        None => attachments.add::<Vec<AuditEvent>>(AUDIT_EVENTS_ATTACHMENT_KEY, vec![event]),
+    }
+}
+
+pub fn audit_events(exchange: &LambdaExchange) -> &[AuditEvent] {
+    exchange
+        .attachments()
+        .get::<Vec<AuditEvent>>(AUDIT_EVENTS_ATTACHMENT_KEY)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Takes the events out of the exchange once its response is ready, `None` when there are
+/// none or the request isn't sampled.
+pub(crate) fn take_record(
+    exchange: &mut LambdaExchange,
+    response: &ApiGatewayProxyResponse,
+) -> Option<AuditRecord> {
+    if audit_events(exchange).is_empty() || !is_recorded(exchange, response.status_code >= 400) {
+        return None;
+    }
+    let correlation_id = exchange
+        .attachments()
+        .well_known()
+        .get::<String>(CORRELATION_ID_ATTACHMENT_KEY)
+        .cloned();
+    let request_id = exchange.metadata().ok().map(|context| context.request_id.clone());
+    let route = matched_route(exchange).map(str::to_string);
+    let principal = principal(exchange).map(str::to_string);
+    let client_id = client_identity(exchange).map(|identity| identity.client_id.clone());
+    let over_budget = over_budget(exchange).to_vec();
+    let events = exchange
+        .attachments_mut()
+        .get_mut::<Vec<AuditEvent>>(AUDIT_EVENTS_ATTACHMENT_KEY)
+        .map(std::mem::take)
+        .unwrap_or_default();
+    Some(AuditRecord {
+        timestamp: epoch_millis(),
+        request_id,
+        correlation_id,
+        route,
+        principal,
+        client_id,
+        status_code: response.status_code,
+        over_budget,
+        events,
+    })
+}
+
+/// Where audit records go and how they are batched:
+///
+/// ```json
+/// { "enabled": true, "sink": { "type": "firehose", "delivery_stream": "audit" },
+///   "batch_size": 50, "max_delay": "5s", "fallback": { "type": "stdout" } }
+/// ```
+///
+/// Records are written once `batch_size` of them are buffered, or with the first response after
+/// the oldest has waited `max_delay`; without `max_delay` they are written with every response.
+/// A batch the sink fails to take goes to the `fallback` sink, standard output by default.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    pub sink: AuditSinkConfig,
+    pub batch_size: usize,
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub max_delay: Option<Duration>,
+    pub fallback: AuditSinkConfig,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sink: AuditSinkConfig::Stdout,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_delay: None,
+            fallback: AuditSinkConfig::Stdout,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    records: Vec<AuditRecord>,
+    /* when the oldest buffered record came in */
+    since: Option<Instant>,
+}
+
+/// Buffers audit records and writes them to a sink in batches.
+pub struct AuditPipeline {
+    batch_size: usize,
+    max_delay: Option<Duration>,
+    sink: Box<dyn AuditSink>,
+    fallback: Box<dyn AuditSink>,
+    buffer: Mutex<Buffer>,
+}
+
+impl AuditPipeline {
+    pub fn new(config: &AuditConfig) -> Self {
+        Self::with_sinks(config, config.sink.build(), config.fallback.build())
+    }
+
+    pub fn with_sinks(
+        config: &AuditConfig,
+        sink: Box<dyn AuditSink>,
+        fallback: Box<dyn AuditSink>,
+    ) -> Self {
+        Self {
+            batch_size: config.batch_size.max(1),
+            max_delay: config.max_delay,
+            sink,
+            fallback,
+            buffer: Mutex::new(Buffer::default()),
+        }
+    }
+
+    pub async fn submit(&self, record: AuditRecord) {
+        let due = {
+            let mut buffer = self.buffer.lock().await;
+            buffer.records.push(record);
+            let since = *buffer.since.get_or_insert_with(Instant::now);
+            let is_due = match self.max_delay {
+                Some(max_delay) => {
+                    buffer.records.len() >= self.batch_size || since.elapsed() >= max_delay
+                }
+                None => true,
+            };
+            match is_due {
+                true => Some(std::mem::take(&mut *buffer).records),
+                false => None,
+            }
+        };
+        if let Some(records) = due {
+            self.write(records).await;
+        }
+    }
+
+    /// Writes every buffered record.
+    pub async fn flush(&self) {
+        let records = std::mem::take(&mut *self.buffer.lock().await).records;
+        if !records.is_empty() {
+            self.write(records).await;
+        }
+    }
+
+    async fn write(&self, records: Vec<AuditRecord>) {
+        for batch in records.chunks(self.batch_size) {
+            let Err(e) = self.sink.write(batch).await else {
+                continue;
+            };
+            tracing::warn!("Audit sink failed, using the fallback: {}", e);
+            if let Err(e) = self.fallback.write(batch).await {
+                tracing::error!("{} audit records lost: {}", batch.len(), e);
+            }
+        }
+    }
+}
+
+static PIPELINE: OnceLock<AuditPipeline> = OnceLock::new();
+
+/// Sets up the process-wide pipeline; audit events are dropped unless the config enables it.
+pub fn init(config: &AuditConfig) {
+    if config.enabled {
+        let _ = PIPELINE.set(AuditPipeline::new(config));
+    }
+}
+
+pub async fn submit(record: AuditRecord) {
+    if let Some(pipeline) = PIPELINE.get() {
+        pipeline.submit(record).await;
+    }
+}
+
+pub async fn shutdown() {
+    if let Some(pipeline) = PIPELINE.get() {
+        pipeline.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::sink::AuditSink;
+    use crate::audit::{AuditConfig, AuditEvent, AuditOutcome, AuditPipeline, AuditRecord};
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct CollectingSink {
+        batches: Arc<Mutex<Vec<usize>>>,
+        failing: bool,
+    }
+
+    #[async_trait]
+    impl AuditSink for CollectingSink {
+        async fn write(&self, records: &[AuditRecord]) -> Result<(), String> {
+            if self.failing {
+                return Err("unavailable".to_string());
+            }
+            self.batches.lock().unwrap().push(records.len());
+            Ok(())
+        }
+    }
+
+    fn record() -> AuditRecord {
+        AuditRecord {
+            timestamp: 0,
+            request_id: None,
+            correlation_id: Some("abc".to_string()),
+            route: Some("/pets".to_string()),
+            principal: None,
+            client_id: None,
+            status_code: 200,
+            over_budget: vec![],
+            events: vec![AuditEvent::new(
+                "JwtValidationHandler",
+                "authenticate",
+                AuditOutcome::Success,
+            )],
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_audit_pipeline_batches() {
+        let config = AuditConfig {
+            enabled: true,
+            batch_size: 2,
+            max_delay: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let sink = CollectingSink::default();
+        let fallback = CollectingSink::default();
+        let pipeline =
+            AuditPipeline::with_sinks(&config, Box::new(sink.clone()), Box::new(fallback.clone()));
+        pipeline.submit(record()).await;
+        assert!(sink.batches.lock().unwrap().is_empty());
+        pipeline.submit(record()).await;
+        pipeline.submit(record()).await;
+        assert_eq!(*sink.batches.lock().unwrap(), vec![2]);
+        pipeline.flush().await;
+        assert_eq!(*sink.batches.lock().unwrap(), vec![2, 1]);
+        assert!(fallback.batches.lock().unwrap().is_empty());
+
+        let failing = CollectingSink {
+            failing: true,
+            ..Default::default()
+        };
+        let pipeline = AuditPipeline::with_sinks(
+            &AuditConfig::default(),
+            Box::new(failing),
+            Box::new(fallback.clone()),
+        );
+        pipeline.submit(record()).await;
+        assert_eq!(*fallback.batches.lock().unwrap(), vec![1]);
+    }
+}
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_cloudwatchlogs::types::InputLogEvent;
+use aws_sdk_cloudwatchlogs::Client as CloudWatchLogsClient;
+use aws_sdk_firehose::primitives::Blob;
+use aws_sdk_firehose::types::Record;
+use aws_sdk_firehose::Client as FirehoseClient;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client as S3Client;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+use crate::audit::AuditRecord;
+
+/// Takes batches of audit records somewhere durable.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn write(&self, records: &[AuditRecord]) -> Result<(), String>;
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditSinkConfig {
+    /* one JSON line per record, picked up by the function's log group */
+    #[default]
+    Stdout,
+    CloudWatch {
+        log_group: String,
+        log_stream: String,
+    },
+    Firehose {
+        delivery_stream: String,
+    },
+    /* one newline-delimited JSON object per batch, under `prefix` */
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+    },
+}
+
+impl AuditSinkConfig {
+    pub fn build(&self) -> Box<dyn AuditSink> {
+        match self.clone() {
+            AuditSinkConfig::Stdout => Box::new(StdoutSink),
+            AuditSinkConfig::CloudWatch {
+                log_group,
+                log_stream,
+            } => Box::new(CloudWatchSink {
+                log_group,
+                log_stream,
+                client: OnceCell::new(),
+            }),
+            AuditSinkConfig::Firehose { delivery_stream } => Box::new(FirehoseSink {
+                delivery_stream,
+                client: OnceCell::new(),
+            }),
+            AuditSinkConfig::S3 { bucket, prefix } => Box::new(S3Sink {
+                bucket,
+                prefix,
+                client: OnceCell::new(),
+            }),
+        }
+    }
+}
+
+fn to_json(record: &AuditRecord) -> Result<String, String> {
+    serde_json::to_string(record).map_err(|e| e.to_string())
+}
+
+async fn sdk_config() -> aws_config::SdkConfig {
+    aws_config::load_defaults(BehaviorVersion::latest()).await
+}
+
+pub struct StdoutSink;
+
+#[async_trait]
+impl AuditSink for StdoutSink {
+    async fn write(&self, records: &[AuditRecord]) -> Result<(), String> {
+        for record in records {
+            println!("{}", to_json(record)?);
+        }
+        Ok(())
+    }
+}
+
+pub struct CloudWatchSink {
+    log_group: String,
+    log_stream: String,
+    /* created with the log stream on first use */
+    client: OnceCell<CloudWatchLogsClient>,
+}
+
+impl CloudWatchSink {
+    async fn client(&self) -> &CloudWatchLogsClient {
+        self.client
+            .get_or_init(|| async {
+                let client = CloudWatchLogsClient::new(&sdk_config().await);
+                /* fails when the stream exists, which is fine */
+                let _ = client
+                    .create_log_stream()
+                    .log_group_name(&self.log_group)
+                    .log_stream_name(&self.log_stream)
+                    .send()
+                    .await;
+                client
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl AuditSink for CloudWatchSink {
+    async fn write(&self, records: &[AuditRecord]) -> Result<(), String> {
+        let events = records
+            .iter()
+            .map(|record| {
+                InputLogEvent::builder()
+                    .timestamp(record.timestamp as i64)
+                    .message(to_json(record)?)
+                    .build()
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.client()
+            .await
+            .put_log_events()
+            .log_group_name(&self.log_group)
+            .log_stream_name(&self.log_stream)
+            .set_log_events(Some(events))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+pub struct FirehoseSink {
+    delivery_stream: String,
+    client: OnceCell<FirehoseClient>,
+}
+
+#[async_trait]
+impl AuditSink for FirehoseSink {
+    async fn write(&self, records: &[AuditRecord]) -> Result<(), String> {
+        let batch = records
+            .iter()
+            .map(|record| {
+                Record::builder()
+                    .data(Blob::new(format!("{}\n", to_json(record)?)))
+                    .build()
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let client = self
+            .client
+            .get_or_init(|| async { FirehoseClient::new(&sdk_config().await) })
+            .await;
+        let response = client
+            .put_record_batch()
+            .delivery_stream_name(&self.delivery_stream)
+            .set_records(Some(batch))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.failed_put_count() {
+            0 => Ok(()),
+            failed => Err(format!("{} of {} records not delivered", failed, records.len())),
+        }
+    }
+}
+
+pub struct S3Sink {
+    bucket: String,
+    prefix: String,
+    client: OnceCell<S3Client>,
+}
+
+#[async_trait]
+impl AuditSink for S3Sink {
+    async fn write(&self, records: &[AuditRecord]) -> Result<(), String> {
+        let mut body = String::new();
+        for record in records {
+            body.push_str(&to_json(record)?);
+            body.push('\n');
+        }
+        let first = records.first().map_or(0, |record| record.timestamp);
+        let key = format!("{}{}-{}.jsonl", self.prefix, first, uuid::Uuid::new_v4().simple());
+        let client = self
+            .client
+            .get_or_init(|| async { S3Client::new(&sdk_config().await) })
+            .await;
+        client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type("application/x-ndjson")
+            .body(ByteStream::from(body.into_bytes()))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use crate::config::load_error;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+
+const ENV_OVERRIDE_PREFIX: &str = "IDEM_";
+const ENV_NESTED_SEPARATOR: &str = "__";
+
+/// Wraps another provider and applies `IDEM_<HANDLER>_<FIELD>` environment variable overrides.
+///
+/// Field names are matched ignoring case and underscores, so `IDEM_JWT_JWKS_URL` overrides
+/// `jwks_url` (or `jwksUrl`) for the `jwt` handler. `__` steps into nested objects. Overrides are
+/// parsed as the type of the value they replace; new fields are parsed as JSON and fall back to
+/// a string.
+pub struct EnvOverrideProvider<P> {
+    pub handler_name: String,
+    pub inner: P,
+}
+
+impl<P> EnvOverrideProvider<P>
+where
+    P: ConfigProvider<Value>,
+{
+    pub fn new(handler_name: impl Into<String>, inner: P) -> Self {
+        Self {
+            handler_name: handler_name.into(),
+            inner,
+        }
+    }
+
+    fn env_prefix(&self) -> String {
+        format!(
+            "{}{}_",
+            ENV_OVERRIDE_PREFIX,
+            self.handler_name.to_uppercase().replace('-', "_")
+        )
+    }
+
+    fn normalize_key(key: &str) -> String {
+        key.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+
+    fn parse_override(existing: Option<&Value>, raw: &str) -> Result<Value, ()> {
+        match existing {
+            Some(Value::String(_)) => Ok(Value::String(raw.to_string())),
+            Some(Value::Bool(_)) => raw.trim().parse::<bool>().map(Value::Bool).map_err(|_| ()),
+            Some(Value::Number(_)) => {
+                let raw = raw.trim();
+                if let Ok(int) = raw.parse::<i64>() {
+                    return Ok(Value::Number(int.into()));
+                }
+                match raw.parse::<f64>().ok().and_then(Number::from_f64) {
+                    Some(number) => Ok(Value::Number(number)),
+                    None => Err(()),
+                }
+            }
+            Some(Value::Array(_)) | Some(Value::Object(_)) => {
+                serde_json::from_str(raw).map_err(|_| ())
+            }
+            Some(Value::Null) | None => {
+                Ok(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())))
+            }
+        }
+    }
+
+    /* Returns the JSON pointer of every field that was overridden. */
+    pub(crate) fn apply_overrides(
+        &self,
+        value: &mut Value,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<Vec<String>, ConfigProviderError> {
+        let prefix = self.env_prefix();
+        let mut overridden = Vec::new();
+        for (name, raw) in vars {
+            let Some(path) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+            let mut segments: Vec<&str> = path
+                .split(ENV_NESTED_SEPARATOR)
+                .filter(|s| !s.is_empty())
+                .collect();
+            let Some(field) = segments.pop() else {
+                continue;
+            };
+            if !value.is_object() {
+                *value = Value::Object(Map::new());
+            }
+            let mut current = value.as_object_mut().unwrap();
+            let mut pointer = String::new();
+            for segment in segments {
+                let key = Self::find_key(current, segment);
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                let entry = current
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if !entry.is_object() {
+                    *entry = Value::Object(Map::new());
+                }
+                current = entry.as_object_mut().unwrap();
+            }
+            let key = Self::find_key(current, field);
+            let parsed = Self::parse_override(current.get(&key), &raw)
+                .map_err(|_| load_error(format!("Could not parse environment override {}", name)))?;
+            pointer.push('/');
+            pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+            current.insert(key, parsed);
+            overridden.push(pointer);
+        }
+        Ok(overridden)
+    }
+
+    /* Use the existing key if one matches, otherwise the snake_case form of the env name. */
+    fn find_key(map: &Map<String, Value>, segment: &str) -> String {
+        let normalized = Self::normalize_key(segment);
+        if let Some(key) = map.keys().find(|key| Self::normalize_key(key) == normalized) {
+            return key.clone();
+        }
+        segment.to_lowercase()
+    }
+}
+
+impl<C, P> ConfigProvider<C> for EnvOverrideProvider<P>
+where
+    C: Default + DeserializeOwned,
+    P: ConfigProvider<Value>,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let mut value = self.inner.load()?;
+        self.apply_overrides(&mut value, std::env::vars())?;
+        serde_json::from_value(value).map_err(|e| {
+            load_error(format!(
+                "Could not map config for {} after environment overrides: {}",
+                self.handler_name, e
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::env::EnvOverrideProvider;
+    use idemio::config::DefaultConfigProvider;
+    use serde_json::json;
+
+    #[test]
+    fn test_env_overrides_are_typed() {
+        let provider = EnvOverrideProvider::new("jwt", DefaultConfigProvider);
+        let mut config = json!({
+            "enabled": true,
+            "jwksUrl": "https://old.example.com",
+            "cacheTtl": 300,
+            "scopes": ["read"],
+            "keys": {"defaultKid": "a"}
+        });
+        let vars = vec![
+            ("IDEM_JWT_ENABLED".to_string(), "false".to_string()),
+            ("IDEM_JWT_JWKS_URL".to_string(), "https://new.example.com".to_string()),
+            ("IDEM_JWT_CACHE_TTL".to_string(), "60".to_string()),
+            ("IDEM_JWT_SCOPES".to_string(), "[\"read\",\"write\"]".to_string()),
+            ("IDEM_JWT_KEYS__DEFAULT_KID".to_string(), "b".to_string()),
+            ("IDEM_JWT_NEW_FIELD".to_string(), "42".to_string()),
+            ("IDEM_CORS_ENABLED".to_string(), "false".to_string()),
+        ];
+        let overridden = provider.apply_overrides(&mut config, vars.into_iter()).unwrap();
+        assert!(overridden.contains(&"/keys/defaultKid".to_string()));
+        assert_eq!(
+            config,
+            json!({
+                "enabled": false,
+                "jwksUrl": "https://new.example.com",
+                "cacheTtl": 60,
+                "scopes": ["read", "write"],
+                "keys": {"defaultKid": "b"},
+                "new_field": 42
+            })
+        );
+
+        let bad_vars = vec![("IDEM_JWT_CACHE_TTL".to_string(), "soon".to_string())];
+        assert!(provider.apply_overrides(&mut config, bad_vars.into_iter()).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::load_error;
+use crate::metrics::{self, names, Unit};
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+fn file_cache() -> &'static Mutex<HashMap<PathBuf, Arc<Value>>> {
+    static FILE_CONFIG_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Value>>>> = OnceLock::new();
+    FILE_CONFIG_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Same as idemio's `FileConfigProvider`, but each file is read and parsed once per container.
+/// Every later load of the same path is served from a process-wide cache, until
+/// [`CachedFileConfigProvider::clear_cache`] drops it.
+pub struct CachedFileConfigProvider {
+    pub base_path: String,
+    pub config_name: String,
+}
+
+impl CachedFileConfigProvider {
+    pub fn new(base_path: impl Into<String>, config_name: impl Into<String>) -> Self {
+        Self {
+            base_path: base_path.into(),
+            config_name: config_name.into(),
+        }
+    }
+
+    pub(crate) fn load_value(&self) -> Result<Arc<Value>, ConfigProviderError> {
+        let config_path = Path::new(&self.base_path).join(&self.config_name);
+        let mut cache = file_cache().lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = cache.get(&config_path) {
+            metrics::counter(names::CONFIG_CACHE_HITS, 1, &[]);
+            return Ok(value.clone());
+        }
+        metrics::counter(names::CONFIG_CACHE_MISSES, 1, &[]);
+        let file = File::open(&config_path).map_err(|e| {
+            load_error(format!("Could not open config file {}: {}", config_path.display(), e))
+        })?;
+        let value: Value = serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            load_error(format!("Could not parse config file {}: {}", config_path.display(), e))
+        })?;
+        let value = Arc::new(value);
+        cache.insert(config_path, value.clone());
+        metrics::gauge(names::CONFIG_CACHE_SIZE, cache.len() as f64, Unit::Count, &[]);
+        Ok(value)
+    }
+
+    /// Drops every cached file, so the next load goes back to disk.
+    pub fn clear_cache() {
+        file_cache()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        metrics::gauge(names::CONFIG_CACHE_SIZE, 0.0, Unit::Count, &[]);
+    }
+}
+
+impl<C> ConfigProvider<C> for CachedFileConfigProvider
+where
+    C: Default + DeserializeOwned,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let value = self.load_value()?;
+        C::deserialize(value.as_ref()).map_err(|e| {
+            load_error(format!("Could not map config file {}: {}", self.config_name, e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::file::CachedFileConfigProvider;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_file_is_parsed_once() {
+        let base_path = std::env::temp_dir().join(format!("idem-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&base_path).unwrap();
+        std::fs::write(base_path.join("cors.json"), r#"{"enabled": true}"#).unwrap();
+
+        let provider = CachedFileConfigProvider::new(base_path.to_string_lossy(), "cors.json");
+        let first = provider.load_value().unwrap();
+        std::fs::remove_file(base_path.join("cors.json")).unwrap();
+        let second = provider.load_value().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second["enabled"], true);
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
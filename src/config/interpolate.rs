@@ -0,0 +1,236 @@
+use crate::config::secrets::fetch_secrets;
+use crate::config::ssm::fetch_ssm_parameters;
+use crate::config::{block_on, load_error};
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+const PLACEHOLDER_START: &str = "${";
+const PLACEHOLDER_END: char = '}';
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Placeholder {
+    Env(String),
+    Ssm(String),
+    Secret(String),
+}
+
+impl Placeholder {
+    fn parse(expression: &str) -> Result<Self, ConfigProviderError> {
+        match expression.split_once(':') {
+            Some(("env", name)) => Ok(Placeholder::Env(name.to_string())),
+            Some(("ssm", path)) => Ok(Placeholder::Ssm(path.to_string())),
+            Some(("secret", name)) => Ok(Placeholder::Secret(name.to_string())),
+            _ => Err(load_error(format!(
+                "Unknown config placeholder ${{{}}}",
+                expression
+            ))),
+        }
+    }
+}
+
+enum TemplatePart<'a> {
+    Literal(&'a str),
+    Placeholder(Placeholder),
+}
+
+/// Wraps another provider and resolves `${env:VAR}`, `${ssm:/path}` and `${secret:name}`
+/// placeholders inside string values. A string may contain several placeholders mixed with
+/// plain text; the result is always a string.
+pub struct InterpolatingProvider<P> {
+    pub inner: P,
+}
+
+impl<P> InterpolatingProvider<P>
+where
+    P: ConfigProvider<Value>,
+{
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    /* Splits a string into literal text and placeholders, in order. */
+    fn parse_template(template: &str) -> Result<Vec<TemplatePart<'_>>, ConfigProviderError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(&rest[..start]));
+            }
+            let after_start = &rest[start + PLACEHOLDER_START.len()..];
+            let Some(end) = after_start.find(PLACEHOLDER_END) else {
+                return Err(load_error(format!("Unterminated config placeholder in '{}'", template)));
+            };
+            parts.push(TemplatePart::Placeholder(Placeholder::parse(&after_start[..end])?));
+            rest = &after_start[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest));
+        }
+        Ok(parts)
+    }
+
+    fn collect_placeholders(
+        value: &Value,
+        placeholders: &mut BTreeSet<Placeholder>,
+    ) -> Result<(), ConfigProviderError> {
+        match value {
+            Value::String(template) => {
+                for part in Self::parse_template(template)? {
+                    if let TemplatePart::Placeholder(placeholder) = part {
+                        placeholders.insert(placeholder);
+                    }
+                }
+            }
+            Value::Object(map) => {
+                for child in map.values() {
+                    Self::collect_placeholders(child, placeholders)?;
+                }
+            }
+            Value::Array(items) => {
+                for child in items {
+                    Self::collect_placeholders(child, placeholders)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve_placeholders(
+        placeholders: BTreeSet<Placeholder>,
+    ) -> Result<HashMap<Placeholder, String>, ConfigProviderError> {
+        let mut resolved = HashMap::new();
+        let mut ssm_names = BTreeSet::new();
+        let mut secret_ids = BTreeSet::new();
+        for placeholder in placeholders {
+            match &placeholder {
+                Placeholder::Env(name) => {
+                    let value = std::env::var(name).map_err(|_| {
+                        load_error(format!("Environment variable {} is not set", name))
+                    })?;
+                    resolved.insert(placeholder, value);
+                }
+                Placeholder::Ssm(name) => {
+                    ssm_names.insert(name.clone());
+                }
+                Placeholder::Secret(name) => {
+                    secret_ids.insert(name.clone());
+                }
+            }
+        }
+        if !ssm_names.is_empty() {
+            for (name, value) in block_on(fetch_ssm_parameters(ssm_names))?? {
+                resolved.insert(Placeholder::Ssm(name), value);
+            }
+        }
+        if !secret_ids.is_empty() {
+            for (name, value) in block_on(fetch_secrets(secret_ids))?? {
+                resolved.insert(Placeholder::Secret(name), value);
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn interpolate(
+        value: &mut Value,
+        resolved: &HashMap<Placeholder, String>,
+    ) -> Result<(), ConfigProviderError> {
+        match value {
+            Value::String(template) => {
+                if !template.contains(PLACEHOLDER_START) {
+                    return Ok(());
+                }
+                let mut output = String::with_capacity(template.len());
+                for part in Self::parse_template(template)? {
+                    match part {
+                        TemplatePart::Literal(literal) => output.push_str(literal),
+                        TemplatePart::Placeholder(placeholder) => match resolved.get(&placeholder) {
+                            Some(resolved_value) => output.push_str(resolved_value),
+                            None => {
+                                return Err(load_error(format!(
+                                    "Config placeholder {:?} was not resolved",
+                                    placeholder
+                                )));
+                            }
+                        },
+                    }
+                }
+                *template = output;
+            }
+            Value::Object(map) => {
+                for child in map.values_mut() {
+                    Self::interpolate(child, resolved)?;
+                }
+            }
+            Value::Array(items) => {
+                for child in items.iter_mut() {
+                    Self::interpolate(child, resolved)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl<C, P> ConfigProvider<C> for InterpolatingProvider<P>
+where
+    C: Default + DeserializeOwned,
+    P: ConfigProvider<Value>,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let mut value = self.inner.load()?;
+        let mut placeholders = BTreeSet::new();
+        Self::collect_placeholders(&value, &mut placeholders)?;
+        if !placeholders.is_empty() {
+            let resolved = Self::resolve_placeholders(placeholders)?;
+            Self::interpolate(&mut value, &resolved)?;
+        }
+        serde_json::from_value(value)
+            .map_err(|e| load_error(format!("Could not map interpolated config: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::interpolate::{InterpolatingProvider, Placeholder};
+    use idemio::config::DefaultConfigProvider;
+    use serde_json::json;
+    use std::collections::{BTreeSet, HashMap};
+
+    type Interpolator = InterpolatingProvider<DefaultConfigProvider>;
+
+    #[test]
+    fn test_placeholders_are_interpolated() {
+        let mut config = json!({
+            "jwksUrl": "https://${env:AUTH_HOST}/.well-known/jwks.json",
+            "functionName": "${ssm:/idem/prod/backend}",
+            "keys": [{"secret": "${secret:jwt-key}"}],
+            "timeout": 30
+        });
+        let mut placeholders = BTreeSet::new();
+        Interpolator::collect_placeholders(&config, &mut placeholders).unwrap();
+        assert_eq!(placeholders.len(), 3);
+
+        let resolved = HashMap::from([
+            (Placeholder::Env("AUTH_HOST".to_string()), "auth.example.com".to_string()),
+            (Placeholder::Ssm("/idem/prod/backend".to_string()), "backend-prod".to_string()),
+            (Placeholder::Secret("jwt-key".to_string()), "s3cr3t".to_string()),
+        ]);
+        Interpolator::interpolate(&mut config, &resolved).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "jwksUrl": "https://auth.example.com/.well-known/jwks.json",
+                "functionName": "backend-prod",
+                "keys": [{"secret": "s3cr3t"}],
+                "timeout": 30
+            })
+        );
+
+        assert!(Interpolator::parse_template("${vault:thing}").is_err());
+        assert!(Interpolator::parse_template("${env:UNTERMINATED").is_err());
+    }
+}
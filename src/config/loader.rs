@@ -0,0 +1,210 @@
+use crate::config::file::CachedFileConfigProvider;
+use crate::config::interpolate::InterpolatingProvider;
+use crate::config::load_error;
+use crate::config::profile::ProfileProvider;
+use crate::config::registry::ConfigRegistry;
+use crate::config::secrets::SecretsManagerResolver;
+use crate::config::ssm::SsmConfigProvider;
+use crate::config::stack::ConfigStack;
+use crate::config::units::{deserialize_optional_duration, serialize_optional_duration};
+use crate::ROOT_CONFIG_PATH;
+use idemio::config::{Config, ConfigProvider, ConfigProviderError, DefaultConfigProvider};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+pub const FILE_LAYER: &str = "file";
+pub const SSM_LAYER: &str = "ssm";
+
+/// Where a handler's config is loaded from, set by handler name under `config_sources` in
+/// `handlers.json`.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct ConfigSource {
+    /* under /opt/config, `<handler name>.json` when unset */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /* SSM parameters under this path, e.g. "/idem/prod/jwt/", override the file's fields */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssm_path: Option<String>,
+    /* how long SSM parameters are kept before they are read again, 5m when unset */
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_duration",
+        serialize_with = "serialize_optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ssm_ttl: Option<Duration>,
+}
+
+/* a config file, which contributes nothing when the handler doesn't have one; it's read once
+   and cached until the handler configs are reloaded */
+struct OptionalFile(CachedFileConfigProvider);
+
+impl ConfigProvider<Value> for OptionalFile {
+    fn load(&self) -> Result<Value, ConfigProviderError> {
+        if !Path::new(&self.0.base_path).join(&self.0.config_name).exists() {
+            return Ok(Value::Null);
+        }
+        Ok(self.0.load_value()?.as_ref().clone())
+    }
+}
+
+/* the layers of a handler's config, each overriding the ones before it */
+fn config_stack(handler_name: &str, source: &ConfigSource) -> ConfigStack {
+    let file = source.file.clone().unwrap_or_else(|| format!("{}.json", handler_name));
+    let file = CachedFileConfigProvider::new(ROOT_CONFIG_PATH, file);
+    let file = ProfileProvider::new(OptionalFile(file));
+    let mut stack = ConfigStack::new().layer(FILE_LAYER, file);
+    if let Some(ssm_path) = &source.ssm_path {
+        let mut ssm = SsmConfigProvider::new(ssm_path.clone());
+        if let Some(ttl) = source.ssm_ttl {
+            ssm = ssm.with_ttl(ttl);
+        }
+        stack = stack.layer(SSM_LAYER, ssm);
+    }
+    stack.env_overrides(handler_name)
+}
+
+/* the merged layers with their `${env:}`, `${ssm:}` and `${secret:}` placeholders and their
+   `{"secretsManager": ...}` references resolved */
+fn config_provider(
+    handler_name: &str,
+    source: &ConfigSource,
+) -> impl ConfigProvider<Value> + Send + Sync + 'static {
+    SecretsManagerResolver::new(InterpolatingProvider::new(config_stack(handler_name, source)))
+}
+
+/* the source each handler's config is registered with, by handler name */
+fn registered_sources() -> &'static Mutex<HashMap<String, ConfigSource>> {
+    static REGISTERED_SOURCES: OnceLock<Mutex<HashMap<String, ConfigSource>>> = OnceLock::new();
+    REGISTERED_SOURCES.get_or_init(Mutex::default)
+}
+
+/* a config none of the layers contributed to is its type's default */
+fn into_config<C>(handler_name: &str, value: Value) -> Result<Config<C>, ConfigProviderError>
+where
+    C: Default + DeserializeOwned,
+{
+    let mut config = Config::new(DefaultConfigProvider)
+        .map_err(|_| load_error(format!("Could not create config of {}", handler_name)))?;
+    if !value.is_null() {
+        *config.get_mut() = serde_json::from_value(value)
+            .map_err(|e| load_error(format!("Could not map config of {}: {}", handler_name, e)))?;
+    }
+    Ok(config)
+}
+
+/// Loads the config of the handler a flow lists as `handler_name` through its [`ConfigStack`]:
+/// its file, with the profile `IDEM_ENV` selects merged over it, then its SSM parameters, then
+/// `IDEM_<HANDLER>_<FIELD>` environment variables, like `IDEM_JWTVALIDATIONHANDLER_AUDIENCE`.
+/// Placeholders like `${env:AUDIENCE}` and Secrets Manager references like
+/// `{"secretsManager": "admin", "jsonKey": "token"}` are resolved in the result.
+///
+/// The config is registered in the global [`ConfigRegistry`] under the handler name, the first
+/// time it's loaded or when its source changes. Handlers built later get the registry's current
+/// version of it, so reloads, pins and rollbacks there reach them when the router is rebuilt.
+pub fn handler_config<C>(
+    handler_name: &str,
+    source: &ConfigSource,
+) -> Result<Config<C>, ConfigProviderError>
+where
+    C: Default + DeserializeOwned + Send + Sync + 'static,
+{
+    let registry = ConfigRegistry::global();
+    {
+        let mut sources = registered_sources().lock().unwrap_or_else(PoisonError::into_inner);
+        if sources.get(handler_name) != Some(source) {
+            registry.register::<C>(handler_name, config_provider(handler_name, source))?;
+            sources.insert(handler_name.to_string(), source.clone());
+        }
+    }
+    let value = registry.value(handler_name).unwrap_or_default();
+    into_config(handler_name, value)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::loader::{config_provider, config_stack, into_config, ConfigSource};
+    use crate::config::stack::ENV_LAYER;
+    use idemio::config::{Config, ConfigProvider};
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(default)]
+    struct TestConfig {
+        enabled: bool,
+        name: String,
+    }
+
+    impl Default for TestConfig {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                name: "default".to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_file_is_default() {
+        let merged = config_stack("MissingTestHandler", &ConfigSource::default())
+            .load_merged()
+            .unwrap();
+        assert!(merged.value.is_null());
+        let config: Config<TestConfig> = into_config("MissingTestHandler", merged.value).unwrap();
+        assert_eq!(*config.get(), TestConfig::default());
+
+        let value = json!({ "enabled": false, "name": "file" });
+        let config: Config<TestConfig> = into_config("TestHandler", value).unwrap();
+        assert_eq!(config.get().name, "file");
+        assert!(into_config::<TestConfig>("TestHandler", json!({ "enabled": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_config_source() {
+        let source: ConfigSource = serde_json::from_value(json!({
+            "file": "jwt_validator.json",
+            "ssm_path": "/idem/prod/jwt/",
+            "ssm_ttl": "30s"
+        }))
+        .unwrap();
+        assert_eq!(source.ssm_ttl, Some(Duration::from_secs(30)));
+        let serialized = serde_json::to_value(&source).unwrap();
+        assert_eq!(serde_json::from_value::<ConfigSource>(serialized).unwrap(), source);
+        let stack = config_stack("JwtValidationHandler", &ConfigSource::default());
+        assert!(stack.load_merged().unwrap().sources.is_empty());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        unsafe { std::env::set_var("IDEM_LOADERTESTHANDLER_NAME", "env") };
+        let merged = config_stack("LoaderTestHandler", &ConfigSource::default())
+            .load_merged()
+            .unwrap();
+        unsafe { std::env::remove_var("IDEM_LOADERTESTHANDLER_NAME") };
+        assert_eq!(merged.sources["/name"], ENV_LAYER);
+        let config: Config<TestConfig> = into_config("LoaderTestHandler", merged.value).unwrap();
+        assert_eq!(config.get().name, "env");
+        assert!(config.get().enabled);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        unsafe {
+            std::env::set_var("IDEM_PLACEHOLDERTESTHANDLER_NAME", "${env:IDEM_TEST_NAME}");
+            std::env::set_var("IDEM_TEST_NAME", "interpolated");
+        }
+        let value = config_provider("PlaceholderTestHandler", &ConfigSource::default()).load();
+        unsafe {
+            std::env::remove_var("IDEM_PLACEHOLDERTESTHANDLER_NAME");
+            std::env::remove_var("IDEM_TEST_NAME");
+        }
+        assert_eq!(value.unwrap(), json!({ "name": "interpolated" }));
+    }
+}
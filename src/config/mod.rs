@@ -0,0 +1,41 @@
+use idemio::config::ConfigProviderError;
+use std::future::Future;
+
+pub(crate) mod env;
+pub(crate) mod file;
+pub(crate) mod interpolate;
+pub(crate) mod loader;
+pub(crate) mod profile;
+pub(crate) mod registry;
+pub(crate) mod reload;
+pub(crate) mod secrets;
+pub(crate) mod ssm;
+pub(crate) mod stack;
+pub(crate) mod units;
+
+pub(crate) fn load_error(message: impl Into<String>) -> ConfigProviderError {
+    ConfigProviderError::Load {
+        message: message.into(),
+    }
+}
+
+/* Config providers are synchronous, but the AWS SDK is not. The future is driven on a
+   dedicated thread so this works both before the lambda runtime starts and from inside it. */
+pub(crate) fn block_on<F>(future: F) -> Result<F::Output, ConfigProviderError>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map(|runtime| runtime.block_on(future))
+            })
+            .join()
+    })
+    .map_err(|_| load_error("Config loader thread panicked"))?
+    .map_err(|e| load_error(format!("Could not start config runtime: {}", e)))
+}
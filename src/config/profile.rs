@@ -0,0 +1,124 @@
+use crate::config::load_error;
+use crate::config::stack::ConfigStack;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const PROFILE_ENV_VAR: &str = "IDEM_ENV";
+const PROFILES_KEY: &str = "profiles";
+
+/// Wraps another provider and applies the `profiles` section of its config.
+///
+/// ```json
+/// {
+///   "enabled": true,
+///   "audience": "dev-api",
+///   "profiles": {
+///     "prod": { "audience": "prod-api" }
+///   }
+/// }
+/// ```
+///
+/// The profile named by `IDEM_ENV` is deep merged over the base section and the `profiles`
+/// section itself is dropped. With no profile selected, or no matching one, the base is used.
+pub struct ProfileProvider<P> {
+    pub profile: Option<String>,
+    pub inner: P,
+}
+
+impl<P> ProfileProvider<P>
+where
+    P: ConfigProvider<Value>,
+{
+    pub fn new(inner: P) -> Self {
+        Self {
+            profile: std::env::var(PROFILE_ENV_VAR).ok().filter(|profile| !profile.is_empty()),
+            inner,
+        }
+    }
+
+    pub fn with_profile(profile: impl Into<String>, inner: P) -> Self {
+        Self {
+            profile: Some(profile.into()),
+            inner,
+        }
+    }
+
+    fn apply_profile(&self, mut value: Value) -> Value {
+        let profiles = match value.as_object_mut() {
+            Some(map) => map.remove(PROFILES_KEY),
+            None => return value,
+        };
+        let selected = match (profiles, &self.profile) {
+            (Some(Value::Object(mut profiles)), Some(profile)) => profiles.remove(profile),
+            _ => None,
+        };
+        if let Some(profile_value) = selected {
+            ConfigStack::merge(
+                &mut value,
+                profile_value,
+                PROFILES_KEY,
+                &mut String::new(),
+                &mut BTreeMap::new(),
+            );
+        }
+        value
+    }
+}
+
+impl<C, P> ConfigProvider<C> for ProfileProvider<P>
+where
+    C: Default + DeserializeOwned,
+    P: ConfigProvider<Value>,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let value = self.apply_profile(self.inner.load()?);
+        serde_json::from_value(value).map_err(|e| {
+            load_error(format!(
+                "Could not map config for profile {}: {}",
+                self.profile.as_deref().unwrap_or("base"),
+                e
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::profile::ProfileProvider;
+    use idemio::config::DefaultConfigProvider;
+    use serde_json::json;
+
+    #[test]
+    fn test_profile_overrides_base() {
+        let config = json!({
+            "enabled": true,
+            "audience": "dev-api",
+            "keys": {"kid": "dev", "algorithm": "RS256"},
+            "profiles": {
+                "prod": {"audience": "prod-api", "keys": {"kid": "prod"}}
+            }
+        });
+
+        let prod = ProfileProvider::with_profile("prod", DefaultConfigProvider);
+        assert_eq!(
+            prod.apply_profile(config.clone()),
+            json!({
+                "enabled": true,
+                "audience": "prod-api",
+                "keys": {"kid": "prod", "algorithm": "RS256"}
+            })
+        );
+
+        let stage = ProfileProvider::with_profile("stage", DefaultConfigProvider);
+        assert_eq!(
+            stage.apply_profile(config),
+            json!({
+                "enabled": true,
+                "audience": "dev-api",
+                "keys": {"kid": "dev", "algorithm": "RS256"}
+            })
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use crate::config::load_error;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+pub type ReloadListener = Box<dyn Fn(&str) + Send + Sync>;
+
+type TypedConfig = Arc<dyn Any + Send + Sync>;
+
+/// Identifies the content of a loaded config; equal values always have equal versions.
+pub fn config_version(value: &Value) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[derive(Clone)]
+struct Snapshot {
+    value: Value,
+    typed: TypedConfig,
+    version: String,
+}
+
+struct EntryState {
+    current: Snapshot,
+    previous: Option<Snapshot>,
+    pinned: Option<String>,
+}
+
+struct RegisteredConfig {
+    provider: Box<dyn ConfigProvider<Value> + Send + Sync>,
+    decode: fn(&Value) -> Result<TypedConfig, ConfigProviderError>,
+    state: RwLock<EntryState>,
+}
+
+impl RegisteredConfig {
+    fn snapshot(&self, value: Value) -> Result<Snapshot, ConfigProviderError> {
+        let typed = (self.decode)(&value)?;
+        let version = config_version(&value);
+        Ok(Snapshot {
+            value,
+            typed,
+            version,
+        })
+    }
+
+    fn decode_typed<T>(value: &Value) -> Result<TypedConfig, ConfigProviderError>
+    where
+        T: Default + DeserializeOwned + Send + Sync + 'static,
+    {
+        /* a config no layer contributed to is its type's default */
+        if value.is_null() {
+            return Ok(Arc::new(T::default()));
+        }
+        let typed: T = serde_json::from_value(value.clone())
+            .map_err(|e| load_error(format!("Could not map registered config: {}", e)))?;
+        Ok(Arc::new(typed))
+    }
+}
+
+/// Owns the config of every registered handler.
+///
+/// Configs are registered by name with the provider that loads them. The registry keeps both
+/// the raw value (for inspection) and the typed config, swaps both at once on reload, and
+/// notifies subscribers when a reload actually changed something. Each config keeps the
+/// snapshot it replaced, so a bad reload can be rolled back, and can be pinned to a version.
+#[derive(Default)]
+pub struct ConfigRegistry {
+    entries: RwLock<HashMap<String, Arc<RegisteredConfig>>>,
+    types: RwLock<HashMap<TypeId, String>>,
+    listeners: RwLock<Vec<(Option<String>, ReloadListener)>>,
+}
+
+impl ConfigRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global() -> &'static ConfigRegistry {
+        static GLOBAL_CONFIG_REGISTRY: OnceLock<ConfigRegistry> = OnceLock::new();
+        GLOBAL_CONFIG_REGISTRY.get_or_init(ConfigRegistry::new)
+    }
+
+    pub fn register<T>(
+        &self,
+        name: impl Into<String>,
+        provider: impl ConfigProvider<Value> + Send + Sync + 'static,
+    ) -> Result<(), ConfigProviderError>
+    where
+        T: Default + DeserializeOwned + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let value = provider.load()?;
+        let decode = RegisteredConfig::decode_typed::<T>;
+        let typed = decode(&value)?;
+        let version = config_version(&value);
+        let entry = RegisteredConfig {
+            provider: Box::new(provider),
+            decode,
+            state: RwLock::new(EntryState {
+                current: Snapshot {
+                    value,
+                    typed,
+                    version,
+                },
+                previous: None,
+                pinned: None,
+            }),
+        };
+        self.entries
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.clone(), Arc::new(entry));
+        self.types
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(TypeId::of::<T>(), name);
+        Ok(())
+    }
+
+    /// Returns the config most recently registered for type `T`.
+    pub fn get_typed<T>(&self) -> Option<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        let name = self
+            .types
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&TypeId::of::<T>())?
+            .clone();
+        self.get_named(&name)
+    }
+
+    pub fn get_named<T>(&self, name: &str) -> Option<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        let entry = self.entry(name)?;
+        let state = entry.state.read().unwrap_or_else(PoisonError::into_inner);
+        state.current.typed.clone().downcast::<T>().ok()
+    }
+
+    /// The raw value behind a registered config, before it was mapped to its type.
+    pub fn value(&self, name: &str) -> Option<Value> {
+        let entry = self.entry(name)?;
+        let state = entry.state.read().unwrap_or_else(PoisonError::into_inner);
+        Some(state.current.value.clone())
+    }
+
+    pub fn version(&self, name: &str) -> Option<String> {
+        let entry = self.entry(name)?;
+        let state = entry.state.read().unwrap_or_else(PoisonError::into_inner);
+        Some(state.current.version.clone())
+    }
+
+    pub fn versions(&self) -> BTreeMap<String, String> {
+        self.names()
+            .into_iter()
+            .filter_map(|name| self.version(&name).map(|version| (name, version)))
+            .collect()
+    }
+
+    /// A single version covering every registered config, e.g. for a response header.
+    pub fn combined_version(&self) -> String {
+        let versions = self.versions();
+        config_version(&serde_json::to_value(versions).unwrap_or(Value::Null))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Calls `listener` with the config name whenever `name` (or, with `None`, any config) reloads.
+    pub fn subscribe(&self, name: Option<&str>, listener: impl Fn(&str) + Send + Sync + 'static) {
+        self.listeners
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name.map(str::to_string), Box::new(listener)));
+    }
+
+    /// A provider that hands out the registry's current value for `name`, so handlers can keep
+    /// building their `Config<T>` the usual way.
+    pub fn provider(&self, name: impl Into<String>) -> RegistryConfigProvider<'_> {
+        RegistryConfigProvider {
+            registry: self,
+            name: name.into(),
+        }
+    }
+
+    /// Reloads one config. Returns whether the value changed; a config that fails to load or map
+    /// leaves the previous one in place, as does a new version while the config is pinned.
+    pub fn reload(&self, name: &str) -> Result<bool, ConfigProviderError> {
+        let entry = self.lookup(name)?;
+        let value = entry.provider.load()?;
+        {
+            let state = entry.state.read().unwrap_or_else(PoisonError::into_inner);
+            if state.current.value == value {
+                return Ok(false);
+            }
+            if let Some(pinned) = &state.pinned
+                && *pinned != config_version(&value)
+            {
+                tracing::info!("Config {} is pinned to {}, skipping reload", name, pinned);
+                return Ok(false);
+            }
+        }
+        let snapshot = entry.snapshot(value)?;
+        {
+            let mut state = entry.state.write().unwrap_or_else(PoisonError::into_inner);
+            let replaced = std::mem::replace(&mut state.current, snapshot);
+            state.previous = Some(replaced);
+        }
+        self.notify(name);
+        Ok(true)
+    }
+
+    /// Swaps a config back to the snapshot the last reload replaced.
+    pub fn rollback(&self, name: &str) -> Result<String, ConfigProviderError> {
+        let entry = self.lookup(name)?;
+        let version = {
+            let mut state = entry.state.write().unwrap_or_else(PoisonError::into_inner);
+            let Some(previous) = state.previous.take() else {
+                return Err(load_error(format!("Config {} has no previous version", name)));
+            };
+            let replaced = std::mem::replace(&mut state.current, previous);
+            state.previous = Some(replaced);
+            state.current.version.clone()
+        };
+        self.notify(name);
+        Ok(version)
+    }
+
+    /// Pins a config to `version`, which must be its current or previous version. Reloads are
+    /// ignored until [`ConfigRegistry::unpin`] unless they produce the pinned version.
+    pub fn pin(&self, name: &str, version: &str) -> Result<(), ConfigProviderError> {
+        let entry = self.lookup(name)?;
+        let rolled_back = {
+            let mut state = entry.state.write().unwrap_or_else(PoisonError::into_inner);
+            let rolled_back = if state.current.version == version {
+                false
+            } else if state.previous.as_ref().is_some_and(|previous| previous.version == version) {
+                let previous = state.previous.take().unwrap();
+                let replaced = std::mem::replace(&mut state.current, previous);
+                state.previous = Some(replaced);
+                true
+            } else {
+                return Err(load_error(format!("Config {} has no version {}", name, version)));
+            };
+            state.pinned = Some(version.to_string());
+            rolled_back
+        };
+        if rolled_back {
+            self.notify(name);
+        }
+        Ok(())
+    }
+
+    pub fn unpin(&self, name: &str) -> Result<(), ConfigProviderError> {
+        let entry = self.lookup(name)?;
+        entry.state.write().unwrap_or_else(PoisonError::into_inner).pinned = None;
+        Ok(())
+    }
+
+    /// Reloads every config, returning the ones that failed.
+    pub fn reload_all(&self) -> Vec<(String, ConfigProviderError)> {
+        self.names()
+            .into_iter()
+            .filter_map(|name| match self.reload(&name) {
+                Ok(_) => None,
+                Err(e) => Some((name, e)),
+            })
+            .collect()
+    }
+
+    fn entry(&self, name: &str) -> Option<Arc<RegisteredConfig>> {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<RegisteredConfig>, ConfigProviderError> {
+        self.entry(name)
+            .ok_or_else(|| load_error(format!("No config registered as {}", name)))
+    }
+
+    fn notify(&self, name: &str) {
+        let listeners = self.listeners.read().unwrap_or_else(PoisonError::into_inner);
+        for (filter, listener) in listeners.iter() {
+            if filter.as_deref().is_none_or(|filter| filter == name) {
+                listener(name);
+            }
+        }
+    }
+}
+
+pub struct RegistryConfigProvider<'a> {
+    registry: &'a ConfigRegistry,
+    name: String,
+}
+
+impl<C> ConfigProvider<C> for RegistryConfigProvider<'_>
+where
+    C: Default + DeserializeOwned,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let value = self
+            .registry
+            .value(&self.name)
+            .ok_or_else(|| load_error(format!("No config registered as {}", self.name)))?;
+        if value.is_null() {
+            return Ok(C::default());
+        }
+        serde_json::from_value(value)
+            .map_err(|e| load_error(format!("Could not map config {}: {}", self.name, e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::registry::ConfigRegistry;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Deserialize, Default, Debug, PartialEq)]
+    struct CounterConfig {
+        count: u32,
+    }
+
+    struct CountingProvider(Arc<AtomicU32>);
+
+    impl ConfigProvider<Value> for CountingProvider {
+        fn load(&self) -> Result<Value, ConfigProviderError> {
+            Ok(json!({"count": self.0.load(Ordering::SeqCst)}))
+        }
+    }
+
+    #[test]
+    fn test_registry_reload_notifies_subscribers() {
+        let registry = ConfigRegistry::new();
+        let counter = Arc::new(AtomicU32::new(1));
+        registry
+            .register::<CounterConfig>("counter", CountingProvider(counter.clone()))
+            .unwrap();
+        assert_eq!(registry.get_typed::<CounterConfig>().unwrap().count, 1);
+
+        let notified = Arc::new(AtomicU32::new(0));
+        let notified_clone = notified.clone();
+        registry.subscribe(Some("counter"), move |_| {
+            notified_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(!registry.reload("counter").unwrap());
+        counter.store(2, Ordering::SeqCst);
+        assert!(registry.reload("counter").unwrap());
+        assert_eq!(notified.load(Ordering::SeqCst), 1);
+        assert_eq!(registry.get_named::<CounterConfig>("counter").unwrap().count, 2);
+
+        let config: Config<CounterConfig> = Config::new(registry.provider("counter")).unwrap();
+        assert_eq!(config.get().count, 2);
+        assert!(registry.reload("missing").is_err());
+    }
+
+    #[test]
+    fn test_registry_rollback_and_pin() {
+        let registry = ConfigRegistry::new();
+        let counter = Arc::new(AtomicU32::new(1));
+        registry
+            .register::<CounterConfig>("counter", CountingProvider(counter.clone()))
+            .unwrap();
+        let first_version = registry.version("counter").unwrap();
+
+        counter.store(2, Ordering::SeqCst);
+        assert!(registry.reload("counter").unwrap());
+        assert_ne!(registry.version("counter").unwrap(), first_version);
+
+        assert_eq!(registry.rollback("counter").unwrap(), first_version);
+        assert_eq!(registry.get_typed::<CounterConfig>().unwrap().count, 1);
+
+        registry.pin("counter", &first_version).unwrap();
+        counter.store(3, Ordering::SeqCst);
+        assert!(!registry.reload("counter").unwrap());
+        assert_eq!(registry.get_typed::<CounterConfig>().unwrap().count, 1);
+
+        registry.unpin("counter").unwrap();
+        assert!(registry.reload("counter").unwrap());
+        assert_eq!(registry.get_typed::<CounterConfig>().unwrap().count, 3);
+        assert!(registry.pin("counter", "not-a-version").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::load_error;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Reports a cheap version marker for a config source, so a reload only happens when it changes.
+pub trait VersionSource: Send + Sync {
+    fn current_version(&self) -> Result<String, ConfigProviderError>;
+}
+
+/// Uses the modification time of a file (e.g. `/opt/config/handlers.json`) as its version.
+pub struct FileVersion {
+    pub path: PathBuf,
+}
+
+impl VersionSource for FileVersion {
+    fn current_version(&self) -> Result<String, ConfigProviderError> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| load_error(format!("Could not stat {}: {}", self.path.display(), e)))?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(since_epoch.as_nanos().to_string())
+    }
+}
+
+pub enum RefreshPolicy {
+    /// Load once and keep the config for the life of the container.
+    Never,
+    /// Reload from the provider whenever the cached config is older than the TTL.
+    Ttl(Duration),
+    /// Check the version source every `interval` and reload only when the version changed.
+    VersionCheck {
+        interval: Duration,
+        version: Box<dyn VersionSource>,
+    },
+}
+
+struct Snapshot<C> {
+    config: Arc<C>,
+    checked_at: Instant,
+    version: Option<String>,
+}
+
+/// A config that warm containers refresh in place. Readers get an `Arc` to the current
+/// snapshot; a reload swaps the whole snapshot at once, and a failed reload keeps the old one.
+pub struct ReloadableConfig<C> {
+    provider: Box<dyn ConfigProvider<C> + Send + Sync>,
+    policy: RefreshPolicy,
+    snapshot: RwLock<Snapshot<C>>,
+}
+
+impl<C> ReloadableConfig<C>
+where
+    C: Default + DeserializeOwned,
+{
+    pub fn new(
+        provider: impl ConfigProvider<C> + Send + Sync + 'static,
+        policy: RefreshPolicy,
+    ) -> Result<Self, ConfigProviderError> {
+        let version = match &policy {
+            RefreshPolicy::VersionCheck { version, .. } => Some(version.current_version()?),
+            _ => None,
+        };
+        let config = provider.load()?;
+        Ok(Self {
+            provider: Box::new(provider),
+            policy,
+            snapshot: RwLock::new(Snapshot {
+                config: Arc::new(config),
+                checked_at: Instant::now(),
+                version,
+            }),
+        })
+    }
+
+    pub fn get(&self) -> Arc<C> {
+        {
+            let snapshot = self.snapshot.read().unwrap_or_else(PoisonError::into_inner);
+            if !self.refresh_due(&snapshot) {
+                return snapshot.config.clone();
+            }
+        }
+        self.refresh()
+    }
+
+    fn refresh_due(&self, snapshot: &Snapshot<C>) -> bool {
+        match &self.policy {
+            RefreshPolicy::Never => false,
+            RefreshPolicy::Ttl(ttl) => snapshot.checked_at.elapsed() >= *ttl,
+            RefreshPolicy::VersionCheck { interval, .. } => {
+                snapshot.checked_at.elapsed() >= *interval
+            }
+        }
+    }
+
+    fn refresh(&self) -> Arc<C> {
+        let mut snapshot = self.snapshot.write().unwrap_or_else(PoisonError::into_inner);
+        // another caller may have refreshed while we waited for the lock.
+        if !self.refresh_due(&snapshot) {
+            return snapshot.config.clone();
+        }
+        snapshot.checked_at = Instant::now();
+        let new_version = match &self.policy {
+            RefreshPolicy::VersionCheck { version, .. } => match version.current_version() {
+                Ok(current) if snapshot.version.as_ref() == Some(&current) => {
+                    return snapshot.config.clone();
+                }
+                Ok(current) => Some(current),
+                Err(e) => {
+                    tracing::warn!("Config version check failed, keeping current config: {}", e);
+                    return snapshot.config.clone();
+                }
+            },
+            _ => None,
+        };
+        match self.provider.load() {
+            Ok(config) => {
+                snapshot.config = Arc::new(config);
+                if new_version.is_some() {
+                    snapshot.version = new_version;
+                }
+            }
+            Err(e) => tracing::warn!("Config reload failed, keeping current config: {}", e),
+        }
+        snapshot.config.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::load_error;
+    use crate::config::reload::{RefreshPolicy, ReloadableConfig};
+    use idemio::config::{ConfigProvider, ConfigProviderError};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct CountingProvider {
+        loads: Arc<AtomicU32>,
+        fail_after: u32,
+    }
+
+    impl ConfigProvider<u32> for CountingProvider {
+        fn load(&self) -> Result<u32, ConfigProviderError> {
+            let count = self.loads.fetch_add(1, Ordering::SeqCst) + 1;
+            if count > self.fail_after {
+                return Err(load_error("broken config"));
+            }
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_ttl_reload_keeps_last_good_config() {
+        let loads = Arc::new(AtomicU32::new(0));
+        let provider = CountingProvider {
+            loads: loads.clone(),
+            fail_after: 2,
+        };
+        let config = ReloadableConfig::new(provider, RefreshPolicy::Ttl(Duration::ZERO)).unwrap();
+        assert_eq!(*config.get(), 2);
+        assert_eq!(*config.get(), 2);
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+
+        let never_loads = Arc::new(AtomicU32::new(0));
+        let provider = CountingProvider {
+            loads: never_loads.clone(),
+            fail_after: u32::MAX,
+        };
+        let config = ReloadableConfig::new(provider, RefreshPolicy::Never).unwrap();
+        assert_eq!(*config.get(), 1);
+        assert_eq!(*config.get(), 1);
+        assert_eq!(never_loads.load(Ordering::SeqCst), 1);
+    }
+}
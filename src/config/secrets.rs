@@ -0,0 +1,185 @@
+use crate::config::{block_on, load_error};
+use aws_config::BehaviorVersion;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+const SECRET_REF_KEY: &str = "secretsManager";
+
+/// A config field that should be replaced by a Secrets Manager value at load time, e.g.
+/// `{"secretsManager": "arn:aws:secretsmanager:...", "jsonKey": "password"}`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SecretRef {
+    #[serde(rename = "secretsManager")]
+    pub secret_id: String,
+    #[serde(rename = "jsonKey", default)]
+    pub json_key: Option<String>,
+}
+
+impl SecretRef {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Object(map) if map.contains_key(SECRET_REF_KEY) => {
+                serde_json::from_value(value.clone()).ok()
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn resolve(&self, secret_string: &str) -> Result<Value, ConfigProviderError> {
+        let Some(json_key) = &self.json_key else {
+            return Ok(Value::String(secret_string.to_string()));
+        };
+        let secret: Value = serde_json::from_str(secret_string).map_err(|_| {
+            load_error(format!("Secret {} is not a JSON object", self.secret_id))
+        })?;
+        match secret.get(json_key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(load_error(format!(
+                "Secret {} has no key {}",
+                self.secret_id, json_key
+            ))),
+        }
+    }
+}
+
+/// Wraps another provider and resolves any [`SecretRef`] fields in its output via Secrets Manager.
+pub struct SecretsManagerResolver<P> {
+    pub inner: P,
+}
+
+impl<P> SecretsManagerResolver<P>
+where
+    P: ConfigProvider<Value>,
+{
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    fn collect_secret_ids(value: &Value, secret_ids: &mut BTreeSet<String>) {
+        if let Some(secret_ref) = SecretRef::from_value(value) {
+            secret_ids.insert(secret_ref.secret_id);
+            return;
+        }
+        match value {
+            Value::Object(map) => map
+                .values()
+                .for_each(|child| Self::collect_secret_ids(child, secret_ids)),
+            Value::Array(items) => items
+                .iter()
+                .for_each(|child| Self::collect_secret_ids(child, secret_ids)),
+            _ => {}
+        }
+    }
+
+    fn replace_secret_refs(
+        value: &mut Value,
+        secrets: &HashMap<String, String>,
+    ) -> Result<(), ConfigProviderError> {
+        if let Some(secret_ref) = SecretRef::from_value(value) {
+            let secret_string = secrets.get(&secret_ref.secret_id).ok_or_else(|| {
+                load_error(format!("Secret {} was not loaded", secret_ref.secret_id))
+            })?;
+            *value = secret_ref.resolve(secret_string)?;
+            return Ok(());
+        }
+        match value {
+            Value::Object(map) => {
+                for child in map.values_mut() {
+                    Self::replace_secret_refs(child, secrets)?;
+                }
+            }
+            Value::Array(items) => {
+                for child in items.iter_mut() {
+                    Self::replace_secret_refs(child, secrets)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub(crate) async fn fetch_secrets(
+    secret_ids: BTreeSet<String>,
+) -> Result<HashMap<String, String>, ConfigProviderError> {
+    let client =
+        SecretsManagerClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await);
+    let mut secrets = HashMap::new();
+    for secret_id in secret_ids {
+        let output = client
+            .get_secret_value()
+            .secret_id(&secret_id)
+            .send()
+            .await
+            .map_err(|e| load_error(format!("Could not read secret {}: {}", secret_id, e)))?;
+        let Some(secret_string) = output.secret_string() else {
+            return Err(load_error(format!("Secret {} has no string value", secret_id)));
+        };
+        secrets.insert(secret_id.clone(), secret_string.to_string());
+    }
+    Ok(secrets)
+}
+
+impl<C, P> ConfigProvider<C> for SecretsManagerResolver<P>
+where
+    C: Default + DeserializeOwned,
+    P: ConfigProvider<Value>,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let mut value = self.inner.load()?;
+        let mut secret_ids = BTreeSet::new();
+        Self::collect_secret_ids(&value, &mut secret_ids);
+        if !secret_ids.is_empty() {
+            let secrets = block_on(fetch_secrets(secret_ids))??;
+            Self::replace_secret_refs(&mut value, &secrets)?;
+        }
+        serde_json::from_value(value)
+            .map_err(|e| load_error(format!("Could not map resolved config: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::secrets::SecretsManagerResolver;
+    use idemio::config::DefaultConfigProvider;
+    use serde_json::json;
+    use std::collections::{BTreeSet, HashMap};
+
+    type Resolver = SecretsManagerResolver<DefaultConfigProvider>;
+
+    #[test]
+    fn test_secret_refs_are_replaced() {
+        let mut config = json!({
+            "audience": "my-api",
+            "sharedSecret": {"secretsManager": "arn:jwt"},
+            "basicAuth": {
+                "password": {"secretsManager": "arn:basic", "jsonKey": "password"}
+            }
+        });
+        let mut secret_ids = BTreeSet::new();
+        Resolver::collect_secret_ids(&config, &mut secret_ids);
+        assert_eq!(secret_ids.len(), 2);
+
+        let secrets = HashMap::from([
+            ("arn:jwt".to_string(), "s3cr3t".to_string()),
+            (
+                "arn:basic".to_string(),
+                "{\"username\":\"admin\",\"password\":\"hunter2\"}".to_string(),
+            ),
+        ]);
+        Resolver::replace_secret_refs(&mut config, &secrets).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "audience": "my-api",
+                "sharedSecret": "s3cr3t",
+                "basicAuth": {"password": "hunter2"}
+            })
+        );
+    }
+}
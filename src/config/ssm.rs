@@ -0,0 +1,163 @@
+use crate::config::{block_on, load_error};
+use aws_config::BehaviorVersion;
+use aws_sdk_ssm::Client as SsmClient;
+use idemio::config::{ConfigProvider, ConfigProviderError};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_SSM_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Loads handler config from SSM Parameter Store.
+///
+/// Every parameter under `path_prefix` becomes a field; the remainder of the parameter name
+/// after the prefix is split on `/` into nested objects. Values that parse as JSON keep their
+/// type, anything else is used as a string. SecureString parameters are decrypted.
+pub struct SsmConfigProvider {
+    path_prefix: String,
+    ttl: Duration,
+    cache: Mutex<Option<(Instant, Value)>>,
+}
+
+impl SsmConfigProvider {
+    pub fn new(path_prefix: impl Into<String>) -> Self {
+        Self {
+            path_prefix: path_prefix.into(),
+            ttl: DEFAULT_SSM_CACHE_TTL,
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub(crate) fn load_value(&self) -> Result<Value, ConfigProviderError> {
+        let mut cache = self
+            .cache
+            .lock()
+            .map_err(|_| load_error("SSM parameter cache is poisoned"))?;
+        if let Some((loaded_at, value)) = cache.as_ref()
+            && loaded_at.elapsed() < self.ttl
+        {
+            return Ok(value.clone());
+        }
+        let parameters = block_on(Self::fetch_parameters(&self.path_prefix))??;
+        let value = Self::parameters_to_value(&self.path_prefix, parameters);
+        *cache = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    async fn fetch_parameters(path_prefix: &str) -> Result<Vec<(String, String)>, ConfigProviderError> {
+        let client = SsmClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await);
+        let mut parameters = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let output = client
+                .get_parameters_by_path()
+                .path(path_prefix)
+                .recursive(true)
+                .with_decryption(true)
+                .set_next_token(next_token.take())
+                .send()
+                .await
+                .map_err(|e| load_error(format!("Could not read SSM parameters under {}: {}", path_prefix, e)))?;
+            for parameter in output.parameters() {
+                if let (Some(name), Some(value)) = (parameter.name(), parameter.value()) {
+                    parameters.push((name.to_string(), value.to_string()));
+                }
+            }
+            match output.next_token() {
+                Some(token) => next_token = Some(token.to_string()),
+                None => break,
+            }
+        }
+        Ok(parameters)
+    }
+
+    fn parameters_to_value(path_prefix: &str, parameters: Vec<(String, String)>) -> Value {
+        let mut root = Map::new();
+        for (name, raw_value) in parameters {
+            let relative = name.strip_prefix(path_prefix).unwrap_or(&name);
+            let mut segments: Vec<&str> = relative.split('/').filter(|s| !s.is_empty()).collect();
+            let Some(field) = segments.pop() else {
+                continue;
+            };
+            let mut current = &mut root;
+            for segment in segments {
+                let entry = current
+                    .entry(segment.to_string())
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if !entry.is_object() {
+                    *entry = Value::Object(Map::new());
+                }
+                current = entry.as_object_mut().unwrap();
+            }
+            let value = serde_json::from_str(&raw_value).unwrap_or(Value::String(raw_value));
+            current.insert(field.to_string(), value);
+        }
+        Value::Object(root)
+    }
+}
+
+pub(crate) async fn fetch_ssm_parameters(
+    names: BTreeSet<String>,
+) -> Result<HashMap<String, String>, ConfigProviderError> {
+    let client = SsmClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await);
+    let mut parameters = HashMap::new();
+    for name in names {
+        let output = client
+            .get_parameter()
+            .name(&name)
+            .with_decryption(true)
+            .send()
+            .await
+            .map_err(|e| load_error(format!("Could not read SSM parameter {}: {}", name, e)))?;
+        let Some(value) = output.parameter().and_then(|parameter| parameter.value()) else {
+            return Err(load_error(format!("SSM parameter {} has no value", name)));
+        };
+        parameters.insert(name.clone(), value.to_string());
+    }
+    Ok(parameters)
+}
+
+impl<C> ConfigProvider<C> for SsmConfigProvider
+where
+    C: Default + DeserializeOwned,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let value = self.load_value()?;
+        serde_json::from_value(value).map_err(|e| {
+            load_error(format!("Could not map SSM parameters under {}: {}", self.path_prefix, e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::ssm::SsmConfigProvider;
+    use serde_json::json;
+
+    #[test]
+    fn test_parameters_to_value() {
+        let parameters = vec![
+            ("/idem/prod/jwt/audience".to_string(), "my-api".to_string()),
+            ("/idem/prod/jwt/enabled".to_string(), "true".to_string()),
+            ("/idem/prod/jwt/keys/kid".to_string(), "abc".to_string()),
+            ("/idem/prod/jwt/scopes".to_string(), "[\"read\",\"write\"]".to_string()),
+        ];
+        let value = SsmConfigProvider::parameters_to_value("/idem/prod/jwt", parameters);
+        assert_eq!(
+            value,
+            json!({
+                "audience": "my-api",
+                "enabled": true,
+                "keys": {"kid": "abc"},
+                "scopes": ["read", "write"]
+            })
+        );
+    }
+}
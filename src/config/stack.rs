@@ -0,0 +1,213 @@
+use crate::config::env::EnvOverrideProvider;
+use crate::config::load_error;
+use idemio::config::{ConfigProvider, ConfigProviderError, DefaultConfigProvider};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_LAYER: &str = "default";
+pub const ENV_LAYER: &str = "env";
+
+/* Hands back a value that was computed up front, e.g. the serialized defaults of a config struct. */
+struct StaticValueProvider(Value);
+
+impl<C> ConfigProvider<C> for StaticValueProvider
+where
+    C: Default + DeserializeOwned,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        serde_json::from_value(self.0.clone())
+            .map_err(|e| load_error(format!("Could not map static config value: {}", e)))
+    }
+}
+
+/// The result of merging a [`ConfigStack`]: the effective value plus, for every leaf field
+/// (keyed by JSON pointer), the name of the layer it came from.
+#[derive(Debug, Clone, Default)]
+pub struct MergedConfig {
+    pub value: Value,
+    pub sources: BTreeMap<String, String>,
+}
+
+/// Merges config layers in the order they were added, later layers taking precedence.
+///
+/// The usual stack is defaults → packaged file → remote provider → environment overrides.
+/// Objects are merged key by key; arrays and scalars from a later layer replace the earlier
+/// value wholesale. A layer that loads as `null` contributes nothing.
+pub struct ConfigStack {
+    layers: Vec<(String, Box<dyn ConfigProvider<Value> + Send + Sync>)>,
+    env_handler_name: Option<String>,
+}
+
+impl Default for ConfigStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigStack {
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            env_handler_name: None,
+        }
+    }
+
+    pub fn defaults<C>(self) -> Self
+    where
+        C: Default + Serialize,
+    {
+        let value = serde_json::to_value(C::default()).unwrap_or(Value::Null);
+        self.layer(DEFAULT_LAYER, StaticValueProvider(value))
+    }
+
+    pub fn layer(
+        mut self,
+        name: impl Into<String>,
+        provider: impl ConfigProvider<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.layers.push((name.into(), Box::new(provider)));
+        self
+    }
+
+    /// Applies `IDEM_<HANDLER>_<FIELD>` overrides on top of every other layer.
+    pub fn env_overrides(mut self, handler_name: impl Into<String>) -> Self {
+        self.env_handler_name = Some(handler_name.into());
+        self
+    }
+
+    pub fn load_merged(&self) -> Result<MergedConfig, ConfigProviderError> {
+        let mut merged = MergedConfig::default();
+        for (name, provider) in &self.layers {
+            let layer_value = provider.load()?;
+            if layer_value.is_null() {
+                continue;
+            }
+            let mut pointer = String::new();
+            Self::merge(&mut merged.value, layer_value, name, &mut pointer, &mut merged.sources);
+        }
+        if let Some(handler_name) = &self.env_handler_name {
+            let env = EnvOverrideProvider::new(handler_name.clone(), DefaultConfigProvider);
+            for pointer in env.apply_overrides(&mut merged.value, std::env::vars())? {
+                Self::clear_sources(&pointer, &mut merged.sources);
+                merged.sources.insert(pointer, ENV_LAYER.to_string());
+            }
+        }
+        Ok(merged)
+    }
+
+    pub(crate) fn merge(
+        target: &mut Value,
+        overlay: Value,
+        layer: &str,
+        pointer: &mut String,
+        sources: &mut BTreeMap<String, String>,
+    ) {
+        match (target, overlay) {
+            (Value::Object(target_map), Value::Object(overlay_map)) => {
+                for (key, child) in overlay_map {
+                    let parent_len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                    match target_map.get_mut(&key) {
+                        Some(existing) => Self::merge(existing, child, layer, pointer, sources),
+                        None => {
+                            Self::record_sources(&child, layer, pointer, sources);
+                            target_map.insert(key, child);
+                        }
+                    }
+                    pointer.truncate(parent_len);
+                }
+            }
+            (target, overlay) => {
+                Self::clear_sources(pointer, sources);
+                Self::record_sources(&overlay, layer, pointer, sources);
+                *target = overlay;
+            }
+        }
+    }
+
+    fn clear_sources(pointer: &str, sources: &mut BTreeMap<String, String>) {
+        sources.retain(|field, _| {
+            field != pointer
+                && !(field.starts_with(pointer) && field[pointer.len()..].starts_with('/'))
+        });
+    }
+
+    fn record_sources(
+        value: &Value,
+        layer: &str,
+        pointer: &mut String,
+        sources: &mut BTreeMap<String, String>,
+    ) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, child) in map {
+                    let parent_len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                    Self::record_sources(child, layer, pointer, sources);
+                    pointer.truncate(parent_len);
+                }
+            }
+            _ => {
+                sources.insert(pointer.clone(), layer.to_string());
+            }
+        }
+    }
+}
+
+impl<C> ConfigProvider<C> for ConfigStack
+where
+    C: Default + DeserializeOwned,
+{
+    fn load(&self) -> Result<C, ConfigProviderError> {
+        let merged = self.load_merged()?;
+        serde_json::from_value(merged.value)
+            .map_err(|e| load_error(format!("Could not map merged config: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::stack::{ConfigStack, StaticValueProvider};
+    use serde_json::json;
+
+    #[test]
+    fn test_stack_deep_merge_and_sources() {
+        let stack = ConfigStack::new()
+            .layer(
+                "file",
+                StaticValueProvider(json!({
+                    "enabled": true,
+                    "audience": "dev-api",
+                    "scopes": ["read", "write"],
+                    "keys": {"kid": "a", "algorithm": "RS256"}
+                })),
+            )
+            .layer(
+                "ssm",
+                StaticValueProvider(json!({
+                    "audience": "prod-api",
+                    "scopes": ["read"],
+                    "keys": {"kid": "b"}
+                })),
+            );
+        let merged = stack.load_merged().unwrap();
+        assert_eq!(
+            merged.value,
+            json!({
+                "enabled": true,
+                "audience": "prod-api",
+                "scopes": ["read"],
+                "keys": {"kid": "b", "algorithm": "RS256"}
+            })
+        );
+        assert_eq!(merged.sources["/enabled"], "file");
+        assert_eq!(merged.sources["/audience"], "ssm");
+        assert_eq!(merged.sources["/scopes"], "ssm");
+        assert_eq!(merged.sources["/keys/kid"], "ssm");
+        assert_eq!(merged.sources["/keys/algorithm"], "file");
+    }
+}
@@ -0,0 +1,175 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/* Bare numbers are accepted so existing configs keep working; they are read as milliseconds
+   for durations and bytes for sizes. */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText {
+    Number(u64),
+    Text(String),
+}
+
+fn split_unit(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    (text[..unit_start].trim(), text[unit_start..].trim())
+}
+
+/// Parses durations like `250ms`, `5s`, `2m` or `1h`. A number without a unit is milliseconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (amount, unit) = split_unit(text);
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}'", text))?;
+    let seconds_per_unit = match unit.to_lowercase().as_str() {
+        "" | "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("Unknown duration unit in '{}'", text)),
+    };
+    Duration::try_from_secs_f64(amount * seconds_per_unit)
+        .map_err(|_| format!("Duration '{}' is out of range", text))
+}
+
+/// Parses sizes like `512B`, `64KB`, `1MB` (powers of 1000) or `512KiB`, `6MiB` (powers of 1024).
+/// A number without a unit is bytes.
+pub fn parse_byte_size(text: &str) -> Result<usize, String> {
+    let (amount, unit) = split_unit(text);
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid size '{}'", text))?;
+    let bytes_per_unit: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(format!("Unknown size unit in '{}'", text)),
+    };
+    let bytes = (amount * bytes_per_unit as f64).round();
+    if !bytes.is_finite() || bytes < 0.0 || bytes > usize::MAX as f64 {
+        return Err(format!("Size '{}' is out of range", text));
+    }
+    Ok(bytes as usize)
+}
+
+pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match NumberOrText::deserialize(deserializer)? {
+        NumberOrText::Number(millis) => Ok(Duration::from_millis(millis)),
+        NumberOrText::Text(text) => parse_duration(&text).map_err(D::Error::custom),
+    }
+}
+
+/// Writes a duration the way [`deserialize_duration`] reads it, in milliseconds.
+pub fn serialize_duration<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("{}ms", duration.as_millis()))
+}
+
+pub fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<NumberOrText>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrText::Number(millis)) => Ok(Some(Duration::from_millis(millis))),
+        Some(NumberOrText::Text(text)) => parse_duration(&text).map(Some).map_err(D::Error::custom),
+    }
+}
+
+pub fn serialize_optional_duration<S>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serialize_duration(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Reads durations by name, each the way [`deserialize_duration`] reads one.
+pub fn deserialize_duration_map<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, NumberOrText>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, duration)| match duration {
+            NumberOrText::Number(millis) => Ok((name, Duration::from_millis(millis))),
+            NumberOrText::Text(text) => parse_duration(&text)
+                .map(|duration| (name, duration))
+                .map_err(D::Error::custom),
+        })
+        .collect()
+}
+
+pub fn serialize_duration_map<S>(
+    durations: &BTreeMap<String, Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(
+        durations
+            .iter()
+            .map(|(name, duration)| (name, format!("{}ms", duration.as_millis()))),
+    )
+}
+
+pub fn deserialize_optional_byte_size<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<NumberOrText>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrText::Number(bytes)) => usize::try_from(bytes)
+            .map(Some)
+            .map_err(|_| D::Error::custom("Size is out of range")),
+        Some(NumberOrText::Text(text)) => parse_byte_size(&text).map(Some).map_err(D::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::units::{parse_byte_size, parse_duration};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("300"), Ok(Duration::from_millis(300)));
+        assert!(parse_duration("5 fortnights").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1MB"), Ok(1_000_000));
+        assert_eq!(parse_byte_size("512KiB"), Ok(512 * 1024));
+        assert_eq!(parse_byte_size("6 MiB"), Ok(6 * 1024 * 1024));
+        assert_eq!(parse_byte_size("128"), Ok(128));
+        assert!(parse_byte_size("1XB").is_err());
+    }
+}
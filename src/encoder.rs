@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use tiny_clean::java_script_encoder::JavaScriptEncoder;
+use tiny_clean::uri_encoder::UriEncoder;
+use tiny_clean::xml_encoder::XmlEncoder;
+use unicode_normalization::{is_nfc, is_nfkc, UnicodeNormalization};
+
+/// Common interface over the tiny-clean encoder presets so handlers can hold
+/// any of them as a `Box<dyn Encoder>`.
+pub trait Encoder: Send + Sync {
+    fn encode(&self, input: &str) -> String;
+
+    fn encode_cow<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let encoded = self.encode(input);
+        if encoded == input {
+            Cow::Borrowed(input)
+        } else {
+            Cow::Owned(encoded)
+        }
+    }
+
+    fn name(&self) -> &str;
+}
+
+impl Encoder for JavaScriptEncoder {
+    fn encode(&self, input: &str) -> String {
+        JavaScriptEncoder::encode(self, input)
+    }
+
+    fn name(&self) -> &str {
+        "JavaScriptEncoder"
+    }
+}
+
+impl Encoder for UriEncoder {
+    fn encode(&self, input: &str) -> String {
+        UriEncoder::encode(self, input)
+    }
+
+    fn name(&self) -> &str {
+        "UriEncoder"
+    }
+}
+
+impl Encoder for XmlEncoder {
+    fn encode(&self, input: &str) -> String {
+        XmlEncoder::encode(self, input)
+    }
+
+    fn name(&self) -> &str {
+        "XmlEncoder"
+    }
+}
+
+impl<E> Encoder for Box<E>
+where
+    E: Encoder + ?Sized,
+{
+    fn encode(&self, input: &str) -> String {
+        (**self).encode(input)
+    }
+
+    fn encode_cow<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        (**self).encode_cow(input)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
+
+/// Applies each encoder in order, feeding the output of one stage into the next.
+///
+/// Stages should be listed innermost context first (e.g. JavaScript, then HTML for a
+/// script value inside an attribute). Escape characters produced by an earlier stage are
+/// encoded again by later stages on purpose, since the outer context is decoded first.
+pub struct EncoderChain {
+    encoders: Vec<Box<dyn Encoder>>,
+    name: String,
+}
+
+impl EncoderChain {
+    pub fn new(encoders: Vec<Box<dyn Encoder>>) -> Self {
+        let name = encoders
+            .iter()
+            .map(|encoder| encoder.name())
+            .collect::<Vec<&str>>()
+            .join("->");
+        Self { encoders, name }
+    }
+}
+
+impl Encoder for EncoderChain {
+    fn encode(&self, input: &str) -> String {
+        self.encode_cow(input).into_owned()
+    }
+
+    fn encode_cow<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let mut current = Cow::Borrowed(input);
+        for encoder in &self.encoders {
+            let encoded = match encoder.encode_cow(&current) {
+                Cow::Owned(encoded) => Some(encoded),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(encoded) = encoded {
+                current = Cow::Owned(encoded);
+            }
+        }
+        current
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum NormalizationForm {
+    #[serde(alias = "NFC")]
+    Nfc,
+    #[serde(alias = "NFKC")]
+    Nfkc,
+}
+
+/// Normalizes input before handing it to the wrapped encoder, so decomposed or
+/// compatibility forms of a character (e.g. fullwidth `＜`) are encoded like the
+/// character they render as.
+pub struct NormalizingEncoder<E> {
+    form: NormalizationForm,
+    inner: E,
+}
+
+impl<E> NormalizingEncoder<E>
+where
+    E: Encoder,
+{
+    pub fn new(form: NormalizationForm, inner: E) -> Self {
+        Self { form, inner }
+    }
+
+    fn normalize<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        match self.form {
+            NormalizationForm::Nfc if is_nfc(input) => Cow::Borrowed(input),
+            NormalizationForm::Nfc => Cow::Owned(input.nfc().collect()),
+            NormalizationForm::Nfkc if is_nfkc(input) => Cow::Borrowed(input),
+            NormalizationForm::Nfkc => Cow::Owned(input.nfkc().collect()),
+        }
+    }
+}
+
+impl<E> Encoder for NormalizingEncoder<E>
+where
+    E: Encoder,
+{
+    fn encode(&self, input: &str) -> String {
+        self.encode_cow(input).into_owned()
+    }
+
+    fn encode_cow<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        match self.normalize(input) {
+            Cow::Borrowed(input) => self.inner.encode_cow(input),
+            Cow::Owned(normalized) => Cow::Owned(self.inner.encode(&normalized)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputOverflowPolicy {
+    #[default]
+    Error,
+    Truncate,
+}
+
+/// Encodes `input` while keeping the output within `max_output_len` bytes.
+///
+/// When truncating, input is encoded one character at a time and output stops before the
+/// first character whose escape sequence would not fit, so an escape is never cut in half.
+pub fn encode_bounded(
+    encoder: &dyn Encoder,
+    input: &str,
+    max_output_len: usize,
+    policy: OutputOverflowPolicy,
+) -> Result<String, ()> {
+    let encoded = encoder.encode(input);
+    if encoded.len() <= max_output_len {
+        return Ok(encoded);
+    }
+    match policy {
+        OutputOverflowPolicy::Error => Err(()),
+        OutputOverflowPolicy::Truncate => {
+            let mut output = String::with_capacity(max_output_len);
+            let mut buffer = [0u8; 4];
+            for character in input.chars() {
+                let piece = encoder.encode_cow(character.encode_utf8(&mut buffer));
+                if output.len() + piece.len() > max_output_len {
+                    break;
+                }
+                output.push_str(&piece);
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// Encodes every string leaf of `value` in place.
+///
+/// Entries in `include` and `exclude` match a field either by its key name or by its JSON
+/// pointer (e.g. `/user/name`, `/items/0`). An excluded field is left untouched along with
+/// everything beneath it. When `include` is set, only string leaves at or beneath an included
+/// field are encoded; otherwise every string leaf is.
+pub fn encode_json_value(
+    value: &mut Value,
+    encoder: &dyn Encoder,
+    include: Option<&[String]>,
+    exclude: Option<&[String]>,
+) {
+    let walker = JsonEncodeWalker {
+        encoder,
+        include,
+        exclude,
+    };
+    let mut pointer = String::new();
+    walker.walk(value, &mut pointer, None, include.is_none());
+}
+
+struct JsonEncodeWalker<'a> {
+    encoder: &'a dyn Encoder,
+    include: Option<&'a [String]>,
+    exclude: Option<&'a [String]>,
+}
+
+impl JsonEncodeWalker<'_> {
+    fn matches(list: Option<&[String]>, pointer: &str, key: Option<&str>) -> bool {
+        list.is_some_and(|list| {
+            list.iter()
+                .any(|entry| entry == pointer || key.is_some_and(|key| entry == key))
+        })
+    }
+
+    fn walk(&self, value: &mut Value, pointer: &mut String, key: Option<&str>, included: bool) {
+        if Self::matches(self.exclude, pointer, key) {
+            return;
+        }
+        let included = included || Self::matches(self.include, pointer, key);
+        match value {
+            Value::String(string_value) => {
+                if !included {
+                    return;
+                }
+                let encoded = match self.encoder.encode_cow(string_value) {
+                    Cow::Owned(encoded) => Some(encoded),
+                    Cow::Borrowed(_) => None,
+                };
+                if let Some(encoded) = encoded {
+                    *string_value = encoded;
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    let parent_len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&index.to_string());
+                    self.walk(item, pointer, None, included);
+                    pointer.truncate(parent_len);
+                }
+            }
+            Value::Object(map) => {
+                for (child_key, child) in map.iter_mut() {
+                    let parent_len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&child_key.replace('~', "~0").replace('/', "~1"));
+                    self.walk(child, pointer, Some(child_key), included);
+                    pointer.truncate(parent_len);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{encode_bounded, encode_json_value, Encoder, EncoderChain, NormalizationForm, NormalizingEncoder, OutputOverflowPolicy};
+    use serde_json::json;
+    use std::borrow::Cow;
+    use tiny_clean::java_script_encoder::{JavaScriptEncoder, JavaScriptEncoderMode};
+    use tiny_clean::xml_encoder::{XmlEncoder, XmlEncoderMode};
+
+    #[test]
+    fn test_encode_cow_borrows_clean_input() {
+        let encoder: Box<dyn Encoder> = Box::new(XmlEncoder::new(XmlEncoderMode::All));
+        let clean = encoder.encode_cow("hello world");
+        assert!(matches!(clean, Cow::Borrowed(_)));
+
+        let dirty = encoder.encode_cow("<b>hello</b>");
+        assert!(matches!(dirty, Cow::Owned(_)));
+        assert_eq!(dirty, encoder.encode("<b>hello</b>"));
+    }
+
+    #[test]
+    fn test_encoder_names() {
+        let encoders: Vec<Box<dyn Encoder>> = vec![
+            Box::new(JavaScriptEncoder::new(JavaScriptEncoderMode::Source, true)),
+            Box::new(XmlEncoder::new(XmlEncoderMode::Content)),
+        ];
+        let names = encoders.iter().map(|e| e.name()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["JavaScriptEncoder", "XmlEncoder"]);
+    }
+
+    #[test]
+    fn test_encoder_chain_applies_stages_in_order() {
+        let java_script = JavaScriptEncoder::new(JavaScriptEncoderMode::Attribute, true);
+        let xml = XmlEncoder::new(XmlEncoderMode::Attribute);
+        let input = "alert('<x>' & \"y\")";
+        let expected = xml.encode(&java_script.encode(input));
+
+        let chain = EncoderChain::new(vec![
+            Box::new(JavaScriptEncoder::new(JavaScriptEncoderMode::Attribute, true)),
+            Box::new(XmlEncoder::new(XmlEncoderMode::Attribute)),
+        ]);
+        assert_eq!(chain.encode(input), expected);
+        assert_eq!(chain.name(), "JavaScriptEncoder->XmlEncoder");
+        assert!(matches!(chain.encode_cow("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_nfkc_normalization_before_encoding() {
+        let xml = XmlEncoder::new(XmlEncoderMode::All);
+        let expected = xml.encode("<script>");
+
+        let encoder = NormalizingEncoder::new(NormalizationForm::Nfkc, xml);
+        assert_eq!(encoder.encode("\u{FF1C}script\u{FF1E}"), expected);
+        assert!(matches!(encoder.encode_cow("already clean"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_encode_bounded_never_splits_escapes() {
+        let xml = XmlEncoder::new(XmlEncoderMode::All);
+        let full = xml.encode("a<b");
+
+        let within = encode_bounded(&xml, "a<b", full.len(), OutputOverflowPolicy::Error);
+        assert_eq!(within.unwrap(), full);
+
+        let error = encode_bounded(&xml, "a<b", full.len() - 1, OutputOverflowPolicy::Error);
+        assert!(error.is_err());
+
+        let truncated = encode_bounded(&xml, "a<b", full.len() - 1, OutputOverflowPolicy::Truncate).unwrap();
+        assert_eq!(truncated, xml.encode("a<"));
+        let truncated = encode_bounded(&xml, "a<b", 3, OutputOverflowPolicy::Truncate).unwrap();
+        assert_eq!(truncated, "a");
+    }
+
+    #[test]
+    fn test_encode_json_value_include_and_exclude() {
+        let xml = XmlEncoder::new(XmlEncoderMode::All);
+        let original = json!({
+            "name": "<a>",
+            "tags": ["<b>", 1],
+            "nested": {"safe": "<c>", "raw": "<d>"}
+        });
+
+        let mut all = original.clone();
+        let exclude = vec!["/nested/raw".to_string()];
+        encode_json_value(&mut all, &xml, None, Some(&exclude));
+        assert_eq!(all["name"], "&lt;a&gt;");
+        assert_eq!(all["tags"][0], "&lt;b&gt;");
+        assert_eq!(all["tags"][1], 1);
+        assert_eq!(all["nested"]["safe"], "&lt;c&gt;");
+        assert_eq!(all["nested"]["raw"], "<d>");
+
+        let mut only_nested = original.clone();
+        let include = vec!["nested".to_string()];
+        let exclude = vec!["raw".to_string()];
+        encode_json_value(&mut only_nested, &xml, Some(&include), Some(&exclude));
+        assert_eq!(only_nested["name"], "<a>");
+        assert_eq!(only_nested["tags"][0], "<b>");
+        assert_eq!(only_nested["nested"]["safe"], "&lt;c&gt;");
+        assert_eq!(only_nested["nested"]["raw"], "<d>");
+    }
+}
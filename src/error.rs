@@ -0,0 +1,222 @@
+use idemio::status::{ExchangeState, HandlerStatus};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use crate::handler::LambdaExchange;
+
+const HANDLER_ERROR_ATTACHMENT_KEY: &str = "handler_error";
+
+/// A documented error the gateway can return. Codes are grouped by area:
+/// `ERR10xxx` authentication and authorization, `ERR11xxx` request validation,
+/// `ERR12xxx` upstream calls and `ERR13xxx` gateway failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub state: ExchangeState,
+    pub status_code: i64,
+    pub title: &'static str,
+}
+
+impl ErrorCode {
+    const fn new(
+        code: &'static str,
+        state: ExchangeState,
+        status_code: i64,
+        title: &'static str,
+    ) -> Self {
+        Self {
+            code,
+            state,
+            status_code,
+            title,
+        }
+    }
+
+    pub fn error(self) -> HandlerError {
+        HandlerError {
+            code: self,
+            detail: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Records this error on the exchange and returns the matching handler status.
+    pub fn raise(self, exchange: &mut LambdaExchange) -> HandlerStatus {
+        self.error().raise(exchange)
+    }
+}
+
+pub const ERR10000_MISSING_TOKEN: ErrorCode =
+    ErrorCode::new("ERR10000", ExchangeState::CLIENT_ERROR, 401, "Missing bearer token");
+pub const ERR10001_INVALID_TOKEN: ErrorCode =
+    ErrorCode::new("ERR10001", ExchangeState::CLIENT_ERROR, 401, "Invalid token");
+pub const ERR10002_INSUFFICIENT_SCOPE: ErrorCode =
+    ErrorCode::new("ERR10002", ExchangeState::CLIENT_ERROR, 403, "Insufficient scope");
+pub const ERR10003_INVALID_ADMIN_TOKEN: ErrorCode =
+    ErrorCode::new("ERR10003", ExchangeState::CLIENT_ERROR, 401, "Invalid admin token");
+pub const ERR10004_ORIGIN_FORBIDDEN: ErrorCode =
+    ErrorCode::new("ERR10004", ExchangeState::CLIENT_ERROR, 403, "Origin is forbidden");
+pub const ERR10005_PREFLIGHT_REJECTED: ErrorCode =
+    ErrorCode::new("ERR10005", ExchangeState::CLIENT_ERROR, 403, "Preflight request rejected");
+pub const ERR10006_MISSING_API_KEY: ErrorCode =
+    ErrorCode::new("ERR10006", ExchangeState::CLIENT_ERROR, 401, "Missing API key");
+pub const ERR10007_INVALID_API_KEY: ErrorCode =
+    ErrorCode::new("ERR10007", ExchangeState::CLIENT_ERROR, 401, "Invalid API key");
+pub const ERR10008_ADDRESS_FORBIDDEN: ErrorCode =
+    ErrorCode::new("ERR10008", ExchangeState::CLIENT_ERROR, 403, "Caller address is forbidden");
+pub const ERR10009_REPLAYED_REQUEST: ErrorCode =
+    ErrorCode::new("ERR10009", ExchangeState::CLIENT_ERROR, 401, "Request was replayed");
+pub const ERR10010_STALE_REQUEST: ErrorCode =
+    ErrorCode::new("ERR10010", ExchangeState::CLIENT_ERROR, 401, "Request timestamp is stale");
+pub const ERR10011_INVALID_SIGNATURE: ErrorCode =
+    ErrorCode::new("ERR10011", ExchangeState::CLIENT_ERROR, 401, "Invalid request signature");
+pub const ERR11000_INVALID_REQUEST: ErrorCode =
+    ErrorCode::new("ERR11000", ExchangeState::CLIENT_ERROR, 400, "Invalid request");
+pub const ERR11001_PAYLOAD_TOO_LARGE: ErrorCode =
+    ErrorCode::new("ERR11001", ExchangeState::CLIENT_ERROR, 413, "Payload too large");
+pub const ERR11002_NO_ROUTE: ErrorCode =
+    ErrorCode::new("ERR11002", ExchangeState::CLIENT_ERROR, 404, "No route for request");
+pub const ERR11003_METHOD_NOT_ALLOWED: ErrorCode =
+    ErrorCode::new("ERR11003", ExchangeState::CLIENT_ERROR, 405, "Method not allowed");
+pub const ERR11004_RATE_LIMITED: ErrorCode =
+    ErrorCode::new("ERR11004", ExchangeState::CLIENT_ERROR, 429, "Too many requests");
+pub const ERR12000_UPSTREAM_FAILED: ErrorCode =
+    ErrorCode::new("ERR12000", ExchangeState::SERVER_ERROR, 502, "Upstream call failed");
+pub const ERR12001_UPSTREAM_TIMEOUT: ErrorCode =
+    ErrorCode::new("ERR12001", ExchangeState::TIMEOUT, 504, "Upstream call timed out");
+pub const ERR12002_UPSTREAM_RESPONSE_TOO_LARGE: ErrorCode =
+    ErrorCode::new("ERR12002", ExchangeState::SERVER_ERROR, 502, "Upstream response too large");
+pub const ERR12003_UPSTREAM_BUSY: ErrorCode =
+    ErrorCode::new("ERR12003", ExchangeState::SERVER_ERROR, 503, "Upstream at capacity");
+pub const ERR13000_INTERNAL: ErrorCode =
+    ErrorCode::new("ERR13000", ExchangeState::SERVER_ERROR, 500, "Internal gateway error");
+pub const ERR13001_KEYS_UNAVAILABLE: ErrorCode =
+    ErrorCode::new("ERR13001", ExchangeState::SERVER_ERROR, 500, "Signing keys unavailable");
+
+/// Every error code the gateway returns.
+pub const CATALOG: &[ErrorCode] = &[
+    ERR10000_MISSING_TOKEN,
+    ERR10001_INVALID_TOKEN,
+    ERR10002_INSUFFICIENT_SCOPE,
+    ERR10003_INVALID_ADMIN_TOKEN,
+    ERR10004_ORIGIN_FORBIDDEN,
+    ERR10005_PREFLIGHT_REJECTED,
+    ERR10006_MISSING_API_KEY,
+    ERR10007_INVALID_API_KEY,
+    ERR10008_ADDRESS_FORBIDDEN,
+    ERR10009_REPLAYED_REQUEST,
+    ERR10010_STALE_REQUEST,
+    ERR10011_INVALID_SIGNATURE,
+    ERR11000_INVALID_REQUEST,
+    ERR11001_PAYLOAD_TOO_LARGE,
+    ERR11002_NO_ROUTE,
+    ERR11003_METHOD_NOT_ALLOWED,
+    ERR11004_RATE_LIMITED,
+    ERR12000_UPSTREAM_FAILED,
+    ERR12001_UPSTREAM_TIMEOUT,
+    ERR12002_UPSTREAM_RESPONSE_TOO_LARGE,
+    ERR12003_UPSTREAM_BUSY,
+    ERR13000_INTERNAL,
+    ERR13001_KEYS_UNAVAILABLE,
+];
+
+/// An error code with the specifics of one occurrence. The detail and metadata are returned to
+/// the caller, so they must not contain secrets.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HandlerError {
+    #[serde(skip)]
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl HandlerError {
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn status(&self) -> HandlerStatus {
+        let status = HandlerStatus::new(self.code.state);
+        match &self.detail {
+            Some(detail) => status.message(format!("{}: {}", self.code.code, detail)),
+            None => status.message(format!("{}: {}", self.code.code, self.code.title)),
+        }
+    }
+
+    /// Records the error on the exchange, for the executor's error response, and returns the
+    /// matching handler status.
+    pub fn raise(self, exchange: &mut LambdaExchange) -> HandlerStatus {
+        let status = self.status();
+        let attachments = exchange.attachments_mut();
+        match attachments.get_mut::<HandlerError>(HANDLER_ERROR_ATTACHMENT_KEY) {
+            Some(current) => *current = self,
+            None => attachments.add::<HandlerError>(HANDLER_ERROR_ATTACHMENT_KEY, self),
+        }
+        status
+    }
+
+    /// The error as an RFC 7807 problem body.
+    pub fn problem(&self, correlation_id: &str) -> Value {
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": self.code.title,
+            "status": self.code.status_code,
+            "code": self.code.code,
+            "correlationId": correlation_id,
+        });
+        if let Value::Object(fields) = serde_json::to_value(self).unwrap_or_default() {
+            for (key, value) in fields {
+                problem[key] = value;
+            }
+        }
+        problem
+    }
+}
+
+/// The error raised by the last failing handler, if it used the catalog.
+pub fn handler_error(exchange: &LambdaExchange) -> Option<&HandlerError> {
+    exchange
+        .attachments()
+        .get::<HandlerError>(HANDLER_ERROR_ATTACHMENT_KEY)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{handler_error, CATALOG, ERR10001_INVALID_TOKEN};
+    use crate::handler::LambdaExchange;
+    use idemio::exchange::Exchange;
+    use idemio::status::ExchangeState;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalog_codes_are_unique() {
+        let codes: HashSet<&str> = CATALOG.iter().map(|code| code.code).collect();
+        assert_eq!(codes.len(), CATALOG.len());
+    }
+
+    #[test]
+    fn test_raise_records_problem() {
+        let mut exchange: LambdaExchange = Exchange::new();
+        let status = ERR10001_INVALID_TOKEN
+            .error()
+            .detail("Token has expired")
+            .metadata("kid", "key-1")
+            .raise(&mut exchange);
+        assert!(status.code().any_flags(ExchangeState::CLIENT_ERROR));
+
+        let problem = handler_error(&exchange).unwrap().problem("abc");
+        assert_eq!(problem["status"], 401);
+        assert_eq!(problem["code"], "ERR10001");
+        assert_eq!(problem["detail"], "Token has expired");
+        assert_eq!(problem["metadata"]["kid"], "key-1");
+        assert_eq!(problem["correlationId"], "abc");
+    }
+}
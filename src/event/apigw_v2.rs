@@ -0,0 +1,94 @@
+use lambda_http::aws_lambda_events::apigw::{
+    ApiGatewayProxyRequest, ApiGatewayProxyRequestContext, ApiGatewayV2httpRequest,
+};
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use lambda_http::http::header::COOKIE;
+use lambda_http::http::HeaderValue;
+
+/// Normalizes an HTTP API (payload format 2.0) event into the REST API shape the handler chains
+/// work with: the method comes from `requestContext.http`, the path from `rawPath`, the query
+/// from `rawQueryString` and the `cookies` array goes back into a `Cookie` header.
+pub fn to_proxy_request(request: ApiGatewayV2httpRequest) -> ApiGatewayProxyRequest {
+    let http_method = request.request_context.http.method.clone();
+    let query: QueryMap = match &request.raw_query_string {
+        Some(query) if !query.is_empty() => query.parse().unwrap_or_default(),
+        _ => request.query_string_parameters,
+    };
+
+    let mut headers = request.headers;
+    if let Some(cookies) = request.cookies.filter(|cookies| !cookies.is_empty())
+        && let Ok(cookie) = HeaderValue::from_str(&cookies.join("; "))
+    {
+        headers.insert(COOKIE, cookie);
+    }
+
+    ApiGatewayProxyRequest {
+        path: request.raw_path,
+        http_method: http_method.clone(),
+        headers,
+        query_string_parameters: query.clone(),
+        multi_value_query_string_parameters: query,
+        path_parameters: request.path_parameters,
+        stage_variables: request.stage_variables,
+        request_context: ApiGatewayProxyRequestContext {
+            stage: request.request_context.stage,
+            request_id: request.request_context.request_id,
+            domain_name: request.request_context.domain_name,
+            http_method,
+            ..Default::default()
+        },
+        body: request.body,
+        is_base64_encoded: request.is_base64_encoded,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::{GatewayRequest, GatewayResponse, PayloadFormat};
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::http::Method;
+    use serde_json::json;
+
+    #[test]
+    fn test_v2_event_is_normalized() {
+        let event = json!({
+            "version": "2.0",
+            "routeKey": "$default",
+            "rawPath": "/pets/123",
+            "rawQueryString": "size=small&size=large",
+            "cookies": ["a=1", "b=2"],
+            "headers": { "accept": "application/json" },
+            "requestContext": {
+                "http": { "method": "POST", "path": "/pets/123", "protocol": "HTTP/1.1",
+                          "sourceIp": "127.0.0.1", "userAgent": "test" },
+                "requestId": "abc",
+                "stage": "$default"
+            },
+            "body": "{}",
+            "isBase64Encoded": false
+        });
+        let (request, format) = GatewayRequest::parse(event, PayloadFormat::Auto)
+            .unwrap()
+            .into_proxy_request();
+        assert_eq!(format, PayloadFormat::V2);
+        assert_eq!(request.http_method, Method::POST);
+        assert_eq!(request.path.as_deref(), Some("/pets/123"));
+        assert_eq!(request.headers["cookie"], "a=1; b=2");
+        assert_eq!(
+            request.multi_value_query_string_parameters.all("size"),
+            Some(vec!["small", "large"])
+        );
+
+        let mut response = ApiGatewayProxyResponse {
+            status_code: 201,
+            ..Default::default()
+        };
+        response.headers.append("set-cookie", "c=3".parse().unwrap());
+        let GatewayResponse::V2(response) = GatewayResponse::new(response, format) else {
+            panic!("expected a payload format 2.0 response");
+        };
+        assert_eq!(response.status_code, 201);
+        assert_eq!(response.cookies, vec!["c=3".to_string()]);
+    }
+}
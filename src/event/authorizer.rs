@@ -0,0 +1,196 @@
+use lambda_http::aws_lambda_events::apigw::{
+    ApiGatewayCustomAuthorizerRequest, ApiGatewayProxyRequest, ApiGatewayProxyResponse,
+    ApiGatewayV2httpRequest,
+};
+use lambda_http::http::header::AUTHORIZATION;
+use lambda_http::http::{HeaderMap, Method};
+use serde_json::{json, Value};
+use crate::event::{apigw_v2, insert_header, is_success};
+use crate::executor::authorizer::AuthorizerOutput;
+
+/* the error message API Gateway turns into a 401 */
+const UNAUTHORIZED: &str = "Unauthorized";
+const POLICY_VERSION: &str = "2012-10-17";
+const INVOKE_ACTION: &str = "execute-api:Invoke";
+
+pub fn is_authorizer_event(event: &Value) -> bool {
+    event.get("methodArn").is_some() || event.get("routeArn").is_some()
+}
+
+/// An API Gateway Lambda authorizer event: a `TOKEN` or `REQUEST` authorizer of a REST API,
+/// or a `REQUEST` authorizer of an HTTP API. The request is routed like any proxied request,
+/// so the path's own chain authorizes it, with the same handlers and configuration, but only
+/// its request phase runs.
+pub enum AuthorizerRequest {
+    Token(ApiGatewayCustomAuthorizerRequest),
+    Request {
+        request: ApiGatewayProxyRequest,
+        method_arn: String,
+    },
+    V2(ApiGatewayV2httpRequest),
+}
+
+impl AuthorizerRequest {
+    pub fn parse(event: Value) -> Result<Self, serde_json::Error> {
+        if event.get("version").and_then(Value::as_str) == Some("2.0") {
+            return serde_json::from_value(event).map(AuthorizerRequest::V2);
+        }
+        if event.get("type").and_then(Value::as_str) == Some("TOKEN") {
+            return serde_json::from_value(event).map(AuthorizerRequest::Token);
+        }
+        let method_arn = event
+            .get("methodArn")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        serde_json::from_value(event).map(|request| AuthorizerRequest::Request {
+            request,
+            method_arn,
+        })
+    }
+
+    /// Splits the event into the request the authorizing chain runs on, and what the result
+    /// has to be reported for.
+    pub fn into_proxy_request(self) -> (ApiGatewayProxyRequest, AuthorizerResult) {
+        match self {
+            AuthorizerRequest::Token(event) => {
+                let method_arn = event.method_arn.unwrap_or_default();
+                let mut headers = HeaderMap::new();
+                if let Some(token) = &event.authorization_token {
+                    insert_header(&mut headers, AUTHORIZATION.as_str(), token);
+                }
+                let (method, path) = method_and_path(&method_arn);
+                let request = ApiGatewayProxyRequest {
+                    path: Some(path),
+                    http_method: method,
+                    headers,
+                    ..Default::default()
+                };
+                (request, AuthorizerResult::Policy { method_arn })
+            }
+            AuthorizerRequest::Request {
+                request,
+                method_arn,
+            } => (request, AuthorizerResult::Policy { method_arn }),
+            AuthorizerRequest::V2(event) => {
+                (apigw_v2::to_proxy_request(event), AuthorizerResult::Simple)
+            }
+        }
+    }
+}
+
+/* `arn:aws:execute-api:{region}:{account}:{api}/{stage}/{method}/{path}` */
+fn method_and_path(method_arn: &str) -> (Method, String) {
+    let mut parts = method_arn.splitn(4, '/').skip(2);
+    let method = parts
+        .next()
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+        .unwrap_or(Method::GET);
+    let path = format!("/{}", parts.next().unwrap_or_default());
+    (method, path)
+}
+
+/// The kind of answer the authorizer gives: an IAM policy for the method (REST APIs), or the
+/// simple `isAuthorized` response of HTTP APIs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthorizerResult {
+    Policy { method_arn: String },
+    Simple,
+}
+
+impl AuthorizerResult {
+    /// Turns the response of the authorizing chain into the authorizer's answer. A 401 from the
+    /// chain fails the invocation with `Unauthorized`, which API Gateway answers with a 401;
+    /// any other failure denies the request.
+    pub fn respond(self, response: ApiGatewayProxyResponse) -> Result<Value, String> {
+        let authorized = is_success(&response);
+        let output: AuthorizerOutput = match authorized {
+            true => response
+                .body
+                .as_deref()
+                .and_then(|body| std::str::from_utf8(body).ok())
+                .and_then(|body| serde_json::from_str(body).ok())
+                .unwrap_or_default(),
+            false => AuthorizerOutput::default(),
+        };
+        match self {
+            AuthorizerResult::Simple => Ok(json!({
+                "isAuthorized": authorized,
+                "context": output.context,
+            })),
+            AuthorizerResult::Policy { .. } if response.status_code == 401 => {
+                Err(UNAUTHORIZED.to_string())
+            }
+            AuthorizerResult::Policy { method_arn } => Ok(json!({
+                "principalId": output.principal_id.unwrap_or_else(|| "anonymous".to_string()),
+                "policyDocument": {
+                    "Version": POLICY_VERSION,
+                    "Statement": [{
+                        "Action": INVOKE_ACTION,
+                        "Effect": if authorized { "Allow" } else { "Deny" },
+                        "Resource": method_arn,
+                    }]
+                },
+                "context": output.context,
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::authorizer::{AuthorizerRequest, AuthorizerResult};
+    use crate::event::EventKind;
+    use crate::executor::authorizer::AuthorizerOutput;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::http::Method;
+    use serde_json::json;
+
+    #[test]
+    fn test_token_authorizer() {
+        let method_arn = "arn:aws:execute-api:us-east-1:123456789012:abcdef/prod/POST/pets/123";
+        let event = json!({
+            "type": "TOKEN",
+            "authorizationToken": "Bearer abc",
+            "methodArn": method_arn,
+        });
+        assert_eq!(EventKind::detect(&event), EventKind::Authorizer);
+        let (request, result) = AuthorizerRequest::parse(event).unwrap().into_proxy_request();
+        assert_eq!(request.http_method, Method::POST);
+        assert_eq!(request.path.as_deref(), Some("/pets/123"));
+        assert_eq!(request.headers["authorization"], "Bearer abc");
+
+        let output = AuthorizerOutput {
+            principal_id: Some("user-1".to_string()),
+            context: [("tenant".to_string(), json!("acme"))].into(),
+        };
+        let response = ApiGatewayProxyResponse {
+            status_code: 200,
+            body: Some(serde_json::to_string(&output).unwrap().into()),
+            ..Default::default()
+        };
+        let policy = result.clone().respond(response).unwrap();
+        assert_eq!(policy["principalId"], "user-1");
+        assert_eq!(policy["policyDocument"]["Statement"][0]["Effect"], "Allow");
+        assert_eq!(policy["policyDocument"]["Statement"][0]["Resource"], method_arn);
+        assert_eq!(policy["context"]["tenant"], "acme");
+
+        let forbidden = ApiGatewayProxyResponse {
+            status_code: 403,
+            ..Default::default()
+        };
+        let policy = result.clone().respond(forbidden).unwrap();
+        assert_eq!(policy["policyDocument"]["Statement"][0]["Effect"], "Deny");
+        let unauthorized = ApiGatewayProxyResponse {
+            status_code: 401,
+            ..Default::default()
+        };
+        assert!(result.respond(unauthorized).is_err());
+        assert_eq!(
+            AuthorizerResult::Simple
+                .respond(ApiGatewayProxyResponse::default())
+                .unwrap()["isAuthorized"],
+            false
+        );
+    }
+}
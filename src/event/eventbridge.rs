@@ -0,0 +1,95 @@
+use aws_lambda_events::eventbridge::EventBridgeEvent;
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::HeaderMap;
+use serde_json::Value;
+use std::future::Future;
+use crate::event::{event_request, insert_header, is_success, EventRoutes};
+
+pub const DETAIL_TYPE_HEADER: &str = "x-eventbridge-detail-type";
+pub const SOURCE_HEADER: &str = "x-eventbridge-source";
+pub const EVENT_ID_HEADER: &str = "x-eventbridge-id";
+
+/// Turns an event into the request the detail-type's flow runs on. The body is the event's
+/// `detail`; its type, source and id go into headers.
+fn to_proxy_request(event: EventBridgeEvent<Value>, path: &str) -> ApiGatewayProxyRequest {
+    let mut headers = HeaderMap::new();
+    insert_header(&mut headers, DETAIL_TYPE_HEADER, &event.detail_type);
+    insert_header(&mut headers, SOURCE_HEADER, &event.source);
+    if let Some(id) = &event.id {
+        insert_header(&mut headers, EVENT_ID_HEADER, id);
+    }
+    event_request(path, headers, Some(event.detail.to_string()))
+}
+
+/// Runs the event through the flow configured for its detail-type. An event without a flow, or
+/// whose flow did not end with a 2xx status, fails the invocation so EventBridge retries it.
+pub async fn run_event<F, Fut>(
+    event: EventBridgeEvent<Value>,
+    routes: &EventRoutes,
+    process: F,
+) -> Result<(), String>
+where
+    F: FnOnce(ApiGatewayProxyRequest) -> Fut,
+    Fut: Future<Output = ApiGatewayProxyResponse>,
+{
+    let detail_type = event.detail_type.clone();
+    let Some(path) = EventRoutes::find(&routes.eventbridge, &detail_type) else {
+        return Err(format!("No flow configured for {} events", detail_type));
+    };
+    let response = process(to_proxy_request(event, path)).await;
+    match is_success(&response) {
+        true => Ok(()),
+        false => Err(format!(
+            "{} event failed with status {}",
+            detail_type, response.status_code
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::eventbridge::{run_event, DETAIL_TYPE_HEADER, SOURCE_HEADER};
+    use crate::event::{EventKind, EventRoutes};
+    use aws_lambda_events::eventbridge::EventBridgeEvent;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use serde_json::{json, Value};
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_eventbridge_event() {
+        let event = json!({
+            "version": "0",
+            "id": "event-0",
+            "detail-type": "Order Created",
+            "source": "shop.orders",
+            "account": "123456789012",
+            "time": "2024-01-01T00:00:00Z",
+            "region": "us-east-1",
+            "resources": [],
+            "detail": { "id": 1 }
+        });
+        assert_eq!(EventKind::detect(&event), EventKind::EventBridge);
+        let event: EventBridgeEvent<Value> = serde_json::from_value(event).unwrap();
+        let routes = EventRoutes {
+            eventbridge: [("Order Created".to_string(), "/events/orders".to_string())].into(),
+            ..Default::default()
+        };
+        let result = run_event(event.clone(), &routes, |request| async move {
+            assert_eq!(request.path.as_deref(), Some("/events/orders"));
+            assert_eq!(request.headers[DETAIL_TYPE_HEADER], "Order Created");
+            assert_eq!(request.headers[SOURCE_HEADER], "shop.orders");
+            assert_eq!(request.body.as_deref(), Some("{\"id\":1}"));
+            ApiGatewayProxyResponse {
+                status_code: 422,
+                ..Default::default()
+            }
+        })
+        .await;
+        assert!(result.is_err());
+
+        let result = run_event(event, &EventRoutes::default(), |_| async {
+            ApiGatewayProxyResponse::default()
+        })
+        .await;
+        assert!(result.is_err());
+    }
+}
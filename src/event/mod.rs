@@ -0,0 +1,187 @@
+use lambda_http::aws_lambda_events::apigw::{
+    ApiGatewayProxyRequest, ApiGatewayProxyResponse, ApiGatewayV2httpRequest,
+    ApiGatewayV2httpResponse,
+};
+use aws_lambda_events::sqs::SqsBatchResponse;
+use lambda_http::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::handler::adapter::HttpResponseEvent;
+
+pub mod apigw_v2;
+pub mod authorizer;
+pub mod eventbridge;
+pub mod sns;
+pub mod sqs;
+pub mod warmup;
+
+/* "v1", "v2" or "auto" (the default) */
+pub const PAYLOAD_FORMAT_ENV_VAR: &str = "IDEM_PAYLOAD_FORMAT";
+
+/// The API Gateway payload format the function accepts. REST APIs send version 1.0 events,
+/// HTTP APIs version 2.0 by default; `Auto` tells them apart by the event's `version` field.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum PayloadFormat {
+    #[default]
+    Auto,
+    V1,
+    V2,
+}
+
+impl PayloadFormat {
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(PAYLOAD_FORMAT_ENV_VAR).as_deref() {
+            Err(_) | Ok("auto") => Ok(PayloadFormat::Auto),
+            Ok("v1") => Ok(PayloadFormat::V1),
+            Ok("v2") => Ok(PayloadFormat::V2),
+            Ok(other) => Err(format!("Unknown payload format {}", other)),
+        }
+    }
+}
+
+/// An API Gateway event in either payload format.
+pub enum GatewayRequest {
+    V1(ApiGatewayProxyRequest),
+    V2(ApiGatewayV2httpRequest),
+}
+
+impl GatewayRequest {
+    pub fn parse(event: Value, format: PayloadFormat) -> Result<Self, serde_json::Error> {
+        let is_v2 = match format {
+            PayloadFormat::Auto => event.get("version").and_then(Value::as_str) == Some("2.0"),
+            PayloadFormat::V1 => false,
+            PayloadFormat::V2 => true,
+        };
+        match is_v2 {
+            true => serde_json::from_value(event).map(GatewayRequest::V2),
+            false => serde_json::from_value(event).map(GatewayRequest::V1),
+        }
+    }
+
+    /// Splits the event into the request the handler chains work with, and the format the
+    /// response has to be returned in.
+    pub fn into_proxy_request(self) -> (ApiGatewayProxyRequest, PayloadFormat) {
+        match self {
+            GatewayRequest::V1(request) => (request, PayloadFormat::V1),
+            GatewayRequest::V2(request) => (apigw_v2::to_proxy_request(request), PayloadFormat::V2),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum GatewayResponse {
+    V1(ApiGatewayProxyResponse),
+    V2(ApiGatewayV2httpResponse),
+}
+
+impl GatewayResponse {
+    pub fn new(response: ApiGatewayProxyResponse, format: PayloadFormat) -> Self {
+        match format {
+            PayloadFormat::V2 => {
+                GatewayResponse::V2(ApiGatewayV2httpResponse::from_http(response.into_http()))
+            }
+            _ => GatewayResponse::V1(response),
+        }
+    }
+}
+
+/// The kind of event the function was invoked with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Gateway,
+    Authorizer,
+    Sqs,
+    Sns,
+    EventBridge,
+}
+
+impl EventKind {
+    pub fn detect(event: &Value) -> Self {
+        if authorizer::is_authorizer_event(event) {
+            return EventKind::Authorizer;
+        }
+        if event.get("detail-type").is_some() && event.get("source").is_some() {
+            return EventKind::EventBridge;
+        }
+        let record = event.get("Records").and_then(|records| records.get(0));
+        /* SQS records spell it `eventSource`, SNS records `EventSource` */
+        let source = record
+            .and_then(|record| record.get("eventSource").or_else(|| record.get("EventSource")))
+            .and_then(Value::as_str);
+        match source {
+            Some("aws:sqs") => EventKind::Sqs,
+            Some("aws:sns") => EventKind::Sns,
+            _ => EventKind::Gateway,
+        }
+    }
+}
+
+/// What the function returns to the invoking service. SNS and EventBridge ignore the result of
+/// a successful invocation.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum EventResponse {
+    Gateway(Box<GatewayResponse>),
+    Authorizer(Value),
+    Sqs(SqsBatchResponse),
+    Empty,
+}
+
+/* matches every source without a route of its own */
+pub const ANY_SOURCE: &str = "*";
+
+/// The paths whose flows process events that don't come from API Gateway, by event source.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct EventRoutes {
+    /* path by queue name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sqs: BTreeMap<String, String>,
+    /* path by topic name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sns: BTreeMap<String, String>,
+    /* path by detail-type */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub eventbridge: BTreeMap<String, String>,
+}
+
+impl EventRoutes {
+    pub fn is_empty(&self) -> bool {
+        self.sqs.is_empty() && self.sns.is_empty() && self.eventbridge.is_empty()
+    }
+
+    fn find<'a>(routes: &'a BTreeMap<String, String>, source: &str) -> Option<&'a str> {
+        routes
+            .get(source)
+            .or_else(|| routes.get(ANY_SOURCE))
+            .map(String::as_str)
+    }
+}
+
+/* the last part of an ARN, e.g. the queue or topic name */
+fn arn_resource(arn: &str) -> &str {
+    arn.rsplit(':').next().unwrap_or_default()
+}
+
+/* skips names or values that can't be sent as a header */
+fn insert_header(headers: &mut HeaderMap, name: &str, value: &str) {
+    if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_str(value)) {
+        headers.insert(name, value);
+    }
+}
+
+/// The request the flow of an event's path runs on: a `POST` carrying the event's payload.
+fn event_request(path: &str, headers: HeaderMap, body: Option<String>) -> ApiGatewayProxyRequest {
+    ApiGatewayProxyRequest {
+        path: Some(path.to_string()),
+        http_method: Method::POST,
+        headers,
+        body,
+        ..Default::default()
+    }
+}
+
+fn is_success(response: &ApiGatewayProxyResponse) -> bool {
+    (200..300).contains(&response.status_code)
+}
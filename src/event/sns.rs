@@ -0,0 +1,118 @@
+use aws_lambda_events::sns::{SnsEvent, SnsMessage};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::HeaderMap;
+use std::future::Future;
+use crate::event::{arn_resource, event_request, insert_header, is_success, EventRoutes};
+
+pub const MESSAGE_ID_HEADER: &str = "x-sns-message-id";
+pub const TOPIC_HEADER: &str = "x-sns-topic";
+pub const SUBJECT_HEADER: &str = "x-sns-subject";
+
+/// Turns a notification into the request the topic's flow runs on. String message attributes
+/// become headers of the same name, next to the message id, topic name and subject.
+fn to_proxy_request(message: SnsMessage, path: &str) -> ApiGatewayProxyRequest {
+    let mut headers = HeaderMap::new();
+    for (name, attribute) in &message.message_attributes {
+        if attribute.data_type == "String" {
+            insert_header(&mut headers, name, &attribute.value);
+        }
+    }
+    insert_header(&mut headers, TOPIC_HEADER, arn_resource(&message.topic_arn));
+    insert_header(&mut headers, MESSAGE_ID_HEADER, &message.message_id);
+    if let Some(subject) = &message.subject {
+        insert_header(&mut headers, SUBJECT_HEADER, subject);
+    }
+    event_request(path, headers, Some(message.message))
+}
+
+/// Runs every notification through the flow configured for its topic. Any notification without
+/// a flow, or whose flow did not end with a 2xx status, fails the invocation so SNS retries it.
+pub async fn run_notifications<F, Fut>(
+    event: SnsEvent,
+    routes: &EventRoutes,
+    mut process: F,
+) -> Result<(), String>
+where
+    F: FnMut(ApiGatewayProxyRequest) -> Fut,
+    Fut: Future<Output = ApiGatewayProxyResponse>,
+{
+    let mut failed = vec![];
+    for record in event.records {
+        let message = record.sns;
+        let message_id = message.message_id.clone();
+        let topic = arn_resource(&message.topic_arn).to_string();
+        let succeeded = match EventRoutes::find(&routes.sns, &topic) {
+            None => {
+                tracing::error!("No flow configured for notifications from topic {}", topic);
+                false
+            }
+            Some(path) => is_success(&process(to_proxy_request(message, path)).await),
+        };
+        if !succeeded {
+            failed.push(message_id);
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(format!("SNS notifications failed: {}", failed.join(", "))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::sns::{run_notifications, TOPIC_HEADER};
+    use crate::event::{EventKind, EventRoutes};
+    use aws_lambda_events::sns::SnsEvent;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_sns_notifications() {
+        let event = json!({
+            "Records": [{
+                "EventSource": "aws:sns",
+                "EventVersion": "1.0",
+                "EventSubscriptionArn": "arn:aws:sns:us-east-1:123456789012:orders:sub",
+                "Sns": {
+                    "Type": "Notification",
+                    "MessageId": "message-0",
+                    "TopicArn": "arn:aws:sns:us-east-1:123456789012:orders",
+                    "Subject": "created",
+                    "Message": "{\"id\":1}",
+                    "Timestamp": "2024-01-01T00:00:00.000Z",
+                    "SignatureVersion": "1",
+                    "Signature": "",
+                    "SigningCertUrl": "",
+                    "UnsubscribeUrl": "",
+                    "MessageAttributes": {
+                        "tenant": { "Type": "String", "Value": "acme" }
+                    }
+                }
+            }]
+        });
+        assert_eq!(EventKind::detect(&event), EventKind::Sns);
+        let event: SnsEvent = serde_json::from_value(event).unwrap();
+        let routes = EventRoutes {
+            sns: [("orders".to_string(), "/events/orders".to_string())].into(),
+            ..Default::default()
+        };
+        let result = run_notifications(event.clone(), &routes, |request| async move {
+            assert_eq!(request.path.as_deref(), Some("/events/orders"));
+            assert_eq!(request.headers["tenant"], "acme");
+            assert_eq!(request.headers[TOPIC_HEADER], "orders");
+            assert_eq!(request.body.as_deref(), Some("{\"id\":1}"));
+            ApiGatewayProxyResponse {
+                status_code: 200,
+                ..Default::default()
+            }
+        })
+        .await;
+        assert!(result.is_ok());
+
+        let result = run_notifications(event, &EventRoutes::default(), |_| async {
+            ApiGatewayProxyResponse::default()
+        })
+        .await;
+        assert!(result.is_err());
+    }
+}
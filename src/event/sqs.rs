@@ -0,0 +1,155 @@
+use aws_lambda_events::sqs::{BatchItemFailure, SqsBatchResponse, SqsEvent, SqsMessage};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::HeaderMap;
+use std::future::Future;
+use crate::event::{arn_resource, event_request, insert_header, is_success, EventRoutes};
+
+pub const MESSAGE_ID_HEADER: &str = "x-sqs-message-id";
+pub const QUEUE_HEADER: &str = "x-sqs-queue";
+const FIFO_QUEUE_SUFFIX: &str = ".fifo";
+
+fn queue_name(message: &SqsMessage) -> &str {
+    message.event_source_arn.as_deref().map(arn_resource).unwrap_or_default()
+}
+
+/// Turns a message into the request the queue's flow runs on. String message attributes become
+/// headers of the same name, next to the message id and queue name.
+fn to_proxy_request(message: SqsMessage, path: &str) -> ApiGatewayProxyRequest {
+    let mut headers = HeaderMap::new();
+    for (name, attribute) in &message.message_attributes {
+        if let Some(value) = &attribute.string_value {
+            insert_header(&mut headers, name, value);
+        }
+    }
+    insert_header(&mut headers, QUEUE_HEADER, queue_name(&message));
+    if let Some(message_id) = &message.message_id {
+        insert_header(&mut headers, MESSAGE_ID_HEADER, message_id);
+    }
+    event_request(path, headers, message.body)
+}
+
+/// Runs every message of the batch through the flow configured for its queue, one after the
+/// other, and reports the messages whose flow did not end with a 2xx status as failed so SQS
+/// only redelivers those. Once a message of a FIFO queue fails, the rest of the batch is
+/// reported as failed without running, to keep the messages in order.
+pub async fn run_batch<F, Fut>(
+    event: SqsEvent,
+    routes: &EventRoutes,
+    mut process: F,
+) -> SqsBatchResponse
+where
+    F: FnMut(ApiGatewayProxyRequest) -> Fut,
+    Fut: Future<Output = ApiGatewayProxyResponse>,
+{
+    let mut batch_item_failures = vec![];
+    let mut fifo_failed = false;
+    for message in event.records {
+        let Some(message_id) = message.message_id.clone() else {
+            tracing::warn!("Skipping SQS message without a message id");
+            continue;
+        };
+        let queue = queue_name(&message).to_string();
+        let succeeded = match EventRoutes::find(&routes.sqs, &queue) {
+            _ if fifo_failed => false,
+            None => {
+                tracing::error!("No flow configured for messages from queue {}", queue);
+                false
+            }
+            Some(path) => is_success(&process(to_proxy_request(message, path)).await),
+        };
+        if !succeeded {
+            fifo_failed = queue.ends_with(FIFO_QUEUE_SUFFIX);
+            batch_item_failures.push(BatchItemFailure {
+                item_identifier: message_id,
+            });
+        }
+    }
+    SqsBatchResponse {
+        batch_item_failures,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::sqs::{run_batch, MESSAGE_ID_HEADER, QUEUE_HEADER};
+    use crate::event::{EventKind, EventRoutes};
+    use aws_lambda_events::sqs::SqsEvent;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use serde_json::{json, Value};
+
+    fn sqs_event(queue: &str, bodies: &[&str]) -> Value {
+        let records: Vec<Value> = bodies
+            .iter()
+            .enumerate()
+            .map(|(index, body)| {
+                json!({
+                    "messageId": format!("message-{}", index),
+                    "body": body,
+                    "messageAttributes": {
+                        "tenant": { "stringValue": "acme", "dataType": "String" }
+                    },
+                    "eventSource": "aws:sqs",
+                    "eventSourceARN": format!("arn:aws:sqs:us-east-1:123456789012:{}", queue),
+                })
+            })
+            .collect();
+        json!({ "Records": records })
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_sqs_batch_item_failures() {
+        let routes = EventRoutes {
+            sqs: [("orders".to_string(), "/events/orders".to_string())].into(),
+            ..Default::default()
+        };
+        let event = sqs_event("orders", &["ok", "bad", "ok"]);
+        assert_eq!(EventKind::detect(&event), EventKind::Sqs);
+        let event: SqsEvent = serde_json::from_value(event).unwrap();
+        let response = run_batch(event, &routes, |request| async move {
+            assert_eq!(request.path.as_deref(), Some("/events/orders"));
+            assert_eq!(request.headers["tenant"], "acme");
+            assert_eq!(request.headers[QUEUE_HEADER], "orders");
+            assert!(request.headers.contains_key(MESSAGE_ID_HEADER));
+            let status_code = match request.body.as_deref() {
+                Some("ok") => 200,
+                _ => 400,
+            };
+            ApiGatewayProxyResponse {
+                status_code,
+                ..Default::default()
+            }
+        })
+        .await;
+        let failed: Vec<&str> = response
+            .batch_item_failures
+            .iter()
+            .map(|failure| failure.item_identifier.as_str())
+            .collect();
+        assert_eq!(failed, vec!["message-1"]);
+
+        /* after a failure, the rest of a FIFO batch is not processed */
+        let event = sqs_event("orders.fifo", &["ok", "bad", "ok"]);
+        let event: SqsEvent = serde_json::from_value(event).unwrap();
+        let routes = EventRoutes {
+            sqs: [("*".to_string(), "/events/orders".to_string())].into(),
+            ..Default::default()
+        };
+        let mut processed = 0;
+        let response = run_batch(event, &routes, |request| {
+            processed += 1;
+            let status_code = match request.body.as_deref() {
+                Some("ok") => 200,
+                _ => 500,
+            };
+            async move {
+                ApiGatewayProxyResponse {
+                    status_code,
+                    ..Default::default()
+                }
+            }
+        })
+        .await;
+        assert_eq!(processed, 2);
+        assert_eq!(response.batch_item_failures.len(), 2);
+    }
+}
@@ -0,0 +1,106 @@
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::event::{EventResponse, GatewayResponse, PayloadFormat};
+
+/// How scheduled warm-up pings are told apart from real traffic. Any configured marker is
+/// enough: a request for `path`, a request carrying the `header`, or an event whose `source`
+/// matches, like `aws.events` for a scheduled EventBridge rule.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct WarmupConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /* run the handlers' warm-up hooks, e.g. to fetch keys or compile validators */
+    #[serde(default)]
+    pub prewarm: bool,
+}
+
+/// What kind of warm-up ping an event is, which decides the shape of the answer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Warmup {
+    /* sent through API Gateway, by path or header */
+    Request,
+    /* sent by a schedule or invoked directly, by source */
+    Event,
+}
+
+impl WarmupConfig {
+    pub fn is_empty(&self) -> bool {
+        self.path.is_none() && self.header.is_none() && self.source.is_none()
+    }
+
+    pub fn detect(&self, event: &Value) -> Option<Warmup> {
+        let source = event.get("source").and_then(Value::as_str);
+        if self.source.is_some() && self.source.as_deref() == source {
+            return Some(Warmup::Event);
+        }
+        /* REST APIs send `path`, HTTP APIs `rawPath` */
+        let path = event
+            .get("path")
+            .or_else(|| event.get("rawPath"))
+            .and_then(Value::as_str);
+        if self.path.is_some() && self.path.as_deref() == path {
+            return Some(Warmup::Request);
+        }
+        let headers = event.get("headers").and_then(Value::as_object);
+        let has_header = match (&self.header, headers) {
+            (Some(header), Some(headers)) => {
+                headers.keys().any(|name| name.eq_ignore_ascii_case(header))
+            }
+            _ => false,
+        };
+        has_header.then_some(Warmup::Request)
+    }
+}
+
+impl Warmup {
+    /// The answer to the ping, without running any chain.
+    pub fn response(&self, event: &Value, format: PayloadFormat) -> EventResponse {
+        let format = match format {
+            PayloadFormat::Auto => match event.get("version").and_then(Value::as_str) {
+                Some("2.0") => PayloadFormat::V2,
+                _ => PayloadFormat::V1,
+            },
+            format => format,
+        };
+        match self {
+            Warmup::Event => EventResponse::Empty,
+            Warmup::Request => {
+                let response = ApiGatewayProxyResponse {
+                    status_code: 200,
+                    ..Default::default()
+                };
+                EventResponse::Gateway(Box::new(GatewayResponse::new(response, format)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::warmup::{Warmup, WarmupConfig};
+    use serde_json::json;
+
+    #[test]
+    fn test_warmup_markers() {
+        let config: WarmupConfig = serde_json::from_value(json!({
+            "path": "/warmup",
+            "header": "X-Warmup",
+            "source": "serverless-plugin-warmup"
+        }))
+        .unwrap();
+        let scheduled = json!({ "source": "serverless-plugin-warmup" });
+        assert_eq!(config.detect(&scheduled), Some(Warmup::Event));
+        let by_path = json!({ "version": "2.0", "rawPath": "/warmup", "headers": {} });
+        assert_eq!(config.detect(&by_path), Some(Warmup::Request));
+        let by_header = json!({ "path": "/pets", "headers": { "x-warmup": "1" } });
+        assert_eq!(config.detect(&by_header), Some(Warmup::Request));
+        let request = json!({ "path": "/pets", "headers": { "accept": "*/*" } });
+        assert_eq!(config.detect(&request), None);
+        assert_eq!(WarmupConfig::default().detect(&json!({})), None);
+    }
+}
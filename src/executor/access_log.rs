@@ -0,0 +1,257 @@
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::Body;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::executor::budget::over_budget;
+use crate::executor::sampling::is_recorded;
+use crate::flow::matcher::matched_route;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::traceability::{CORRELATION_ID_ATTACHMENT_KEY, TRACEABILITY_ID_ATTACHMENT_KEY};
+use crate::handler::LambdaExchange;
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogField {
+    /* milliseconds since the epoch */
+    Timestamp,
+    RequestId,
+    CorrelationId,
+    TraceabilityId,
+    Method,
+    Path,
+    Route,
+    Query,
+    Headers,
+    SourceIp,
+    UserAgent,
+    Status,
+    DurationMs,
+    RequestBytes,
+    ResponseBytes,
+}
+
+/// Writes one JSON line per request to stdout once its response is ready:
+///
+/// ```json
+/// { "fields": ["method", "path", "status", "duration_ms", "headers"],
+///   "redact": ["authorization", "token"] }
+/// ```
+///
+/// Header and query parameter values named in `redact` are logged as `[REDACTED]`. Which
+/// requests are logged is up to the flow's `sampling`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    pub fields: Vec<AccessLogField>,
+    /* header and query parameter names, case-insensitive */
+    pub redact: Vec<String>,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fields: vec![
+                AccessLogField::Timestamp,
+                AccessLogField::RequestId,
+                AccessLogField::CorrelationId,
+                AccessLogField::Method,
+                AccessLogField::Path,
+                AccessLogField::Route,
+                AccessLogField::Status,
+                AccessLogField::DurationMs,
+                AccessLogField::SourceIp,
+                AccessLogField::UserAgent,
+                AccessLogField::ResponseBytes,
+            ],
+            redact: vec![
+                "authorization".to_string(),
+                "cookie".to_string(),
+                "x-api-key".to_string(),
+            ],
+        }
+    }
+}
+
+fn body_len(body: Option<&Body>) -> usize {
+    match body {
+        Some(Body::Text(text)) => text.len(),
+        Some(Body::Binary(bytes)) => bytes.len(),
+        _ => 0,
+    }
+}
+
+impl AccessLogConfig {
+    fn has(&self, field: AccessLogField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    fn redacted(&self, name: &str, value: &str) -> Value {
+        match self.redact.iter().any(|redacted| redacted.eq_ignore_ascii_case(name)) {
+            true => json!(REDACTED),
+            false => json!(value),
+        }
+    }
+
+    /// The fields taken from the request, before handlers get to change it.
+    pub(crate) fn request_fields(&self, request: &ApiGatewayProxyRequest) -> Map<String, Value> {
+        let mut entry = Map::new();
+        if self.has(AccessLogField::RequestId) {
+            entry.insert("request_id".into(), json!(request.request_context.request_id));
+        }
+        if self.has(AccessLogField::Method) {
+            entry.insert("method".into(), json!(request.http_method.as_str()));
+        }
+        if self.has(AccessLogField::Path) {
+            entry.insert("path".into(), json!(request.path));
+        }
+        if self.has(AccessLogField::Query) {
+            let query: Map<String, Value> = request
+                .query_string_parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), self.redacted(name, value)))
+                .collect();
+            entry.insert("query".into(), Value::Object(query));
+        }
+        if self.has(AccessLogField::Headers) {
+            let headers: Map<String, Value> = request
+                .headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+                .map(|(name, value)| (name.to_string(), self.redacted(name, value)))
+                .collect();
+            entry.insert("headers".into(), Value::Object(headers));
+        }
+        if self.has(AccessLogField::SourceIp) {
+            entry.insert("source_ip".into(), json!(request.request_context.identity.source_ip));
+        }
+        if self.has(AccessLogField::UserAgent) {
+            let user_agent = request
+                .headers
+                .get("user-agent")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+                .or_else(|| request.request_context.identity.user_agent.clone());
+            entry.insert("user_agent".into(), json!(user_agent));
+        }
+        if self.has(AccessLogField::RequestBytes) {
+            let request_bytes = request.body.as_ref().map_or(0, String::len);
+            entry.insert("request_bytes".into(), json!(request_bytes));
+        }
+        entry
+    }
+
+    /// Completes the entry with what the chain produced, `None` when the request isn't sampled.
+    pub(crate) fn finish(
+        &self,
+        mut entry: Map<String, Value>,
+        exchange: &LambdaExchange,
+        response: &ApiGatewayProxyResponse,
+        duration: Duration,
+    ) -> Option<Map<String, Value>> {
+        if !is_recorded(exchange, response.status_code >= 400) {
+            return None;
+        }
+        let well_known = exchange.attachments().well_known();
+        if self.has(AccessLogField::Timestamp) {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            entry.insert("timestamp".into(), json!(now.as_millis() as u64));
+        }
+        if self.has(AccessLogField::CorrelationId) {
+            let correlation_id = well_known.get::<String>(CORRELATION_ID_ATTACHMENT_KEY);
+            entry.insert("correlation_id".into(), json!(correlation_id));
+        }
+        if self.has(AccessLogField::TraceabilityId) {
+            let traceability_id = well_known.get::<String>(TRACEABILITY_ID_ATTACHMENT_KEY);
+            entry.insert("traceability_id".into(), json!(traceability_id));
+        }
+        if self.has(AccessLogField::Route) {
+            entry.insert("route".into(), json!(matched_route(exchange)));
+        }
+        if self.has(AccessLogField::Status) {
+            entry.insert("status".into(), json!(response.status_code));
+        }
+        if self.has(AccessLogField::DurationMs) {
+            entry.insert("duration_ms".into(), json!(duration.as_secs_f64() * 1000.0));
+        }
+        if self.has(AccessLogField::ResponseBytes) {
+            entry.insert("response_bytes".into(), json!(body_len(response.body.as_ref())));
+        }
+        let over_budget = over_budget(exchange);
+        if !over_budget.is_empty() {
+            entry.insert("over_budget".into(), json!(over_budget));
+        }
+        Some(entry)
+    }
+}
+
+pub(crate) fn write_access_log(entry: Map<String, Value>) {
+    println!("{}", Value::Object(entry));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::access_log::{AccessLogConfig, AccessLogField};
+    use crate::executor::sampling::{set_sampling, Sampling};
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use idemio::exchange::Exchange;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::Body;
+    use std::time::Duration;
+
+    #[test]
+    fn test_access_log_entry() {
+        let config = AccessLogConfig {
+            fields: vec![
+                AccessLogField::Method,
+                AccessLogField::Path,
+                AccessLogField::Query,
+                AccessLogField::Headers,
+                AccessLogField::Status,
+                AccessLogField::ResponseBytes,
+            ],
+            redact: vec!["Authorization".to_string(), "token".to_string()],
+            ..Default::default()
+        };
+        let request = RequestBuilder::get("/pets")
+            .header("authorization", "Bearer secret")
+            .header("accept", "application/json")
+            .query("token", "abc")
+            .query("limit", "10")
+            .build();
+        let entry = config.request_fields(&request);
+        assert_eq!(entry["method"], "GET");
+        assert_eq!(entry["path"], "/pets");
+        assert_eq!(entry["query"]["token"], "[REDACTED]");
+        assert_eq!(entry["query"]["limit"], "10");
+        assert_eq!(entry["headers"]["authorization"], "[REDACTED]");
+        assert_eq!(entry["headers"]["accept"], "application/json");
+
+        let mut exchange: LambdaExchange = Exchange::new();
+        set_sampling(
+            &mut exchange,
+            Sampling {
+                sampled: false,
+                always_on_errors: true,
+            },
+        );
+        let mut response = ApiGatewayProxyResponse {
+            status_code: 200,
+            ..Default::default()
+        };
+        /* sampled out */
+        assert!(config.finish(entry.clone(), &exchange, &response, Duration::ZERO).is_none());
+
+        response.status_code = 502;
+        response.body = Some(Body::Text("bad gateway".to_string()));
+        let entry = config.finish(entry, &exchange, &response, Duration::ZERO).unwrap();
+        assert_eq!(entry["status"], 502);
+        assert_eq!(entry["response_bytes"], 11);
+        assert!(!entry.contains_key("duration_ms"));
+    }
+}
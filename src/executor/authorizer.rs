@@ -0,0 +1,95 @@
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+
+const AUTHORIZE_ONLY_ATTACHMENT_KEY: &str = "authorize_only";
+const AUTHORIZER_OUTPUT_ATTACHMENT_KEY: &str = "authorizer_output";
+
+/// What an authorizing chain found out about the caller: the principal and the context values
+/// API Gateway hands to the integration. Authentication handlers fill it in as they succeed.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizerOutput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal_id: Option<String>,
+    #[serde(default)]
+    pub context: BTreeMap<String, Value>,
+}
+
+/// Marks the exchange as an authorization: the executor stops after the request phase and
+/// answers with the [`AuthorizerOutput`] instead of running the termination handler.
+pub fn set_authorize_only(exchange: &mut LambdaExchange) {
+    exchange
+        .attachments_mut()
+        .well_known_mut()
+        .add::<bool>(AUTHORIZE_ONLY_ATTACHMENT_KEY, true);
+}
+
+pub fn is_authorize_only(exchange: &LambdaExchange) -> bool {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<bool>(AUTHORIZE_ONLY_ATTACHMENT_KEY)
+        .is_some_and(|authorize_only| *authorize_only)
+}
+
+fn authorizer_output_mut(exchange: &mut LambdaExchange) -> &mut AuthorizerOutput {
+    let mut attachments = exchange.attachments_mut().well_known_mut();
+    if attachments
+        .get::<AuthorizerOutput>(AUTHORIZER_OUTPUT_ATTACHMENT_KEY)
+        .is_none()
+    {
+        attachments.add(AUTHORIZER_OUTPUT_ATTACHMENT_KEY, AuthorizerOutput::default());
+    }
+    attachments
+        .into_mut::<AuthorizerOutput>(AUTHORIZER_OUTPUT_ATTACHMENT_KEY)
+        .unwrap()
+}
+
+pub fn set_principal(exchange: &mut LambdaExchange, principal_id: impl Into<String>) {
+    authorizer_output_mut(exchange).principal_id = Some(principal_id.into());
+}
+
+pub fn principal(exchange: &LambdaExchange) -> Option<&str> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<AuthorizerOutput>(AUTHORIZER_OUTPUT_ATTACHMENT_KEY)?
+        .principal_id
+        .as_deref()
+}
+
+/// Adds a context value. API Gateway only passes strings, numbers and booleans on.
+pub fn add_authorizer_context(exchange: &mut LambdaExchange, key: impl Into<String>, value: Value) {
+    if value.is_string() || value.is_number() || value.is_boolean() {
+        authorizer_output_mut(exchange).context.insert(key.into(), value);
+    }
+}
+
+/// A context value an authentication handler added, like a claim of the caller's token.
+pub fn authorizer_context<'a>(exchange: &'a LambdaExchange, key: &str) -> Option<&'a Value> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<AuthorizerOutput>(AUTHORIZER_OUTPUT_ATTACHMENT_KEY)?
+        .context
+        .get(key)
+}
+
+/* the response of a chain whose request phase authorized the caller */
+pub(crate) fn authorized_response(exchange: &LambdaExchange) -> ApiGatewayProxyResponse {
+    let output = exchange
+        .attachments()
+        .well_known()
+        .get::<AuthorizerOutput>(AUTHORIZER_OUTPUT_ATTACHMENT_KEY)
+        .cloned()
+        .unwrap_or_default();
+    ApiGatewayProxyResponse {
+        status_code: 200,
+        body: serde_json::to_string(&output).ok().map(Into::into),
+        ..Default::default()
+    }
+}
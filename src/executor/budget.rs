@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::config::units::{
+    deserialize_duration_map, deserialize_optional_duration, serialize_duration_map,
+    serialize_optional_duration,
+};
+use crate::executor::HandlerTiming;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+
+/* shared with the access log and audit records, in the well-known namespace */
+const OVER_BUDGET_ATTACHMENT_KEY: &str = "over_budget";
+/* stands for the whole request among the handler names that went over budget */
+pub const TOTAL_BUDGET: &str = "total";
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+/// How long a flow's requests may take, in total and in single handlers, before they count as
+/// slow: `{ "total": "800ms", "handlers": { "LambdaProxyHandler": "600ms" } }`.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct LatencyBudget {
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_duration",
+        serialize_with = "serialize_optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub total: Option<Duration>,
+    /* by handler name */
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_map",
+        serialize_with = "serialize_duration_map",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub handlers: BTreeMap<String, Duration>,
+    /* report every handler's duration in a Server-Timing response header */
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub server_timing: bool,
+}
+
+impl LatencyBudget {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// What took longer than its budget: `total` for the whole request, and handler names.
+    pub fn overruns(&self, total: Duration, timings: &[HandlerTiming]) -> Vec<String> {
+        let mut overruns = vec![];
+        if self.total.is_some_and(|budget| total > budget) {
+            overruns.push(TOTAL_BUDGET.to_string());
+        }
+        for timing in timings {
+            let over = self
+                .handlers
+                .get(&timing.handler_name)
+                .is_some_and(|budget| timing.duration > *budget);
+            if over && !overruns.contains(&timing.handler_name) {
+                overruns.push(timing.handler_name.clone());
+            }
+        }
+        overruns
+    }
+}
+
+/// The `Server-Timing` header value for the handlers that ran, e.g. `Jwt;dur=1.250`.
+pub fn server_timing(timings: &[HandlerTiming]) -> String {
+    timings
+        .iter()
+        .map(|timing| {
+            format!(
+                "{};dur={:.3}",
+                timing.handler_name,
+                timing.duration.as_secs_f64() * 1000.0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn set_over_budget(exchange: &mut LambdaExchange, overruns: Vec<String>) {
+    exchange
+        .attachments_mut()
+        .well_known_mut()
+        .add::<Vec<String>>(OVER_BUDGET_ATTACHMENT_KEY, overruns);
+}
+
+/// What went over the latency budget of the exchange's flow, empty when it stayed within.
+pub fn over_budget(exchange: &LambdaExchange) -> &[String] {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<Vec<String>>(OVER_BUDGET_ATTACHMENT_KEY)
+        .map_or(&[], Vec::as_slice)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::budget::{server_timing, LatencyBudget};
+    use crate::executor::HandlerTiming;
+    use idemio::status::ExchangeState;
+    use std::time::Duration;
+
+    #[test]
+    fn test_latency_budget_overruns() {
+        let budget: LatencyBudget = serde_json::from_str(
+            r#"{ "total": "100ms", "handlers": { "Proxy": 50, "Jwt": "10ms" } }"#,
+        )
+        .unwrap();
+        let timings = [
+            HandlerTiming {
+                handler_name: "Jwt".to_string(),
+                duration: Duration::from_millis(5),
+                status: ExchangeState::OK,
+            },
+            HandlerTiming {
+                handler_name: "Proxy".to_string(),
+                duration: Duration::from_millis(80),
+                status: ExchangeState::EXCHANGE_COMPLETED,
+            },
+        ];
+        assert_eq!(budget.overruns(Duration::from_millis(90), &timings), vec!["Proxy"]);
+        assert_eq!(
+            budget.overruns(Duration::from_millis(120), &timings),
+            vec!["total", "Proxy"]
+        );
+        assert_eq!(server_timing(&timings), "Jwt;dur=5.000, Proxy;dur=80.000");
+        assert_eq!(
+            serde_json::to_value(&budget).unwrap(),
+            serde_json::json!({ "total": "100ms", "handlers": { "Jwt": "10ms", "Proxy": "50ms" } })
+        );
+    }
+}
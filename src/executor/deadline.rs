@@ -0,0 +1,90 @@
+use lambda_http::Context;
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime};
+use crate::handler::LambdaExchange;
+
+const DEADLINE_ATTACHMENT_KEY: &str = "deadline";
+
+/* Time kept back from the Lambda deadline to turn a timed out handler into a response. */
+pub const DEADLINE_SAFETY_MARGIN: Duration = Duration::from_millis(100);
+
+/// The point in time by which the exchange must have its response. Handlers doing long work
+/// (remote calls, aggregation) can race it against [`Deadline::expired`] or wrap the work in
+/// [`Deadline::run`] and return `TIMEOUT` when it wins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    pub fn after(duration: Duration) -> Self {
+        Self {
+            at: Instant::now() + duration,
+        }
+    }
+
+    /// The invocation deadline of `context`, less the safety margin.
+    pub fn from_context(context: &Context) -> Self {
+        let remaining = context
+            .deadline()
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        Self::after(remaining.saturating_sub(DEADLINE_SAFETY_MARGIN))
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Completes once the deadline has passed.
+    pub async fn expired(&self) {
+        tokio::time::sleep_until(self.at.into()).await
+    }
+
+    /// Runs `future` until it completes or the deadline passes, whichever is first.
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, ()> {
+        tokio::time::timeout_at(self.at.into(), future)
+            .await
+            .map_err(|_| ())
+    }
+}
+
+pub fn set_deadline(exchange: &mut LambdaExchange, deadline: Deadline) {
+    let attachments = exchange.attachments_mut();
+    match attachments.get_mut::<Deadline>(DEADLINE_ATTACHMENT_KEY) {
+        Some(current) => *current = deadline,
+        None => attachments.add::<Deadline>(DEADLINE_ATTACHMENT_KEY, deadline),
+    }
+}
+
+/// The deadline of the exchange, when it was created for a Lambda invocation.
+pub fn deadline(exchange: &LambdaExchange) -> Option<Deadline> {
+    exchange
+        .attachments()
+        .get::<Deadline>(DEADLINE_ATTACHMENT_KEY)
+        .copied()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::deadline::{deadline, set_deadline, Deadline};
+    use crate::handler::LambdaExchange;
+    use idemio::exchange::Exchange;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_deadline() {
+        let mut exchange: LambdaExchange = Exchange::new();
+        assert!(deadline(&exchange).is_none());
+        set_deadline(&mut exchange, Deadline::after(Duration::from_millis(10)));
+        let deadline = deadline(&exchange).unwrap();
+        assert!(!deadline.is_expired());
+        assert!(deadline.run(tokio::time::sleep(Duration::from_secs(5))).await.is_err());
+        assert!(deadline.is_expired());
+        assert!(deadline.run(async { 1 }).await.is_ok());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use idemio::status::ExchangeState;
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use lambda_http::http::header::CONTENT_TYPE;
+use lambda_http::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::error::HandlerError;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::traceability::CORRELATION_ID_ATTACHMENT_KEY;
+use crate::handler::LambdaExchange;
+
+const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum StatusClass {
+    ClientError,
+    ServerError,
+    Timeout,
+    /// The termination handler of the route is disabled.
+    Disabled,
+    /// No route matches the request's path.
+    NotFound,
+    /// A route matches the path, but not the method.
+    MethodNotAllowed,
+}
+
+impl StatusClass {
+    pub fn of(code: ExchangeState) -> Option<Self> {
+        if code.any_flags(ExchangeState::TIMEOUT) {
+            Some(StatusClass::Timeout)
+        } else if code.any_flags(ExchangeState::CLIENT_ERROR) {
+            Some(StatusClass::ClientError)
+        } else if code.any_flags(ExchangeState::SERVER_ERROR) {
+            Some(StatusClass::ServerError)
+        } else if code.any_flags(ExchangeState::DISABLED) {
+            Some(StatusClass::Disabled)
+        } else {
+            None
+        }
+    }
+}
+
+/// What went wrong, as far as it is safe to tell the caller. `status_class` is `None` when the
+/// chain failed outside of a terminal handler status (unknown state, missing output, router errors).
+pub struct ErrorContext<'a> {
+    pub status_class: Option<StatusClass>,
+    pub handler_name: Option<&'a str>,
+    pub correlation_id: &'a str,
+    /* set when the failing handler raised an error from the catalog */
+    pub error: Option<&'a HandlerError>,
+}
+
+pub trait ErrorHandler: Send + Sync {
+    fn handle(&self, context: &ErrorContext<'_>) -> ApiGatewayProxyResponse;
+}
+
+/// Builds an RFC 7807 `application/problem+json` response. Only the configured title and detail
+/// are returned; handler messages stay in the logs. Errors raised from the catalog are returned
+/// as they are, with their own status, code and detail.
+pub struct ProblemJsonErrorHandler {
+    pub status_code: i64,
+    pub title: String,
+    pub detail: Option<String>,
+}
+
+impl ProblemJsonErrorHandler {
+    pub fn new(status_code: i64, title: impl Into<String>) -> Self {
+        Self {
+            status_code,
+            title: title.into(),
+            detail: None,
+        }
+    }
+}
+
+impl ErrorHandler for ProblemJsonErrorHandler {
+    fn handle(&self, context: &ErrorContext<'_>) -> ApiGatewayProxyResponse {
+        if let Some(error) = context.error {
+            return problem_response(error.code.status_code, error.problem(context.correlation_id));
+        }
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": self.title,
+            "status": self.status_code,
+            "correlationId": context.correlation_id,
+        });
+        if let Some(detail) = &self.detail {
+            problem["detail"] = json!(detail);
+        }
+        problem_response(self.status_code, problem)
+    }
+}
+
+fn problem_response(status_code: i64, problem: Value) -> ApiGatewayProxyResponse {
+    let mut response = ApiGatewayProxyResponse {
+        status_code,
+        body: Some(Body::Text(problem.to_string())),
+        ..Default::default()
+    };
+    response
+        .headers
+        .insert(CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE.parse().unwrap());
+    response
+}
+
+/// Error handlers per status class, plus a fallback for everything else. Used by the executor
+/// whenever a chain ends in an error without a handler having set its own response.
+pub struct ExceptionHandlers {
+    handlers: HashMap<StatusClass, Box<dyn ErrorHandler>>,
+    fallback: Box<dyn ErrorHandler>,
+}
+
+impl Default for ExceptionHandlers {
+    fn default() -> Self {
+        Self::new(ProblemJsonErrorHandler::new(500, "Internal Server Error"))
+            .handler(StatusClass::ClientError, ProblemJsonErrorHandler::new(400, "Bad Request"))
+            .handler(StatusClass::ServerError, ProblemJsonErrorHandler::new(500, "Internal Server Error"))
+            .handler(StatusClass::Timeout, ProblemJsonErrorHandler::new(504, "Gateway Timeout"))
+            .handler(StatusClass::Disabled, ProblemJsonErrorHandler::new(503, "Service Unavailable"))
+            .handler(StatusClass::NotFound, ProblemJsonErrorHandler::new(404, "Not Found"))
+            .handler(
+                StatusClass::MethodNotAllowed,
+                ProblemJsonErrorHandler::new(405, "Method Not Allowed"),
+            )
+    }
+}
+
+impl ExceptionHandlers {
+    pub fn new(fallback: impl ErrorHandler + 'static) -> Self {
+        Self {
+            handlers: HashMap::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    pub fn fallback(mut self, fallback: impl ErrorHandler + 'static) -> Self {
+        self.fallback = Box::new(fallback);
+        self
+    }
+
+    pub fn handler(mut self, status_class: StatusClass, handler: impl ErrorHandler + 'static) -> Self {
+        self.handlers.insert(status_class, Box::new(handler));
+        self
+    }
+
+    pub fn handle(&self, context: &ErrorContext<'_>) -> ApiGatewayProxyResponse {
+        let handler = context
+            .status_class
+            .and_then(|status_class| self.handlers.get(&status_class))
+            .unwrap_or(&self.fallback);
+        handler.handle(context)
+    }
+}
+
+/// The traceability handler's correlation id when it ran, otherwise the exchange id.
+pub(crate) fn correlation_id(exchange: &LambdaExchange) -> String {
+    match exchange
+        .attachments()
+        .well_known()
+        .get::<String>(CORRELATION_ID_ATTACHMENT_KEY)
+    {
+        Some(correlation_id) => correlation_id.clone(),
+        None => exchange.uuid().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::exception::{ErrorContext, ExceptionHandlers, StatusClass};
+    use idemio::status::ExchangeState;
+    use lambda_http::Body;
+    use serde_json::Value;
+
+    #[test]
+    fn test_status_class_responses() {
+        assert_eq!(StatusClass::of(ExchangeState::CLIENT_ERROR), Some(StatusClass::ClientError));
+        assert_eq!(
+            StatusClass::of(ExchangeState::SERVER_ERROR | ExchangeState::TIMEOUT),
+            Some(StatusClass::Timeout)
+        );
+        assert_eq!(StatusClass::of(ExchangeState::OK), None);
+
+        let handlers = ExceptionHandlers::default();
+        let response = handlers.handle(&ErrorContext {
+            status_class: Some(StatusClass::Timeout),
+            handler_name: Some("LambdaProxyHandler"),
+            correlation_id: "abc123",
+            error: None,
+        });
+        assert_eq!(response.status_code, 504);
+        assert_eq!(response.headers["content-type"], "application/problem+json");
+        let Some(Body::Text(body)) = response.body else {
+            panic!("problem response has no body");
+        };
+        let problem: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(problem["correlationId"], "abc123");
+        assert_eq!(problem["status"], 504);
+
+        let fallback = handlers.handle(&ErrorContext {
+            status_class: None,
+            handler_name: None,
+            correlation_id: "abc123",
+            error: None,
+        });
+        assert_eq!(fallback.status_code, 500);
+    }
+}
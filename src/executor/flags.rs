@@ -0,0 +1,138 @@
+use idemio::status::ExchangeState;
+
+/* idemio uses the low bits; the bits below USER_FLAG_OFFSET stay reserved for it */
+const USER_FLAG_OFFSET: u32 = 16;
+const USER_FLAG_COUNT: u32 = 15;
+
+const BUILTIN_FLAGS: &[(&str, ExchangeState)] = &[
+    ("OK", ExchangeState::OK),
+    ("EXCHANGE_COMPLETED", ExchangeState::EXCHANGE_COMPLETED),
+    ("SERVER_ERROR", ExchangeState::SERVER_ERROR),
+    ("CLIENT_ERROR", ExchangeState::CLIENT_ERROR),
+    ("DISABLED", ExchangeState::DISABLED),
+    ("TIMEOUT", ExchangeState::TIMEOUT),
+    ("CONTINUE", ExchangeState::CONTINUE),
+];
+
+/// A domain-specific disposition a handler can add to its status, e.g.
+///
+/// ```ignore
+/// const CACHED: UserFlag = UserFlag::new("CACHED", 0);
+/// HandlerStatus::new(ExchangeState::OK | CACHED.state())
+/// ```
+///
+/// User flags live in their own bit range, so they never change how idemio reads the status.
+/// The bit is part of the flag's identity: keep it stable once chains refer to the name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserFlag {
+    name: &'static str,
+    state: ExchangeState,
+}
+
+impl UserFlag {
+    /// Creates the flag for `bit` of the user range (0 to 14).
+    pub const fn new(name: &'static str, bit: u32) -> Self {
+        assert!(bit < USER_FLAG_COUNT, "user flag bit out of range");
+        Self {
+            name,
+            state: ExchangeState(1 << (USER_FLAG_OFFSET + bit)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn state(&self) -> ExchangeState {
+        self.state
+    }
+
+    pub fn is_set(&self, code: ExchangeState) -> bool {
+        code.any_flags(self.state)
+    }
+}
+
+/// The user flags known to the gateway, used to name status flags in config and in traces.
+#[derive(Default, Clone, Debug)]
+pub struct StatusFlags {
+    flags: Vec<UserFlag>,
+}
+
+impl StatusFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, flag: UserFlag) -> Result<&mut Self, String> {
+        if self.find(flag.name).is_some() {
+            return Err(format!("Status flag {} is already defined", flag.name));
+        }
+        if let Some(existing) = self.flags.iter().find(|f| f.state == flag.state) {
+            return Err(format!("{} uses the same bit as {}", flag.name, existing.name));
+        }
+        self.flags.push(flag);
+        Ok(self)
+    }
+
+    fn all(&self) -> impl Iterator<Item = (&'static str, ExchangeState)> + '_ {
+        BUILTIN_FLAGS
+            .iter()
+            .copied()
+            .chain(self.flags.iter().map(|flag| (flag.name, flag.state)))
+    }
+
+    /// The flag with this name, built-in or user-defined.
+    pub fn find(&self, name: &str) -> Option<ExchangeState> {
+        self.all()
+            .find(|(flag_name, _)| *flag_name == name)
+            .map(|(_, state)| state)
+    }
+
+    /// The names of the flags set in `code`, lowest bit first. Unknown bits are named `BIT<n>`.
+    pub fn names(&self, code: ExchangeState) -> Vec<String> {
+        (0..i32::BITS)
+            .map(|bit| ExchangeState(1 << bit))
+            .filter(|flag| code.any_flags(*flag))
+            .map(|flag| {
+                self.all()
+                    .find(|(_, state)| *state == flag)
+                    .map(|(name, _)| name.to_string())
+                    .unwrap_or_else(|| format!("BIT{}", flag.0.trailing_zeros()))
+            })
+            .collect()
+    }
+
+    /// The state with every named flag set; the inverse of [`StatusFlags::names`].
+    pub fn parse(&self, names: &[impl AsRef<str>]) -> Result<ExchangeState, String> {
+        names.iter().try_fold(ExchangeState(0), |state, name| {
+            match self.find(name.as_ref()) {
+                Some(flag) => Ok(state | flag),
+                None => Err(format!("Unknown status flag {}", name.as_ref())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::flags::{StatusFlags, UserFlag};
+    use idemio::status::ExchangeState;
+
+    const CACHED: UserFlag = UserFlag::new("CACHED", 0);
+    const SHADOWED: UserFlag = UserFlag::new("SHADOWED", 1);
+
+    #[test]
+    fn test_user_flags() {
+        let mut flags = StatusFlags::new();
+        flags.register(CACHED).unwrap().register(SHADOWED).unwrap();
+        assert!(flags.register(UserFlag::new("OTHER", 0)).is_err());
+
+        let code = ExchangeState::OK | CACHED.state();
+        assert!(code.is_in_flight());
+        assert!(CACHED.is_set(code));
+        assert!(!SHADOWED.is_set(code));
+        assert_eq!(flags.names(code), vec!["OK", "CACHED"]);
+        assert_eq!(flags.parse(&["OK", "CACHED"]).unwrap(), code);
+        assert!(flags.parse(&["MISSING"]).is_err());
+    }
+}
@@ -0,0 +1,896 @@
+use async_trait::async_trait;
+use idemio::exchange::Attachments;
+use idemio::handler::Handler;
+use idemio::router::executor::{ExecutorError, HandlerExecutor};
+use idemio::router::path::LoadedChain;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Semaphore;
+use tracing::Instrument;
+use crate::audit::{self, take_record};
+use crate::error::{handler_error, HandlerError};
+use crate::executor::access_log::{write_access_log, AccessLogConfig};
+use crate::executor::authorizer::{authorized_response, is_authorize_only};
+use crate::executor::budget::{server_timing, set_over_budget, LatencyBudget, SERVER_TIMING_HEADER};
+use crate::executor::deadline::deadline;
+use crate::executor::flags::StatusFlags;
+use crate::executor::exception::{correlation_id, ErrorContext, ExceptionHandlers, StatusClass};
+use crate::executor::pool::HandlerPool;
+use crate::executor::retry::RetryPolicy;
+use crate::executor::sampling::{set_sampling, Sampling, SamplingConfig};
+use crate::executor::trace::{
+    is_tracing, key_attachments, record_trace, start_trace, write_trace, ChainTraceConfig,
+    TraceDecision, TraceEntry,
+};
+use crate::executor::xray::{is_sampled, record_subsegment, Subsegment};
+use crate::flow::matcher::matched_route;
+use crate::handler::LambdaExchange;
+use crate::metrics::{self, names, Unit};
+
+pub mod access_log;
+pub mod authorizer;
+pub mod budget;
+pub mod deadline;
+pub mod exception;
+pub mod flags;
+pub mod pool;
+pub mod response_mapper;
+pub mod retry;
+pub mod sampling;
+pub mod trace;
+pub mod xray;
+
+const ASYNC_OUTPUT_LISTENERS_ATTACHMENT_KEY: &str = "async_output_listeners";
+
+/// Response-phase work that needs to await something (cache writes, audit submission, metrics
+/// flushes). Listeners run in the order they were added, after the synchronous output listeners.
+#[async_trait]
+pub trait AsyncOutputListener: Send + Sync {
+    async fn on_output(&self, output: &mut ApiGatewayProxyResponse, attachments: &mut Attachments);
+}
+
+type AsyncOutputListeners = Vec<Box<dyn AsyncOutputListener>>;
+
+pub fn add_async_output_listener(
+    exchange: &mut LambdaExchange,
+    listener: impl AsyncOutputListener + 'static,
+) {
+    let attachments = exchange.attachments_mut();
+    match attachments.get_mut::<AsyncOutputListeners>(ASYNC_OUTPUT_LISTENERS_ATTACHMENT_KEY) {
+        Some(listeners) => listeners.push(Box::new(listener)),
+        None => attachments.add::<AsyncOutputListeners>(
+            ASYNC_OUTPUT_LISTENERS_ATTACHMENT_KEY,
+            vec![Box::new(listener)],
+        ),
+    }
+}
+
+/// Seeds the attachments of every exchange before its chain runs, with values the embedding
+/// application knows better than the handlers (tenant config, feature flags, deployment
+/// metadata). An error ends the exchange with that error's response, without running the chain.
+pub type ContextProvider = Box<
+    dyn Fn(&ApiGatewayProxyRequest, &mut Attachments) -> Result<(), HandlerError> + Send + Sync,
+>;
+
+const CONTEXT_PROVIDER_NAME: &str = "ContextProvider";
+
+const HANDLER_TIMINGS_ATTACHMENT_KEY: &str = "handler_timings";
+
+/// How long one handler in the chain took and the status it returned.
+#[derive(Debug, Clone)]
+pub struct HandlerTiming {
+    pub handler_name: String,
+    pub duration: Duration,
+    pub status: ExchangeState,
+}
+
+fn record_handler_timing(exchange: &mut LambdaExchange, timing: HandlerTiming) {
+    let attachments = exchange.attachments_mut();
+    match attachments.get_mut::<Vec<HandlerTiming>>(HANDLER_TIMINGS_ATTACHMENT_KEY) {
+        Some(timings) => timings.push(timing),
+        None => attachments.add::<Vec<HandlerTiming>>(HANDLER_TIMINGS_ATTACHMENT_KEY, vec![timing]),
+    }
+}
+
+/// Timings of every handler that has finished so far, in execution order.
+pub fn handler_timings(exchange: &LambdaExchange) -> &[HandlerTiming] {
+    exchange
+        .attachments()
+        .get::<Vec<HandlerTiming>>(HANDLER_TIMINGS_ATTACHMENT_KEY)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Runs handler chains for API Gateway requests. Works like idemio's `DefaultExecutor`, and also
+/// awaits the exchange's async output listeners when the response is taken. A chain that ends
+/// in an error without a response gets one from the exception handlers instead of failing.
+/// Timeouts, retries, concurrency limits, tracing and early completion are set on the builder.
+#[derive(Default)]
+pub struct LambdaExecutor {
+    exception_handlers: ExceptionHandlers,
+    handler_timeouts: HashMap<String, Duration>,
+    default_handler_timeout: Option<Duration>,
+    retry_policies: HashMap<String, RetryPolicy>,
+    concurrency_limits: HashMap<String, Arc<Semaphore>>,
+    context_providers: Vec<ContextProvider>,
+    handler_pool: Option<Arc<HandlerPool>>,
+    chain_trace: ChainTraceConfig,
+    access_log: AccessLogConfig,
+    sampling: SamplingConfig,
+    /* by route, then method */
+    latency_budgets: BTreeMap<String, BTreeMap<String, LatencyBudget>>,
+    status_flags: StatusFlags,
+    complete_on: Option<ExchangeState>,
+}
+
+impl LambdaExecutor {
+    pub fn new(exception_handlers: ExceptionHandlers) -> Self {
+        Self {
+            exception_handlers,
+            ..Default::default()
+        }
+    }
+
+    pub fn handler_timeout(mut self, handler_name: impl Into<String>, timeout: Duration) -> Self {
+        self.handler_timeouts.insert(handler_name.into(), timeout);
+        self
+    }
+
+    pub fn default_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.default_handler_timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry_policy(mut self, handler_name: impl Into<String>, policy: RetryPolicy) -> Self {
+        self.retry_policies.insert(handler_name.into(), policy);
+        self
+    }
+
+    pub fn max_concurrency(mut self, handler_name: impl Into<String>, limit: usize) -> Self {
+        self.concurrency_limits
+            .insert(handler_name.into(), Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// Adds a context provider. Providers run in the order they were added.
+    pub fn context_provider<F>(mut self, provider: F) -> Self
+    where
+        F: Fn(&ApiGatewayProxyRequest, &mut Attachments) -> Result<(), HandlerError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.context_providers.push(Box::new(provider));
+        self
+    }
+
+    /* The request is taken out of the exchange so providers can read it next to the attachments. */
+    async fn provide_context(&self, exchange: &mut LambdaExchange) -> Result<(), HandlerError> {
+        if self.context_providers.is_empty() {
+            return Ok(());
+        }
+        let Ok(request) = exchange.take_input().await else {
+            return Ok(());
+        };
+        let result = self
+            .context_providers
+            .iter()
+            .try_for_each(|provider| provider(&request, exchange.attachments_mut()));
+        exchange.set_input(request);
+        result
+    }
+
+    pub fn handler_pool(mut self, handler_pool: Arc<HandlerPool>) -> Self {
+        self.handler_pool = Some(handler_pool);
+        self
+    }
+
+    pub fn status_flags(mut self, status_flags: StatusFlags) -> Self {
+        self.status_flags = status_flags;
+        self
+    }
+
+    pub fn complete_on(mut self, flags: ExchangeState) -> Self {
+        self.complete_on = Some(self.complete_on.map_or(flags, |current| current | flags));
+        self
+    }
+
+    pub fn chain_trace(mut self, chain_trace: ChainTraceConfig) -> Self {
+        self.chain_trace = chain_trace;
+        self
+    }
+
+    pub fn access_log(mut self, access_log: AccessLogConfig) -> Self {
+        self.access_log = access_log;
+        self
+    }
+
+    pub fn sampling(mut self, sampling: SamplingConfig) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn latency_budgets(
+        mut self,
+        latency_budgets: BTreeMap<String, BTreeMap<String, LatencyBudget>>,
+    ) -> Self {
+        self.latency_budgets = latency_budgets;
+        self
+    }
+
+    fn latency_budget(&self, exchange: &LambdaExchange, method: &str) -> Option<&LatencyBudget> {
+        self.latency_budgets
+            .get(matched_route(exchange)?)?
+            .get(method)
+    }
+
+    /* Flags what went over the route's budget and adds the Server-Timing header it asks for. */
+    fn check_budget(
+        &self,
+        exchange: &mut LambdaExchange,
+        method: &str,
+        output: &mut ApiGatewayProxyResponse,
+        elapsed: Duration,
+    ) {
+        let Some(budget) = self.latency_budget(exchange, method) else {
+            return;
+        };
+        let timings = handler_timings(exchange);
+        if budget.server_timing
+            && let Ok(header_value) = server_timing(timings).parse()
+        {
+            output.headers.insert(SERVER_TIMING_HEADER, header_value);
+        }
+        let overruns = budget.overruns(elapsed, timings);
+        if overruns.is_empty() {
+            return;
+        }
+        let route = matched_route(exchange).unwrap_or("");
+        for overrun in &overruns {
+            metrics::counter(
+                names::SLOW_REQUESTS,
+                1,
+                &[("route", route), ("budget", overrun.as_str())],
+            );
+        }
+        tracing::warn!(route, over_budget = ?overruns, "Request over its latency budget");
+        set_over_budget(exchange, overruns);
+    }
+
+    /* Returns the status and handler name that stopped the chain, or None when every handler let it continue. */
+    async fn run_handlers(
+        &self,
+        handlers: &[Arc<dyn Handler<LambdaExchange>>],
+        phase: &'static str,
+        exchange: &mut LambdaExchange,
+    ) -> Result<Option<(HandlerStatus, String)>, ExecutorError> {
+        for (index, handler) in handlers.iter().enumerate() {
+            let status = self.run_traced(handler, phase, exchange).await;
+            let code = status.code();
+            let completes_request = phase == "request"
+                && self
+                    .complete_on
+                    .is_some_and(|complete_on| code.any_flags(complete_on));
+            // a disabled request or response handler is skipped
+            if code.is_in_flight() && completes_request {
+                trace_skipped(exchange, &handlers[index + 1..], phase);
+                return Ok(Some((status, handler.name().to_string())));
+            } else if code.is_in_flight() || code == ExchangeState::DISABLED {
+                continue;
+            } else if code.is_completed() || code.is_error() {
+                trace_skipped(exchange, &handlers[index + 1..], phase);
+                return Ok(Some((status, handler.name().to_string())));
+            } else {
+                return Err(ExecutorError::unknown_exchange_state(code));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn run_chain(
+        &self,
+        executables: &LoadedChain<LambdaExchange>,
+        exchange: &mut LambdaExchange,
+    ) -> Result<Option<(HandlerStatus, String)>, ExecutorError> {
+        // Early exit
+        if let Some(stopped) = self
+            .run_handlers(executables.request_handlers(), "request", exchange)
+            .await?
+        {
+            trace_skipped(
+                exchange,
+                std::slice::from_ref(executables.termination_handler()),
+                "termination",
+            );
+            return Ok(Some(stopped));
+        }
+
+        if is_authorize_only(exchange) {
+            trace_skipped(
+                exchange,
+                std::slice::from_ref(executables.termination_handler()),
+                "termination",
+            );
+            let response = authorized_response(exchange);
+            exchange.set_output(response);
+            return Ok(None);
+        }
+
+        let termination_handler = executables.termination_handler();
+        let status = self
+            .run_traced(termination_handler, "termination", exchange)
+            .await;
+        let code = status.code();
+        if code.is_completed() || code.is_error() || code == ExchangeState::DISABLED {
+            return Ok(Some((status, termination_handler.name().to_string())));
+        } else if !code.is_in_flight() {
+            return Err(ExecutorError::unknown_exchange_state(code));
+        }
+        Ok(None)
+    }
+
+    /* Response handlers can only adjust the output; they cannot fail the exchange any more. */
+    async fn run_response_phase(
+        &self,
+        executables: &LoadedChain<LambdaExchange>,
+        exchange: &mut LambdaExchange,
+    ) {
+        match self
+            .run_handlers(executables.response_handlers(), "response", exchange)
+            .await
+        {
+            Ok(Some((status, handler_name))) if status.code().is_error() => {
+                tracing::warn!("{} failed in the response phase with {:?}", handler_name, status);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Response phase failed: {}", e),
+        }
+    }
+
+    async fn run_traced(
+        &self,
+        handler: &Arc<dyn Handler<LambdaExchange>>,
+        phase: &'static str,
+        exchange: &mut LambdaExchange,
+    ) -> HandlerStatus {
+        if !is_tracing(exchange) {
+            return self.run_handler(handler, exchange).await;
+        }
+        let before = key_attachments(exchange);
+        let started = Instant::now();
+        let status = self.run_handler(handler, exchange).await;
+        let duration = started.elapsed();
+        let mut changes = key_attachments(exchange);
+        changes.retain(|key, value| before.get(key) != Some(value));
+        let decision = match status.code() == ExchangeState::DISABLED {
+            true => TraceDecision::Disabled,
+            false => TraceDecision::Executed,
+        };
+        record_trace(
+            exchange,
+            TraceEntry {
+                handler: handler.name().to_string(),
+                phase,
+                decision,
+                status: Some(self.status_flags.names(status.code()).join("|")),
+                duration_ms: Some(duration.as_secs_f64() * 1000.0),
+                changes,
+            },
+        );
+        status
+    }
+
+    async fn run_handler(
+        &self,
+        handler: &Arc<dyn Handler<LambdaExchange>>,
+        exchange: &mut LambdaExchange,
+    ) -> HandlerStatus {
+        let span = match sampling::is_sampled(exchange) {
+            true => tracing::info_span!(
+                "handler",
+                handler = handler.name(),
+                duration_ms = tracing::field::Empty,
+                status = tracing::field::Empty
+            ),
+            false => tracing::Span::none(),
+        };
+        let started = Instant::now();
+        let started_at = SystemTime::now();
+        let status = self
+            .exec_with_retry(handler, exchange)
+            .instrument(span.clone())
+            .await;
+        let duration = started.elapsed();
+        span.record("duration_ms", duration.as_secs_f64() * 1000.0);
+        span.record("status", tracing::field::display(status.code()));
+        let dimensions = [("handler", handler.name())];
+        metrics::histogram(
+            names::HANDLER_DURATION,
+            duration.as_secs_f64() * 1000.0,
+            Unit::Milliseconds,
+            &dimensions,
+        );
+        if status.code().is_error() {
+            metrics::counter(names::HANDLER_ERRORS, 1, &dimensions);
+        }
+        record_handler_timing(
+            exchange,
+            HandlerTiming {
+                handler_name: handler.name().to_string(),
+                duration,
+                status: status.code(),
+            },
+        );
+        if is_sampled(exchange) && sampling::is_recorded(exchange, status.code().is_error()) {
+            let mut subsegment = Subsegment::for_exchange(exchange, handler.name(), started_at)
+                .status(status.code());
+            if let Ok(output) = exchange.output().await {
+                subsegment = subsegment.status_code(output.status_code);
+            }
+            record_subsegment(exchange, &subsegment);
+        }
+        status
+    }
+
+    async fn exec_with_retry(
+        &self,
+        handler: &Arc<dyn Handler<LambdaExchange>>,
+        exchange: &mut LambdaExchange,
+    ) -> HandlerStatus {
+        let policy = match self.retry_policies.get(handler.name()) {
+            Some(policy) => policy,
+            None => return self.exec_with_timeout(handler, exchange).await,
+        };
+        let request = exchange.input().await.ok().cloned();
+        let mut attempt = 1;
+        loop {
+            let status = self.exec_with_timeout(handler, exchange).await;
+            if !policy.should_retry(attempt, status.code()) {
+                return status;
+            }
+            let delay = policy.delay(attempt);
+            if deadline(exchange).is_some_and(|deadline| deadline.remaining() <= delay) {
+                return status;
+            }
+            tracing::debug!("Retrying {} after {:?}", handler.name(), status);
+            tokio::time::sleep(delay).await;
+            if let Some(request) = &request {
+                exchange.set_input(request.clone());
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn exec_with_timeout(
+        &self,
+        handler: &Arc<dyn Handler<LambdaExchange>>,
+        exchange: &mut LambdaExchange,
+    ) -> HandlerStatus {
+        let handler_timeout = self
+            .handler_timeouts
+            .get(handler.name())
+            .copied()
+            .or(self.default_handler_timeout);
+        let remaining = deadline(exchange).map(|deadline| deadline.remaining());
+        if remaining.is_some_and(|remaining| remaining.is_zero()) {
+            return HandlerStatus::new(ExchangeState::TIMEOUT)
+                .message(format!("Invocation deadline reached before {}", handler.name()));
+        }
+        let _permit = match self.concurrency_limits.get(handler.name()) {
+            Some(semaphore) => {
+                let acquire = semaphore.acquire();
+                let permit = match remaining {
+                    Some(remaining) => tokio::time::timeout(remaining, acquire).await.ok(),
+                    None => Some(acquire.await),
+                };
+                match permit {
+                    Some(Ok(permit)) => Some(permit),
+                    _ => {
+                        return HandlerStatus::new(ExchangeState::TIMEOUT)
+                            .message(format!("No free execution slot for {}", handler.name()));
+                    }
+                }
+            }
+            None => None,
+        };
+        let remaining = deadline(exchange).map(|deadline| deadline.remaining());
+        let timeout = match (handler_timeout, remaining) {
+            (Some(handler_timeout), Some(remaining)) => Some(handler_timeout.min(remaining)),
+            (handler_timeout, remaining) => handler_timeout.or(remaining),
+        };
+        let result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, handler.exec(exchange)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    return HandlerStatus::new(ExchangeState::TIMEOUT)
+                        .message(format!("{} timed out after {:?}", handler.name(), timeout));
+                }
+            },
+            None => handler.exec(exchange).await,
+        };
+        match result {
+            Ok(status) => status,
+            Err(never) => match never {},
+        }
+    }
+
+    /* runs the chain and builds the response, see `execute_handlers` */
+    async fn execute_chain(
+        &self,
+        executables: Arc<LoadedChain<LambdaExchange>>,
+        exchange: &mut LambdaExchange,
+    ) -> Result<ApiGatewayProxyResponse, ExecutorError> {
+        if let Some(handler_pool) = &self.handler_pool
+            && let Err(e) = handler_pool.init().await
+        {
+            tracing::error!("Handler pool could not be initialized: {}", e);
+            let correlation_id = correlation_id(exchange);
+            exchange.set_output(self.exception_handlers.handle(&ErrorContext {
+                status_class: Some(StatusClass::ServerError),
+                handler_name: None,
+                correlation_id: &correlation_id,
+                error: None,
+            }));
+            return Self::return_output(exchange).await;
+        }
+
+        let trace_requested = exchange
+            .input()
+            .await
+            .is_ok_and(|request| self.chain_trace.is_requested(request));
+        if trace_requested {
+            start_trace(exchange, self.chain_trace.output);
+        }
+
+        let chain_result = match self.provide_context(exchange).await {
+            Ok(()) => self.run_chain(&executables, exchange).await,
+            Err(error) => Ok(Some((error.raise(exchange), CONTEXT_PROVIDER_NAME.to_string()))),
+        };
+        let (status_class, handler_name) = match chain_result {
+            Ok(Some((status, handler_name))) => match StatusClass::of(status.code()) {
+                Some(status_class) => (Some(status_class), Some(handler_name)),
+                None => (None, None),
+            },
+            Ok(None) => (None, None),
+            Err(e) => {
+                tracing::error!("Handler chain failed: {}", e);
+                (None, None)
+            }
+        };
+
+        // a handler that failed may still have written its own response
+        if !exchange.has_output() {
+            if let Some(handler_name) = &handler_name {
+                tracing::warn!("{} ended the chain with {:?}", handler_name, status_class);
+            }
+            let correlation_id = correlation_id(exchange);
+            let response = self.exception_handlers.handle(&ErrorContext {
+                status_class,
+                handler_name: handler_name.as_deref(),
+                correlation_id: &correlation_id,
+                error: handler_error(exchange),
+            });
+            exchange.set_output(response);
+        }
+
+        self.run_response_phase(&executables, exchange).await;
+        let mut output = Self::return_output(exchange).await?;
+        write_trace(exchange, &mut output);
+        Ok(output)
+    }
+}
+
+fn trace_skipped(
+    exchange: &mut LambdaExchange,
+    handlers: &[Arc<dyn Handler<LambdaExchange>>],
+    phase: &'static str,
+) {
+    if !is_tracing(exchange) {
+        return;
+    }
+    for handler in handlers {
+        record_trace(
+            exchange,
+            TraceEntry {
+                handler: handler.name().to_string(),
+                phase,
+                decision: TraceDecision::Skipped,
+                status: None,
+                duration_ms: None,
+                changes: Default::default(),
+            },
+        );
+    }
+}
+
+/* the parent of the handler spans; with the `otel` feature it continues the caller's trace */
+fn request_span(request: Option<&ApiGatewayProxyRequest>, sampled: bool) -> tracing::Span {
+    if !sampled {
+        return tracing::Span::none();
+    }
+    let span = tracing::info_span!(
+        "request",
+        method = request.map(|request| request.http_method.as_str()),
+        path = request.and_then(|request| request.path.as_deref()),
+        status_code = tracing::field::Empty
+    );
+    #[cfg(feature = "otel")]
+    if let Some(request) = request {
+        crate::telemetry::set_remote_parent(&span, &request.headers);
+    }
+    span
+}
+
+#[async_trait]
+impl HandlerExecutor<LambdaExchange> for LambdaExecutor {
+    type Output = ApiGatewayProxyResponse;
+
+    /// Runs the chain in a `request` span, with a child span for every handler, when the request
+    /// is sampled.
+    async fn execute_handlers(
+        &self,
+        executables: Arc<LoadedChain<LambdaExchange>>,
+        exchange: &mut LambdaExchange,
+    ) -> Result<Self::Output, ExecutorError> {
+        let request = exchange.input().await.ok();
+        let method = request.map(|request| request.http_method.to_string());
+        let decision = Sampling {
+            sampled: self.sampling.decide(matched_route(exchange), request),
+            always_on_errors: self.sampling.always_on_errors,
+        };
+        let span = request_span(request, decision.sampled);
+        let access_log = match self.access_log.enabled {
+            true => request.map(|request| self.access_log.request_fields(request)),
+            false => None,
+        };
+        set_sampling(exchange, decision);
+        let started = Instant::now();
+        let mut output = self
+            .execute_chain(executables, exchange)
+            .instrument(span.clone())
+            .await;
+        if let Ok(output) = &mut output {
+            if let Some(method) = &method {
+                self.check_budget(exchange, method, output, started.elapsed());
+            }
+            span.record("status_code", output.status_code);
+            let status_code = output.status_code.to_string();
+            let dimensions = [
+                ("route", matched_route(exchange).unwrap_or("")),
+                ("status_code", status_code.as_str()),
+            ];
+            metrics::counter(names::REQUESTS, 1, &dimensions);
+            metrics::histogram(
+                names::REQUEST_DURATION,
+                started.elapsed().as_secs_f64() * 1000.0,
+                Unit::Milliseconds,
+                &dimensions,
+            );
+            let entry = access_log.and_then(|entry| {
+                self.access_log.finish(entry, exchange, output, started.elapsed())
+            });
+            if let Some(entry) = entry {
+                write_access_log(entry);
+            }
+            if let Some(record) = take_record(exchange, output) {
+                audit::submit(record).await;
+            }
+        }
+        output
+    }
+
+    async fn return_output(exchange: &mut LambdaExchange) -> Result<Self::Output, ExecutorError> {
+        let mut output = exchange
+            .take_output()
+            .await
+            .map_err(ExecutorError::output_read_error)?;
+        let listeners = exchange
+            .attachments_mut()
+            .get_mut::<AsyncOutputListeners>(ASYNC_OUTPUT_LISTENERS_ATTACHMENT_KEY)
+            .map(std::mem::take)
+            .unwrap_or_default();
+        for listener in listeners {
+            listener.on_output(&mut output, exchange.attachments_mut()).await;
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ERR11000_INVALID_REQUEST;
+    use crate::executor::retry::RetryPolicy;
+    use crate::executor::trace::{chain_trace, start_trace, TraceDecision, TraceOutput};
+    use crate::executor::{
+        add_async_output_listener, handler_timings, trace_skipped, AsyncOutputListener,
+        LambdaExecutor,
+    };
+    use crate::handler::LambdaExchange;
+    use async_trait::async_trait;
+    use idemio::exchange::{Attachments, Exchange};
+    use idemio::handler::Handler;
+    use idemio::router::executor::HandlerExecutor;
+    use idemio::status::{ExchangeState, HandlerStatus};
+    use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct StampListener(&'static str);
+
+    #[async_trait]
+    impl AsyncOutputListener for StampListener {
+        async fn on_output(&self, output: &mut ApiGatewayProxyResponse, _: &mut Attachments) {
+            tokio::task::yield_now().await;
+            output
+                .headers
+                .append("x-listener", self.0.parse().unwrap());
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_async_output_listeners_run_in_order() {
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_output(ApiGatewayProxyResponse::default());
+        add_async_output_listener(&mut exchange, StampListener("first"));
+        add_async_output_listener(&mut exchange, StampListener("second"));
+
+        let output = LambdaExecutor::return_output(&mut exchange).await.unwrap();
+        let stamps: Vec<&str> = output
+            .headers
+            .get_all("x-listener")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(stamps, vec!["first", "second"]);
+    }
+
+    struct SlowHandler;
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for SlowHandler {
+        async fn exec(&self, _: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        }
+
+        fn name(&self) -> &str {
+            "SlowHandler"
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_handler_timeout() {
+        let handler: Arc<dyn Handler<LambdaExchange>> = Arc::new(SlowHandler);
+        let mut exchange: LambdaExchange = Exchange::new();
+        let executor =
+            LambdaExecutor::default().handler_timeout("SlowHandler", Duration::from_millis(10));
+        let status = executor.run_handler(&handler, &mut exchange).await;
+        assert!(status.code().any_flags(ExchangeState::TIMEOUT));
+
+        let timings = handler_timings(&exchange);
+        assert_eq!(timings.len(), 1);
+        assert_eq!(timings[0].handler_name, "SlowHandler");
+        assert!(timings[0].status.any_flags(ExchangeState::TIMEOUT));
+    }
+
+    /* consumes the request and fails until its third attempt */
+    struct FlakyHandler(AtomicUsize);
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for FlakyHandler {
+        async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            if exchange.take_input().await.is_err() {
+                return Ok(HandlerStatus::new(ExchangeState::CLIENT_ERROR));
+            }
+            if self.0.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR));
+            }
+            Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED))
+        }
+
+        fn name(&self) -> &str {
+            "FlakyHandler"
+        }
+    }
+
+    /* tracks the highest number of executions running at once */
+    struct CountingHandler {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for CountingHandler {
+        async fn exec(&self, _: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        }
+
+        fn name(&self) -> &str {
+            "CountingHandler"
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_handler_max_concurrency() {
+        let counting = Arc::new(CountingHandler {
+            running: AtomicUsize::new(0),
+            max_running: AtomicUsize::new(0),
+        });
+        let handler: Arc<dyn Handler<LambdaExchange>> = counting.clone();
+        let executor = LambdaExecutor::default().max_concurrency("CountingHandler", 1);
+        let mut first: LambdaExchange = Exchange::new();
+        let mut second: LambdaExchange = Exchange::new();
+        futures::join!(
+            executor.run_handler(&handler, &mut first),
+            executor.run_handler(&handler, &mut second)
+        );
+        assert_eq!(counting.max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_context_provider() {
+        let executor = LambdaExecutor::default()
+            .context_provider(|request, attachments| {
+                attachments.add::<String>("tenant", request.resource.clone().unwrap_or_default());
+                Ok(())
+            })
+            .context_provider(|_, attachments| match attachments.get::<String>("tenant") {
+                Some(tenant) if tenant == "blocked" => Err(ERR11000_INVALID_REQUEST.error()),
+                _ => Ok(()),
+            });
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(ApiGatewayProxyRequest {
+            resource: Some("acme".to_string()),
+            ..Default::default()
+        });
+        assert!(executor.provide_context(&mut exchange).await.is_ok());
+        assert_eq!(exchange.attachments().get::<String>("tenant").unwrap(), "acme");
+        assert!(exchange.input().await.is_ok());
+
+        exchange.input_mut().await.unwrap().resource = Some("blocked".to_string());
+        assert!(executor.provide_context(&mut exchange).await.is_err());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_chain_trace() {
+        let handler: Arc<dyn Handler<LambdaExchange>> = Arc::new(SlowHandler);
+        let mut exchange: LambdaExchange = Exchange::new();
+        let executor =
+            LambdaExecutor::default().handler_timeout("SlowHandler", Duration::from_millis(10));
+        executor.run_traced(&handler, "request", &mut exchange).await;
+        assert!(chain_trace(&exchange).is_none());
+
+        start_trace(&mut exchange, TraceOutput::Header);
+        executor.run_traced(&handler, "request", &mut exchange).await;
+        trace_skipped(&mut exchange, std::slice::from_ref(&handler), "termination");
+        let trace = chain_trace(&exchange).unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0].decision, TraceDecision::Executed);
+        assert!(trace[0].duration_ms.is_some());
+        assert_eq!(trace[1].phase, "termination");
+        assert_eq!(trace[1].decision, TraceDecision::Skipped);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_handler_retry() {
+        let handler: Arc<dyn Handler<LambdaExchange>> = Arc::new(FlakyHandler(AtomicUsize::new(0)));
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(Default::default());
+        let policy = RetryPolicy {
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let executor = LambdaExecutor::default().retry_policy("FlakyHandler", policy);
+        let status = executor.run_handler(&handler, &mut exchange).await;
+        assert!(status.code().is_completed());
+    }
+}
@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use idemio::handler::registry::{HandlerRegistry, HandlerRegistryError};
+use idemio::handler::{Handler, HandlerId};
+use idemio::status::HandlerStatus;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use crate::handler::LambdaExchange;
+
+/// Hooks for handlers that own expensive resources (SDK clients, compiled validators, key
+/// caches). `on_init` runs once per container before the first request, `on_shutdown` when the
+/// runtime stops. `on_warmup` runs on every warm-up ping the flow asks to pre-warm on, to refresh
+/// what would otherwise be loaded by the next request.
+#[async_trait]
+pub trait HandlerLifecycle: Send + Sync {
+    async fn on_init(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn on_warmup(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn on_shutdown(&self) {}
+}
+
+/// Handlers created once per container and shared by every invocation.
+#[derive(Default)]
+pub struct HandlerPool {
+    handlers: Vec<(String, Arc<dyn HandlerLifecycle>)>,
+    initialized: OnceCell<Result<(), String>>,
+}
+
+impl HandlerPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a handler to the pool and registers it under `name`.
+    pub fn register<H>(
+        &mut self,
+        registry: &mut HandlerRegistry<LambdaExchange>,
+        name: &str,
+        handler: H,
+    ) -> Result<(), HandlerRegistryError>
+    where
+        H: Handler<LambdaExchange> + HandlerLifecycle + 'static,
+    {
+        let handler = self.add(name, handler);
+        registry.register_handler(HandlerId::new(name), PooledHandler(handler))
+    }
+
+    /// Adds a handler to the pool, leaving its registration to the caller.
+    pub fn add<H>(&mut self, name: &str, handler: H) -> Arc<H>
+    where
+        H: HandlerLifecycle + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.handlers.push((name.to_string(), handler.clone()));
+        handler
+    }
+
+    /// Runs every `on_init` hook, once. Later calls return the first result.
+    pub async fn init(&self) -> Result<(), String> {
+        self.initialized
+            .get_or_init(|| async {
+                for (name, handler) in &self.handlers {
+                    if let Err(e) = handler.on_init().await {
+                        return Err(format!("{} failed to initialize: {}", name, e));
+                    }
+                }
+                Ok(())
+            })
+            .await
+            .clone()
+    }
+
+    /// Makes sure the pool is initialized, then runs every `on_warmup` hook. A failing hook
+    /// doesn't keep the others from running.
+    pub async fn warm_up(&self) -> Result<(), String> {
+        self.init().await?;
+        let mut failed = vec![];
+        for (name, handler) in &self.handlers {
+            if let Err(e) = handler.on_warmup().await {
+                failed.push(format!("{} failed to warm up: {}", name, e));
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(failed.join("; ")),
+        }
+    }
+
+    /// Runs the `on_shutdown` hooks in reverse registration order.
+    pub async fn shutdown(&self) {
+        for (_, handler) in self.handlers.iter().rev() {
+            handler.on_shutdown().await;
+        }
+    }
+}
+
+struct PooledHandler<H>(Arc<H>);
+
+#[async_trait]
+impl<H> Handler<LambdaExchange> for PooledHandler<H>
+where
+    H: Handler<LambdaExchange> + 'static,
+{
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        self.0.exec(exchange).await
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::pool::{HandlerLifecycle, HandlerPool};
+    use crate::handler::LambdaExchange;
+    use async_trait::async_trait;
+    use idemio::handler::registry::HandlerRegistry;
+    use idemio::handler::Handler;
+    use idemio::status::{ExchangeState, HandlerStatus};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingHandler(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for CountingHandler {
+        async fn exec(&self, _: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        }
+
+        fn name(&self) -> &str {
+            "CountingHandler"
+        }
+    }
+
+    #[async_trait]
+    impl HandlerLifecycle for CountingHandler {
+        async fn on_init(&self) -> Result<(), String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_pool_initializes_once() {
+        let inits = Arc::new(AtomicUsize::new(0));
+        let mut registry = HandlerRegistry::new();
+        let mut pool = HandlerPool::new();
+        pool.register(&mut registry, "CountingHandler", CountingHandler(inits.clone()))
+            .unwrap();
+        assert!(pool.init().await.is_ok());
+        assert!(pool.init().await.is_ok());
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+        assert!(
+            pool.register(&mut registry, "CountingHandler", CountingHandler(inits))
+                .is_err()
+        );
+    }
+}
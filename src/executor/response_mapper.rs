@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use lambda_http::http::{HeaderName, HeaderValue};
+use lambda_http::Body;
+use serde::Deserialize;
+use crate::executor::exception::{ErrorContext, ErrorHandler, ExceptionHandlers, StatusClass};
+
+const CORRELATION_ID_PLACEHOLDER: &str = "{correlation_id}";
+const STATUS_CODE_PLACEHOLDER: &str = "{status_code}";
+const ERROR_CODE_PLACEHOLDER: &str = "{error_code}";
+
+/// The response returned for one status class. `{correlation_id}`, `{status_code}` and
+/// `{error_code}` (the catalog code, when the handler raised one) in the body are filled in per
+/// request.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct ResponseMapping {
+    pub status_code: i64,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Maps terminal handler statuses to responses, e.g.
+///
+/// ```json
+/// {
+///   "mappings": {
+///     "ClientError": { "status_code": 400, "body": "{\"error\":\"bad request\"}" },
+///     "Disabled": { "status_code": 404 }
+///   }
+/// }
+/// ```
+///
+/// Status classes without a mapping keep the default problem+json responses.
+#[derive(Deserialize, Default)]
+pub struct ResponseMapperConfig {
+    #[serde(default)]
+    pub mappings: HashMap<StatusClass, ResponseMapping>,
+    #[serde(default)]
+    pub fallback: Option<ResponseMapping>,
+}
+
+impl ResponseMapperConfig {
+    pub fn exception_handlers(&self) -> ExceptionHandlers {
+        let mut exception_handlers = match &self.fallback {
+            Some(fallback) => ExceptionHandlers::default().fallback(ResponseMapper::new(fallback.clone())),
+            None => ExceptionHandlers::default(),
+        };
+        for (status_class, mapping) in &self.mappings {
+            exception_handlers =
+                exception_handlers.handler(*status_class, ResponseMapper::new(mapping.clone()));
+        }
+        exception_handlers
+    }
+}
+
+pub struct ResponseMapper {
+    mapping: ResponseMapping,
+}
+
+impl ResponseMapper {
+    pub fn new(mapping: ResponseMapping) -> Self {
+        Self { mapping }
+    }
+}
+
+impl ErrorHandler for ResponseMapper {
+    fn handle(&self, context: &ErrorContext<'_>) -> ApiGatewayProxyResponse {
+        let mut response = ApiGatewayProxyResponse {
+            status_code: self.mapping.status_code,
+            ..Default::default()
+        };
+        for (name, value) in &self.mapping.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                response.headers.insert(name, value);
+            }
+        }
+        response.body = self.mapping.body.as_ref().map(|body| {
+            Body::Text(
+                body.replace(CORRELATION_ID_PLACEHOLDER, context.correlation_id)
+                    .replace(STATUS_CODE_PLACEHOLDER, &self.mapping.status_code.to_string())
+                    .replace(
+                        ERROR_CODE_PLACEHOLDER,
+                        context.error.map(|error| error.code.code).unwrap_or_default(),
+                    ),
+            )
+        });
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::exception::{ErrorContext, StatusClass};
+    use crate::executor::response_mapper::ResponseMapperConfig;
+    use lambda_http::Body;
+
+    #[test]
+    fn test_configured_mappings_override_defaults() {
+        let config: ResponseMapperConfig = serde_json::from_str(
+            r#"{
+                "mappings": {
+                    "Disabled": {
+                        "status_code": 404,
+                        "body": "not found ({correlation_id})",
+                        "headers": {"cache-control": "no-store"}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let handlers = config.exception_handlers();
+
+        let disabled = handlers.handle(&ErrorContext {
+            status_class: Some(StatusClass::Disabled),
+            handler_name: Some("LambdaProxyHandler"),
+            correlation_id: "abc123",
+            error: None,
+        });
+        assert_eq!(disabled.status_code, 404);
+        assert_eq!(disabled.headers["cache-control"], "no-store");
+        assert!(matches!(disabled.body, Some(Body::Text(body)) if body == "not found (abc123)"));
+
+        let timeout = handlers.handle(&ErrorContext {
+            status_class: Some(StatusClass::Timeout),
+            handler_name: None,
+            correlation_id: "abc123",
+            error: None,
+        });
+        assert_eq!(timeout.status_code, 504);
+    }
+}
@@ -0,0 +1,83 @@
+use idemio::status::ExchangeState;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use crate::config::units::{deserialize_duration, serialize_duration};
+use crate::executor::exception::StatusClass;
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff() -> Duration {
+    Duration::from_millis(100)
+}
+
+fn default_retry_on() -> Vec<StatusClass> {
+    vec![StatusClass::ServerError, StatusClass::Timeout]
+}
+
+/// How often a handler is run again when it fails transiently, e.g.
+///
+/// ```json
+/// { "max_attempts": 3, "backoff": "100ms", "retry_on": ["ServerError", "Timeout"] }
+/// ```
+///
+/// The delay doubles after every attempt. The request is restored before each retry, so
+/// handlers that consume the input can be retried as well.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(
+        default = "default_backoff",
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration"
+    )]
+    pub backoff: Duration,
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<StatusClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            backoff: default_backoff(),
+            retry_on: default_retry_on(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a handler that returned `code` on attempt `attempt` (starting at 1) is run again.
+    pub fn should_retry(&self, attempt: u32, code: ExchangeState) -> bool {
+        attempt < self.max_attempts
+            && StatusClass::of(code).is_some_and(|status_class| self.retry_on.contains(&status_class))
+    }
+
+    /// The delay before attempt `attempt + 1`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::retry::RetryPolicy;
+    use idemio::status::ExchangeState;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy: RetryPolicy =
+            serde_json::from_str(r#"{ "max_attempts": 3, "backoff": "50ms" }"#).unwrap();
+        assert!(policy.should_retry(1, ExchangeState::SERVER_ERROR));
+        assert!(policy.should_retry(2, ExchangeState::TIMEOUT));
+        assert!(!policy.should_retry(3, ExchangeState::SERVER_ERROR));
+        assert!(!policy.should_retry(1, ExchangeState::CLIENT_ERROR));
+        assert!(!policy.should_retry(1, ExchangeState::OK));
+        assert_eq!(policy.delay(1), Duration::from_millis(50));
+        assert_eq!(policy.delay(3), Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,162 @@
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::executor::xray::TraceHeader;
+use crate::handler::LambdaExchange;
+
+const SAMPLING_ATTACHMENT_KEY: &str = "sampling";
+const TRACEPARENT_HEADER: &str = "traceparent";
+const XRAY_TRACE_HEADER: &str = "x-amzn-trace-id";
+
+/// Which requests the access log, spans, X-Ray subsegments and audit records are kept for:
+///
+/// ```json
+/// { "percentage": 5, "always_on_errors": true, "routes": { "/health": 0, "/orders": 50 } }
+/// ```
+///
+/// The decision is made once per request, before the chain runs, from the route's
+/// `percentage` or, with `honor_upstream`, the sampled flag of an incoming `traceparent` or
+/// `X-Amzn-Trace-Id` header. With `always_on_errors`, requests that fail are logged and audited
+/// even when they weren't sampled; spans are only kept for sampled requests.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /* 0 to 100 */
+    pub percentage: f64,
+    pub always_on_errors: bool,
+    /* percentages by route, e.g. `/pets/{id}` */
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub routes: BTreeMap<String, f64>,
+    pub honor_upstream: bool,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            percentage: 100.0,
+            always_on_errors: true,
+            routes: BTreeMap::new(),
+            honor_upstream: true,
+        }
+    }
+}
+
+/* the sampled flag of a W3C `traceparent` header or an X-Ray trace header */
+fn upstream_sampled(headers: &HeaderMap) -> Option<bool> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(traceparent) = header(TRACEPARENT_HEADER) {
+        let flags = traceparent.split('-').nth(3)?;
+        return u8::from_str_radix(flags, 16).ok().map(|flags| flags & 1 == 1);
+    }
+    let trace = TraceHeader::parse(header(XRAY_TRACE_HEADER)?)?;
+    Some(trace.sampled)
+}
+
+/// A uniformly distributed number in `[0, 1)`.
+pub(crate) fn roll() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() >> 11) as f64 / (1u128 << 117) as f64
+}
+
+impl SamplingConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn percentage(&self, route: Option<&str>) -> f64 {
+        route
+            .and_then(|route| self.routes.get(route))
+            .copied()
+            .unwrap_or(self.percentage)
+    }
+
+    pub fn decide(&self, route: Option<&str>, request: Option<&ApiGatewayProxyRequest>) -> bool {
+        if self.honor_upstream
+            && let Some(sampled) = request.and_then(|request| upstream_sampled(&request.headers))
+        {
+            return sampled;
+        }
+        match self.percentage(route) {
+            percentage if percentage >= 100.0 => true,
+            percentage if percentage <= 0.0 => false,
+            percentage => roll() * 100.0 < percentage,
+        }
+    }
+}
+
+/// The decision made for a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub sampled: bool,
+    pub always_on_errors: bool,
+}
+
+pub(crate) fn set_sampling(exchange: &mut LambdaExchange, sampling: Sampling) {
+    exchange
+        .attachments_mut()
+        .add::<Sampling>(SAMPLING_ATTACHMENT_KEY, sampling);
+}
+
+/// Whether the request was sampled; requests that didn't go through the executor are.
+pub fn is_sampled(exchange: &LambdaExchange) -> bool {
+    exchange
+        .attachments()
+        .get::<Sampling>(SAMPLING_ATTACHMENT_KEY)
+        .is_none_or(|sampling| sampling.sampled)
+}
+
+/// Whether something that happened while serving the request is recorded, given whether it
+/// failed.
+pub fn is_recorded(exchange: &LambdaExchange, failed: bool) -> bool {
+    match exchange.attachments().get::<Sampling>(SAMPLING_ATTACHMENT_KEY) {
+        Some(sampling) => sampling.sampled || (failed && sampling.always_on_errors),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::sampling::{is_recorded, set_sampling, Sampling, SamplingConfig};
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use idemio::exchange::Exchange;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_sampling_decision() {
+        let config = SamplingConfig {
+            percentage: 0.0,
+            routes: BTreeMap::from([("/orders".to_string(), 100.0)]),
+            ..Default::default()
+        };
+        let request = RequestBuilder::get("/pets").build();
+        assert!(!config.decide(Some("/pets"), Some(&request)));
+        assert!(config.decide(Some("/orders"), Some(&request)));
+
+        let traced = RequestBuilder::get("/pets")
+            .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .build();
+        assert!(config.decide(Some("/pets"), Some(&traced)));
+        let not_traced = RequestBuilder::get("/orders")
+            .header("x-amzn-trace-id", "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=0")
+            .build();
+        assert!(!config.decide(Some("/orders"), Some(&not_traced)));
+        let config = SamplingConfig {
+            honor_upstream: false,
+            ..config
+        };
+        assert!(config.decide(Some("/orders"), Some(&not_traced)));
+
+        let mut exchange: LambdaExchange = Exchange::new();
+        assert!(is_recorded(&exchange, false));
+        set_sampling(
+            &mut exchange,
+            Sampling {
+                sampled: false,
+                always_on_errors: true,
+            },
+        );
+        assert!(!is_recorded(&exchange, false));
+        assert!(is_recorded(&exchange, true));
+    }
+}
@@ -0,0 +1,209 @@
+use hmac::{Hmac, Mac};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+use crate::error::handler_error;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::traceability::CORRELATION_ID_ATTACHMENT_KEY;
+use crate::handler::LambdaExchange;
+
+const CHAIN_TRACE_ATTACHMENT_KEY: &str = "chain_trace";
+const DEBUG_HEADER: &str = "x-idem-debug";
+const TRACE_HEADER: &str = "x-idem-trace";
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceOutput {
+    #[default]
+    Header,
+    Body,
+}
+
+/// Records what every handler of a chain did and returns it with the response.
+///
+/// Tracing is on for every request when `enabled` is set. Otherwise, with a `signing_key`, a
+/// single request can ask for it with an `x-idem-debug: <unix expiry>.<hex HMAC-SHA256 of the
+/// expiry>` header, so production chains can be inspected without exposing the trace to anyone.
+#[derive(Deserialize, Default, Clone)]
+pub struct ChainTraceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    #[serde(default)]
+    pub output: TraceOutput,
+}
+
+impl ChainTraceConfig {
+    pub fn is_requested(&self, request: &ApiGatewayProxyRequest) -> bool {
+        if self.enabled {
+            return true;
+        }
+        let Some(signing_key) = &self.signing_key else {
+            return false;
+        };
+        request
+            .headers
+            .get(DEBUG_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .is_some_and(|header| {
+                verify_debug_header(signing_key.as_bytes(), header, SystemTime::now())
+            })
+    }
+}
+
+fn verify_debug_header(signing_key: &[u8], header: &str, now: SystemTime) -> bool {
+    let Some((expiry, signature)) = header.split_once('.') else {
+        return false;
+    };
+    let Ok(expiry_secs) = expiry.parse::<u64>() else {
+        return false;
+    };
+    if SystemTime::UNIX_EPOCH + Duration::from_secs(expiry_secs) <= now {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(signing_key) else {
+        return false;
+    };
+    mac.update(expiry.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceDecision {
+    Executed,
+    Disabled,
+    Skipped,
+}
+
+/// One handler's part in the chain.
+#[derive(Serialize, Clone, Debug)]
+pub struct TraceEntry {
+    pub handler: String,
+    pub phase: &'static str,
+    pub decision: TraceDecision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /* well-known attachments the handler set or changed */
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub changes: BTreeMap<&'static str, String>,
+}
+
+struct ChainTrace {
+    output: TraceOutput,
+    entries: Vec<TraceEntry>,
+}
+
+pub(crate) fn start_trace(exchange: &mut LambdaExchange, output: TraceOutput) {
+    exchange.attachments_mut().add::<ChainTrace>(
+        CHAIN_TRACE_ATTACHMENT_KEY,
+        ChainTrace {
+            output,
+            entries: vec![],
+        },
+    );
+}
+
+pub(crate) fn is_tracing(exchange: &LambdaExchange) -> bool {
+    exchange
+        .attachments()
+        .get::<ChainTrace>(CHAIN_TRACE_ATTACHMENT_KEY)
+        .is_some()
+}
+
+pub(crate) fn record_trace(exchange: &mut LambdaExchange, entry: TraceEntry) {
+    if let Some(trace) = exchange
+        .attachments_mut()
+        .get_mut::<ChainTrace>(CHAIN_TRACE_ATTACHMENT_KEY)
+    {
+        trace.entries.push(entry);
+    }
+}
+
+/// The attachments worth showing in a trace, by name.
+pub(crate) fn key_attachments(exchange: &LambdaExchange) -> BTreeMap<&'static str, String> {
+    let mut attachments = BTreeMap::new();
+    if let Some(correlation_id) = exchange
+        .attachments()
+        .well_known()
+        .get::<String>(CORRELATION_ID_ATTACHMENT_KEY)
+    {
+        attachments.insert("correlation_id", correlation_id.clone());
+    }
+    if let Some(error) = handler_error(exchange) {
+        attachments.insert("error_code", error.code.code.to_string());
+    }
+    attachments
+}
+
+/// The trace entries recorded so far, when tracing is on for the exchange.
+pub fn chain_trace(exchange: &LambdaExchange) -> Option<&[TraceEntry]> {
+    exchange
+        .attachments()
+        .get::<ChainTrace>(CHAIN_TRACE_ATTACHMENT_KEY)
+        .map(|trace| trace.entries.as_slice())
+}
+
+/// Adds the trace to the response, as a header or wrapped around the body.
+pub(crate) fn write_trace(exchange: &LambdaExchange, response: &mut ApiGatewayProxyResponse) {
+    let Some(trace) = exchange
+        .attachments()
+        .get::<ChainTrace>(CHAIN_TRACE_ATTACHMENT_KEY)
+    else {
+        return;
+    };
+    match trace.output {
+        TraceOutput::Header => {
+            if let Ok(value) = json!(trace.entries).to_string().parse() {
+                response.headers.insert(TRACE_HEADER, value);
+            }
+        }
+        TraceOutput::Body => {
+            let body = match response.body.take() {
+                Some(Body::Text(text)) => json!(text),
+                Some(Body::Binary(bytes)) => json!(String::from_utf8_lossy(&bytes)),
+                _ => json!(null),
+            };
+            response.body = Some(Body::Text(
+                json!({ "trace": trace.entries, "body": body }).to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::trace::verify_debug_header;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_verify_debug_header() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(b"2000");
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        assert!(verify_debug_header(b"secret", &format!("2000.{}", signature), now));
+        assert!(!verify_debug_header(b"other", &format!("2000.{}", signature), now));
+        assert!(!verify_debug_header(b"secret", &format!("2001.{}", signature), now));
+        let expired = SystemTime::UNIX_EPOCH + Duration::from_secs(3_000);
+        assert!(!verify_debug_header(b"secret", &format!("2000.{}", signature), expired));
+    }
+}
@@ -0,0 +1,227 @@
+use idemio::status::ExchangeState;
+use serde_json::{json, Map, Value};
+use std::net::UdpSocket;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::executor::exception::StatusClass;
+use crate::flow::matcher::matched_route;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+
+/* set by Lambda when active tracing is on, e.g. "169.254.79.129:2000" */
+const DAEMON_ADDRESS_ENV_VAR: &str = "AWS_XRAY_DAEMON_ADDRESS";
+/* the trace of the current invocation, when the context doesn't carry it */
+const TRACE_ID_ENV_VAR: &str = "_X_AMZN_TRACE_ID";
+const DOCUMENT_HEADER: &str = "{\"format\":\"json\",\"version\":1}\n";
+/* the well-known attachment a context provider or handler can name the tenant in */
+const TENANT_ATTACHMENT_KEY: &str = "tenant";
+
+/// The parts of an `X-Amzn-Trace-Id` header subsegments are attached with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceHeader {
+    pub root: String,
+    pub parent: Option<String>,
+    pub sampled: bool,
+}
+
+impl TraceHeader {
+    /// Parses `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut root = None;
+        let mut parent = None;
+        let mut sampled = false;
+        for part in header.split(';') {
+            match part.trim().split_once('=') {
+                Some(("Root", value)) => root = Some(value.to_string()),
+                Some(("Parent", value)) => parent = Some(value.to_string()),
+                Some(("Sampled", value)) => sampled = value == "1",
+                _ => {}
+            }
+        }
+        Some(Self {
+            root: root?,
+            parent,
+            sampled,
+        })
+    }
+
+    /// The trace of the invocation the exchange belongs to, if X-Ray samples it.
+    pub fn of(exchange: &LambdaExchange) -> Option<Self> {
+        let from_context = exchange
+            .metadata()
+            .ok()
+            .and_then(|context| context.xray_trace_id.clone());
+        let header = from_context.or_else(|| std::env::var(TRACE_ID_ENV_VAR).ok())?;
+        Self::parse(&header).filter(|trace| trace.sampled)
+    }
+}
+
+/// A timed piece of work in the function's X-Ray segment, like one handler or a downstream call.
+#[derive(Debug, Clone)]
+pub struct Subsegment {
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /* `aws` for calls to AWS services, `remote` for other downstream calls */
+    pub namespace: Option<&'static str>,
+    pub annotations: Map<String, Value>,
+    pub status_code: Option<i64>,
+    pub error: bool,
+    pub fault: bool,
+}
+
+fn epoch_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+fn segment_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+impl Subsegment {
+    /// A subsegment ending now, annotated with the route and tenant of the exchange.
+    pub fn for_exchange(exchange: &LambdaExchange, name: &str, start: SystemTime) -> Self {
+        let mut annotations = Map::new();
+        if let Some(route) = matched_route(exchange) {
+            annotations.insert("route".to_string(), json!(route));
+        }
+        let tenant = exchange.attachments().well_known().get::<String>(TENANT_ATTACHMENT_KEY);
+        if let Some(tenant) = tenant {
+            annotations.insert("tenant".to_string(), json!(tenant));
+        }
+        Self {
+            name: name.to_string(),
+            start,
+            end: SystemTime::now(),
+            namespace: None,
+            annotations,
+            status_code: None,
+            error: false,
+            fault: false,
+        }
+    }
+
+    pub fn status_code(mut self, status_code: i64) -> Self {
+        self.annotations.insert("status_code".to_string(), json!(status_code));
+        self.status_code = Some(status_code);
+        self.error = (400..500).contains(&status_code);
+        self.fault = status_code >= 500;
+        self
+    }
+
+    /// Marks the subsegment as failed like the handler status says.
+    pub fn status(mut self, status: ExchangeState) -> Self {
+        match StatusClass::of(status) {
+            Some(StatusClass::ClientError) => self.error = true,
+            Some(StatusClass::ServerError) | Some(StatusClass::Timeout) => self.fault = true,
+            _ => {}
+        }
+        self
+    }
+
+    pub fn document(&self, trace: &TraceHeader) -> Value {
+        let mut document = json!({
+            "name": self.name,
+            "id": segment_id(),
+            "trace_id": trace.root,
+            "type": "subsegment",
+            "start_time": epoch_seconds(self.start),
+            "end_time": epoch_seconds(self.end),
+            "annotations": self.annotations,
+            "error": self.error,
+            "fault": self.fault,
+        });
+        if let Some(parent) = &trace.parent {
+            document["parent_id"] = json!(parent);
+        }
+        if let Some(namespace) = self.namespace {
+            document["namespace"] = json!(namespace);
+        }
+        if let Some(status_code) = self.status_code {
+            document["http"] = json!({ "response": { "status": status_code } });
+        }
+        document
+    }
+}
+
+struct Daemon {
+    socket: UdpSocket,
+    address: String,
+}
+
+/* `None` when tracing isn't active or the socket can't be opened */
+fn daemon() -> Option<&'static Daemon> {
+    static DAEMON: OnceLock<Option<Daemon>> = OnceLock::new();
+    DAEMON
+        .get_or_init(|| {
+            let address = std::env::var(DAEMON_ADDRESS_ENV_VAR).ok()?;
+            /* the address may list a TCP and a UDP endpoint: "tcp:host:port udp:host:port" */
+            let address = address
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix("udp:"))
+                .unwrap_or(&address)
+                .to_string();
+            let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+            socket.set_nonblocking(true).ok()?;
+            Some(Daemon { socket, address })
+        })
+        .as_ref()
+}
+
+/// Whether subsegments of the exchange reach X-Ray, to skip building them otherwise.
+pub fn is_sampled(exchange: &LambdaExchange) -> bool {
+    daemon().is_some() && TraceHeader::of(exchange).is_some()
+}
+
+/// Sends the subsegment to the X-Ray daemon, when the exchange's invocation is sampled.
+pub fn record_subsegment(exchange: &LambdaExchange, subsegment: &Subsegment) {
+    let (Some(trace), Some(daemon)) = (TraceHeader::of(exchange), daemon()) else {
+        return;
+    };
+    let document = format!("{}{}", DOCUMENT_HEADER, subsegment.document(&trace));
+    if let Err(e) = daemon.socket.send_to(document.as_bytes(), &daemon.address) {
+        tracing::debug!("Subsegment {} not sent to X-Ray: {}", subsegment.name, e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::xray::{Subsegment, TraceHeader};
+    use serde_json::Map;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_subsegment_document() {
+        let trace = TraceHeader::parse(
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+        )
+        .unwrap();
+        assert!(trace.sampled);
+        assert!(!TraceHeader::parse("Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=0")
+            .unwrap()
+            .sampled);
+        assert_eq!(TraceHeader::parse("Parent=53995c3f42cd8ad8"), None);
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_millis(1_500);
+        let subsegment = Subsegment {
+            name: "LambdaProxyHandler".to_string(),
+            start,
+            end: start + Duration::from_millis(250),
+            namespace: Some("aws"),
+            annotations: Map::new(),
+            status_code: None,
+            error: false,
+            fault: false,
+        }
+        .status_code(502);
+        let document = subsegment.document(&trace);
+        assert_eq!(document["trace_id"], "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(document["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(document["id"].as_str().unwrap().len(), 16);
+        assert_eq!(document["start_time"], 1.5);
+        assert_eq!(document["end_time"], 1.75);
+        assert_eq!(document["annotations"]["status_code"], 502);
+        assert_eq!(document["http"]["response"]["status"], 502);
+        assert_eq!(document["fault"], true);
+    }
+}
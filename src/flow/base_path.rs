@@ -0,0 +1,83 @@
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use serde::{Deserialize, Serialize};
+
+/* the stage of HTTP APIs without named stages, it never shows up in the path */
+const DEFAULT_STAGE: &str = "$default";
+
+/// Prefixes taken off the request path before it is routed, so the same flow serves every
+/// stage and every base path mapping of a custom domain.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct BasePathConfig {
+    /* strip a leading segment equal to the request's stage, e.g. `/dev` */
+    #[serde(default)]
+    pub strip_stage: bool,
+    /* prefixes to strip, the longest matching one is taken off */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub base_paths: Vec<String>,
+}
+
+/* `path` without `prefix`, if `prefix` makes up whole segments of it */
+fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches('/');
+    let rest = path.strip_prefix(prefix)?;
+    match rest.is_empty() || rest.starts_with('/') {
+        true => Some(rest),
+        false => None,
+    }
+}
+
+impl BasePathConfig {
+    pub fn is_empty(&self) -> bool {
+        !self.strip_stage && self.base_paths.is_empty()
+    }
+
+    /// The path the routes are matched against.
+    pub fn strip<'a>(&self, path: &'a str, stage: Option<&str>) -> &'a str {
+        let mut path = path;
+        let stage = stage.filter(|stage| self.strip_stage && *stage != DEFAULT_STAGE);
+        if let Some(stage) = stage {
+            path = strip_prefix(path, &format!("/{}", stage)).unwrap_or(path);
+        }
+        let stripped = self
+            .base_paths
+            .iter()
+            .filter_map(|base_path| strip_prefix(path, base_path))
+            .min_by_key(|rest| rest.len());
+        match stripped.unwrap_or(path) {
+            "" => "/",
+            path => path,
+        }
+    }
+
+    pub fn strip_request(&self, request: &mut ApiGatewayProxyRequest) {
+        if self.is_empty() {
+            return;
+        }
+        if let Some(path) = &request.path {
+            let stage = request.request_context.stage.as_deref();
+            let stripped = self.strip(path, stage);
+            if stripped.len() != path.len() {
+                request.path = Some(stripped.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::base_path::BasePathConfig;
+
+    #[test]
+    fn test_strip_stage_and_base_path() {
+        let config = BasePathConfig {
+            strip_stage: true,
+            base_paths: vec!["/api".to_string(), "/api/v1/".to_string()],
+        };
+        assert_eq!(config.strip("/dev/api/v1/pets", Some("dev")), "/pets");
+        assert_eq!(config.strip("/api/pets", Some("$default")), "/pets");
+        assert_eq!(config.strip("/api", None), "/");
+        assert_eq!(config.strip("/apis/pets", None), "/apis/pets");
+        assert_eq!(config.strip("/development/pets", Some("dev")), "/development/pets");
+        assert_eq!(BasePathConfig::default().strip("/dev/pets", Some("dev")), "/dev/pets");
+    }
+}
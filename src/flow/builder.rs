@@ -0,0 +1,218 @@
+use crate::flow::{ExecutionFlowConfig, PathFlowConfig};
+
+/// A handler with a fixed registration name, so chains can refer to the type instead of a string.
+pub trait NamedHandler {
+    const NAME: &'static str;
+}
+
+/// Builds an [`ExecutionFlowConfig`] in code:
+///
+/// ```ignore
+/// let flow = ChainBuilder::new()
+///     .chain("default", &[TraceabilityHandler::NAME, JwtValidationHandler::NAME])
+///     .route("/test")
+///     .get()
+///     .request_chain("default")
+///     .request_handler::<HeaderHandler>()
+///     .termination_handler::<LambdaProxyHandler>()
+///     .end_method()
+///     .end_route()
+///     .build();
+/// ```
+///
+/// A method can only be finished after its termination handler is set, and response handlers
+/// can only be added after it.
+#[derive(Default)]
+pub struct ChainBuilder {
+    flow: ExecutionFlowConfig,
+}
+
+impl ChainBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chain(mut self, name: impl Into<String>, handlers: &[&'static str]) -> Self {
+        for handler in handlers {
+            self.add_handler(handler);
+        }
+        self.flow.chains.insert(
+            name.into(),
+            handlers.iter().map(|handler| handler.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Chains or handlers every route runs first in its request phase.
+    pub fn default_chain(mut self, entries: &[&'static str]) -> Self {
+        self.flow.default_chain = entries.iter().map(|entry| entry.to_string()).collect();
+        self
+    }
+
+    pub fn route(self, path: impl Into<String>) -> ChainRouteBuilder {
+        ChainRouteBuilder {
+            builder: self,
+            path: path.into(),
+            flows: vec![],
+        }
+    }
+
+    pub fn build(self) -> ExecutionFlowConfig {
+        self.flow
+    }
+
+    fn add_handler(&mut self, name: &str) {
+        if !self.flow.handlers.iter().any(|handler| handler == name) {
+            self.flow.handlers.push(name.to_string());
+        }
+    }
+}
+
+pub struct ChainRouteBuilder {
+    builder: ChainBuilder,
+    path: String,
+    flows: Vec<PathFlowConfig>,
+}
+
+impl ChainRouteBuilder {
+    pub fn method(self, method: impl Into<String>) -> ChainRequestBuilder {
+        ChainRequestBuilder {
+            route: self,
+            flow: PathFlowConfig {
+                method: method.into().to_uppercase(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn get(self) -> ChainRequestBuilder {
+        self.method("GET")
+    }
+
+    pub fn post(self) -> ChainRequestBuilder {
+        self.method("POST")
+    }
+
+    pub fn put(self) -> ChainRequestBuilder {
+        self.method("PUT")
+    }
+
+    pub fn patch(self) -> ChainRequestBuilder {
+        self.method("PATCH")
+    }
+
+    pub fn delete(self) -> ChainRequestBuilder {
+        self.method("DELETE")
+    }
+
+    pub fn options(self) -> ChainRequestBuilder {
+        self.method("OPTIONS")
+    }
+
+    pub fn head(self) -> ChainRequestBuilder {
+        self.method("HEAD")
+    }
+
+    /// Every method not configured explicitly for the route.
+    pub fn any(self) -> ChainRequestBuilder {
+        self.method("ANY")
+    }
+
+    pub fn end_route(mut self) -> ChainBuilder {
+        self.builder
+            .flow
+            .paths
+            .entry(self.path)
+            .or_default()
+            .extend(self.flows);
+        self.builder
+    }
+}
+
+/// The request phase of a method, finished by setting its termination handler.
+pub struct ChainRequestBuilder {
+    route: ChainRouteBuilder,
+    flow: PathFlowConfig,
+}
+
+impl ChainRequestBuilder {
+    pub fn request_handler<H: NamedHandler>(mut self) -> Self {
+        self.route.builder.add_handler(H::NAME);
+        self.flow.exec.push(H::NAME.to_string());
+        self
+    }
+
+    /// Runs a chain registered with [`ChainBuilder::chain`] in the request phase.
+    pub fn request_chain(mut self, name: &str) -> Self {
+        self.flow.exec.push(name.to_string());
+        self
+    }
+
+    pub fn termination_handler<H: NamedHandler>(mut self) -> ChainResponseBuilder {
+        self.route.builder.add_handler(H::NAME);
+        self.flow.exec.push(H::NAME.to_string());
+        ChainResponseBuilder {
+            route: self.route,
+            flow: self.flow,
+        }
+    }
+}
+
+/// The response phase of a method, after the termination handler.
+pub struct ChainResponseBuilder {
+    route: ChainRouteBuilder,
+    flow: PathFlowConfig,
+}
+
+impl ChainResponseBuilder {
+    pub fn response_handler<H: NamedHandler>(mut self) -> Self {
+        self.route.builder.add_handler(H::NAME);
+        self.flow.response.push(H::NAME.to_string());
+        self
+    }
+
+    pub fn response_chain(mut self, name: &str) -> Self {
+        self.flow.response.push(name.to_string());
+        self
+    }
+
+    pub fn end_method(mut self) -> ChainRouteBuilder {
+        self.route.flows.push(self.flow);
+        self.route
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::builder::{ChainBuilder, NamedHandler};
+    use crate::flow::ExecutionFlowConfig;
+    use crate::handler::health::HealthCheckHandler;
+    use crate::handler::jwt::JwtValidationHandler;
+    use crate::handler::traceability::TraceabilityHandler;
+
+    #[test]
+    fn test_builder_matches_json_flow() {
+        let built = ChainBuilder::new()
+            .chain(
+                "default",
+                &[TraceabilityHandler::NAME, JwtValidationHandler::NAME],
+            )
+            .route("/health")
+            .get()
+            .request_chain("default")
+            .termination_handler::<HealthCheckHandler>()
+            .end_method()
+            .end_route()
+            .build();
+        let parsed: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["TraceabilityHandler", "JwtValidationHandler", "HealthCheckHandler"],
+                "chains": { "default": ["TraceabilityHandler", "JwtValidationHandler"] },
+                "paths": { "/health": { "method": "GET", "exec": ["default", "HealthCheckHandler"] } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(built, parsed);
+        assert!(built.router_config().is_ok());
+    }
+}
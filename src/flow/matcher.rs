@@ -0,0 +1,525 @@
+use idemio::handler::registry::HandlerRegistry;
+use idemio::router::config::{PathChain, RouterConfig};
+use idemio::router::factory::RouteInfo;
+use idemio::router::path::{LoadedChain, PathMatcher, PathMatcherError};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::header::HOST;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use crate::flow::{RouteMetadata, FALLBACK_ROUTE};
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+
+const PATH_PARAMETERS_ATTACHMENT_KEY: &str = "path_parameters";
+const ROUTE_METADATA_ATTACHMENT_KEY: &str = "route_metadata";
+const ROUTE_ATTACHMENT_KEY: &str = "route";
+
+tokio::task_local! {
+    /* the host of the request being routed; `lookup` only gets to see its path and method */
+    pub static REQUEST_HOST: Option<String>;
+}
+
+/// The host a request was sent to: its `Host` header without the port, or else the domain name
+/// API Gateway received it on. Lower case.
+pub fn request_host(request: &ApiGatewayProxyRequest) -> Option<String> {
+    let header = request
+        .headers
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| host.split(':').next().unwrap_or_default());
+    header
+        .or(request.request_context.domain_name.as_deref())
+        .filter(|host| !host.is_empty())
+        .map(str::to_lowercase)
+}
+
+/* name the value of an unnamed trailing `*` is stored under, like API Gateway's `{proxy+}` */
+const WILDCARD_PARAMETER: &str = "proxy";
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateSegment {
+    Static(String),
+    Parameter(String),
+    /* matches one or more trailing segments */
+    Wildcard(String),
+}
+
+impl TemplateSegment {
+    fn parse(segment: &str) -> Self {
+        if segment == "*" {
+            return TemplateSegment::Wildcard(WILDCARD_PARAMETER.to_string());
+        }
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) => match name.strip_suffix('+') {
+                Some(name) => TemplateSegment::Wildcard(name.to_string()),
+                None => TemplateSegment::Parameter(name.to_string()),
+            },
+            None => TemplateSegment::Static(segment.to_string()),
+        }
+    }
+
+    /* static beats parameter beats wildcard, segment by segment */
+    fn rank(&self) -> u8 {
+        match self {
+            TemplateSegment::Static(_) => 2,
+            TemplateSegment::Parameter(_) => 1,
+            TemplateSegment::Wildcard(_) => 0,
+        }
+    }
+}
+
+/// A configured path like `/pets/{id}`, `/files/{path+}` or `/static/*`. `{name}` matches one
+/// segment; `{name+}` and `*` match the rest of the path and may only come last. A path
+/// prefixed with a host, like `api.example.com/pets`, only matches requests for that host.
+#[derive(Debug, Clone)]
+struct PathTemplate {
+    /* the route as configured */
+    route: String,
+    priority: i32,
+    host: Option<String>,
+    segments: Vec<TemplateSegment>,
+    methods: Vec<String>,
+}
+
+impl PathTemplate {
+    fn parse(route: &str) -> Result<Self, PathMatcherError> {
+        let (host, path) = match route.find('/') {
+            Some(0) => (None, route),
+            Some(index) => (Some(route[..index].to_string()), &route[index..]),
+            None => {
+                return Err(PathMatcherError::InvalidPath {
+                    path: route.to_string(),
+                });
+            }
+        };
+        let segments: Vec<TemplateSegment> = split_path(path).map(TemplateSegment::parse).collect();
+        let wildcard = segments
+            .iter()
+            .position(|segment| matches!(segment, TemplateSegment::Wildcard(_)));
+        if wildcard.is_some_and(|position| position + 1 != segments.len()) {
+            return Err(PathMatcherError::InvalidPath {
+                path: path.to_string(),
+            });
+        }
+        Ok(Self {
+            route: route.to_string(),
+            priority: 0,
+            host,
+            segments,
+            methods: vec![],
+        })
+    }
+
+    /* the highest priority first, then the paths of a host before the paths of every host,
+    then the most specific, so a longer prefix beats a shorter one; ties go by route name */
+    fn precedence(&self) -> (i32, bool, Vec<u8>, Reverse<&str>) {
+        (
+            self.priority,
+            self.host.is_some(),
+            self.specificity(),
+            Reverse(self.route.as_str()),
+        )
+    }
+
+    fn specificity(&self) -> Vec<u8> {
+        self.segments.iter().map(TemplateSegment::rank).collect()
+    }
+
+    /// Which precedence rule makes this template win over `other`, for the logs.
+    fn wins_by(&self, other: &PathTemplate) -> &'static str {
+        if self.priority != other.priority {
+            "priority"
+        } else if self.host.is_some() != other.host.is_some() {
+            "host"
+        } else if self.specificity() != other.specificity() {
+            "specificity"
+        } else {
+            "route name"
+        }
+    }
+
+    fn serves_host(&self, host: Option<&str>) -> bool {
+        match &self.host {
+            None => true,
+            Some(template_host) => host == Some(template_host.as_str()),
+        }
+    }
+
+    fn serves(&self, host: Option<&str>, method: &str) -> bool {
+        self.serves_host(host) && self.methods.iter().any(|m| m == method)
+    }
+
+    fn matches(&self, path: &str) -> Option<BTreeMap<String, String>> {
+        let request_segments: Vec<&str> = split_path(path).collect();
+        let mut parameters = BTreeMap::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                TemplateSegment::Wildcard(name) => {
+                    let rest = request_segments.get(index..).filter(|rest| !rest.is_empty())?;
+                    parameters.insert(name.clone(), rest.join("/"));
+                    return Some(parameters);
+                }
+                TemplateSegment::Static(expected) => {
+                    if request_segments.get(index) != Some(&expected.as_str()) {
+                        return None;
+                    }
+                }
+                TemplateSegment::Parameter(name) => {
+                    parameters.insert(name.clone(), request_segments.get(index)?.to_string());
+                }
+            }
+        }
+        (request_segments.len() == self.segments.len()).then_some(parameters)
+    }
+}
+
+/// The configured path templates, shared between the matcher and the exchange factory so the
+/// factory can hand the extracted parameters to the handlers.
+#[derive(Debug, Default)]
+pub struct PathTemplates {
+    /* in order of precedence */
+    templates: Vec<PathTemplate>,
+    /* the methods with a fallback chain */
+    fallback_methods: Vec<String>,
+}
+
+/// Whether a request has a chain to run, and if not, why.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteMatch {
+    Found,
+    /* the path matches, but none of its templates serve the method; the methods that do */
+    MethodNotAllowed(Vec<String>),
+    NotFound,
+}
+
+impl PathTemplates {
+    /// The index and parameters of every template matching the request, in order of precedence.
+    fn matching<'a>(
+        &'a self,
+        host: Option<&'a str>,
+        path: &'a str,
+        method: &'a str,
+    ) -> impl Iterator<Item = (usize, BTreeMap<String, String>)> + 'a {
+        self.templates
+            .iter()
+            .enumerate()
+            .filter(move |(_, template)| template.serves(host, method))
+            .filter_map(move |(index, template)| Some((index, template.matches(path)?)))
+    }
+
+    /// The index and parameters of the first template matching the request.
+    fn resolve(
+        &self,
+        host: Option<&str>,
+        path: &str,
+        method: &str,
+    ) -> Option<(usize, BTreeMap<String, String>)> {
+        self.matching(host, path, method).next()
+    }
+
+    /* logs the template a request went to, and why it won over the next one matching */
+    fn log_resolution(&self, host: Option<&str>, path: &str, method: &str) {
+        let mut matching = self.matching(host, path, method);
+        let Some((winner, _)) = matching.next() else {
+            return;
+        };
+        let winner = &self.templates[winner];
+        let rule = match matching.next() {
+            Some((runner_up, _)) => winner.wins_by(&self.templates[runner_up]),
+            None => "only match",
+        };
+        tracing::debug!("{} {} routed to {} by {}", method, path, winner.route, rule);
+    }
+
+    pub fn route_match(&self, host: Option<&str>, path: &str, method: &str) -> RouteMatch {
+        if self.fallback_methods.iter().any(|m| m == method)
+            || self.resolve(host, path, method).is_some()
+        {
+            return RouteMatch::Found;
+        }
+        let allowed: BTreeSet<&String> = self
+            .templates
+            .iter()
+            .filter(|template| template.serves_host(host) && template.matches(path).is_some())
+            .flat_map(|template| &template.methods)
+            .collect();
+        match allowed.is_empty() {
+            true => RouteMatch::NotFound,
+            false => RouteMatch::MethodNotAllowed(allowed.into_iter().cloned().collect()),
+        }
+    }
+
+    /// The route serving the request, as configured, or the fallback route.
+    pub fn route(&self, host: Option<&str>, path: &str, method: &str) -> Option<&str> {
+        match self.resolve(host, path, method) {
+            Some((index, _)) => Some(self.templates[index].route.as_str()),
+            None => self
+                .fallback_methods
+                .iter()
+                .any(|m| m == method)
+                .then_some(FALLBACK_ROUTE),
+        }
+    }
+
+    pub fn path_parameters(
+        &self,
+        host: Option<&str>,
+        path: &str,
+        method: &str,
+    ) -> Option<BTreeMap<String, String>> {
+        self.resolve(host, path, method).map(|(_, parameters)| parameters)
+    }
+}
+
+/// Routes requests by path template. Unlike idemio's `HttpPathMethodMatcher`, which only knows
+/// exact paths and trailing `*`, paths can hold `{name}` parameters. When several templates
+/// match, a route given a higher priority wins first. Then templates of the request's host,
+/// taken from [`REQUEST_HOST`], come before all others, and then the most specific one wins:
+/// at the first segment where they differ, a static segment beats a parameter and a parameter
+/// beats a wildcard. Templates that are still tied go by route name, so the outcome never
+/// depends on the order of the configuration. Requests no template matches get the
+/// fallback chain of their method, if there is one.
+pub struct TemplatePathMatcher<E>
+where
+    E: Send + Sync,
+{
+    templates: Arc<PathTemplates>,
+    /* chains by method, in the order of `templates` */
+    chains: Vec<HashMap<String, Arc<LoadedChain<E>>>>,
+    /* chains by method for requests no template matches */
+    fallback: HashMap<String, Arc<LoadedChain<E>>>,
+}
+
+impl<E> TemplatePathMatcher<E>
+where
+    E: Send + Sync,
+{
+    pub fn templates(&self) -> Arc<PathTemplates> {
+        self.templates.clone()
+    }
+
+    /// Gives routes an explicit priority over the precedence rules. Routes not listed keep
+    /// priority 0; a higher priority wins.
+    pub fn prioritize(&mut self, priorities: &BTreeMap<String, i32>) {
+        let mut templates = self.templates.templates.clone();
+        for template in &mut templates {
+            template.priority = priorities.get(&template.route).copied().unwrap_or_default();
+        }
+        let chains = std::mem::take(&mut self.chains);
+        let mut routes: Vec<_> = templates.into_iter().zip(chains).collect();
+        routes.sort_by(|(a, _), (b, _)| b.precedence().cmp(&a.precedence()));
+        let (templates, chains) = routes.into_iter().unzip();
+        self.templates = Arc::new(PathTemplates {
+            templates,
+            fallback_methods: self.templates.fallback_methods.clone(),
+        });
+        self.chains = chains;
+    }
+}
+
+impl<E> PathMatcher<E> for TemplatePathMatcher<E>
+where
+    E: Send + Sync,
+{
+    fn parse_config(
+        &mut self,
+        route_config: &RouterConfig,
+        handler_registry: &HandlerRegistry<E>,
+    ) -> Result<(), PathMatcherError> {
+        /* the routes are only reachable through the config's serialized form */
+        let paths: HashMap<String, HashMap<String, PathChain>> = serde_json::to_value(route_config)
+            .ok()
+            .and_then(|mut config| serde_json::from_value(config["routes"]["path"].take()).ok())
+            .ok_or_else(|| PathMatcherError::InvalidConfiguration {
+                message: "Route config type should be HttpRequestPaths".to_string(),
+            })?;
+
+        let mut routes = vec![];
+        let mut fallback = HashMap::new();
+        for (path, methods) in &paths {
+            let mut chains = HashMap::new();
+            for (method, path_chain) in methods {
+                let chain = Self::load_handlers(handler_registry, path_chain)?;
+                chains.insert(method.clone(), Arc::new(chain));
+            }
+            if path == FALLBACK_ROUTE {
+                fallback = chains;
+                continue;
+            }
+            let mut template = PathTemplate::parse(path)?;
+            template.methods = chains.keys().cloned().collect();
+            routes.push((template, chains));
+        }
+        routes.sort_by(|(a, _), (b, _)| b.precedence().cmp(&a.precedence()));
+        let (templates, chains) = routes.into_iter().unzip();
+        self.templates = Arc::new(PathTemplates {
+            templates,
+            fallback_methods: fallback.keys().cloned().collect(),
+        });
+        self.chains = chains;
+        self.fallback = fallback;
+        Ok(())
+    }
+
+    fn lookup(&self, key: RouteInfo<'_>) -> Option<Arc<LoadedChain<E>>> {
+        let (path, method) = (key.path?, key.method?);
+        let host = REQUEST_HOST.try_with(Clone::clone).ok().flatten();
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            self.templates.log_resolution(host.as_deref(), path, method);
+        }
+        match self.templates.resolve(host.as_deref(), path, method) {
+            Some((index, _)) => self.chains[index].get(method).cloned(),
+            None => self.fallback.get(method).cloned(),
+        }
+    }
+
+    fn new(
+        config: &RouterConfig,
+        handler_registry: &HandlerRegistry<E>,
+    ) -> Result<Self, PathMatcherError> {
+        let mut matcher = Self {
+            templates: Arc::default(),
+            chains: vec![],
+            fallback: HashMap::new(),
+        };
+        matcher.parse_config(config, handler_registry)?;
+        Ok(matcher)
+    }
+}
+
+pub fn set_path_parameters(exchange: &mut LambdaExchange, parameters: BTreeMap<String, String>) {
+    exchange
+        .attachments_mut()
+        .well_known_mut()
+        .add::<BTreeMap<String, String>>(PATH_PARAMETERS_ATTACHMENT_KEY, parameters);
+}
+
+pub fn set_route(exchange: &mut LambdaExchange, route: &str) {
+    exchange
+        .attachments_mut()
+        .well_known_mut()
+        .add::<String>(ROUTE_ATTACHMENT_KEY, route.to_string());
+}
+
+/// The configured route serving the request, like `/pets/{id}`, or `*` for the fallback.
+pub fn matched_route(exchange: &LambdaExchange) -> Option<&str> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<String>(ROUTE_ATTACHMENT_KEY)
+        .map(String::as_str)
+}
+
+pub fn set_route_metadata(exchange: &mut LambdaExchange, metadata: RouteMetadata) {
+    exchange
+        .attachments_mut()
+        .well_known_mut()
+        .add::<RouteMetadata>(ROUTE_METADATA_ATTACHMENT_KEY, metadata);
+}
+
+/// The `metadata` of the flow serving the request.
+pub fn route_metadata(exchange: &LambdaExchange) -> Option<&RouteMetadata> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<RouteMetadata>(ROUTE_METADATA_ATTACHMENT_KEY)
+}
+
+/// The parameters extracted from the request path by the route's template.
+pub fn path_parameters(exchange: &LambdaExchange) -> Option<&BTreeMap<String, String>> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<BTreeMap<String, String>>(PATH_PARAMETERS_ATTACHMENT_KEY)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::matcher::{PathTemplate, PathTemplates, RouteMatch};
+
+    fn templates(paths: &[&str]) -> PathTemplates {
+        let mut templates: Vec<PathTemplate> = paths
+            .iter()
+            .map(|path| {
+                let mut template = PathTemplate::parse(path).unwrap();
+                template.methods.push("GET".to_string());
+                template
+            })
+            .collect();
+        templates.sort_by(|a, b| b.precedence().cmp(&a.precedence()));
+        PathTemplates {
+            templates,
+            fallback_methods: vec![],
+        }
+    }
+
+    #[test]
+    fn test_path_template_precedence() {
+        let templates = templates(&["/pets/*", "/pets/{id}", "/pets/mine", "/pets/{id}/toys"]);
+        let parameters = templates.path_parameters(None, "/pets/123", "GET").unwrap();
+        assert_eq!(parameters["id"], "123");
+        assert!(templates.path_parameters(None, "/pets/mine", "GET").unwrap().is_empty());
+        let parameters = templates.path_parameters(None, "/pets/123/toys", "GET").unwrap();
+        assert_eq!(parameters["id"], "123");
+        let parameters = templates.path_parameters(None, "/pets/123/food/dry", "GET").unwrap();
+        assert_eq!(parameters["proxy"], "123/food/dry");
+        assert!(templates.path_parameters(None, "/pets", "GET").is_none());
+        assert!(templates.path_parameters(None, "/pets/123", "POST").is_none());
+    }
+
+    #[test]
+    fn test_host_templates() {
+        let templates = templates(&["/pets/mine", "api.tenant-a.com/pets/{id}"]);
+        let tenant_a = Some("api.tenant-a.com");
+        let (index, parameters) = templates.resolve(tenant_a, "/pets/mine", "GET").unwrap();
+        assert_eq!(templates.templates[index].host.as_deref(), tenant_a);
+        assert_eq!(parameters["id"], "mine");
+        let (index, _) = templates.resolve(Some("api.tenant-b.com"), "/pets/mine", "GET").unwrap();
+        assert_eq!(templates.templates[index].host, None);
+        assert!(templates.path_parameters(None, "/pets/123", "GET").is_none());
+    }
+
+    #[test]
+    fn test_route_match() {
+        let mut templates = templates(&["/pets/{id}", "api.tenant-a.com/toys"]);
+        let pets = templates.templates.iter_mut().find(|t| t.host.is_none()).unwrap();
+        pets.methods.push("DELETE".to_string());
+        assert_eq!(templates.route_match(None, "/pets/1", "GET"), RouteMatch::Found);
+        assert_eq!(
+            templates.route_match(None, "/pets/1", "POST"),
+            RouteMatch::MethodNotAllowed(vec!["DELETE".to_string(), "GET".to_string()])
+        );
+        assert_eq!(templates.route_match(None, "/toys", "GET"), RouteMatch::NotFound);
+        templates.fallback_methods.push("POST".to_string());
+        assert_eq!(templates.route_match(None, "/pets/1", "POST"), RouteMatch::Found);
+    }
+
+    #[test]
+    fn test_route_priority() {
+        let mut templates = templates(&["/files/{name}", "/files/{id}", "/files/*"]);
+        let (index, parameters) = templates.resolve(None, "/files/a", "GET").unwrap();
+        assert_eq!(templates.templates[index].route, "/files/{id}");
+        assert_eq!(parameters["id"], "a");
+        assert_eq!(templates.templates[index].wins_by(&templates.templates[1]), "route name");
+
+        let files = templates.templates.iter_mut().find(|t| t.route == "/files/*").unwrap();
+        files.priority = 1;
+        templates.templates.sort_by(|a, b| b.precedence().cmp(&a.precedence()));
+        let (index, _) = templates.resolve(None, "/files/a", "GET").unwrap();
+        assert_eq!(templates.templates[index].route, "/files/*");
+        assert_eq!(templates.templates[0].wins_by(&templates.templates[1]), "priority");
+        assert_eq!(templates.templates[1].wins_by(&templates.templates[2]), "route name");
+    }
+
+    #[test]
+    fn test_wildcard_must_be_last() {
+        assert!(PathTemplate::parse("/files/{path+}").is_ok());
+        assert!(PathTemplate::parse("/files/*/meta").is_err());
+        assert!(PathTemplate::parse("api.example.com").is_err());
+    }
+}
@@ -0,0 +1,688 @@
+pub mod base_path;
+pub mod builder;
+pub mod matcher;
+pub mod openapi;
+pub mod registry;
+
+use idemio::router::config::builder::{
+    MethodBuilder, RouteBuilder, ServiceBuilder, SingleServiceConfigBuilder,
+};
+use idemio::router::config::RouterConfig;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::config::loader::ConfigSource;
+use crate::config::units::{
+    deserialize_duration_map, deserialize_optional_duration, serialize_duration_map,
+    serialize_optional_duration,
+};
+use crate::event::warmup::WarmupConfig;
+use crate::flow::base_path::BasePathConfig;
+use crate::event::EventRoutes;
+use crate::executor::budget::LatencyBudget;
+use crate::executor::retry::RetryPolicy;
+use crate::executor::sampling::SamplingConfig;
+
+/// The execution flow of the gateway, as found in `handlers.json`:
+///
+/// ```json
+/// {
+///   "handlers": ["TraceabilityHandler", "JwtValidationHandler", "LambdaProxyHandler"],
+///   "chains": { "default": ["TraceabilityHandler", "JwtValidationHandler"] },
+///   "paths": {
+///     "/path/to/resource": { "method": "POST", "exec": ["default", "LambdaProxyHandler"] }
+///   }
+/// }
+/// ```
+///
+/// Each `exec` entry is a chain or handler name. The last handler of `exec` terminates the
+/// request, everything before it runs in the request phase. Handlers in the optional `response`
+/// list run after that with the response in place, whether the chain succeeded or not. A path
+/// can also list several methods as an array of flows.
+///
+/// Chains can contain other chains, referenced by name or as `chain:<name>`, as long as no
+/// chain ends up containing itself:
+/// `"chains": { "secured": ["TraceabilityHandler", "chain:auth"], "auth": ["..."] }`.
+///
+/// Paths are templates: `/pets/{id}` matches one segment as the `id` parameter, and a trailing
+/// `{path+}` or `*` matches the rest of the path. The most specific template wins.
+///
+/// Requests no path matches go to the `fallback` flows, e.g. a catch-all proxy or a 404
+/// responder: `"fallback": { "method": "ANY", "exec": ["NotFoundHandler"] }`.
+///
+/// Handlers every route needs, like traceability or metrics, can go into `default_chain`
+/// instead of every `exec`. A path can add handlers around its own with `prepend` and `append`,
+/// or opt out of some with `exclude`.
+///
+/// When several paths match a request, the one with the highest `priority` wins, then the most
+/// specific one. `"priority": 10` on a flow of `/files/*` makes it win over `/files/{id}`.
+///
+/// A flow's `metadata`, e.g. `{ "service": "pets", "team": "core", "tier": "gold" }`, is placed
+/// into the exchange of every request it serves, for metrics, audit or rate-limit handlers to
+/// tag their output with.
+///
+/// Requests taking longer than their flow's `latency_budget`, in total or in one of the listed
+/// handlers, are flagged in the access log and audit record and counted as slow:
+/// `"latency_budget": { "total": "800ms", "handlers": { "LambdaProxyHandler": "600ms" } }`.
+/// With `"server_timing": true` the response breaks the time down by handler.
+///
+/// A handler running longer than its entry under `timeouts`, or `default_timeout` when it has
+/// none, is stopped and ends the chain with a timeout:
+/// `"timeouts": { "LambdaProxyHandler": "3s" }, "default_timeout": "1s"`.
+///
+/// Handlers that only read the request, like lookups and validations, can run concurrently as one
+/// stage listed under `parallel`. A stage is used in chains and `exec` like a handler, and runs
+/// handlers that must themselves be listed under `handlers`:
+/// `"parallel": { "checks": ["IpFilterHandler", "ApiKeyHandler"] }`.
+///
+/// A flow can serve several `methods` at once, or `ANY` method. A method listed explicitly in
+/// another flow of the same path takes precedence over `ANY`.
+///
+/// Paths under `hosts` only serve requests for that host, taken from the `Host` header or
+/// API Gateway's domain name. They take precedence over the paths serving every host:
+///
+/// ```json
+/// {
+///   "hosts": {
+///     "api.tenant-a.com": { "paths": { "/pets": { "method": "GET", "exec": ["..."] } } }
+///   }
+/// }
+/// ```
+///
+/// Events from SQS, SNS and EventBridge run through the flow of the path configured under
+/// `events` for their queue, topic or detail-type, as `POST` requests carrying the message or
+/// event detail. `*` stands for any other queue, topic or detail-type:
+///
+/// ```json
+/// {
+///   "events": {
+///     "sqs": { "orders": "/events/orders", "*": "/events/default" },
+///     "sns": { "order-updates": "/events/orders" },
+///     "eventbridge": { "Order Created": "/events/orders" }
+///   }
+/// }
+/// ```
+///
+/// Paths are matched after stripping the stage and base path prefixes configured under
+/// `base_path`, so one flow serves `/dev/api/pets` and `/prod/api/pets` alike:
+///
+/// ```json
+/// { "base_path": { "strip_stage": true, "base_paths": ["/api"] } }
+/// ```
+///
+/// Scheduled warm-up pings, recognized by a `warmup` marker, are answered with a 200 before any
+/// chain is built or run. With `prewarm` they also run the handlers' warm-up hooks:
+///
+/// ```json
+/// { "warmup": { "source": "aws.events", "header": "x-warmup", "prewarm": true } }
+/// ```
+///
+/// Access logs, spans and audit records can be kept for a share of the requests only, set per
+/// route under `sampling`:
+///
+/// ```json
+/// { "sampling": { "percentage": 10, "routes": { "/health": 0 } } }
+/// ```
+///
+/// Each handler's config is read from `/opt/config/<handler name>.json`, or from the file set
+/// for it under `config_sources`. Fields the file leaves out keep their defaults:
+///
+/// ```json
+/// { "config_sources": { "JwtValidationHandler": { "file": "jwt_validator.json",
+///   "ssm_path": "/idem/prod/jwt/", "ssm_ttl": "5m" } } }
+/// ```
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct ExecutionFlowConfig {
+    #[serde(default)]
+    pub handlers: Vec<String>,
+    #[serde(default)]
+    pub chains: BTreeMap<String, Vec<String>>,
+    /* chains or handlers every route runs first in its request phase */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_chain: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_paths")]
+    pub paths: BTreeMap<String, Vec<PathFlowConfig>>,
+    /* handles the requests no path matches */
+    #[serde(default, deserialize_with = "deserialize_flows", skip_serializing_if = "Vec::is_empty")]
+    pub fallback: Vec<PathFlowConfig>,
+    /* retry policies by handler name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub retry: BTreeMap<String, RetryPolicy>,
+    /* maximum execution times by handler name */
+    #[serde(
+        default,
+        deserialize_with = "deserialize_duration_map",
+        serialize_with = "serialize_duration_map",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub timeouts: BTreeMap<String, Duration>,
+    /* the maximum execution time of handlers without one under `timeouts` */
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_duration",
+        serialize_with = "serialize_optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_timeout: Option<Duration>,
+    /* handlers run concurrently as one stage, by stage name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parallel: BTreeMap<String, Vec<String>>,
+    /* maximum concurrent executions within the container, by handler name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_concurrency: BTreeMap<String, usize>,
+    /* status flags, built-in or user-defined, that end the request phase without an error */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub complete_on: Vec<String>,
+    /* flows that only serve requests for one host, by host name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, HostFlowConfig>,
+    /* the paths whose flows process non-HTTP events, by event source */
+    #[serde(default, skip_serializing_if = "EventRoutes::is_empty")]
+    pub events: EventRoutes,
+    /* prefixes stripped from request paths before routing */
+    #[serde(default, skip_serializing_if = "BasePathConfig::is_empty")]
+    pub base_path: BasePathConfig,
+    /* how warm-up pings are recognized */
+    #[serde(default, skip_serializing_if = "WarmupConfig::is_empty")]
+    pub warmup: WarmupConfig,
+    /* which requests are logged, traced and audited */
+    #[serde(default, skip_serializing_if = "SamplingConfig::is_empty")]
+    pub sampling: SamplingConfig,
+    /* where handler configs are loaded from, by handler name */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config_sources: BTreeMap<String, ConfigSource>,
+}
+
+/* marks an entry as a reference to a chain, e.g. `chain:auth` */
+const CHAIN_REFERENCE_PREFIX: &str = "chain:";
+
+/* the route the fallback flows are registered under, it can't be mistaken for a path */
+pub(crate) const FALLBACK_ROUTE: &str = "*";
+
+/* every method an `ANY` flow is registered for */
+const ANY_METHOD: &str = "ANY";
+const ANY_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS", "HEAD"];
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct PathFlowConfig {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    pub exec: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response: Vec<String>,
+    /* run after the default chain and before `exec` */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prepend: Vec<String>,
+    /* run after `exec`, before its termination handler */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub append: Vec<String>,
+    /* handlers left out of the request phase, e.g. from the default chain */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /* free-form values handed to the handlers of the flow, like the owning team or SLO tier */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: RouteMetadata,
+    /* ranks the path above (or below) other matching paths; the highest of its flows counts */
+    #[serde(default, skip_serializing_if = "is_default_priority")]
+    pub priority: i32,
+    /* how long the flow's requests may take before they are flagged as slow */
+    #[serde(default, skip_serializing_if = "LatencyBudget::is_empty")]
+    pub latency_budget: LatencyBudget,
+}
+
+/// Metadata of a flow, by key.
+pub type RouteMetadata = BTreeMap<String, Value>;
+
+fn is_default_priority(priority: &i32) -> bool {
+    *priority == 0
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct HostFlowConfig {
+    #[serde(default, deserialize_with = "deserialize_paths")]
+    pub paths: BTreeMap<String, Vec<PathFlowConfig>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrManyFlows {
+    One(Box<PathFlowConfig>),
+    Many(Vec<PathFlowConfig>),
+}
+
+impl From<OneOrManyFlows> for Vec<PathFlowConfig> {
+    fn from(flows: OneOrManyFlows) -> Self {
+        match flows {
+            OneOrManyFlows::One(flow) => vec![*flow],
+            OneOrManyFlows::Many(flows) => flows,
+        }
+    }
+}
+
+fn deserialize_flows<'de, D>(deserializer: D) -> Result<Vec<PathFlowConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    OneOrManyFlows::deserialize(deserializer).map(Vec::from)
+}
+
+fn deserialize_paths<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Vec<PathFlowConfig>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let paths: BTreeMap<String, OneOrManyFlows> = BTreeMap::deserialize(deserializer)?;
+    Ok(paths
+        .into_iter()
+        .map(|(path, flows)| (path, flows.into()))
+        .collect())
+}
+
+impl PathFlowConfig {
+    /// Every method of the flow, upper case, with `ANY` left unexpanded.
+    pub fn all_methods(&self) -> Vec<String> {
+        std::iter::once(&self.method)
+            .filter(|method| !method.is_empty())
+            .chain(&self.methods)
+            .map(|method| method.to_uppercase())
+            .collect()
+    }
+}
+
+impl ExecutionFlowConfig {
+    /* the chain an entry refers to: `chain:<name>`, or the name of a chain on its own */
+    fn chain_reference<'a>(&self, entry: &'a str) -> Option<&'a str> {
+        match entry.strip_prefix(CHAIN_REFERENCE_PREFIX) {
+            Some(name) => Some(name),
+            None => self.chains.contains_key(entry).then_some(entry),
+        }
+    }
+
+    /// Expands chain references in `entries` to the handlers they contain, including the
+    /// chains referenced by those chains.
+    pub fn expand(&self, entries: &[String]) -> Vec<String> {
+        let mut expanded = vec![];
+        self.expand_into(entries, &mut vec![], &mut expanded);
+        expanded
+    }
+
+    /* cycles are cut short here, `check_chains` reports them */
+    fn expand_into<'a>(
+        &'a self,
+        entries: &'a [String],
+        visiting: &mut Vec<&'a str>,
+        expanded: &mut Vec<String>,
+    ) {
+        for entry in entries {
+            let name = match self.chain_reference(entry) {
+                Some(name) => name,
+                None => {
+                    expanded.push(entry.clone());
+                    continue;
+                }
+            };
+            let Some(chain) = self.chains.get(name) else {
+                expanded.push(entry.clone());
+                continue;
+            };
+            if visiting.contains(&name) {
+                continue;
+            }
+            visiting.push(name);
+            self.expand_into(chain, visiting, expanded);
+            visiting.pop();
+        }
+    }
+
+    /// Checks that every chain reference names a chain, and that no chain contains itself.
+    pub fn check_chains(&self) -> Result<(), String> {
+        for (name, chain) in &self.chains {
+            self.check_references(chain, &mut vec![name.as_str()])?;
+        }
+        let flows = self.routes()?.into_iter().flat_map(|(_, flows)| flows);
+        let mut entries: Vec<&[String]> = vec![&self.default_chain];
+        for flow in flows {
+            let lists = [&flow.exec, &flow.prepend, &flow.append, &flow.response];
+            entries.extend(lists.map(Vec::as_slice));
+        }
+        for entries in entries {
+            self.check_references(entries, &mut vec![])?;
+        }
+        Ok(())
+    }
+
+    fn check_references<'a>(
+        &'a self,
+        entries: &'a [String],
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<(), String> {
+        for entry in entries {
+            let Some(name) = self.chain_reference(entry) else {
+                continue;
+            };
+            let Some(chain) = self.chains.get(name) else {
+                return Err(format!("Chain {} is not defined", name));
+            };
+            if visiting.contains(&name) {
+                visiting.push(name);
+                return Err(format!("Chains form a cycle: {}", visiting.join(" -> ")));
+            }
+            visiting.push(name);
+            self.check_references(chain, visiting)?;
+            visiting.pop();
+        }
+        Ok(())
+    }
+
+    /* An explicitly listed method takes precedence over the same method from an `ANY` flow. */
+    fn flows_by_method<'a>(
+        path: &str,
+        flows: &'a [PathFlowConfig],
+    ) -> Result<BTreeMap<String, &'a PathFlowConfig>, String> {
+        let mut by_method = BTreeMap::new();
+        let mut any = BTreeMap::new();
+        for flow in flows {
+            let methods = flow.all_methods();
+            if methods.is_empty() {
+                return Err(format!("A flow of {} has no method", path));
+            }
+            for method in methods {
+                if method == ANY_METHOD {
+                    for any_method in ANY_METHODS {
+                        any.insert(any_method.to_string(), flow);
+                    }
+                } else if by_method.insert(method.clone(), flow).is_some() {
+                    return Err(format!("{} {} is configured more than once", method, path));
+                }
+            }
+        }
+        for (method, flow) in any {
+            by_method.entry(method).or_insert(flow);
+        }
+        Ok(by_method)
+    }
+
+    /// The request handlers and the termination handler of a flow. The request phase runs the
+    /// default chain, `prepend`, `exec` up to its last handler and `append`, in that order,
+    /// without the `exclude`d handlers. A handler listed twice only runs the first time.
+    pub fn handlers_of(&self, flow: &PathFlowConfig) -> Option<(Vec<String>, String)> {
+        let mut exec = self.expand(&flow.exec);
+        let termination_handler = exec.pop()?;
+        let listed = self
+            .expand(&self.default_chain)
+            .into_iter()
+            .chain(self.expand(&flow.prepend))
+            .chain(exec)
+            .chain(self.expand(&flow.append));
+        let mut request_handlers: Vec<String> = vec![];
+        for handler in listed {
+            if !flow.exclude.contains(&handler) && !request_handlers.contains(&handler) {
+                request_handlers.push(handler);
+            }
+        }
+        Some((request_handlers, termination_handler))
+    }
+
+    /* Every path with its flows; the paths of a host are prefixed with the host name. */
+    fn routes(&self) -> Result<Vec<(String, &[PathFlowConfig])>, String> {
+        let mut routes: Vec<(String, &[PathFlowConfig])> = self
+            .paths
+            .iter()
+            .map(|(path, flows)| (path.clone(), flows.as_slice()))
+            .collect();
+        if !self.fallback.is_empty() {
+            routes.push((FALLBACK_ROUTE.to_string(), self.fallback.as_slice()));
+        }
+        for (host, host_flow) in &self.hosts {
+            for (path, flows) in &host_flow.paths {
+                if !path.starts_with('/') {
+                    return Err(format!("Path {} of host {} must start with /", path, host));
+                }
+                routes.push((format!("{}{}", host.to_lowercase(), path), flows.as_slice()));
+            }
+        }
+        Ok(routes)
+    }
+
+    /// The explicit priority of every route that has one, by route as passed to the matcher.
+    pub fn route_priorities(&self) -> Result<BTreeMap<String, i32>, String> {
+        let mut priorities = BTreeMap::new();
+        for (route, flows) in self.routes()? {
+            let priority = flows.iter().map(|flow| flow.priority).max().unwrap_or_default();
+            if priority != 0 {
+                priorities.insert(route, priority);
+            }
+        }
+        Ok(priorities)
+    }
+
+    /// The metadata of every route and method that has some, by route as passed to the matcher.
+    pub fn route_metadata(
+        &self,
+    ) -> Result<BTreeMap<String, BTreeMap<String, RouteMetadata>>, String> {
+        let mut metadata = BTreeMap::new();
+        for (route, flows) in self.routes()? {
+            let by_method: BTreeMap<String, RouteMetadata> = Self::flows_by_method(&route, flows)?
+                .into_iter()
+                .filter(|(_, flow)| !flow.metadata.is_empty())
+                .map(|(method, flow)| (method, flow.metadata.clone()))
+                .collect();
+            if !by_method.is_empty() {
+                metadata.insert(route, by_method);
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// The latency budget of every route and method that has one, by route as passed to the
+    /// matcher.
+    pub fn latency_budgets(
+        &self,
+    ) -> Result<BTreeMap<String, BTreeMap<String, LatencyBudget>>, String> {
+        let mut budgets = BTreeMap::new();
+        for (route, flows) in self.routes()? {
+            let by_method: BTreeMap<String, LatencyBudget> = Self::flows_by_method(&route, flows)?
+                .into_iter()
+                .filter(|(_, flow)| !flow.latency_budget.is_empty())
+                .map(|(method, flow)| (method, flow.latency_budget.clone()))
+                .collect();
+            if !by_method.is_empty() {
+                budgets.insert(route, by_method);
+            }
+        }
+        Ok(budgets)
+    }
+
+    /// Converts the flow into the router configuration used by the path matcher.
+    pub fn router_config(&self) -> Result<RouterConfig, String> {
+        self.check_chains()?;
+        let mut handlers = self.handlers.clone();
+        handlers.extend(self.parallel.keys().cloned());
+        let mut service_builder = SingleServiceConfigBuilder::new().handlers(handlers.as_slice());
+        for (chain_name, chain) in &self.chains {
+            service_builder = service_builder.chain(chain_name, self.expand(chain).as_slice());
+        }
+        for (path, flows) in self.routes()? {
+            let mut route_builder = service_builder.route(&path);
+            for (method, flow) in Self::flows_by_method(&path, flows)? {
+                let (request_handlers, termination_handler) = match self.handlers_of(flow) {
+                    Some(handlers) => handlers,
+                    None => {
+                        return Err(format!("{} {} has no termination handler", method, path));
+                    }
+                };
+                route_builder = route_builder
+                    .create_method_builder(method)
+                    .request_handlers(request_handlers.as_slice())
+                    .termination_handler(termination_handler)
+                    .response_handlers(self.expand(&flow.response).as_slice())
+                    .end_method();
+            }
+            service_builder = route_builder.end_route();
+        }
+        Ok(service_builder.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::{ExecutionFlowConfig, PathFlowConfig, FALLBACK_ROUTE};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_handlers_json() {
+        let flow: ExecutionFlowConfig =
+            serde_json::from_str(include_str!("../../config/handlers.json")).unwrap();
+        let health = &flow.paths["/health"];
+        assert_eq!(health.len(), 1);
+        assert_eq!(health[0].method, "GET");
+        assert_eq!(
+            flow.expand(&health[0].exec),
+            vec!["TraceabilityHandler", "JwtValidationHandler", "HealthCheckHandler"]
+        );
+        assert!(flow.router_config().is_ok());
+
+        let mut empty = flow.clone();
+        empty.paths.insert(
+            "/empty".to_string(),
+            vec![PathFlowConfig {
+                method: "GET".to_string(),
+                ..Default::default()
+            }],
+        );
+        assert!(empty.router_config().is_err());
+    }
+
+    #[test]
+    fn test_nested_chains() {
+        let mut flow: ExecutionFlowConfig = serde_json::from_value(serde_json::json!({
+            "chains": {
+                "auth": ["JwtValidationHandler"],
+                "secured": ["TraceabilityHandler", "chain:auth", "limits"],
+                "limits": ["HeaderHandler"]
+            },
+            "paths": { "/pets": { "method": "GET", "exec": ["secured", "EchoRequestHandler"] } }
+        }))
+        .unwrap();
+        assert!(flow.check_chains().is_ok());
+        assert_eq!(
+            flow.expand(&flow.paths["/pets"][0].exec),
+            vec![
+                "TraceabilityHandler",
+                "JwtValidationHandler",
+                "HeaderHandler",
+                "EchoRequestHandler"
+            ]
+        );
+
+        flow.chains.get_mut("limits").unwrap().push("chain:secured".to_string());
+        assert_eq!(
+            flow.check_chains().unwrap_err(),
+            "Chains form a cycle: limits -> secured -> limits"
+        );
+    }
+
+    #[test]
+    fn test_default_chain() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["TraceabilityHandler", "JwtValidationHandler", "HeaderHandler",
+                             "CorsHandler", "HealthCheckHandler", "LambdaProxyHandler"],
+                "chains": { "auth": ["JwtValidationHandler"] },
+                "default_chain": ["TraceabilityHandler", "auth"],
+                "paths": {
+                    "/health": {
+                        "method": "GET",
+                        "exec": ["HealthCheckHandler"],
+                        "exclude": ["JwtValidationHandler"]
+                    },
+                    "/pets": {
+                        "method": "GET",
+                        "prepend": ["CorsHandler"],
+                        "exec": ["TraceabilityHandler", "LambdaProxyHandler"],
+                        "append": ["HeaderHandler"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let (request_handlers, termination_handler) =
+            flow.handlers_of(&flow.paths["/health"][0]).unwrap();
+        assert_eq!(request_handlers, vec!["TraceabilityHandler"]);
+        assert_eq!(termination_handler, "HealthCheckHandler");
+        let (request_handlers, termination_handler) =
+            flow.handlers_of(&flow.paths["/pets"][0]).unwrap();
+        assert_eq!(
+            request_handlers,
+            vec!["TraceabilityHandler", "JwtValidationHandler", "CorsHandler", "HeaderHandler"]
+        );
+        assert_eq!(termination_handler, "LambdaProxyHandler");
+        assert!(flow.router_config().is_ok());
+    }
+
+    #[test]
+    fn test_fallback() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["EchoRequestHandler", "LambdaProxyHandler"],
+                "paths": { "/echo": { "method": "GET", "exec": ["EchoRequestHandler"] } },
+                "fallback": { "method": "ANY", "exec": ["LambdaProxyHandler"] }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(flow.fallback.len(), 1);
+        let routes = flow.routes().unwrap();
+        assert!(routes.iter().any(|(path, _)| path == FALLBACK_ROUTE));
+        assert!(flow.router_config().is_ok());
+    }
+
+    #[test]
+    fn test_methods_and_any() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["CorsHandler", "EchoRequestHandler", "LambdaProxyHandler"],
+                "paths": {
+                    "/pets": [
+                        { "method": "ANY", "exec": ["LambdaProxyHandler"] },
+                        { "methods": ["OPTIONS", "head"], "exec": ["CorsHandler"] }
+                    ],
+                    "/echo": { "methods": ["GET", "POST"], "exec": ["EchoRequestHandler"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        let pets = ExecutionFlowConfig::flows_by_method("/pets", &flow.paths["/pets"]).unwrap();
+        assert_eq!(pets.len(), 7);
+        assert_eq!(pets["GET"].exec, vec!["LambdaProxyHandler"]);
+        assert_eq!(pets["OPTIONS"].exec, vec!["CorsHandler"]);
+        assert_eq!(pets["HEAD"].exec, vec!["CorsHandler"]);
+        let echo = ExecutionFlowConfig::flows_by_method("/echo", &flow.paths["/echo"]).unwrap();
+        assert_eq!(echo.keys().collect::<Vec<_>>(), vec!["GET", "POST"]);
+        assert!(flow.router_config().is_ok());
+
+        let duplicate = [flow.paths["/echo"][0].clone(), flow.paths["/echo"][0].clone()];
+        assert!(ExecutionFlowConfig::flows_by_method("/echo", &duplicate).is_err());
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["LambdaProxyHandler"],
+                "timeouts": { "LambdaProxyHandler": "3s" },
+                "default_timeout": 500
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(flow.timeouts["LambdaProxyHandler"], Duration::from_secs(3));
+        assert_eq!(flow.default_timeout, Some(Duration::from_millis(500)));
+        let serialized = serde_json::to_value(&flow).unwrap();
+        assert_eq!(serde_json::from_value::<ExecutionFlowConfig>(serialized).unwrap(), flow);
+        assert!(serde_json::from_str::<ExecutionFlowConfig>(r#"{ "default_timeout": "soon" }"#)
+            .is_err());
+    }
+}
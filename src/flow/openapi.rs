@@ -0,0 +1,207 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::flow::{ExecutionFlowConfig, PathFlowConfig};
+use crate::handler::cors::CorsRouteConfig;
+
+/* chain names, a single name or a list, run before the handlers */
+const CHAIN_EXTENSION: &str = "x-idem-chain";
+const HANDLERS_EXTENSION: &str = "x-idem-handlers";
+/* chain definitions at the root of the spec, like `chains` in handlers.json */
+const CHAINS_EXTENSION: &str = "x-idem-chains";
+
+const OPERATION_METHODS: &[&str] =
+    &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(name) => Some(vec![name.clone()]),
+        Value::Array(names) => names
+            .iter()
+            .map(|name| name.as_str().map(String::from))
+            .collect(),
+        _ => None,
+    }
+}
+
+/* the extension of the operation, else of its path item, else of the spec */
+fn extension(levels: &[&Value], name: &str) -> Result<Vec<String>, String> {
+    match levels.iter().find_map(|level| level.get(name)) {
+        Some(value) => string_list(value)
+            .ok_or_else(|| format!("{} must be a name or a list of names", name)),
+        None => Ok(vec![]),
+    }
+}
+
+/// Builds the routes of the flow from an OpenAPI spec instead of `paths` in `handlers.json`,
+/// so the two can't drift apart. Every operation becomes a route whose `exec` is its
+/// `x-idem-chain` followed by its `x-idem-handlers`; either can also be set on the path item or
+/// the spec's root as a default for the operations below. Chains can be defined in the spec
+/// under `x-idem-chains`. Everything else, like retries or event routes, comes from `base`.
+pub fn flow_from_openapi(
+    spec: &Value,
+    base: &ExecutionFlowConfig,
+) -> Result<ExecutionFlowConfig, String> {
+    let mut flow = base.clone();
+    if let Some(chains) = spec.get(CHAINS_EXTENSION).and_then(Value::as_object) {
+        for (name, handlers) in chains {
+            let handlers = string_list(handlers)
+                .ok_or_else(|| format!("Chain {} must be a list of handler names", name))?;
+            flow.chains.insert(name.clone(), handlers);
+        }
+    }
+
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| "The OpenAPI spec has no paths".to_string())?;
+    let mut routes = BTreeMap::new();
+    for (path, path_item) in paths {
+        let mut flows = vec![];
+        for method in OPERATION_METHODS {
+            let Some(operation) = path_item.get(*method) else {
+                continue;
+            };
+            let levels = [operation, path_item, spec];
+            let mut exec = extension(&levels, CHAIN_EXTENSION)?;
+            exec.extend(extension(&levels, HANDLERS_EXTENSION)?);
+            if exec.is_empty() {
+                return Err(format!(
+                    "{} {} has neither {} nor {}",
+                    method.to_uppercase(),
+                    path,
+                    CHAIN_EXTENSION,
+                    HANDLERS_EXTENSION
+                ));
+            }
+            flows.push(PathFlowConfig {
+                method: method.to_uppercase(),
+                exec,
+                ..Default::default()
+            });
+        }
+        if !flows.is_empty() {
+            routes.insert(path.clone(), flows);
+        }
+    }
+    flow.paths = routes;
+
+    /* every handler a route uses has to be created */
+    let used: Vec<String> = flow
+        .paths
+        .values()
+        .flatten()
+        .flat_map(|path_flow| flow.expand(&path_flow.exec))
+        .collect();
+    for handler in used {
+        if !flow.handlers.contains(&handler) {
+            flow.handlers.push(handler);
+        }
+    }
+    Ok(flow)
+}
+
+/* the names of the `in: header` parameters of the levels */
+fn header_parameters(levels: &[&Value]) -> Vec<String> {
+    levels
+        .iter()
+        .filter_map(|level| level.get("parameters").and_then(Value::as_array))
+        .flatten()
+        .filter(|parameter| parameter.get("in").and_then(Value::as_str) == Some("header"))
+        .filter_map(|parameter| parameter.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+/// What a browser may ask for in a preflight for each path of an OpenAPI spec: the methods of
+/// its operations, and the headers they declare as parameters, plus `Content-Type` for request
+/// bodies and `Authorization` for secured operations.
+pub fn cors_routes_from_openapi(spec: &Value) -> BTreeMap<String, CorsRouteConfig> {
+    let mut routes = BTreeMap::new();
+    let Some(paths) = spec.get("paths").and_then(Value::as_object) else {
+        return routes;
+    };
+    for (path, path_item) in paths {
+        let mut route = CorsRouteConfig::default();
+        for method in OPERATION_METHODS {
+            let Some(operation) = path_item.get(*method) else {
+                continue;
+            };
+            route.methods.push(method.to_uppercase());
+            let mut headers = header_parameters(&[path_item, operation]);
+            if operation.get("requestBody").is_some() {
+                headers.push("Content-Type".to_string());
+            }
+            let security = operation.get("security").or_else(|| spec.get("security"));
+            if security.and_then(Value::as_array).is_some_and(|security| !security.is_empty()) {
+                headers.push("Authorization".to_string());
+            }
+            for header in headers {
+                if !route.headers.iter().any(|known| known.eq_ignore_ascii_case(&header)) {
+                    route.headers.push(header);
+                }
+            }
+        }
+        if !route.methods.is_empty() {
+            routes.insert(path.clone(), route);
+        }
+    }
+    routes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::openapi::{cors_routes_from_openapi, flow_from_openapi};
+    use crate::flow::ExecutionFlowConfig;
+    use serde_json::json;
+
+    #[test]
+    fn test_flow_from_openapi() {
+        let spec = json!({
+            "openapi": "3.0.0",
+            "x-idem-chain": "default",
+            "x-idem-chains": { "default": ["TraceabilityHandler", "JwtValidationHandler"] },
+            "paths": {
+                "/pets/{id}": {
+                    "parameters": [],
+                    "x-idem-handlers": ["LambdaProxyHandler"],
+                    "get": { "operationId": "getPet" },
+                    "delete": { "x-idem-chain": [], "x-idem-handlers": "EchoRequestHandler" }
+                },
+                "/health": {
+                    "get": { "x-idem-handlers": ["HealthCheckHandler"] }
+                }
+            }
+        });
+        let flow = flow_from_openapi(&spec, &ExecutionFlowConfig::default()).unwrap();
+        let pet = &flow.paths["/pets/{id}"];
+        assert_eq!(pet.len(), 2);
+        assert_eq!(pet[0].method, "GET");
+        assert_eq!(pet[0].exec, vec!["default", "LambdaProxyHandler"]);
+        assert_eq!(pet[1].method, "DELETE");
+        assert_eq!(pet[1].exec, vec!["EchoRequestHandler"]);
+        assert_eq!(flow.paths["/health"][0].exec, vec!["default", "HealthCheckHandler"]);
+        assert!(flow.handlers.contains(&"JwtValidationHandler".to_string()));
+        assert!(flow.router_config().is_ok());
+
+        let spec = json!({ "paths": { "/pets": { "get": {} } } });
+        assert!(flow_from_openapi(&spec, &ExecutionFlowConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_cors_routes_from_openapi() {
+        let spec = json!({
+            "security": [{ "bearer": [] }],
+            "paths": {
+                "/pets/{id}": {
+                    "parameters": [{ "name": "X-Tenant", "in": "header" }],
+                    "get": { "parameters": [{ "name": "id", "in": "path" }], "security": [] },
+                    "put": { "requestBody": {} }
+                }
+            }
+        });
+        let routes = cors_routes_from_openapi(&spec);
+        let pet = &routes["/pets/{id}"];
+        assert_eq!(pet.methods, vec!["GET", "PUT"]);
+        assert_eq!(pet.headers, vec!["X-Tenant", "Content-Type", "Authorization"]);
+    }
+}
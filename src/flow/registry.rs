@@ -0,0 +1,340 @@
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::registry::HandlerRegistry;
+use idemio::handler::{Handler, HandlerId};
+use idemio::status::HandlerStatus;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use serde::de::DeserializeOwned;
+use crate::config::loader::{handler_config, ConfigSource};
+use crate::config::registry::ConfigRegistry;
+use crate::executor::pool::{HandlerLifecycle, HandlerPool};
+use crate::flow::builder::NamedHandler;
+use crate::flow::ExecutionFlowConfig;
+use crate::handler::admin::AdminConfigHandler;
+use crate::handler::api_key::ApiKeyHandler;
+use crate::handler::context::RequestContextHandler;
+use crate::handler::cors::CorsHandler;
+use crate::handler::echo::EchoRequestHandler;
+use crate::handler::header::HeaderHandler;
+use crate::handler::health::HealthCheckHandler;
+use crate::handler::ip_filter::IpFilterHandler;
+use crate::handler::jwt::JwtValidationHandler;
+use crate::handler::limit::LimitHandler;
+use crate::handler::metrics::MetricsHandler;
+use crate::handler::parallel::ParallelStageHandler;
+use crate::handler::pii::PiiRedactionHandler;
+use crate::handler::proxy::LambdaProxyHandler;
+use crate::handler::rate_limit::RateLimitHandler;
+use crate::handler::replay::ReplayHandler;
+use crate::handler::traceability::TraceabilityHandler;
+use crate::handler::webhook::WebhookHandler;
+#[cfg(feature = "wasm")]
+use crate::handler::wasm::WasmHandler;
+use crate::handler::LambdaExchange;
+
+type SharedHandler = Arc<dyn Handler<LambdaExchange>>;
+
+type HandlerInstaller = Box<
+    dyn Fn(&str, &ConfigSource, &mut HandlerPool) -> Result<SharedHandler, String> + Send + Sync,
+>;
+
+const VERSION_SEPARATOR: char = '@';
+
+/// Maps handler names used in `handlers.json` to constructors. Besides the built-in handlers,
+/// any handler can be added under its own name and then be used in chains like the others.
+///
+/// Several versions of a handler can be registered side by side. Chains pick one with
+/// `JwtValidationHandler@2`; a name without a version gets the unversioned handler, or the
+/// highest version when there is none.
+#[derive(Default)]
+pub struct HandlerFactories {
+    factories: HashMap<String, HandlerInstaller>,
+}
+
+impl HandlerFactories {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The handlers shipped with idem-serverless, each with its config loaded through the
+    /// config stack.
+    pub fn with_builtin() -> Self {
+        let mut factories = Self::new();
+        factories
+            .register_configured(AdminConfigHandler::NAME, |config| {
+                Box::new(AdminConfigHandler {
+                    config,
+                    registry: ConfigRegistry::global(),
+                })
+            })
+            .register_configured(ApiKeyHandler::NAME, |config| {
+                Box::new(ApiKeyHandler { config })
+            })
+            .register_configured(CorsHandler::NAME, |config| {
+                Box::new(CorsHandler { config })
+            })
+            .register_configured(EchoRequestHandler::NAME, |config| {
+                Box::new(EchoRequestHandler { config })
+            })
+            .register_configured(HeaderHandler::NAME, |config| {
+                Box::new(HeaderHandler { config })
+            })
+            .register_configured(HealthCheckHandler::NAME, |config| {
+                Box::new(HealthCheckHandler { config })
+            })
+            .register_configured(IpFilterHandler::NAME, |config| {
+                Box::new(IpFilterHandler { config })
+            })
+            .register_configured(JwtValidationHandler::NAME, |config| {
+                Box::new(JwtValidationHandler::new(config))
+            })
+            .register_configured(LimitHandler::NAME, |config| {
+                Box::new(LimitHandler { config })
+            })
+            .register_configured(MetricsHandler::NAME, |config| {
+                Box::new(MetricsHandler { config })
+            })
+            .register_configured(PiiRedactionHandler::NAME, |config| {
+                Box::new(PiiRedactionHandler { config })
+            })
+            .register_configured(RateLimitHandler::NAME, |config| {
+                Box::new(RateLimitHandler { config })
+            })
+            .register_configured(ReplayHandler::NAME, |config| {
+                Box::new(ReplayHandler { config })
+            })
+            .register_configured(RequestContextHandler::NAME, |config| {
+                Box::new(RequestContextHandler { config })
+            })
+            .register_configured(TraceabilityHandler::NAME, |config| {
+                Box::new(TraceabilityHandler { config })
+            })
+            .register_configured(WebhookHandler::NAME, |config| {
+                Box::new(WebhookHandler { config })
+            })
+            .register_configured_pooled(LambdaProxyHandler::NAME, LambdaProxyHandler::new);
+        #[cfg(feature = "wasm")]
+        factories.register_configured_pooled(WasmHandler::NAME, WasmHandler::new);
+        factories
+    }
+
+    pub fn register<F>(&mut self, name: impl Into<String>, constructor: F) -> &mut Self
+    where
+        F: Fn() -> Box<dyn Handler<LambdaExchange>> + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            name.into(),
+            Box::new(move |_, _, _| Ok(SharedHandler::from(constructor()))),
+        );
+        self
+    }
+
+    /// Registers a handler built from its config, which is loaded under the name the flow lists
+    /// the handler by.
+    pub fn register_configured<C, F>(
+        &mut self,
+        name: impl Into<String>,
+        constructor: F,
+    ) -> &mut Self
+    where
+        C: Default + DeserializeOwned + Send + Sync + 'static,
+        F: Fn(Config<C>) -> Box<dyn Handler<LambdaExchange>> + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            name.into(),
+            Box::new(move |name, source, _| {
+                let config = handler_config(name, source).map_err(|e| e.to_string())?;
+                Ok(SharedHandler::from(constructor(config)))
+            }),
+        );
+        self
+    }
+
+    /// Registers a handler whose lifecycle hooks are run by the executor's handler pool.
+    pub fn register_pooled<H, F>(&mut self, name: impl Into<String>, constructor: F) -> &mut Self
+    where
+        H: Handler<LambdaExchange> + HandlerLifecycle + 'static,
+        F: Fn() -> H + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            name.into(),
+            Box::new(move |name, _, pool| Ok(pool.add(name, constructor()))),
+        );
+        self
+    }
+
+    pub fn register_configured_pooled<C, H, F>(
+        &mut self,
+        name: impl Into<String>,
+        constructor: F,
+    ) -> &mut Self
+    where
+        C: Default + DeserializeOwned + Send + Sync + 'static,
+        H: Handler<LambdaExchange> + HandlerLifecycle + 'static,
+        F: Fn(Config<C>) -> H + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            name.into(),
+            Box::new(move |name, source, pool| {
+                let config = handler_config(name, source).map_err(|e| e.to_string())?;
+                Ok(pool.add(name, constructor(config)))
+            }),
+        );
+        self
+    }
+
+    pub fn register_version<F>(&mut self, name: &str, version: u32, constructor: F) -> &mut Self
+    where
+        F: Fn() -> Box<dyn Handler<LambdaExchange>> + Send + Sync + 'static,
+    {
+        self.register(format!("{}{}{}", name, VERSION_SEPARATOR, version), constructor)
+    }
+
+    pub fn register_pooled_version<H, F>(
+        &mut self,
+        name: &str,
+        version: u32,
+        constructor: F,
+    ) -> &mut Self
+    where
+        H: Handler<LambdaExchange> + HandlerLifecycle + 'static,
+        F: Fn() -> H + Send + Sync + 'static,
+    {
+        self.register_pooled(format!("{}{}{}", name, VERSION_SEPARATOR, version), constructor)
+    }
+
+    fn find(&self, name: &str) -> Option<&HandlerInstaller> {
+        if let Some(install) = self.factories.get(name) {
+            return Some(install);
+        }
+        if name.contains(VERSION_SEPARATOR) {
+            return None;
+        }
+        self.factories
+            .iter()
+            .filter_map(|(registered, install)| {
+                let (registered_name, version) = registered.split_once(VERSION_SEPARATOR)?;
+                if registered_name != name {
+                    return None;
+                }
+                Some((version.parse::<u32>().ok()?, install))
+            })
+            .max_by_key(|(version, _)| *version)
+            .map(|(_, install)| install)
+    }
+
+    /// Creates every handler listed in the flow, and its parallel stages over them.
+    pub fn build(
+        &self,
+        flow: &ExecutionFlowConfig,
+    ) -> Result<(HandlerRegistry<LambdaExchange>, HandlerPool), String> {
+        let mut registry = HandlerRegistry::new();
+        let mut pool = HandlerPool::new();
+        let mut built = HashMap::new();
+        let no_source = ConfigSource::default();
+        for name in &flow.handlers {
+            let install = match self.find(name) {
+                Some(install) => install,
+                None => return Err(format!("No handler registered with name {}", name)),
+            };
+            let source = flow.config_sources.get(name).unwrap_or(&no_source);
+            let handler = install(name, source, &mut pool)?;
+            registry
+                .register_handler(HandlerId::new(name), RegisteredHandler(handler.clone()))
+                .map_err(|e| e.to_string())?;
+            built.insert(name.as_str(), handler);
+        }
+        for (stage, names) in &flow.parallel {
+            let handlers = names
+                .iter()
+                .map(|name| match built.get(name.as_str()) {
+                    Some(handler) => Ok(handler.clone()),
+                    None => Err(format!(
+                        "Parallel stage {} runs {}, which is not listed in handlers",
+                        stage, name
+                    )),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let handler = ParallelStageHandler::new(stage, handlers);
+            registry
+                .register_handler(HandlerId::new(stage), handler)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok((registry, pool))
+    }
+}
+
+struct RegisteredHandler(SharedHandler);
+
+#[async_trait]
+impl Handler<LambdaExchange> for RegisteredHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        self.0.exec(exchange).await
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow::registry::HandlerFactories;
+    use crate::flow::ExecutionFlowConfig;
+    use crate::handler::LambdaExchange;
+    use async_trait::async_trait;
+    use idemio::handler::Handler;
+    use idemio::status::{ExchangeState, HandlerStatus};
+    use std::convert::Infallible;
+
+    struct CustomHandler;
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for CustomHandler {
+        async fn exec(&self, _: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        }
+
+        fn name(&self) -> &str {
+            "CustomHandler"
+        }
+    }
+
+    #[test]
+    fn test_custom_handler_in_flow() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["CustomHandler", "HealthCheckHandler"],
+                "paths": { "/health": { "method": "GET", "exec": ["CustomHandler", "HealthCheckHandler"] } }
+            }"#,
+        )
+        .unwrap();
+        assert!(HandlerFactories::with_builtin().build(&flow).is_err());
+
+        let mut factories = HandlerFactories::with_builtin();
+        factories.register("CustomHandler", || Box::new(CustomHandler));
+        assert!(factories.build(&flow).is_ok());
+    }
+
+    #[test]
+    fn test_find_handler_version() {
+        let mut factories = HandlerFactories::new();
+        factories
+            .register_version("CustomHandler", 1, || Box::new(CustomHandler))
+            .register_version("CustomHandler", 2, || Box::new(CustomHandler));
+        assert!(factories.find("CustomHandler@1").is_some());
+        assert!(factories.find("CustomHandler@3").is_none());
+        assert!(std::ptr::eq(
+            factories.find("CustomHandler").unwrap(),
+            factories.find("CustomHandler@2").unwrap()
+        ));
+
+        factories.register("CustomHandler", || Box::new(CustomHandler));
+        assert!(!std::ptr::eq(
+            factories.find("CustomHandler").unwrap(),
+            factories.find("CustomHandler@2").unwrap()
+        ));
+    }
+}
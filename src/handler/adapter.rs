@@ -0,0 +1,202 @@
+use async_trait::async_trait;
+use idemio::exchange::Exchange;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::alb::AlbTargetGroupRequest;
+use lambda_http::aws_lambda_events::apigw::{
+    ApiGatewayProxyRequest, ApiGatewayProxyResponse, ApiGatewayV2httpRequest,
+    ApiGatewayV2httpResponse,
+};
+use lambda_http::http::header::SET_COOKIE;
+use lambda_http::http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use lambda_http::request::LambdaRequest;
+use lambda_http::Body;
+use std::convert::Infallible;
+
+/// A Lambda event that can be read as an `http::Request`.
+pub trait HttpRequestEvent: Clone + Send + Sync {
+    fn into_http(self) -> Request<Body>;
+}
+
+impl HttpRequestEvent for ApiGatewayProxyRequest {
+    fn into_http(self) -> Request<Body> {
+        LambdaRequest::ApiGatewayV1(self).into()
+    }
+}
+
+impl HttpRequestEvent for ApiGatewayV2httpRequest {
+    fn into_http(self) -> Request<Body> {
+        LambdaRequest::ApiGatewayV2(self).into()
+    }
+}
+
+impl HttpRequestEvent for AlbTargetGroupRequest {
+    fn into_http(self) -> Request<Body> {
+        LambdaRequest::Alb(self).into()
+    }
+}
+
+/// A Lambda response that can be built from, and read as, an `http::Response`.
+pub trait HttpResponseEvent: Sized + Send + Sync {
+    fn from_http(response: Response<Body>) -> Self;
+
+    fn into_http(self) -> Response<Body>;
+}
+
+fn encoded(body: Body) -> (Option<Body>, bool) {
+    match body {
+        Body::Empty => (None, false),
+        body @ Body::Text(_) => (Some(body), false),
+        body @ Body::Binary(_) => (Some(body), true),
+    }
+}
+
+fn http_response(status_code: i64, headers: HeaderMap, body: Option<Body>) -> Response<Body> {
+    let mut response = Response::new(body.unwrap_or_default());
+    *response.status_mut() =
+        StatusCode::from_u16(status_code as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    *response.headers_mut() = headers;
+    response
+}
+
+impl HttpResponseEvent for ApiGatewayProxyResponse {
+    fn from_http(response: Response<Body>) -> Self {
+        let (parts, body) = response.into_parts();
+        let (body, is_base64_encoded) = encoded(body);
+        ApiGatewayProxyResponse {
+            status_code: parts.status.as_u16() as i64,
+            headers: parts.headers,
+            body,
+            is_base64_encoded,
+            ..Default::default()
+        }
+    }
+
+    fn into_http(self) -> Response<Body> {
+        let mut headers = self.multi_value_headers;
+        headers.extend(self.headers);
+        http_response(self.status_code, headers, self.body)
+    }
+}
+
+impl HttpResponseEvent for ApiGatewayV2httpResponse {
+    /* API Gateway v2 wants cookies in their own field */
+    fn from_http(response: Response<Body>) -> Self {
+        let (mut parts, body) = response.into_parts();
+        let (body, is_base64_encoded) = encoded(body);
+        let cookies = parts
+            .headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .map(str::to_string)
+            .collect();
+        parts.headers.remove(SET_COOKIE);
+        ApiGatewayV2httpResponse {
+            status_code: parts.status.as_u16() as i64,
+            headers: parts.headers,
+            cookies,
+            body,
+            is_base64_encoded,
+            ..Default::default()
+        }
+    }
+
+    fn into_http(self) -> Response<Body> {
+        let mut headers = self.multi_value_headers;
+        headers.extend(self.headers);
+        for cookie in self.cookies {
+            if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+                headers.append(SET_COOKIE, cookie);
+            }
+        }
+        http_response(self.status_code, headers, self.body)
+    }
+}
+
+/// A termination handler written against the `http` crate, so it can serve any event source
+/// with an adapter. Wrap it in [`HttpHandlerAdapter`] to register it.
+#[async_trait]
+pub trait HttpHandler: Send + Sync {
+    async fn handle(&self, request: Request<Body>) -> Response<Body>;
+
+    fn name(&self) -> &str;
+}
+
+pub struct HttpHandlerAdapter<H>(pub H);
+
+#[async_trait]
+impl<H, I, O, M> Handler<Exchange<I, O, M>> for HttpHandlerAdapter<H>
+where
+    H: HttpHandler,
+    I: HttpRequestEvent + 'static,
+    O: HttpResponseEvent + 'static,
+    M: Send + Sync + 'static,
+{
+    async fn exec(&self, exchange: &mut Exchange<I, O, M>) -> Result<HandlerStatus, Infallible> {
+        let request = match exchange.input().await {
+            Ok(request) => request.clone().into_http(),
+            Err(_) => {
+                return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
+                    .message("Request could not be read"));
+            }
+        };
+        let response = self.0.handle(request).await;
+        exchange.set_output(O::from_http(response));
+        Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED))
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::adapter::{HttpHandler, HttpHandlerAdapter, HttpResponseEvent};
+    use crate::handler::LambdaExchange;
+    use async_trait::async_trait;
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayV2httpResponse};
+    use lambda_http::http::{Method, Request, Response};
+    use lambda_http::Body;
+
+    struct PathHandler;
+
+    #[async_trait]
+    impl HttpHandler for PathHandler {
+        async fn handle(&self, request: Request<Body>) -> Response<Body> {
+            Response::builder()
+                .status(200)
+                .header("set-cookie", "a=1")
+                .body(Body::Text(format!("{} {}", request.method(), request.uri().path())))
+                .unwrap()
+        }
+
+        fn name(&self) -> &str {
+            "PathHandler"
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_http_handler_adapter() {
+        let request = ApiGatewayProxyRequest {
+            http_method: Method::POST,
+            path: Some("/orders".to_string()),
+            ..Default::default()
+        };
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request);
+
+        let status = HttpHandlerAdapter(PathHandler).exec(&mut exchange).await.unwrap();
+        assert!(status.code().is_completed());
+        let output = exchange.take_output().await.unwrap();
+        assert_eq!(output.status_code, 200);
+        assert_eq!(output.body, Some(Body::Text("POST /orders".to_string())));
+
+        let response = ApiGatewayV2httpResponse::from_http(output.into_http());
+        assert_eq!(response.cookies, vec!["a=1".to_string()]);
+        assert!(response.headers.get("set-cookie").is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use std::convert::Infallible;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use async_trait::async_trait;
+use idemio::config::{Config, ConfigProviderError};
+use idemio::exchange::Exchange;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::header::CONTENT_TYPE;
+use lambda_http::http::Method;
+use lambda_http::{Body, Context};
+use crate::flow::builder::NamedHandler;
+use crate::config::registry::ConfigRegistry;
+use crate::error::{ERR10003_INVALID_ADMIN_TOKEN, ERR11000_INVALID_REQUEST, ERR13000_INTERNAL};
+use crate::handler::body::BodyAccess;
+use crate::handler::LambdaExchange;
+
+const DEFAULT_ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+const MASKED_VALUE: &str = "******";
+const DEFAULT_MASKED_FIELDS: [&str; 6] = ["secret", "password", "token", "private_key", "api_key", "credential"];
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AdminConfigHandlerConfig {
+    pub enabled: bool,
+    /* header carrying the admin token, defaults to x-admin-token */
+    #[serde(default)]
+    pub token_header: Option<String>,
+    /* usually a {"secretsManager": ..., "jsonKey": ...} reference, resolved when the config is
+       loaded, so the token never sits in the config file */
+    pub admin_token: String,
+    /* extra field name fragments to mask on top of the defaults */
+    #[serde(default)]
+    pub masked_fields: Vec<String>,
+}
+
+/* what a POST to the admin route asks of the registry, e.g.
+   {"action": "pin", "config": "JwtValidationHandler", "version": "..."} */
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum AdminAction {
+    Rollback { config: String },
+    Pin { config: String, version: String },
+    Unpin { config: String },
+}
+
+pub struct AdminConfigHandler {
+    pub(crate) config: Config<AdminConfigHandlerConfig>,
+    pub(crate) registry: &'static ConfigRegistry,
+}
+
+impl AdminConfigHandler {
+    fn is_authorized(&self, request: &ApiGatewayProxyRequest) -> bool {
+        let expected = self.config.get().admin_token.as_bytes();
+        if expected.is_empty() {
+            return false;
+        }
+        let header_name = self
+            .config
+            .get()
+            .token_header
+            .as_deref()
+            .unwrap_or(DEFAULT_ADMIN_TOKEN_HEADER);
+        let Some(provided) = request.headers.get(header_name) else {
+            return false;
+        };
+        let provided = provided.as_bytes();
+        /* compare without short-circuiting so the token can't be guessed byte by byte */
+        provided.len() == expected.len()
+            && provided
+                .iter()
+                .zip(expected)
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn should_mask(&self, field: &str) -> bool {
+        let field = field.to_lowercase();
+        DEFAULT_MASKED_FIELDS
+            .iter()
+            .copied()
+            .chain(self.config.get().masked_fields.iter().map(String::as_str))
+            .any(|fragment| field.contains(&fragment.to_lowercase()))
+    }
+
+    fn mask(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    if self.should_mask(key) && !child.is_null() {
+                        *child = Value::String(MASKED_VALUE.to_string());
+                    } else {
+                        self.mask(child);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.mask(item)),
+            _ => {}
+        }
+    }
+
+    fn effective_configs(&self) -> Value {
+        let mut configs = Map::new();
+        for name in self.registry.names() {
+            if let Some(mut value) = self.registry.value(&name) {
+                self.mask(&mut value);
+                configs.insert(name, value);
+            }
+        }
+        Value::Object(configs)
+    }
+
+    fn apply(&self, action: &AdminAction) -> Result<(), ConfigProviderError> {
+        match action {
+            AdminAction::Rollback { config } => self.registry.rollback(config).map(|_| ()),
+            AdminAction::Pin { config, version } => self.registry.pin(config, version),
+            AdminAction::Unpin { config } => self.registry.unpin(config),
+        }
+    }
+}
+
+impl NamedHandler for AdminConfigHandler {
+    const NAME: &'static str = "AdminConfigHandler";
+}
+
+#[async_trait]
+impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for AdminConfigHandler {
+    async fn exec(
+        &self,
+        exchange: &mut LambdaExchange,
+    ) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let (authorized, is_action) = match exchange.input().await {
+            Ok(request) => (self.is_authorized(request), request.http_method == Method::POST),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+
+        if !authorized {
+            return Ok(ERR10003_INVALID_ADMIN_TOKEN.raise(exchange));
+        }
+
+        /* a POST rolls back, pins or unpins a config before the configs are returned */
+        if is_action {
+            let action = match BodyAccess::json(exchange).await {
+                Ok(Some(body)) => AdminAction::deserialize(body).map_err(|e| e.to_string()),
+                _ => Err("Expected a JSON body".to_string()),
+            };
+            let applied = action.and_then(|action| self.apply(&action).map_err(|e| e.to_string()));
+            if let Err(e) = applied {
+                return Ok(ERR11000_INVALID_REQUEST.error().detail(e).raise(exchange));
+            }
+        }
+
+        let mut response = ApiGatewayProxyResponse::default();
+
+        let body = serde_json::json!({
+            "configs": self.effective_configs(),
+            "versions": self.registry.versions(),
+        });
+        response.status_code = 200;
+        response
+            .headers
+            .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        response.body = Some(Body::Text(body.to_string()));
+        exchange.set_output(response);
+        Ok(HandlerStatus::new(ExchangeState::OK))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::registry::ConfigRegistry;
+    use crate::handler::admin::{AdminConfigHandler, AdminConfigHandlerConfig};
+    use crate::handler::LambdaExchange;
+    use http::HeaderValue;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError};
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::ExchangeState;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use lambda_http::http::Method;
+    use lambda_http::Body;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+
+    struct StaticConfig(Value);
+
+    impl ConfigProvider<Value> for StaticConfig {
+        fn load(&self) -> Result<Value, ConfigProviderError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    impl ConfigProvider<AdminConfigHandlerConfig> for StaticConfig {
+        fn load(&self) -> Result<AdminConfigHandlerConfig, ConfigProviderError> {
+            Ok(serde_json::from_value(self.0.clone()).unwrap())
+        }
+    }
+
+    struct SharedConfig(Arc<Mutex<Value>>);
+
+    impl ConfigProvider<Value> for SharedConfig {
+        fn load(&self) -> Result<Value, ConfigProviderError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    fn admin_handler(registry: &'static ConfigRegistry) -> AdminConfigHandler {
+        AdminConfigHandler {
+            config: Config::new(StaticConfig(json!({"enabled": true, "admin_token": "letmein"})))
+                .unwrap(),
+            registry,
+        }
+    }
+
+    async fn post_action(handler: &AdminConfigHandler, action: Value) -> (ExchangeState, Value) {
+        let mut request = ApiGatewayProxyRequest {
+            http_method: Method::POST,
+            body: Some(action.to_string()),
+            ..Default::default()
+        };
+        request
+            .headers
+            .insert("x-admin-token", HeaderValue::from_static("letmein"));
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request);
+        let result = handler.exec(&mut exchange).await.unwrap();
+        let body = match exchange.output().await.ok().and_then(|output| output.body.as_ref()) {
+            Some(Body::Text(body)) => serde_json::from_str(body).unwrap(),
+            _ => Value::Null,
+        };
+        (result.code(), body)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_admin_actions() {
+        let registry: &'static ConfigRegistry = Box::leak(Box::new(ConfigRegistry::new()));
+        let value = Arc::new(Mutex::new(json!({"audience": "first"})));
+        registry
+            .register::<Value>("JwtValidationHandler", SharedConfig(value.clone()))
+            .unwrap();
+        let first = registry.version("JwtValidationHandler").unwrap();
+        *value.lock().unwrap() = json!({"audience": "second"});
+        assert!(registry.reload("JwtValidationHandler").unwrap());
+        let handler = admin_handler(registry);
+
+        let action = json!({"action": "rollback", "config": "JwtValidationHandler"});
+        let (state, body) = post_action(&handler, action).await;
+        assert!(state.any_flags(ExchangeState::OK));
+        assert_eq!(body["configs"]["JwtValidationHandler"]["audience"], "first");
+        assert_eq!(body["versions"]["JwtValidationHandler"], first.as_str());
+
+        let action = json!({"action": "pin", "config": "JwtValidationHandler", "version": first});
+        let (state, _) = post_action(&handler, action).await;
+        assert!(state.any_flags(ExchangeState::OK));
+        assert!(!registry.reload("JwtValidationHandler").unwrap());
+        let action = json!({"action": "unpin", "config": "JwtValidationHandler"});
+        let (state, _) = post_action(&handler, action).await;
+        assert!(state.any_flags(ExchangeState::OK));
+        assert!(registry.reload("JwtValidationHandler").unwrap());
+
+        let action = json!({"action": "pin", "config": "JwtValidationHandler", "version": "v0"});
+        let (state, _) = post_action(&handler, action).await;
+        assert!(state.any_flags(ExchangeState::CLIENT_ERROR));
+        let (state, _) = post_action(&handler, json!({"action": "restart"})).await;
+        assert!(state.any_flags(ExchangeState::CLIENT_ERROR));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_admin_config_is_masked_and_protected() {
+        let registry: &'static ConfigRegistry = Box::leak(Box::new(ConfigRegistry::new()));
+        registry
+            .register::<Value>(
+                "JwtValidationHandler",
+                StaticConfig(json!({"audience": "my-api", "shared_secret": "s3cr3t"})),
+            )
+            .unwrap();
+        let handler = admin_handler(registry);
+
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(ApiGatewayProxyRequest::default());
+        let result = handler.exec(&mut exchange).await.unwrap();
+        assert!(result.code().any_flags(ExchangeState::CLIENT_ERROR));
+
+        let mut request = ApiGatewayProxyRequest::default();
+        request
+            .headers
+            .insert("x-admin-token", HeaderValue::from_static("letmein"));
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request);
+        let result = handler.exec(&mut exchange).await.unwrap();
+        assert!(result.code().any_flags(ExchangeState::OK));
+        let Some(Body::Text(body)) = &exchange.output().await.unwrap().body else {
+            panic!("admin handler returned no body");
+        };
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["configs"]["JwtValidationHandler"]["audience"], "my-api");
+        assert_eq!(body["configs"]["JwtValidationHandler"]["shared_secret"], "******");
+    }
+}
@@ -0,0 +1,295 @@
+use crate::audit::{record_audit_event, AuditEvent, AuditOutcome};
+use crate::config::units::deserialize_duration;
+use crate::error::{
+    handler_error, ERR10006_MISSING_API_KEY, ERR10007_INVALID_API_KEY, ERR13000_INTERNAL,
+};
+use crate::flow::builder::NamedHandler;
+use crate::handler::api_key::store::dynamodb_key_store;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+use crate::metrics::{self, names};
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::header::COOKIE;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::time::Duration;
+
+pub mod store;
+
+/* shared with other handlers, in the well-known namespace */
+const CLIENT_IDENTITY_ATTACHMENT_KEY: &str = "client_identity";
+
+/// The client an API key belongs to, for handlers after the [`ApiKeyHandler`] to tell callers
+/// apart by, like rate limits and audit records.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClientIdentity {
+    pub client_id: String,
+    #[serde(default)]
+    pub plan: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// The client the [`ApiKeyHandler`] resolved the request's key to.
+pub fn client_identity(exchange: &LambdaExchange) -> Option<&ClientIdentity> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<ClientIdentity>(CLIENT_IDENTITY_ATTACHMENT_KEY)
+}
+
+/// Where a request carries its key: `{ "in": "header", "name": "x-api-key" }`, or `query` or
+/// `cookie` with the name of the parameter or cookie.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "in", rename_all = "snake_case")]
+pub enum ApiKeyLocation {
+    Header { name: String },
+    Query { name: String },
+    Cookie { name: String },
+}
+
+impl ApiKeyLocation {
+    fn find(&self, request: &ApiGatewayProxyRequest) -> Option<String> {
+        match self {
+            ApiKeyLocation::Header { name } => request
+                .headers
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            ApiKeyLocation::Query { name } => request
+                .query_string_parameters
+                .first(name)
+                .map(str::to_string),
+            ApiKeyLocation::Cookie { name } => request
+                .headers
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|cookies| cookies.to_str().ok())
+                .flat_map(|cookies| cookies.split(';'))
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .find(|(cookie, _)| *cookie == name.as_str())
+                .map(|(_, value)| value.to_string()),
+        }
+        .filter(|key| !key.is_empty())
+    }
+}
+
+/// The keys a handler accepts: listed in the config by the SHA-256 of the key, as lowercase
+/// hex, or looked up in a DynamoDB table:
+///
+/// ```json
+/// { "type": "config", "clients": { "9f86d08...": { "client_id": "acme", "plan": "gold" } } }
+/// { "type": "dynamodb", "table": "api-keys", "cache_ttl": "5m" }
+/// ```
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiKeyStoreConfig {
+    Config {
+        clients: HashMap<String, ClientIdentity>,
+    },
+    #[serde(rename = "dynamodb")]
+    DynamoDb {
+        table: String,
+        #[serde(default = "default_cache_ttl", deserialize_with = "deserialize_duration")]
+        cache_ttl: Duration,
+    },
+}
+
+impl Default for ApiKeyStoreConfig {
+    fn default() -> Self {
+        ApiKeyStoreConfig::Config {
+            clients: HashMap::new(),
+        }
+    }
+}
+
+fn default_cache_ttl() -> Duration {
+    Duration::from_secs(300)
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ApiKeyHandlerConfig {
+    pub enabled: bool,
+    /* tried in order, the first key found is used */
+    pub locations: Vec<ApiKeyLocation>,
+    pub store: ApiKeyStoreConfig,
+}
+
+impl Default for ApiKeyHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            locations: vec![ApiKeyLocation::Header {
+                name: "x-api-key".to_string(),
+            }],
+            store: ApiKeyStoreConfig::default(),
+        }
+    }
+}
+
+/* keys are only ever compared and stored as their hash */
+fn key_hash(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl ApiKeyStoreConfig {
+    async fn lookup(&self, key_hash: &str) -> Result<Option<ClientIdentity>, String> {
+        match self {
+            ApiKeyStoreConfig::Config { clients } => Ok(clients.get(key_hash).cloned()),
+            ApiKeyStoreConfig::DynamoDb { table, cache_ttl } => {
+                dynamodb_key_store(table).lookup(key_hash, *cache_ttl).await
+            }
+        }
+    }
+}
+
+/// Lets in requests carrying a known API key, and records the client it belongs to as a
+/// well-known [`ClientIdentity`].
+pub struct ApiKeyHandler {
+    pub(crate) config: Config<ApiKeyHandlerConfig>,
+}
+
+impl ApiKeyHandler {
+    async fn authenticate(&self, exchange: &mut LambdaExchange) -> HandlerStatus {
+        let config = self.config.get();
+        let key = match exchange.input().await {
+            Ok(request) => config.locations.iter().find_map(|location| location.find(request)),
+            Err(_) => {
+                return ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange);
+            }
+        };
+        let Some(key) = key else {
+            return ERR10006_MISSING_API_KEY.raise(exchange);
+        };
+        let identity = match config.store.lookup(&key_hash(&key)).await {
+            Ok(Some(identity)) => identity,
+            Ok(None) => return ERR10007_INVALID_API_KEY.raise(exchange),
+            Err(e) => {
+                return ERR13000_INTERNAL
+                    .error()
+                    .detail(format!("API key lookup failed: {}", e))
+                    .raise(exchange);
+            }
+        };
+        exchange
+            .attachments_mut()
+            .well_known_mut()
+            .add::<ClientIdentity>(CLIENT_IDENTITY_ATTACHMENT_KEY, identity);
+        HandlerStatus::new(ExchangeState::OK)
+    }
+}
+
+impl NamedHandler for ApiKeyHandler {
+    const NAME: &'static str = "ApiKeyHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for ApiKeyHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let status = self.authenticate(exchange).await;
+        let outcome = if status.code().any_flags(ExchangeState::CLIENT_ERROR) {
+            metrics::counter(names::AUTH_REJECTIONS, 1, &[("handler", Self::NAME)]);
+            AuditOutcome::Denied
+        } else if status.code().is_error() {
+            AuditOutcome::Failure
+        } else {
+            AuditOutcome::Success
+        };
+        let mut event = AuditEvent::new(Self::NAME, "authenticate", outcome);
+        if let Some(identity) = client_identity(exchange) {
+            event = event.detail("client_id", identity.client_id.as_str());
+        }
+        if let Some(error) = handler_error(exchange) {
+            event = event.detail("error", error.code.code);
+        }
+        record_audit_event(exchange, event);
+        Ok(status)
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::api_key::{
+        client_identity, key_hash, ApiKeyHandler, ApiKeyHandlerConfig, ClientIdentity,
+    };
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError};
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::ExchangeState;
+    use serde_json::json;
+
+    struct StaticConfig;
+
+    impl ConfigProvider<ApiKeyHandlerConfig> for StaticConfig {
+        fn load(&self) -> Result<ApiKeyHandlerConfig, ConfigProviderError> {
+            Ok(serde_json::from_value(json!({
+                "locations": [
+                    { "in": "header", "name": "x-api-key" },
+                    { "in": "query", "name": "api_key" },
+                    { "in": "cookie", "name": "key" }
+                ],
+                "store": {
+                    "type": "config",
+                    "clients": { (key_hash("secret-1")): { "client_id": "acme", "plan": "gold" } }
+                }
+            }))
+            .unwrap())
+        }
+    }
+
+    async fn run(request: RequestBuilder) -> (ExchangeState, Option<ClientIdentity>) {
+        let handler = ApiKeyHandler {
+            config: Config::new(StaticConfig).unwrap(),
+        };
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request.build());
+        let status = handler.exec(&mut exchange).await.unwrap().code();
+        (status, client_identity(&exchange).cloned())
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_api_key_locations() {
+        let header = RequestBuilder::get("/pets").header("x-api-key", "secret-1");
+        let (status, identity) = run(header).await;
+        assert_eq!(status, ExchangeState::OK);
+        assert_eq!(identity.unwrap().client_id, "acme");
+
+        let (status, _) = run(RequestBuilder::get("/pets").query("api_key", "secret-1")).await;
+        assert_eq!(status, ExchangeState::OK);
+
+        let cookie = RequestBuilder::get("/pets").header("cookie", "theme=dark; key=secret-1");
+        let (status, _) = run(cookie).await;
+        assert_eq!(status, ExchangeState::OK);
+
+        let unknown = RequestBuilder::get("/pets").header("x-api-key", "nope");
+        let (status, identity) = run(unknown).await;
+        assert!(status.any_flags(ExchangeState::CLIENT_ERROR));
+        assert!(identity.is_none());
+
+        let (status, _) = run(RequestBuilder::get("/pets")).await;
+        assert!(status.any_flags(ExchangeState::CLIENT_ERROR));
+    }
+}
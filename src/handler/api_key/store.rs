@@ -0,0 +1,135 @@
+use crate::handler::api_key::ClientIdentity;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+const KEY_HASH_ATTRIBUTE: &str = "key_hash";
+const CLIENT_ID_ATTRIBUTE: &str = "client_id";
+const PLAN_ATTRIBUTE: &str = "plan";
+/* keys can be revoked without deleting them */
+const ENABLED_ATTRIBUTE: &str = "enabled";
+/* cached lookups kept before the expired ones are dropped */
+const CACHE_PRUNE_SIZE: usize = 10_000;
+
+/// The clients of a DynamoDB table, keyed by the SHA-256 of their API key in the `key_hash`
+/// partition key. String attributes besides `client_id` and `plan` become the identity's
+/// metadata. Lookups, misses included, are cached per container.
+pub struct DynamoDbKeyStore {
+    table: String,
+    client: OnceCell<DynamoDbClient>,
+    cache: Mutex<HashMap<String, (Option<ClientIdentity>, Instant)>>,
+}
+
+/// The store of the table, shared by every handler using it for the lifetime of the container.
+pub fn dynamodb_key_store(table: &str) -> Arc<DynamoDbKeyStore> {
+    static STORES: OnceLock<Mutex<HashMap<String, Arc<DynamoDbKeyStore>>>> = OnceLock::new();
+    STORES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(table.to_string())
+        .or_insert_with(|| {
+            Arc::new(DynamoDbKeyStore {
+                table: table.to_string(),
+                client: OnceCell::new(),
+                cache: Mutex::default(),
+            })
+        })
+        .clone()
+}
+
+fn identity(item: &HashMap<String, AttributeValue>) -> Option<ClientIdentity> {
+    if item
+        .get(ENABLED_ATTRIBUTE)
+        .and_then(|enabled| enabled.as_bool().ok())
+        .is_some_and(|enabled| !enabled)
+    {
+        return None;
+    }
+    let text = |name: &str| item.get(name).and_then(|value| value.as_s().ok()).cloned();
+    let metadata: BTreeMap<String, String> = item
+        .iter()
+        .filter(|(name, _)| {
+            ![KEY_HASH_ATTRIBUTE, CLIENT_ID_ATTRIBUTE, PLAN_ATTRIBUTE].contains(&name.as_str())
+        })
+        .filter_map(|(name, value)| Some((name.clone(), value.as_s().ok()?.clone())))
+        .collect();
+    Some(ClientIdentity {
+        client_id: text(CLIENT_ID_ATTRIBUTE)?,
+        plan: text(PLAN_ATTRIBUTE),
+        metadata,
+    })
+}
+
+impl DynamoDbKeyStore {
+    async fn client(&self) -> &DynamoDbClient {
+        self.client
+            .get_or_init(|| async {
+                DynamoDbClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await)
+            })
+            .await
+    }
+
+    /// The client of the key hash, `None` for unknown and disabled keys.
+    pub async fn lookup(
+        &self,
+        key_hash: &str,
+        cache_ttl: Duration,
+    ) -> Result<Option<ClientIdentity>, String> {
+        let now = Instant::now();
+        {
+            let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if cache.len() >= CACHE_PRUNE_SIZE {
+                cache.retain(|_, (_, expires)| *expires > now);
+            }
+            if let Some((identity, _)) = cache.get(key_hash).filter(|(_, expires)| *expires > now) {
+                return Ok(identity.clone());
+            }
+        }
+
+        let output = self
+            .client()
+            .await
+            .get_item()
+            .table_name(&self.table)
+            .key(KEY_HASH_ATTRIBUTE, AttributeValue::S(key_hash.to_string()))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let identity = output.item().and_then(identity);
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key_hash.to_string(), (identity.clone(), now + cache_ttl));
+        Ok(identity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::api_key::store::identity;
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_dynamodb_identity() {
+        let mut item = HashMap::from([
+            ("key_hash".to_string(), AttributeValue::S("ab12".to_string())),
+            ("client_id".to_string(), AttributeValue::S("acme".to_string())),
+            ("plan".to_string(), AttributeValue::S("gold".to_string())),
+            ("team".to_string(), AttributeValue::S("billing".to_string())),
+        ]);
+        let client = identity(&item).unwrap();
+        assert_eq!(client.client_id, "acme");
+        assert_eq!(client.plan.as_deref(), Some("gold"));
+        assert_eq!(client.metadata.len(), 1);
+        assert_eq!(client.metadata["team"], "billing");
+
+        item.insert("enabled".to_string(), AttributeValue::Bool(false));
+        assert!(identity(&item).is_none());
+    }
+}
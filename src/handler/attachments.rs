@@ -0,0 +1,99 @@
+use idemio::exchange::Attachments;
+
+/// Namespace for values that several handlers (and the executor) read, like the correlation id.
+pub const WELL_KNOWN: &str = "well_known";
+
+const NAMESPACE_SEPARATOR: char = ':';
+
+/// A view of the exchange attachments where every key is prefixed with a namespace. Handlers
+/// keep their private values in their own namespace, so two handlers using the same key name
+/// no longer overwrite each other. Values meant to be shared go into [`WELL_KNOWN`].
+pub struct ScopedAttachments<A> {
+    attachments: A,
+    namespace: &'static str,
+}
+
+impl<A> ScopedAttachments<A> {
+    fn key(&self, key: &str) -> String {
+        format!("{}{}{}", self.namespace, NAMESPACE_SEPARATOR, key)
+    }
+}
+
+impl<'a> ScopedAttachments<&'a Attachments> {
+    pub fn get<K: Send + 'static>(&self, key: &str) -> Option<&'a K> {
+        self.attachments.get::<K>(self.key(key))
+    }
+}
+
+impl<'a> ScopedAttachments<&'a mut Attachments> {
+    pub fn get<K: Send + 'static>(&self, key: &str) -> Option<&K> {
+        self.attachments.get::<K>(self.key(key))
+    }
+
+    pub fn get_mut<K: Send + 'static>(&mut self, key: &str) -> Option<&mut K> {
+        let key = self.key(key);
+        self.attachments.get_mut::<K>(key)
+    }
+
+    /// Like `get_mut`, but keeps the borrow of the underlying attachments.
+    pub fn into_mut<K: Send + 'static>(self, key: &str) -> Option<&'a mut K> {
+        let key = self.key(key);
+        self.attachments.get_mut::<K>(key)
+    }
+
+    /// Adds the value, replacing any previous value of the same key and type.
+    pub fn add<K: Send + Sync + 'static>(&mut self, key: &str, value: K) {
+        let key = self.key(key);
+        self.attachments.add::<K>(key, value);
+    }
+}
+
+/// Adds namespaced views to [`Attachments`].
+pub trait ScopedAttachmentsExt {
+    fn scoped(&self, namespace: &'static str) -> ScopedAttachments<&Attachments>;
+
+    fn scoped_mut(&mut self, namespace: &'static str) -> ScopedAttachments<&mut Attachments>;
+
+    fn well_known(&self) -> ScopedAttachments<&Attachments> {
+        self.scoped(WELL_KNOWN)
+    }
+
+    fn well_known_mut(&mut self) -> ScopedAttachments<&mut Attachments> {
+        self.scoped_mut(WELL_KNOWN)
+    }
+}
+
+impl ScopedAttachmentsExt for Attachments {
+    fn scoped(&self, namespace: &'static str) -> ScopedAttachments<&Attachments> {
+        ScopedAttachments {
+            attachments: self,
+            namespace,
+        }
+    }
+
+    fn scoped_mut(&mut self, namespace: &'static str) -> ScopedAttachments<&mut Attachments> {
+        ScopedAttachments {
+            attachments: self,
+            namespace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::attachments::ScopedAttachmentsExt;
+    use idemio::exchange::Attachments;
+
+    #[test]
+    fn test_scoped_keys_do_not_collide() {
+        let mut attachments = Attachments::new();
+        attachments.scoped_mut("cors").add::<String>("origin", "a".to_string());
+        attachments.scoped_mut("header").add::<String>("origin", "b".to_string());
+        attachments.well_known_mut().add::<String>("origin", "c".to_string());
+
+        assert_eq!(attachments.scoped("cors").get::<String>("origin").unwrap(), "a");
+        assert_eq!(attachments.scoped("header").get::<String>("origin").unwrap(), "b");
+        assert_eq!(attachments.well_known().get::<String>("origin").unwrap(), "c");
+        assert!(attachments.get::<String>("origin").is_none());
+    }
+}
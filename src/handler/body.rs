@@ -0,0 +1,128 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+
+const DECODED_BODY_ATTACHMENT_KEY: &str = "decoded_body";
+
+#[derive(Default)]
+struct DecodedBody {
+    decoded: bool,
+    bytes: Option<Vec<u8>>,
+    parsed: bool,
+    json: Option<Value>,
+}
+
+/// Shared access to the request body. The body is base64 decoded (when `isBase64Encoded` is set)
+/// and parsed as JSON at most once per exchange; every handler after the first reads the cached
+/// result. Handlers that rewrite the body should do so through [`BodyAccess::set_json`] so the
+/// cache stays in step with the request.
+pub struct BodyAccess;
+
+impl BodyAccess {
+    /// The decoded request body, or `None` when the request has no body.
+    pub async fn bytes(exchange: &mut LambdaExchange) -> Result<Option<&[u8]>, ()> {
+        if !Self::cached(exchange).is_some_and(|cached| cached.decoded) {
+            let request = exchange.input().await.map_err(|_| ())?;
+            let bytes = match &request.body {
+                None => None,
+                Some(body) if request.is_base64_encoded => Some(STANDARD.decode(body).map_err(|_| ())?),
+                Some(body) => Some(body.as_bytes().to_vec()),
+            };
+            let cached = Self::cached_mut(exchange);
+            cached.decoded = true;
+            cached.bytes = bytes;
+        }
+        Ok(Self::cached(exchange).and_then(|cached| cached.bytes.as_deref()))
+    }
+
+    /// The request body parsed as JSON, or `None` when there is no body or it is not JSON.
+    pub async fn json(exchange: &mut LambdaExchange) -> Result<Option<&Value>, ()> {
+        if !Self::cached(exchange).is_some_and(|cached| cached.parsed) {
+            let json = match Self::bytes(exchange).await? {
+                Some(bytes) => serde_json::from_slice::<Value>(bytes).ok(),
+                None => None,
+            };
+            let cached = Self::cached_mut(exchange);
+            cached.parsed = true;
+            cached.json = json;
+        }
+        Ok(Self::cached(exchange).and_then(|cached| cached.json.as_ref()))
+    }
+
+    /// Replaces the request body with `value` and updates the cached copies to match.
+    pub async fn set_json(exchange: &mut LambdaExchange, value: Value) -> Result<(), ()> {
+        let body = serde_json::to_string(&value).map_err(|_| ())?;
+        let request = exchange.input_mut().await.map_err(|_| ())?;
+        request.body = Some(body.clone());
+        request.is_base64_encoded = false;
+        let cached = Self::cached_mut(exchange);
+        cached.decoded = true;
+        cached.bytes = Some(body.into_bytes());
+        cached.parsed = true;
+        cached.json = Some(value);
+        Ok(())
+    }
+
+    /// Replaces the request body with `text`, which is parsed again the next time it is read
+    /// as JSON.
+    pub async fn set_text(exchange: &mut LambdaExchange, text: String) -> Result<(), ()> {
+        let request = exchange.input_mut().await.map_err(|_| ())?;
+        request.body = Some(text.clone());
+        request.is_base64_encoded = false;
+        let cached = Self::cached_mut(exchange);
+        cached.decoded = true;
+        cached.bytes = Some(text.into_bytes());
+        cached.parsed = false;
+        cached.json = None;
+        Ok(())
+    }
+
+    fn cached(exchange: &LambdaExchange) -> Option<&DecodedBody> {
+        exchange
+            .attachments()
+            .well_known()
+            .get::<DecodedBody>(DECODED_BODY_ATTACHMENT_KEY)
+    }
+
+    fn cached_mut(exchange: &mut LambdaExchange) -> &mut DecodedBody {
+        let mut attachments = exchange.attachments_mut().well_known_mut();
+        if attachments.get::<DecodedBody>(DECODED_BODY_ATTACHMENT_KEY).is_none() {
+            attachments.add::<DecodedBody>(DECODED_BODY_ATTACHMENT_KEY, DecodedBody::default());
+        }
+        attachments
+            .into_mut::<DecodedBody>(DECODED_BODY_ATTACHMENT_KEY)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::body::BodyAccess;
+    use crate::handler::LambdaExchange;
+    use idemio::exchange::Exchange;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_base64_body_is_decoded_and_cached() {
+        let request = ApiGatewayProxyRequest {
+            // {"name":"idem"}
+            body: Some("eyJuYW1lIjoiaWRlbSJ9".to_string()),
+            is_base64_encoded: true,
+            ..Default::default()
+        };
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request);
+
+        assert_eq!(BodyAccess::bytes(&mut exchange).await.unwrap(), Some(&b"{\"name\":\"idem\"}"[..]));
+        assert_eq!(BodyAccess::json(&mut exchange).await.unwrap(), Some(&json!({"name": "idem"})));
+
+        BodyAccess::set_json(&mut exchange, json!({"name": "changed"})).await.unwrap();
+        assert_eq!(BodyAccess::json(&mut exchange).await.unwrap(), Some(&json!({"name": "changed"})));
+        let request = exchange.input().await.unwrap();
+        assert!(!request.is_base64_encoded);
+        assert_eq!(request.body.as_deref(), Some("{\"name\":\"changed\"}"));
+    }
+}
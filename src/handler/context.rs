@@ -0,0 +1,178 @@
+use crate::error::ERR13000_INTERNAL;
+use crate::flow::builder::NamedHandler;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+/* shared with other handlers, in the well-known namespace */
+const REQUEST_CONTEXT_ATTACHMENT_KEY: &str = "request_context";
+
+/* header fields name authorizer values as `authorizer.<key>` */
+const AUTHORIZER_FIELD_PREFIX: &str = "authorizer.";
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RequestContextHandlerConfig {
+    pub enabled: bool,
+    /* request headers to set, by field: `source_ip`, `user_agent`, `request_id`, `stage` or
+    `authorizer.<key>` */
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for RequestContextHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+/// The parts of API Gateway's `requestContext` handlers commonly need, taken out of the raw
+/// event by [`RequestContextHandler`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+    pub stage: Option<String>,
+    pub source_ip: Option<String>,
+    pub user_agent: Option<String>,
+    /* the claims of a Cognito authorizer, or the context of a Lambda authorizer */
+    pub authorizer: BTreeMap<String, Value>,
+}
+
+impl RequestContext {
+    pub fn from_request(request: &ApiGatewayProxyRequest) -> Self {
+        let context = &request.request_context;
+        let mut authorizer: BTreeMap<String, Value> = context
+            .authorizer
+            .fields
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        /* Cognito user pool authorizers nest their claims */
+        if let Some(Value::Object(claims)) = authorizer.remove("claims") {
+            authorizer.extend(claims);
+        }
+        Self {
+            request_id: context.request_id.clone(),
+            stage: context.stage.clone(),
+            source_ip: context.identity.source_ip.clone(),
+            user_agent: context.identity.user_agent.clone(),
+            authorizer,
+        }
+    }
+
+    /// A field by the name used in the handler's `headers` config, as text.
+    pub fn field(&self, name: &str) -> Option<String> {
+        if let Some(key) = name.strip_prefix(AUTHORIZER_FIELD_PREFIX) {
+            return match self.authorizer.get(key)? {
+                Value::String(value) => Some(value.clone()),
+                Value::Null | Value::Array(_) | Value::Object(_) => None,
+                value => Some(value.to_string()),
+            };
+        }
+        match name {
+            "request_id" => self.request_id.clone(),
+            "stage" => self.stage.clone(),
+            "source_ip" => self.source_ip.clone(),
+            "user_agent" => self.user_agent.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// The request context the [`RequestContextHandler`] extracted.
+pub fn request_context(exchange: &LambdaExchange) -> Option<&RequestContext> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<RequestContext>(REQUEST_CONTEXT_ATTACHMENT_KEY)
+}
+
+/// Lifts the request's `requestContext` into a well-known [`RequestContext`] attachment, and
+/// optionally copies its fields into request headers for handlers and targets that only look
+/// at headers.
+pub struct RequestContextHandler {
+    pub(crate) config: Config<RequestContextHandlerConfig>,
+}
+
+impl NamedHandler for RequestContextHandler {
+    const NAME: &'static str = "RequestContextHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for RequestContextHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let context = match exchange.input().await {
+            Ok(request) => RequestContext::from_request(request),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+        if let Ok(request) = exchange.input_mut().await {
+            for (field, header) in &self.config.get().headers {
+                let value = context.field(field).and_then(|v| HeaderValue::from_str(&v).ok());
+                if let (Ok(header), Some(value)) = (HeaderName::try_from(header.as_str()), value) {
+                    request.headers.insert(header, value);
+                }
+            }
+        }
+        exchange
+            .attachments_mut()
+            .well_known_mut()
+            .add::<RequestContext>(REQUEST_CONTEXT_ATTACHMENT_KEY, context);
+        Ok(HandlerStatus::new(ExchangeState::OK))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::context::RequestContext;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_context_fields() {
+        let request: ApiGatewayProxyRequest = serde_json::from_value(json!({
+            "httpMethod": "GET",
+            "path": "/pets",
+            "headers": {},
+            "requestContext": {
+                "requestId": "abc",
+                "stage": "dev",
+                "httpMethod": "GET",
+                "identity": { "sourceIp": "10.0.0.1", "userAgent": "curl/8.0" },
+                "authorizer": { "claims": { "sub": "user-1", "email_verified": true } }
+            }
+        }))
+        .unwrap();
+        let context = RequestContext::from_request(&request);
+        assert_eq!(context.request_id.as_deref(), Some("abc"));
+        assert_eq!(context.field("source_ip").as_deref(), Some("10.0.0.1"));
+        assert_eq!(context.field("user_agent").as_deref(), Some("curl/8.0"));
+        assert_eq!(context.field("authorizer.sub").as_deref(), Some("user-1"));
+        assert_eq!(context.field("authorizer.email_verified").as_deref(), Some("true"));
+        assert_eq!(context.field("authorizer.missing"), None);
+    }
+}
@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 use idemio::config::Config;
@@ -12,25 +13,227 @@ use idemio::status::{ExchangeState, HandlerStatus};
 //use idem_handler_config::config::Config;
 use lambda_http::Context;
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::header::VARY;
 use lambda_http::http::HeaderValue;
 //use idem_handler_macro::ConfigurableHandler;
+use crate::flow::builder::NamedHandler;
+use crate::flow::matcher::matched_route;
+use crate::flow::openapi::cors_routes_from_openapi;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::error::{ERR10004_ORIGIN_FORBIDDEN, ERR10005_PREFLIGHT_REJECTED, ERR13000_INTERNAL};
 use crate::handler::LambdaExchange;
+use crate::ROOT_CONFIG_PATH;
 
-#[derive(Deserialize, Serialize, Default, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct CorsHandlerConfig {
     pub enabled: bool,
     pub allowed_origins: Vec<String>,
     pub allowed_methods: Vec<String>,
+    /* sent when the preflight doesn't list the headers it wants */
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /* response headers scripts on the allowed origins may read */
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    /* seconds browsers may cache a preflight for */
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+    #[serde(default = "default_allow_credentials")]
+    pub allow_credentials: bool,
+    /* of successful preflights, 204 or 200 for older clients */
+    #[serde(default = "default_preflight_status_code")]
+    pub preflight_status_code: i64,
     pub path_prefix_cors_config: HashMap<String, CorsHandlerPathConfig>,
+    /* what preflights may ask for, keyed by route template like `/pets/{id}` */
+    #[serde(default)]
+    pub routes: HashMap<String, CorsRouteConfig>,
+    /* OpenAPI spec under the config path to derive routes from; `routes` take precedence */
+    #[serde(default)]
+    pub specification_name: Option<String>,
+}
+
+impl Default for CorsHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_origins: vec![],
+            allowed_methods: vec![],
+            allowed_headers: default_allowed_headers(),
+            expose_headers: vec![],
+            max_age: default_max_age(),
+            allow_credentials: default_allow_credentials(),
+            preflight_status_code: default_preflight_status_code(),
+            path_prefix_cors_config: HashMap::new(),
+            routes: HashMap::new(),
+            specification_name: None,
+        }
+    }
+}
+
+/// The methods and request headers a route actually supports. Preflights asking for anything
+/// else are rejected; an empty list doesn't restrict.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CorsRouteConfig {
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
 }
 
+impl CorsRouteConfig {
+    /* the first method or header the preflight asks for that the route doesn't support */
+    fn rejects(&self, method: &str, headers: &str) -> Option<String> {
+        if !self.methods.is_empty()
+            && !self.methods.iter().any(|allowed| allowed.eq_ignore_ascii_case(method))
+        {
+            return Some(format!("Method {} is not supported", method));
+        }
+        if self.headers.is_empty() {
+            return None;
+        }
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .find(|header| !self.headers.iter().any(|allowed| allowed.eq_ignore_ascii_case(header)))
+            .map(|header| format!("Header {} is not supported", header))
+    }
+}
+
+type SpecRoutes = Arc<BTreeMap<String, CorsRouteConfig>>;
+
+/* the routes derived from each spec, read once for the lifetime of the container */
+fn spec_routes(specification_name: &str) -> SpecRoutes {
+    static ROUTES: OnceLock<Mutex<HashMap<String, SpecRoutes>>> = OnceLock::new();
+    let mut routes = ROUTES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    routes
+        .entry(specification_name.to_string())
+        .or_insert_with(|| {
+            let path = format!("{}/{}", ROOT_CONFIG_PATH, specification_name);
+            let spec = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|spec| serde_json::from_str(&spec).map_err(|e| e.to_string()));
+            match spec {
+                Ok(spec) => Arc::new(cors_routes_from_openapi(&spec)),
+                Err(e) => {
+                    tracing::warn!("Unable to derive CORS routes from {}: {}", path, e);
+                    Arc::default()
+                }
+            }
+        })
+        .clone()
+}
+
+/// Settings for the paths under one prefix. Origins and methods are added to the handler's;
+/// the other directives replace the handler's when set. The longest matching prefix applies.
 #[derive(Deserialize, Serialize, Default, Clone)]
 pub struct CorsHandlerPathConfig {
     pub allowed_origins: Vec<String>,
     pub allowed_methods: Vec<String>,
+    #[serde(default)]
+    pub allowed_headers: Option<Vec<String>>,
+    #[serde(default)]
+    pub expose_headers: Option<Vec<String>>,
+    #[serde(default)]
+    pub max_age: Option<u64>,
+    #[serde(default)]
+    pub allow_credentials: Option<bool>,
+}
+
+fn default_allowed_headers() -> Vec<String> {
+    ["Content-Type", "WWW-Authenticate", "Authorization"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+fn default_max_age() -> u64 {
+    3600
 }
 
+fn default_allow_credentials() -> bool {
+    true
+}
 
+fn default_preflight_status_code() -> i64 {
+    204
+}
+
+/* adds a name to the response's Vary header, keeping the names already there */
+fn append_vary(response: &mut ApiGatewayProxyResponse, name: &str) {
+    let current = response
+        .headers
+        .get(VARY)
+        .and_then(|vary| vary.to_str().ok())
+        .unwrap_or_default();
+    let varies = current
+        .split(',')
+        .any(|current| current.trim().eq_ignore_ascii_case(name) || current.trim() == "*");
+    if varies {
+        return;
+    }
+    let vary = match current.is_empty() {
+        true => name.to_string(),
+        false => format!("{}, {}", current, name),
+    };
+    if let Ok(vary) = HeaderValue::from_str(&vary) {
+        response.headers.insert(VARY, vary);
+    }
+}
+
+/* the directives that apply to one request, after the path prefix overrides */
+#[derive(Debug, Clone, PartialEq)]
+struct CorsSettings {
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<String>,
+    allowed_headers: Vec<String>,
+    expose_headers: Vec<String>,
+    max_age: u64,
+    allow_credentials: bool,
+}
+
+impl CorsHandlerConfig {
+    fn settings(&self, path: &str) -> CorsSettings {
+        let mut settings = CorsSettings {
+            allowed_origins: self.allowed_origins.clone(),
+            allowed_methods: self.allowed_methods.clone(),
+            allowed_headers: self.allowed_headers.clone(),
+            expose_headers: self.expose_headers.clone(),
+            max_age: self.max_age,
+            allow_credentials: self.allow_credentials,
+        };
+        let path_config = self
+            .path_prefix_cors_config
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, path_config)| path_config);
+        if let Some(path_config) = path_config {
+            settings.allowed_origins.extend(path_config.allowed_origins.iter().cloned());
+            settings.allowed_methods.extend(path_config.allowed_methods.iter().cloned());
+            if let Some(allowed_headers) = &path_config.allowed_headers {
+                settings.allowed_headers = allowed_headers.clone();
+            }
+            if let Some(expose_headers) = &path_config.expose_headers {
+                settings.expose_headers = expose_headers.clone();
+            }
+            settings.max_age = path_config.max_age.unwrap_or(settings.max_age);
+            settings.allow_credentials =
+                path_config.allow_credentials.unwrap_or(settings.allow_credentials);
+        }
+        settings
+    }
+
+    fn route(&self, route: &str) -> Option<CorsRouteConfig> {
+        if let Some(route_config) = self.routes.get(route) {
+            return Some(route_config.clone());
+        }
+        let specification_name = self.specification_name.as_deref()?;
+        spec_routes(specification_name).get(route).cloned()
+    }
+}
 
 const ORIGIN_HEADER_KEY: &str = "Origin";
 const ACCESS_CONTROL_REQUEST_METHOD: &str = "Access-Control-Request-Method";
@@ -40,52 +243,43 @@ const ACCESS_CONTROL_ALLOW_CREDENTIALS: &str = "Access-Control-Allow-Credentials
 const ACCESS_CONTROL_MAX_AGE: &str = "Access-Control-Max-Age";
 const ACCESS_CONTROL_ALLOW_METHODS: &str = "Access-Control-Allow-Methods";
 const ACCESS_CONTROL_ALLOW_HEADERS: &str = "Access-Control-Allow-Headers";
+const ACCESS_CONTROL_EXPOSE_HEADERS: &str = "Access-Control-Expose-Headers";
+/* a preflight's answer depends on what it asks for as well as on its origin */
+const PREFLIGHT_VARY: &str =
+    "Origin, Access-Control-Request-Method, Access-Control-Request-Headers";
 
 //#[derive(ConfigurableHandler)]
 pub struct CorsHandler {
-    config: Config<CorsHandlerConfig>,
+    pub(crate) config: Config<CorsHandlerConfig>,
 }
 
 impl CorsHandler {
-    fn remove_default_ports(url: &str) -> &str {
-        let scheme_pattern = "://";
-        let ipv6_start_pattern = "[";
-        let ipv6_end_pattern = "]";
-        let port_pattern = ":";
-
-        if let Some(index) = url[scheme_pattern.len()..]
-            .find(scheme_pattern)
-            .map(|i| i + scheme_pattern.len())
-        {
-            let scheme = &url[..index];
-            let mut after_scheme_index = scheme.len() + scheme_pattern.len();
-            if let Some(ipv6_start_index) = url[after_scheme_index..]
-                .find(ipv6_start_pattern)
-                .map(|i| i + ipv6_start_pattern.len())
-            {
-                after_scheme_index = url[ipv6_start_index..]
-                    .find(ipv6_end_pattern)
-                    .map(|i| i + ipv6_end_pattern.len())
-                    .unwrap();
-            }
-
-            if let Some(port_index) = url[after_scheme_index..]
-                .find(port_pattern)
-                .map(|i| i + port_pattern.len())
-            {
-                let port = url[after_scheme_index + port_index..]
-                    .parse::<i32>()
-                    .unwrap();
-                if (scheme == "http" && port == 80) || (scheme == "https" && port == 443) {
-                    return &url[..after_scheme_index + port_index - 1];
-                }
-            }
+    /* the origin without its scheme's default port, `None` when it isn't scheme://host[:port] */
+    fn remove_default_ports(origin: &str) -> Option<&str> {
+        let (scheme, authority) = origin.split_once("://")?;
+        /* the colons of an IPv6 address don't start a port */
+        let host_end = match authority.strip_prefix('[') {
+            Some(address) => address.find(']')? + 2,
+            None => 0,
+        };
+        let Some(port_start) = authority[host_end..].find(':').map(|i| host_end + i) else {
+            return Some(origin);
+        };
+        let port = authority[port_start + 1..].parse::<u16>().ok()?;
+        if (scheme == "http" && port == 80) || (scheme == "https" && port == 443) {
+            return Some(&origin[..scheme.len() + "://".len() + port_start]);
         }
-        url
+        Some(origin)
     }
 }
 
-const ORIGIN_ATTACHMENT_KEY: &'static str = "origin_header_value";
+const ORIGIN_ATTACHMENT_KEY: &str = "origin_header_value";
+const EXPOSE_HEADERS_ATTACHMENT_KEY: &str = "expose_headers";
+const ALLOW_CREDENTIALS_ATTACHMENT_KEY: &str = "allow_credentials";
+
+impl NamedHandler for CorsHandler {
+    const NAME: &'static str = "CorsHandler";
+}
 
 #[async_trait]
 impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for CorsHandler {
@@ -97,151 +291,296 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             return Ok(HandlerStatus::new(ExchangeState::DISABLED));
         }
 
-        let mut found_origin_header: Option<String> = None;
-        let request = exchange.input().await.unwrap();
-        if let Some(origin_header) = request
-            .headers
-            .iter()
-            .find(|(k, _)| k.to_string().to_lowercase() == ORIGIN_HEADER_KEY.to_lowercase())
-        {
-            let origin_header_value = Self::remove_default_ports(origin_header.1.to_str().unwrap());
-            found_origin_header = Some(origin_header_value.to_string());
-
-            let mut exchange_allowed_origins = self.config.get().allowed_origins.clone();
-            let mut exchange_allowed_methods = self.config.get().allowed_methods.clone();
-
-            /* check path specific configuration */
-            if !self.config.get().path_prefix_cors_config.is_empty() {
-                let request_path = request.path.clone().unwrap_or("/".to_string());
-                let path_config = self
-                    .config
-                    .get()
-                    .path_prefix_cors_config
-                    .iter()
-                    .find(|(k, _)| request_path.starts_with(k.as_str()))
-                    .map(|(_, v)| v.clone());
-
-                if path_config.is_some() {
-                    let path_config = path_config.unwrap();
-                    exchange_allowed_origins.extend(path_config.allowed_origins);
-                    exchange_allowed_methods.extend(path_config.allowed_methods);
-                }
+        let request = match exchange.input().await {
+            Ok(request) => request,
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
             }
+        };
+        /* a malformed origin is never an allowed one */
+        let origin_header_value = request
+            .headers
+            .get(ORIGIN_HEADER_KEY)
+            .and_then(|origin| origin.to_str().ok())
+            .and_then(Self::remove_default_ports)
+            .map(str::to_string);
+        let is_preflight = request.http_method.eq("OPTIONS")
+            && request.headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD);
 
-            /* check if preflight */
-            if request.http_method.eq("OPTIONS") {
-                let mut response = ApiGatewayProxyResponse::default();
-                if exchange_allowed_origins
-                    .iter()
-                    .any(|origin| origin.to_lowercase().eq(origin_header_value))
-                {
-                    response.headers.insert(
-                        ACCESS_CONTROL_ALLOW_ORIGIN,
-                        HeaderValue::from_str(origin_header_value).unwrap(),
-                    );
-                    response
-                        .headers
-                        .insert("Vary", HeaderValue::from_str(ORIGIN_HEADER_KEY).unwrap());
-                } else {
-                    /* invalid origin, early return */
-                    response.status_code = 403;
-                    exchange.set_output(response);
-                    return Ok(
-                        HandlerStatus::new(ExchangeState::CLIENT_ERROR).message("Origin is forbidden")
-                    );
-                }
+        /* check path specific configuration */
+        let settings = self.config.get().settings(request.path.as_deref().unwrap_or("/"));
+        let allowed_origin = origin_header_value.filter(|origin_header_value| {
+            settings
+                .allowed_origins
+                .iter()
+                .any(|origin| origin.to_lowercase().eq(origin_header_value))
+        });
 
-                response.headers.insert(
-                    ACCESS_CONTROL_ALLOW_METHODS,
-                    HeaderValue::from_str(
-                        exchange_allowed_methods
-                            .iter()
-                            .map(|x| x.to_string() + ",")
-                            .collect::<Vec<_>>()
-                            .join(",")
-                            .as_str(),
-                    )
-                        .unwrap(),
-                );
-
-                if let Some((_, ac_header_value)) =
-                    request.headers.iter().find(|(header_key, _)| {
-                        header_key.to_string().to_lowercase()
-                            == ACCESS_CONTROL_REQUEST_HEADERS.to_lowercase()
-                    })
-                {
-                    response
-                        .headers
-                        .insert(ACCESS_CONTROL_ALLOW_HEADERS, ac_header_value.clone());
-                } else {
-                    response.headers.insert(
-                        ACCESS_CONTROL_ALLOW_HEADERS,
-                        HeaderValue::from_str("Content-Type, WWW-Authenticate, Authorization")
-                            .unwrap(),
-                    );
-                }
+        /* check if preflight */
+        if is_preflight {
+            let Some(allowed_origin) = allowed_origin else {
+                /* invalid origin, early return */
+                return Ok(ERR10004_ORIGIN_FORBIDDEN.raise(exchange));
+            };
+            let requested_method = request
+                .headers
+                .get(ACCESS_CONTROL_REQUEST_METHOD)
+                .and_then(|method| method.to_str().ok())
+                .unwrap_or_default();
+            let requested_headers = request.headers.get(ACCESS_CONTROL_REQUEST_HEADERS).cloned();
+            let route = matched_route(exchange).and_then(|route| self.config.get().route(route));
+            let rejection = route.as_ref().and_then(|route| {
+                let headers = requested_headers.as_ref().and_then(|h| h.to_str().ok());
+                route.rejects(requested_method, headers.unwrap_or_default())
+            });
+            if let Some(rejection) = rejection {
+                return Ok(ERR10005_PREFLIGHT_REJECTED.error().detail(rejection).raise(exchange));
+            }
+            let allowed_methods = match &route {
+                Some(route) if !route.methods.is_empty() => &route.methods,
+                _ => &settings.allowed_methods,
+            };
+            let mut response = ApiGatewayProxyResponse {
+                status_code: self.config.get().preflight_status_code,
+                ..Default::default()
+            };
+            let headers = &mut response.headers;
+            if let Ok(allowed_origin) = HeaderValue::from_str(&allowed_origin) {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+            }
+            headers.insert(VARY, HeaderValue::from_static(PREFLIGHT_VARY));
+            if let Ok(allowed_methods) = HeaderValue::from_str(&allowed_methods.join(",")) {
+                headers.insert(ACCESS_CONTROL_ALLOW_METHODS, allowed_methods);
+            }
+            let allowed_headers = requested_headers
+                .or_else(|| HeaderValue::from_str(&settings.allowed_headers.join(", ")).ok());
+            if let Some(allowed_headers) = allowed_headers {
+                headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+            }
+            if settings.allow_credentials {
+                headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+            }
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(settings.max_age));
+            exchange.set_output(response);
+            return Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED));
+        }
 
+        /* the allow-origin header and the rest are only added for allowed origins; every
+        response varies by origin, whether or not the request had one */
+        if let Some(allowed_origin) = allowed_origin {
+            let mut scoped = exchange.attachments_mut().scoped_mut(Self::NAME);
+            scoped.add::<String>(ORIGIN_ATTACHMENT_KEY, allowed_origin);
+            /* read by the browser on the actual response only */
+            if !settings.expose_headers.is_empty() {
+                let expose_headers = settings.expose_headers.join(", ");
+                scoped.add::<String>(EXPOSE_HEADERS_ATTACHMENT_KEY, expose_headers);
+            }
+            if settings.allow_credentials {
+                scoped.add::<bool>(ALLOW_CREDENTIALS_ATTACHMENT_KEY, true);
+            }
+        }
+        exchange.add_output_listener(|response, attachments| {
+            append_vary(response, ORIGIN_HEADER_KEY);
+            let scoped = attachments.scoped(Self::NAME);
+            if let Some(Ok(origin_header_value)) = scoped
+                .get::<String>(ORIGIN_ATTACHMENT_KEY)
+                .map(|value| HeaderValue::from_str(value))
+            {
+                response.headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin_header_value);
+            }
+            if let Some(Ok(expose_headers)) = scoped
+                .get::<String>(EXPOSE_HEADERS_ATTACHMENT_KEY)
+                .map(|value| HeaderValue::from_str(value))
+            {
+                response.headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers);
+            }
+            if scoped.get::<bool>(ALLOW_CREDENTIALS_ATTACHMENT_KEY).is_some() {
                 response.headers.insert(
                     ACCESS_CONTROL_ALLOW_CREDENTIALS,
-                    HeaderValue::from_str("true").unwrap(),
-                );
-                response.headers.insert(
-                    ACCESS_CONTROL_MAX_AGE,
-                    HeaderValue::from_str("3600").unwrap(),
+                    HeaderValue::from_static("true"),
                 );
-            } else {
-                if !exchange_allowed_origins
-                    .iter()
-                    .any(|origin| origin.to_lowercase().eq(origin_header_value))
-                {
-                    // TODO - Handle validation failure return.
-                    return Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED));
-                }
             }
-        }
-
-        /* if we found an origin header, add it to the response as well. */
-        /* if the handler is disabled or the origin header could not be found in the request, 'found_origin_header' will be None. */
-        if let Some(found_origin_header) = found_origin_header {
-            exchange
-                .attachments_mut()
-                .add::<String>(ORIGIN_ATTACHMENT_KEY, found_origin_header);
-            exchange.add_output_listener(|response, attachments| {
-                if let Some(origin_header_value) =
-                    attachments.get::<String>(ORIGIN_ATTACHMENT_KEY)
-                {
-                    response.headers.insert(
-                        ACCESS_CONTROL_ALLOW_ORIGIN,
-                        HeaderValue::from_str(origin_header_value).unwrap(),
-                    );
-                }
-            });
-        }
+        });
         Ok(HandlerStatus::new(ExchangeState::OK))
     }
 
     fn name(&self) -> &str {
-        "CorsHandler"
+        Self::NAME
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::handler::cors::CorsHandler;
+    use crate::flow::matcher::set_route;
+    use crate::handler::cors::{CorsHandler, CorsHandlerConfig, CorsRouteConfig};
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError};
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::ExchangeState;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::http::Method;
+
+    struct StaticConfig(CorsHandlerConfig);
+
+    impl ConfigProvider<CorsHandlerConfig> for StaticConfig {
+        fn load(&self) -> Result<CorsHandlerConfig, ConfigProviderError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn config() -> CorsHandlerConfig {
+        CorsHandlerConfig {
+            enabled: true,
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            ..Default::default()
+        }
+    }
+
+    async fn run(request: RequestBuilder) -> (ExchangeState, ApiGatewayProxyResponse) {
+        run_with(config(), None, request).await
+    }
+
+    async fn run_with(
+        config: CorsHandlerConfig,
+        route: Option<&str>,
+        request: RequestBuilder,
+    ) -> (ExchangeState, ApiGatewayProxyResponse) {
+        let handler = CorsHandler {
+            config: Config::new(StaticConfig(config)).unwrap(),
+        };
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request.build());
+        if let Some(route) = route {
+            set_route(&mut exchange, route);
+        }
+        let status = handler.exec(&mut exchange).await.unwrap().code();
+        if status == ExchangeState::OK {
+            exchange.set_output(ApiGatewayProxyResponse::default());
+        }
+        (status, exchange.take_output().await.unwrap_or_default())
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_preflight_and_vary() {
+        let preflight = RequestBuilder::new(Method::OPTIONS, "/pets")
+            .header("origin", "https://app.example.com")
+            .header("access-control-request-method", "POST");
+        let (status, response) = run(preflight).await;
+        assert_eq!(status, ExchangeState::EXCHANGE_COMPLETED);
+        assert_eq!(response.status_code, 204);
+        assert_eq!(response.headers["access-control-allow-methods"], "GET,POST");
+        assert_eq!(response.headers["access-control-max-age"], "3600");
+        let vary = response.headers["vary"].to_str().unwrap();
+        assert!(vary.contains("Access-Control-Request-Method"));
+
+        let allowed = RequestBuilder::get("/pets").header("origin", "https://app.example.com");
+        let (status, response) = run(allowed).await;
+        assert_eq!(status, ExchangeState::OK);
+        assert_eq!(response.headers["access-control-allow-origin"], "https://app.example.com");
+        assert_eq!(response.headers["vary"], "Origin");
+
+        let forbidden = RequestBuilder::get("/pets").header("origin", "https://evil.example.com");
+        let (status, response) = run(forbidden).await;
+        assert_eq!(status, ExchangeState::OK);
+        assert!(response.headers.get("access-control-allow-origin").is_none());
+        assert_eq!(response.headers["vary"], "Origin");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_route_preflight_validation() {
+        let mut config = config();
+        config.routes.insert(
+            "/pets/{id}".to_string(),
+            CorsRouteConfig {
+                methods: vec!["GET".to_string(), "PUT".to_string()],
+                headers: vec!["Content-Type".to_string(), "X-Tenant".to_string()],
+            },
+        );
+        let preflight = |method: &str, headers: &str| {
+            RequestBuilder::new(Method::OPTIONS, "/pets/7")
+                .header("origin", "https://app.example.com")
+                .header("access-control-request-method", method)
+                .header("access-control-request-headers", headers)
+        };
+
+        let request = preflight("PUT", "content-type, x-tenant");
+        let (status, response) = run_with(config.clone(), Some("/pets/{id}"), request).await;
+        assert_eq!(status, ExchangeState::EXCHANGE_COMPLETED);
+        assert_eq!(response.headers["access-control-allow-methods"], "GET,PUT");
+
+        let request = preflight("DELETE", "content-type");
+        let (status, _) = run_with(config.clone(), Some("/pets/{id}"), request).await;
+        assert!(status.any_flags(ExchangeState::CLIENT_ERROR));
+
+        let request = preflight("GET", "x-debug");
+        let (status, _) = run_with(config.clone(), Some("/pets/{id}"), request).await;
+        assert!(status.any_flags(ExchangeState::CLIENT_ERROR));
+
+        /* routes without a config keep the handler-wide behaviour */
+        let request = preflight("DELETE", "x-debug");
+        let (status, _) = run_with(config, Some("/owners"), request).await;
+        assert_eq!(status, ExchangeState::EXCHANGE_COMPLETED);
+    }
+
+    #[test]
+    fn test_path_prefix_settings() {
+        let config: CorsHandlerConfig = serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "allowed_origins": ["https://app.example.com"],
+                "allowed_methods": ["GET"],
+                "expose_headers": ["x-request-id"],
+                "path_prefix_cors_config": {
+                    "/public": { "allowed_origins": ["https://docs.example.com"],
+                                 "allowed_methods": [], "allow_credentials": false },
+                    "/public/uploads": { "allowed_origins": [], "allowed_methods": ["PUT"],
+                                         "max_age": 60, "allowed_headers": ["Content-Type"] }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let settings = config.settings("/orders");
+        assert_eq!(settings.max_age, 3600);
+        assert!(settings.allow_credentials);
+        assert_eq!(settings.allowed_headers.len(), 3);
+        assert_eq!(settings.expose_headers, vec!["x-request-id"]);
+
+        let settings = config.settings("/public/index.html");
+        assert!(!settings.allow_credentials);
+        assert_eq!(settings.allowed_origins.len(), 2);
+
+        let settings = config.settings("/public/uploads/1");
+        assert_eq!(settings.max_age, 60);
+        assert!(settings.allow_credentials);
+        assert_eq!(settings.allowed_methods, vec!["GET", "PUT"]);
+        assert_eq!(settings.allowed_headers, vec!["Content-Type"]);
+    }
 
     #[test]
     fn test_default_port_filtering() {
         let http_url = "http://testurl.com:80";
         let sanitized_url = CorsHandler::remove_default_ports(http_url);
-        assert_eq!(sanitized_url, "http://testurl.com");
+        assert_eq!(sanitized_url, Some("http://testurl.com"));
 
         let http_url = "https://testurl.com:8080";
         let sanitized_url = CorsHandler::remove_default_ports(http_url);
-        assert_eq!(sanitized_url, "https://testurl.com:8080");
+        assert_eq!(sanitized_url, Some("https://testurl.com:8080"));
 
         let http_url = "http://[2001:db8:4006:812::200e]:80";
         let sanitized_url = CorsHandler::remove_default_ports(http_url);
-        assert_eq!(sanitized_url, "http://[2001:db8:4006:812::200e]");
+        assert_eq!(sanitized_url, Some("http://[2001:db8:4006:812::200e]"));
+
+        /* malformed origins come from clients, and are never allowed */
+        for origin in ["", "a", "null", "http://testurl.com:http", "http://[2001:db8::1:80"] {
+            assert_eq!(CorsHandler::remove_default_ports(origin), None);
+        }
     }
 
     //    // TODO - test cors functionality using tokio test: https://tokio.rs/tokio/topics/testing
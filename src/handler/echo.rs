@@ -5,21 +5,31 @@ use idemio::handler::Handler;
 use idemio::status::{ExchangeState, HandlerStatus};
 use idemio::config::Config;
 use idemio::exchange::Exchange;
-use idemio_macro::ConfigurableHandler;
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use lambda_http::{Body, Context};
+use crate::flow::builder::NamedHandler;
+use crate::executor::budget::{server_timing, SERVER_TIMING_HEADER};
+use crate::executor::handler_timings;
 use crate::handler::LambdaExchange;
 
 #[derive(Default, Deserialize)]
+#[serde(default)]
 pub struct EchoRequestHandlerConfig {
     pub enabled: bool,
     pub echo_headers: bool,
-    pub static_body: Option<String>
+    pub static_body: Option<String>,
+    /* report the time spent in each earlier handler as a Server-Timing header */
+    #[serde(default)]
+    pub server_timing: bool,
 }
 
 //#[derive(ConfigurableHandler)]
 pub struct EchoRequestHandler {
-    config: Config<EchoRequestHandlerConfig>,
+    pub(crate) config: Config<EchoRequestHandlerConfig>,
+}
+
+impl NamedHandler for EchoRequestHandler {
+    const NAME: &'static str = "EchoRequestHandler";
 }
 
 #[async_trait]
@@ -41,10 +51,7 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
                 None => None,
             }
         } else {
-            match request_payload.body {
-                Some(body) => Some(Body::Text(body)),
-                None => None,
-            }
+            request_payload.body.map(Body::Text)
         };
 
         let mut response_payload = ApiGatewayProxyResponse {
@@ -58,12 +65,18 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             response_payload.headers.extend(request_headers);
         }
 
+        if self.config.get().server_timing
+            && let Ok(header_value) = server_timing(handler_timings(exchange)).parse()
+        {
+            response_payload.headers.insert(SERVER_TIMING_HEADER, header_value);
+        }
+
         exchange.set_output(response_payload);
         Ok(HandlerStatus::new(ExchangeState::OK))
     }
 
     fn name(&self) -> &str {
-        "EchoRequestHandler"
+        Self::NAME
     }
 }
 
@@ -0,0 +1,202 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+const METHOD_SEPARATOR: char = '@';
+const ANY_METHOD: &str = "ANY";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Static(String),
+    /* `{name}` or `*`, exactly one segment */
+    Single,
+    /* `**`, any number of segments, none included */
+    Any,
+}
+
+impl Segment {
+    fn parse(segment: &str) -> Self {
+        match segment {
+            "**" => Segment::Any,
+            "*" => Segment::Single,
+            _ if segment.starts_with('{') && segment.ends_with('}') => Segment::Single,
+            _ => Segment::Static(segment.to_string()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 2,
+            Segment::Single => 1,
+            Segment::Any => 0,
+        }
+    }
+}
+
+/// A function key with a path template or glob, like `/pets/{id}@GET`, `/admin/**@ANY` or
+/// `api.example.com/files/*@GET`.
+#[derive(Debug, Clone)]
+struct FunctionRoute {
+    key: String,
+    host: Option<String>,
+    segments: Vec<Segment>,
+    method: String,
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn matches(segments: &[Segment], path: &[&str]) -> bool {
+    match segments.split_first() {
+        None => path.is_empty(),
+        Some((Segment::Any, rest)) => (0..=path.len()).any(|skip| matches(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, path)) => {
+                let matched = match segment {
+                    Segment::Static(expected) => expected == first,
+                    _ => true,
+                };
+                matched && matches(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+impl FunctionRoute {
+    fn parse(key: &str) -> Result<Self, String> {
+        let (route, method) = key
+            .rsplit_once(METHOD_SEPARATOR)
+            .ok_or_else(|| format!("{} has no {}METHOD", key, METHOD_SEPARATOR))?;
+        let (host, path) = match route.find('/') {
+            Some(0) => (None, route),
+            Some(index) => (Some(route[..index].to_string()), &route[index..]),
+            None => return Err(format!("{} has no path", key)),
+        };
+        Ok(Self {
+            key: key.to_string(),
+            host,
+            segments: split_path(path).map(Segment::parse).collect(),
+            method: method.to_uppercase(),
+        })
+    }
+
+    fn is_pattern(&self) -> bool {
+        self.method == ANY_METHOD
+            || self
+                .segments
+                .iter()
+                .any(|segment| !matches!(segment, Segment::Static(_)))
+    }
+
+    /* the routes of a host first, then the most specific; ties go by key */
+    fn precedence(&self) -> (bool, Vec<u8>, bool, Reverse<&str>) {
+        (
+            self.host.is_some(),
+            self.segments.iter().map(Segment::rank).collect(),
+            self.method != ANY_METHOD,
+            Reverse(self.key.as_str()),
+        )
+    }
+
+    fn matches(&self, host: Option<&str>, path: &str, method: &str) -> bool {
+        let serves_host = self.host.is_none() || self.host.as_deref() == host;
+        let serves_method = self.method == ANY_METHOD || self.method == method;
+        let path: Vec<&str> = split_path(path).collect();
+        serves_host && serves_method && matches(&self.segments, &path)
+    }
+}
+
+/// The proxy's functions by route. Exact keys (`/pets@GET`, `host/pets@GET`) are looked up
+/// directly; patterns are tried after them, in order of precedence: the patterns of a host
+/// before the patterns of every host, then the most specific, segment by segment, with a
+/// method before `ANY`.
+#[derive(Debug, Clone)]
+pub struct FunctionRoutes<T> {
+    exact: HashMap<String, T>,
+    /* in order of precedence */
+    patterns: Vec<(FunctionRoute, T)>,
+}
+
+impl<T> Default for FunctionRoutes<T> {
+    fn default() -> Self {
+        Self {
+            exact: HashMap::new(),
+            patterns: vec![],
+        }
+    }
+}
+
+impl<T> FunctionRoutes<T> {
+    pub fn new(routes: impl IntoIterator<Item = (String, T)>) -> Result<Self, String> {
+        let mut function_routes = Self::default();
+        for (key, target) in routes {
+            let route = FunctionRoute::parse(&key)?;
+            match route.is_pattern() {
+                true => function_routes.patterns.push((route, target)),
+                false => {
+                    function_routes.exact.insert(key, target);
+                }
+            }
+        }
+        function_routes
+            .patterns
+            .sort_by(|(a, _), (b, _)| b.precedence().cmp(&a.precedence()));
+        Ok(function_routes)
+    }
+
+    pub fn find(&self, host: Option<&str>, path: &str, method: &str) -> Option<&T> {
+        let key = format!("{}{}{}", path, METHOD_SEPARATOR, method);
+        host.and_then(|host| self.exact.get(&format!("{}{}", host, key)))
+            .or_else(|| self.exact.get(&key))
+            .or_else(|| {
+                self.patterns
+                    .iter()
+                    .find(|(route, _)| route.matches(host, path, method))
+                    .map(|(_, target)| target)
+            })
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FunctionRoutes<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let routes: HashMap<String, T> = HashMap::deserialize(deserializer)?;
+        FunctionRoutes::new(routes).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::function_routes::FunctionRoutes;
+
+    #[test]
+    fn test_function_route_patterns() {
+        let routes: FunctionRoutes<String> = serde_json::from_str(
+            r#"{
+                "/pets@GET": "list",
+                "/pets/{id}@GET": "get",
+                "/pets/*@ANY": "any",
+                "/admin/**@ANY": "admin",
+                "/admin/users/{id}@DELETE": "delete-user",
+                "api.example.com/pets/{id}@GET": "host-get"
+            }"#,
+        )
+        .unwrap();
+        let find = |host, path, method| routes.find(host, path, method).map(String::as_str);
+        assert_eq!(find(None, "/pets", "GET"), Some("list"));
+        assert_eq!(find(None, "/pets/7", "GET"), Some("get"));
+        assert_eq!(find(None, "/pets/7", "PUT"), Some("any"));
+        assert_eq!(find(Some("api.example.com"), "/pets/7", "GET"), Some("host-get"));
+        assert_eq!(find(None, "/admin", "POST"), Some("admin"));
+        assert_eq!(find(None, "/admin/users/7", "DELETE"), Some("delete-user"));
+        assert_eq!(find(None, "/admin/users/7", "GET"), Some("admin"));
+        assert_eq!(find(None, "/pets/7/toys", "GET"), None);
+
+        assert!(serde_json::from_str::<FunctionRoutes<String>>(r#"{ "/pets": "x" }"#).is_err());
+    }
+}
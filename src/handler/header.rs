@@ -1,26 +1,232 @@
+use crate::executor::authorizer::{authorizer_context, principal};
+use crate::executor::exception::correlation_id;
+use crate::flow::builder::NamedHandler;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::context::request_context;
 use crate::handler::LambdaExchange;
 use async_trait::async_trait;
 use lambda_http::Context;
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use lambda_http::http::{HeaderMap, HeaderName, HeaderValue};
 use serde::Deserialize;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::time::{SystemTime, UNIX_EPOCH};
 use idemio::config::Config;
 use idemio::exchange::Exchange;
 use idemio::handler::Handler;
 use idemio::status::{ExchangeState, HandlerStatus};
 
+const PLACEHOLDER_START: &str = "${";
+const PLACEHOLDER_END: char = '}';
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] =
+    ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
 #[derive(Deserialize, Default, Clone, PartialOrd, PartialEq, Hash, Eq)]
 pub struct ModifyHeaderKey(pub String);
 
-#[derive(Deserialize, Default, Clone)]
-pub struct ModifyHeaderValue(pub String);
+/// A header value, plain text or `{ "value": "...", "when": { "header": "..." } }` to only
+/// change the header while another one matches. Values may contain placeholders resolved for
+/// each request: `${correlation_id}`, `${claim:<name>}`, `${now:rfc3339}`, `${now:http}`,
+/// `${now:epoch}` and `${env:<name>}`. A change whose placeholders don't resolve, like a claim
+/// of an anonymous caller, is skipped.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(try_from = "ModifyHeaderValueConfig")]
+pub struct ModifyHeaderValue {
+    pub value: String,
+    pub when: Option<HeaderCondition>,
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModifyHeaderValueConfig {
+    Value(String),
+    Guarded {
+        value: String,
+        #[serde(default)]
+        when: Option<HeaderCondition>,
+    },
+}
+
+impl TryFrom<ModifyHeaderValueConfig> for ModifyHeaderValue {
+    type Error = String;
+
+    fn try_from(config: ModifyHeaderValueConfig) -> Result<Self, Self::Error> {
+        let (value, when) = match config {
+            ModifyHeaderValueConfig::Value(value) => (value, None),
+            ModifyHeaderValueConfig::Guarded { value, when } => (value, when),
+        };
+        Ok(Self {
+            parts: parse_template(&value)?,
+            value,
+            when,
+        })
+    }
+}
+
+/// Matches while the header is present with a value equal to `equals` (ignoring case) and
+/// containing `contains`, when those are set, or while it is missing with `absent`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct HeaderCondition {
+    pub header: String,
+    #[serde(default)]
+    pub equals: Option<String>,
+    #[serde(default)]
+    pub contains: Option<String>,
+    #[serde(default)]
+    pub absent: bool,
+}
+
+impl HeaderCondition {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        let mut values = headers
+            .get_all(self.header.as_str())
+            .iter()
+            .filter_map(|value| value.to_str().ok());
+        if self.absent {
+            return values.next().is_none();
+        }
+        values.any(|value| {
+            self.equals.as_ref().is_none_or(|equals| value.eq_ignore_ascii_case(equals))
+                && self.contains.as_ref().is_none_or(|contains| value.contains(contains.as_str()))
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimeFormat {
+    Rfc3339,
+    Http,
+    Epoch,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Placeholder {
+    CorrelationId,
+    Claim(String),
+    Now(TimeFormat),
+    Env(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+impl Placeholder {
+    fn parse(expression: &str) -> Result<Self, String> {
+        match expression.split_once(':') {
+            None if expression == "correlation_id" => Ok(Placeholder::CorrelationId),
+            Some(("claim", name)) => Ok(Placeholder::Claim(name.to_string())),
+            Some(("now", "rfc3339")) => Ok(Placeholder::Now(TimeFormat::Rfc3339)),
+            Some(("now", "http")) => Ok(Placeholder::Now(TimeFormat::Http)),
+            Some(("now", "epoch")) => Ok(Placeholder::Now(TimeFormat::Epoch)),
+            Some(("env", name)) => Ok(Placeholder::Env(name.to_string())),
+            _ => Err(format!("Unknown header placeholder ${{{}}}", expression)),
+        }
+    }
+
+    fn resolve(&self, exchange: &LambdaExchange) -> Option<String> {
+        match self {
+            Placeholder::CorrelationId => Some(correlation_id(exchange)),
+            Placeholder::Claim(name) => match authorizer_context(exchange, name) {
+                Some(Value::String(value)) => Some(value.clone()),
+                Some(value @ (Value::Number(_) | Value::Bool(_))) => Some(value.to_string()),
+                _ => request_context(exchange)
+                    .and_then(|context| context.field(&format!("authorizer.{}", name)))
+                    .or_else(|| principal(exchange).filter(|_| name == "sub").map(String::from)),
+            },
+            Placeholder::Now(format) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Some(format_time(now.as_secs(), *format))
+            }
+            Placeholder::Env(name) => std::env::var(name).ok(),
+        }
+    }
+}
+
+/* splits a value into literal text and placeholders, in order */
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        if start > 0 {
+            parts.push(TemplatePart::Literal(rest[..start].to_string()));
+        }
+        let after_start = &rest[start + PLACEHOLDER_START.len()..];
+        let Some(end) = after_start.find(PLACEHOLDER_END) else {
+            return Err(format!("Unterminated header placeholder in '{}'", template));
+        };
+        parts.push(TemplatePart::Placeholder(Placeholder::parse(&after_start[..end])?));
+        rest = &after_start[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+/* epoch seconds as UTC, with the civil date from days since the epoch */
+fn format_time(seconds: u64, format: TimeFormat) -> String {
+    if format == TimeFormat::Epoch {
+        return seconds.to_string();
+    }
+    let days = (seconds / 86_400) as i64;
+    let (hour, minute, second) = (seconds % 86_400 / 3600, seconds % 3600 / 60, seconds % 60);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    match format {
+        TimeFormat::Http => format!(
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            hour,
+            minute,
+            second
+        ),
+        _ => format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year, month, day, hour, minute, second
+        ),
+    }
+}
+
+impl ModifyHeaderValue {
+    /* the value for the current request, `None` when a placeholder resolves to nothing */
+    fn resolve(&self, exchange: &LambdaExchange) -> Option<String> {
+        let mut value = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => value.push_str(text),
+                TemplatePart::Placeholder(placeholder) => {
+                    value.push_str(&placeholder.resolve(exchange)?)
+                }
+            }
+        }
+        Some(value)
+    }
+}
 
 #[derive(Deserialize, Default, PartialOrd, PartialEq, Hash, Eq)]
 pub struct PathPrefix(pub String);
 
 #[derive(Deserialize, Default)]
+#[serde(default)]
 pub struct HeaderHandlerConfig {
     pub enabled: bool,
     pub request: ModifyHeaderHandlerConfig,
@@ -34,10 +240,74 @@ pub struct PathHeaderHandlerConfig {
     pub response: ModifyHeaderHandlerConfig,
 }
 
-#[derive(Deserialize, Default)]
+/// The changes to the headers on one side of the exchange. They run in the order `rename`,
+/// `update`, `append`, `set_if_absent`; a `rename` value is the new name of the header.
+#[derive(Deserialize, Default, Clone)]
 pub struct ModifyHeaderHandlerConfig {
     pub update: HashMap<ModifyHeaderKey, ModifyHeaderValue>,
     pub remove: Vec<ModifyHeaderKey>,
+    /* adds a value next to the ones already there */
+    #[serde(default)]
+    pub append: HashMap<ModifyHeaderKey, ModifyHeaderValue>,
+    #[serde(default)]
+    pub set_if_absent: HashMap<ModifyHeaderKey, ModifyHeaderValue>,
+    #[serde(default)]
+    pub rename: HashMap<ModifyHeaderKey, ModifyHeaderValue>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HeaderOperation {
+    Rename,
+    Update,
+    Append,
+    SetIfAbsent,
+}
+
+/* an operation with its value resolved for the current request */
+#[derive(Clone, Debug)]
+struct HeaderChange {
+    operation: HeaderOperation,
+    name: String,
+    value: String,
+    when: Option<HeaderCondition>,
+}
+
+impl ModifyHeaderHandlerConfig {
+    /* the path's changes on top of these, replacing the ones for the same header */
+    fn merge(&mut self, other: &ModifyHeaderHandlerConfig) {
+        self.update.extend(other.update.clone());
+        self.remove.extend(other.remove.clone());
+        self.append.extend(other.append.clone());
+        self.set_if_absent.extend(other.set_if_absent.clone());
+        self.rename.extend(other.rename.clone());
+    }
+
+    fn changes(&self, exchange: &LambdaExchange) -> Vec<HeaderChange> {
+        let operations = [
+            (HeaderOperation::Rename, &self.rename),
+            (HeaderOperation::Update, &self.update),
+            (HeaderOperation::Append, &self.append),
+            (HeaderOperation::SetIfAbsent, &self.set_if_absent),
+        ];
+        let mut changes = vec![];
+        for (operation, headers) in operations {
+            for (name, value) in headers {
+                let resolved = match operation {
+                    HeaderOperation::Rename => Some(value.value.clone()),
+                    _ => value.resolve(exchange),
+                };
+                if let Some(resolved) = resolved {
+                    changes.push(HeaderChange {
+                        operation,
+                        name: name.0.clone(),
+                        value: resolved,
+                        when: value.when.clone(),
+                    });
+                }
+            }
+        }
+        changes
+    }
 }
 
 //#[derive(ConfigurableHandler)]
@@ -48,25 +318,51 @@ pub struct HeaderHandler {
 impl HeaderHandler {
     fn remove_headers(headers: &mut HeaderMap, remove_headers: Vec<ModifyHeaderKey>) {
         for header in remove_headers {
-            headers.remove(header.0).unwrap();
+            headers.remove(header.0);
         }
     }
 
-    fn update_headers(
-        headers: &mut HeaderMap,
-        update_headers: HashMap<ModifyHeaderKey, ModifyHeaderValue>,
-    ) {
-        for (header_key, header_value) in update_headers {
-            headers.insert(
-                HeaderName::from_bytes(header_key.0.as_bytes()).unwrap(),
-                HeaderValue::from_str(header_value.0.as_str()).unwrap(),
-            );
+    fn apply_changes(headers: &mut HeaderMap, changes: &[HeaderChange]) {
+        for change in changes {
+            if change.when.as_ref().is_some_and(|when| !when.matches(headers)) {
+                continue;
+            }
+            let Ok(name) = HeaderName::from_bytes(change.name.as_bytes()) else {
+                continue;
+            };
+            if change.operation == HeaderOperation::Rename {
+                let Ok(new_name) = HeaderName::from_bytes(change.value.as_bytes()) else {
+                    continue;
+                };
+                let values: Vec<HeaderValue> = headers.get_all(&name).iter().cloned().collect();
+                headers.remove(&name);
+                for value in values {
+                    headers.append(new_name.clone(), value);
+                }
+                continue;
+            }
+            let Ok(value) = HeaderValue::from_str(&change.value) else {
+                continue;
+            };
+            match change.operation {
+                HeaderOperation::Append => {
+                    headers.append(name, value);
+                }
+                HeaderOperation::SetIfAbsent if headers.contains_key(&name) => {}
+                _ => {
+                    headers.insert(name, value);
+                }
+            }
         }
     }
 }
 
-const REMOVE_RESPONSE_HEADER_ATTACHMENT_KEY: &'static str = "remove_response_headers";
-const UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY: &'static str = "update_response_headers";
+const REMOVE_RESPONSE_HEADER_ATTACHMENT_KEY: &str = "remove_response_headers";
+const UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY: &str = "update_response_headers";
+
+impl NamedHandler for HeaderHandler {
+    const NAME: &'static str = "HeaderHandler";
+}
 
 #[async_trait]
 impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for HeaderHandler {
@@ -81,16 +377,9 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
         let request = exchange.input().await.unwrap();
         let request_path = request.path.as_deref().unwrap_or("/");
 
-        let mut request_remove_headers = vec![];
-        let mut request_update_headers = HashMap::new();
-        let mut response_remove_headers = vec![];
-        let mut response_update_headers = HashMap::new();
-
         // Gather rules for current path
-        request_remove_headers.extend(self.config.get().request.remove.clone());
-        request_update_headers.extend(self.config.get().request.update.clone());
-        response_remove_headers.extend(self.config.get().response.remove.clone());
-        response_update_headers.extend(self.config.get().response.update.clone());
+        let mut request_config = self.config.get().request.clone();
+        let mut response_config = self.config.get().response.clone();
 
         if let Some((_, path_config)) = self
             .config
@@ -99,51 +388,42 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             .iter()
             .find(|(path_prefix, _)| request_path.starts_with(&path_prefix.0))
         {
-            request_remove_headers.extend(path_config.request.remove.clone());
-            request_update_headers.extend(path_config.request.update.clone());
-            response_remove_headers.extend(path_config.response.remove.clone());
-            response_update_headers.extend(path_config.response.update.clone());
+            request_config.merge(&path_config.request);
+            response_config.merge(&path_config.response);
         }
 
-        /* handle header request changes */
-        // TODO - add input mut
-        Self::update_headers(
-            &mut exchange.input_mut().await.unwrap().headers,
-            request_update_headers,
-        );
+        /* placeholders resolve now, for the response too */
+        let request_changes = request_config.changes(exchange);
+        let response_changes = response_config.changes(exchange);
 
-        Self::remove_headers(
-            &mut exchange.input_mut().await.unwrap().headers,
-            request_remove_headers,
-        );
+        /* handle header request changes */
+        let request = exchange.input_mut().await.unwrap();
+        Self::apply_changes(&mut request.headers, &request_changes);
+        Self::remove_headers(&mut request.headers, request_config.remove);
 
         /* handle header response changes */
-        exchange
-            .attachments_mut()
-            .add::<Vec<ModifyHeaderKey>>(
-                REMOVE_RESPONSE_HEADER_ATTACHMENT_KEY,
-                response_remove_headers,
-            );
-        exchange
-            .attachments_mut()
-            .add::<HashMap<ModifyHeaderKey, ModifyHeaderValue>>(
-                UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY,
-                response_update_headers,
-            );
+        let mut attachments = exchange.attachments_mut().scoped_mut(Self::NAME);
+        attachments.add::<Vec<ModifyHeaderKey>>(
+            REMOVE_RESPONSE_HEADER_ATTACHMENT_KEY,
+            response_config.remove,
+        );
+        attachments.add::<Vec<HeaderChange>>(
+            UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY,
+            response_changes,
+        );
 
         exchange.add_output_listener(|response, attachments| {
+            let attachments = attachments.scoped(Self::NAME);
             if let Some(remove_headers) = attachments
                 .get::<Vec<ModifyHeaderKey>>(REMOVE_RESPONSE_HEADER_ATTACHMENT_KEY)
             {
                 Self::remove_headers(&mut response.headers, remove_headers.clone())
             }
 
-            if let Some(update_headers) = attachments
-                .get::<HashMap<ModifyHeaderKey, ModifyHeaderValue>>(
-                    UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY,
-                )
+            if let Some(changes) =
+                attachments.get::<Vec<HeaderChange>>(UPDATE_RESPONSE_HEADER_ATTACHMENT_KEY)
             {
-                Self::update_headers(&mut response.headers, update_headers.clone())
+                Self::apply_changes(&mut response.headers, changes)
             }
         });
 
@@ -151,6 +431,79 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     }
 
     fn name(&self) -> &str {
-        "HeaderHandler"
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::authorizer::add_authorizer_context;
+    use crate::handler::attachments::ScopedAttachmentsExt;
+    use crate::handler::header::{
+        format_time, HeaderHandler, ModifyHeaderHandlerConfig, TimeFormat,
+    };
+    use crate::handler::traceability::CORRELATION_ID_ATTACHMENT_KEY;
+    use crate::handler::LambdaExchange;
+    use idemio::exchange::Exchange;
+    use lambda_http::http::{HeaderMap, HeaderValue};
+    use serde_json::json;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(784_111_777, TimeFormat::Rfc3339), "1994-11-06T08:49:37Z");
+        assert_eq!(format_time(784_111_777, TimeFormat::Http), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format_time(951_782_400, TimeFormat::Rfc3339), "2000-02-29T00:00:00Z");
+        assert_eq!(format_time(0, TimeFormat::Epoch), "0");
+    }
+
+    #[test]
+    fn test_templates_and_operations() {
+        let config: ModifyHeaderHandlerConfig = serde_json::from_value(json!({
+            "update": {
+                "x-correlation-id": "${correlation_id}",
+                "x-user": "user/${claim:sub}",
+                "x-tenant": "${claim:tenant}",
+                "cache-control": {
+                    "value": "no-store",
+                    "when": { "header": "authorization", "contains": "Bearer" }
+                }
+            },
+            "remove": [],
+            "append": { "via": "idem" },
+            "set_if_absent": { "x-stage": "dev", "accept": "application/json" },
+            "rename": { "x-api-version": "api-version" }
+        }))
+        .unwrap();
+        let unknown: Result<ModifyHeaderHandlerConfig, _> = serde_json::from_value(json!({
+            "update": { "x-now": "${now:iso}" }, "remove": []
+        }));
+        assert!(unknown.is_err());
+
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange
+            .attachments_mut()
+            .well_known_mut()
+            .add::<String>(CORRELATION_ID_ATTACHMENT_KEY, "abc".to_string());
+        add_authorizer_context(&mut exchange, "sub", json!("user-1"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("via", HeaderValue::from_static("edge"));
+        headers.insert("accept", HeaderValue::from_static("text/html"));
+        headers.insert("x-api-version", HeaderValue::from_static("2"));
+        HeaderHandler::apply_changes(&mut headers, &config.changes(&exchange));
+        assert_eq!(headers["x-correlation-id"], "abc");
+        assert_eq!(headers["x-user"], "user/user-1");
+        /* no tenant claim, so no header */
+        assert!(!headers.contains_key("x-tenant"));
+        assert!(!headers.contains_key("cache-control"));
+        assert_eq!(headers.get_all("via").iter().count(), 2);
+        assert_eq!(headers["x-stage"], "dev");
+        assert_eq!(headers["accept"], "text/html");
+        assert_eq!(headers["api-version"], "2");
+        assert!(!headers.contains_key("x-api-version"));
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer token"));
+        HeaderHandler::apply_changes(&mut headers, &config.changes(&exchange));
+        assert_eq!(headers["cache-control"], "no-store");
     }
 }
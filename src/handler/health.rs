@@ -1,9 +1,8 @@
 use std::convert::Infallible;
+use std::time::Duration;
 use serde::Deserialize;
 use async_trait::async_trait;
-use aws_sdk_lambda::config::BehaviorVersion;
 use aws_sdk_lambda::primitives::Blob;
-use aws_sdk_lambda::Client as LambdaClient;
 use idemio::config::Config;
 use idemio::exchange::Exchange;
 use idemio::handler::Handler;
@@ -15,24 +14,37 @@ use idemio::status::{ExchangeState, HandlerStatus};
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use lambda_http::http::header::CONTENT_TYPE;
 use lambda_http::Context;
+use crate::flow::builder::NamedHandler;
+use crate::config::registry::ConfigRegistry;
+use crate::config::units::deserialize_duration;
+use crate::handler::lambda::{lambda_client, LambdaClientConfig};
 use crate::handler::LambdaExchange;
 
 #[derive(Deserialize, Default)]
+#[serde(default)]
 pub struct HealthCheckHandlerConfig {
     pub enabled: bool,
     pub use_json: bool,
-    pub timeout: u32,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
     pub downstream_enabled: bool,
     pub downstream_function: String,
     pub downstream_function_health_payload: String,
+    #[serde(flatten)]
+    pub client: LambdaClientConfig,
 }
 const HEALTH_STATUS: u32 = 200u32;
 const HEALTH_BODY: &str = "OK";
 const HEALTH_ERROR: &str = "ERROR";
+const CONFIG_VERSION_HEADER: &str = "x-config-version";
 
 //#[derive(ConfigurableHandler)]
 pub struct HealthCheckHandler {
-    config: Config<HealthCheckHandlerConfig>,
+    pub(crate) config: Config<HealthCheckHandlerConfig>,
+}
+
+impl NamedHandler for HealthCheckHandler {
+    const NAME: &'static str = "HealthCheckHandler";
 }
 
 #[async_trait]
@@ -40,10 +52,6 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
 
     async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible>
     {
-        /* maybe we can grab this from a central location instead of the struct itself? cache? */
-
-        let client =
-            LambdaClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await);
         if !self.config.get().enabled {
             return Ok(HandlerStatus::new(ExchangeState::DISABLED));
         }
@@ -52,7 +60,8 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             let payload =
                 Blob::new(self.config.get().downstream_function_health_payload.clone());
             let function_name = self.config.get().downstream_function.clone();
-            match client
+            match lambda_client(&self.config.get().client)
+                .await
                 .invoke()
                 .function_name(&function_name)
                 .payload(payload)
@@ -69,6 +78,10 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
         response
             .headers
             .insert(CONTENT_TYPE, "plain/text".parse().unwrap());
+        /* lets operators confirm which config snapshot a warm container is running */
+        if let Ok(version) = ConfigRegistry::global().combined_version().parse() {
+            response.headers.insert(CONFIG_VERSION_HEADER, version);
+        }
         if response_status.gt(&200u32) && response_status.lt(&300u32) {
             response.body = Some(HEALTH_BODY.into());
             response.status_code = HEALTH_STATUS as i64
@@ -81,6 +94,6 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     }
 
     fn name(&self) -> &str {
-        "HealthCheckHandler"
+        Self::NAME
     }
 }
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::header::{CONNECTION, CONTENT_LENGTH, HOST};
+use lambda_http::http::HeaderMap;
+use lambda_http::Body;
+use reqwest::{Certificate, Client, Identity};
+use serde::Deserialize;
+use crate::config::units::deserialize_optional_duration;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendType {
+    Http,
+}
+
+/// How the gateway connects to an HTTP backend. Certificates and keys are PEM files; a client
+/// certificate and key together enable mutual TLS.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct HttpClientConfig {
+    /* trusted on top of the system roots, e.g. a private CA */
+    pub ca_certificate: Option<String>,
+    pub client_certificate: Option<String>,
+    pub client_key: Option<String>,
+    /* for test backends only */
+    pub accept_invalid_certificates: bool,
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub connect_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub pool_idle_timeout: Option<Duration>,
+}
+
+/// A private HTTP endpoint the proxy forwards requests to instead of invoking a function, like a
+/// service in the gateway's VPC. The request path and query are appended to `url`:
+///
+/// ```json
+/// { "type": "http", "url": "https://orders.internal:8443/api", "timeout": "3s",
+///   "ca_certificate": "/opt/certs/ca.pem", "client_certificate": "/opt/certs/gateway.pem",
+///   "client_key": "/opt/certs/gateway.key" }
+/// ```
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct HttpTarget {
+    #[serde(rename = "type")]
+    pub backend_type: BackendType,
+    pub url: String,
+    /* overrides the handler's timeout */
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub timeout: Option<Duration>,
+    /* overrides the handler's max_concurrency */
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    #[serde(flatten)]
+    pub client: HttpClientConfig,
+}
+
+#[derive(Debug)]
+pub enum HttpBackendError {
+    Timeout,
+    Failed(String),
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn build_client(config: &HttpClientConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(config.accept_invalid_certificates);
+    if let Some(path) = &config.ca_certificate {
+        let certificate = Certificate::from_pem(&read_pem(path)?).map_err(|e| e.to_string())?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    match (&config.client_certificate, &config.client_key) {
+        (Some(certificate), Some(key)) => {
+            let mut pem = read_pem(certificate)?;
+            pem.push(b'\n');
+            pem.extend(read_pem(key)?);
+            builder = builder.identity(Identity::from_pem(&pem).map_err(|e| e.to_string())?);
+        }
+        (None, None) => {}
+        _ => return Err("client_certificate and client_key go together".to_string()),
+    }
+    if let Some(timeout) = config.connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(idle_timeout) = config.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(idle_timeout);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// The client for the config, shared by every target with the same one so their connections are
+/// pooled together. It is built on first use and kept for the lifetime of the container.
+pub(crate) fn http_client(config: &HttpClientConfig) -> Result<Client, String> {
+    static CLIENTS: OnceLock<Mutex<HashMap<HttpClientConfig, Client>>> = OnceLock::new();
+    let mut clients = CLIENTS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(client) = clients.get(config) {
+        return Ok(client.clone());
+    }
+    let client = build_client(config)?;
+    clients.insert(config.clone(), client.clone());
+    Ok(client)
+}
+
+/* headers that only concern one connection, which a proxy doesn't pass on (RFC 9110 7.6.1) */
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/* removes the hop-by-hop headers, along with the ones the `Connection` header names */
+fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    let named: Vec<String> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    for name in HOP_BY_HOP_HEADERS.into_iter().chain(named.iter().map(String::as_str)) {
+        headers.remove(name);
+    }
+}
+
+/* every value of the request's headers: the multi-value ones, unless `headers` holds values for
+the name they don't have, like one a handler set */
+fn merged_headers(request: &ApiGatewayProxyRequest) -> HeaderMap {
+    let mut headers = request.multi_value_headers.clone();
+    for name in request.headers.keys() {
+        let values = request.headers.get_all(name);
+        if values.iter().all(|value| headers.get_all(name).iter().any(|v| v == value)) {
+            continue;
+        }
+        headers.remove(name);
+        for value in values {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+    headers
+}
+
+/* `url` with the request path appended, without doubling the slash between them */
+fn target_url(url: &str, path: &str) -> String {
+    format!("{}/{}", url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+impl HttpTarget {
+    /// Sends the request on to the backend and returns its answer as a proxy response. Headers
+    /// keep all their values; the hop-by-hop ones are dropped both ways.
+    pub async fn forward(
+        &self,
+        request: &ApiGatewayProxyRequest,
+        path: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ApiGatewayProxyResponse, HttpBackendError> {
+        let client = http_client(&self.client).map_err(HttpBackendError::Failed)?;
+        let mut headers = merged_headers(request);
+        remove_hop_by_hop(&mut headers);
+        headers.remove(HOST);
+        headers.remove(CONTENT_LENGTH);
+        let query = match request.multi_value_query_string_parameters.is_empty() {
+            true => &request.query_string_parameters,
+            false => &request.multi_value_query_string_parameters,
+        };
+        let query: Vec<(&str, &str)> = query.iter().collect();
+        let body = match &request.body {
+            None => vec![],
+            Some(body) if request.is_base64_encoded => STANDARD
+                .decode(body)
+                .map_err(|e| HttpBackendError::Failed(e.to_string()))?,
+            Some(body) => body.as_bytes().to_vec(),
+        };
+        let mut outgoing = client
+            .request(request.http_method.clone(), target_url(&self.url, path))
+            .headers(headers)
+            .query(&query)
+            .body(body);
+        if let Some(timeout) = self.timeout.or(timeout) {
+            outgoing = outgoing.timeout(timeout);
+        }
+
+        let response = outgoing.send().await.map_err(|e| match e.is_timeout() {
+            true => HttpBackendError::Timeout,
+            false => HttpBackendError::Failed(e.to_string()),
+        })?;
+        let status_code = response.status().as_u16() as i64;
+        let mut headers = response.headers().clone();
+        remove_hop_by_hop(&mut headers);
+        let bytes = response.bytes().await.map_err(|e| match e.is_timeout() {
+            true => HttpBackendError::Timeout,
+            false => HttpBackendError::Failed(e.to_string()),
+        })?;
+        let (body, is_base64_encoded) = match String::from_utf8(bytes.to_vec()) {
+            Ok(text) if text.is_empty() => (None, false),
+            Ok(text) => (Some(Body::Text(text)), false),
+            Err(e) => (Some(Body::Binary(e.into_bytes())), true),
+        };
+        Ok(ApiGatewayProxyResponse {
+            status_code,
+            multi_value_headers: headers.clone(),
+            headers,
+            body,
+            is_base64_encoded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::http_backend::{merged_headers, remove_hop_by_hop, target_url, HttpTarget};
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use lambda_http::http::{HeaderMap, HeaderValue};
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_target() {
+        let target: HttpTarget = serde_json::from_str(
+            r#"{ "type": "http", "url": "https://orders.internal/api/", "timeout": "2s",
+                 "client_certificate": "gateway.pem", "client_key": "gateway.key" }"#,
+        )
+        .unwrap();
+        assert_eq!(target.timeout, Some(Duration::from_secs(2)));
+        assert_eq!(target.client.client_key.as_deref(), Some("gateway.key"));
+        assert_eq!(
+            target_url(&target.url, "/orders/7"),
+            "https://orders.internal/api/orders/7"
+        );
+        assert!(serde_json::from_str::<HttpTarget>(r#"{ "type": "lambda", "url": "x" }"#).is_err());
+    }
+
+    #[test]
+    fn test_forwarded_headers() {
+        let mut request = ApiGatewayProxyRequest::default();
+        for value in ["a=1", "b=2"] {
+            request.multi_value_headers.append("cookie", HeaderValue::from_static(value));
+        }
+        request.multi_value_headers.insert("x-user", HeaderValue::from_static("spoofed"));
+        request.headers.insert("cookie", HeaderValue::from_static("b=2"));
+        request.headers.insert("x-user", HeaderValue::from_static("verified"));
+        request.headers.insert("x-scope", HeaderValue::from_static("read"));
+        let headers = merged_headers(&request);
+        assert_eq!(headers.get_all("cookie").iter().count(), 2);
+        assert_eq!(headers["x-user"], "verified");
+        assert_eq!(headers["x-scope"], "read");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", HeaderValue::from_static("keep-alive, X-Session"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("transfer-encoding", HeaderValue::from_static("chunked"));
+        headers.insert("x-session", HeaderValue::from_static("1"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        remove_hop_by_hop(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["content-type"], "application/json");
+    }
+}
@@ -0,0 +1,226 @@
+use crate::error::{ERR10008_ADDRESS_FORBIDDEN, ERR13000_INTERNAL};
+use crate::flow::builder::NamedHandler;
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// An address or a CIDR range of them, like `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value.as_str(), None),
+        };
+        let network: IpAddr = address
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid address in '{}'", value))?;
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(|| format!("Invalid prefix length in '{}'", value))?,
+            None => bits,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+/* IPv4 clients reaching a dual-stack endpoint show up as IPv4-mapped IPv6 addresses */
+fn canonical(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+impl IpRange {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (canonical(self.network), canonical(address)) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which callers may reach the routes. A caller in a `deny` range is rejected; with a
+/// non-empty `allow` list, so is every caller outside it:
+///
+/// ```json
+/// { "enabled": true, "allow": ["10.0.0.0/8"], "deny": ["10.0.66.0/24"],
+///   "path_prefix_ip_config": { "/public": { "allow": [] } } }
+/// ```
+///
+/// The caller is API Gateway's source IP. Behind proxies like CloudFront, `trusted_proxy_hops`
+/// is the number of them, and the caller is taken from `X-Forwarded-For` instead, skipping the
+/// addresses the trusted proxies added.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct IpFilterHandlerConfig {
+    pub enabled: bool,
+    pub allow: Vec<IpRange>,
+    pub deny: Vec<IpRange>,
+    pub trusted_proxy_hops: usize,
+    pub path_prefix_ip_config: HashMap<String, IpFilterPathConfig>,
+}
+
+/// Lists replacing the handler's for the paths under one prefix. The longest matching prefix
+/// applies.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct IpFilterPathConfig {
+    pub allow: Option<Vec<IpRange>>,
+    pub deny: Option<Vec<IpRange>>,
+}
+
+impl IpFilterHandlerConfig {
+    fn caller(&self, request: &ApiGatewayProxyRequest) -> Option<IpAddr> {
+        if self.trusted_proxy_hops == 0 {
+            return request.request_context.identity.source_ip.as_deref()?.parse().ok();
+        }
+        /* every proxy appends the address it got the request from */
+        let forwarded_for: Vec<&str> = request
+            .headers
+            .get_all(FORWARDED_FOR_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        let index = forwarded_for.len().checked_sub(self.trusted_proxy_hops + 1)?;
+        forwarded_for[index].parse().ok()
+    }
+
+    /* whether the caller may reach the path */
+    fn admits(&self, path: &str, caller: IpAddr) -> bool {
+        let path_config = self
+            .path_prefix_ip_config
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, path_config)| path_config);
+        let allow = path_config.and_then(|config| config.allow.as_ref()).unwrap_or(&self.allow);
+        let deny = path_config.and_then(|config| config.deny.as_ref()).unwrap_or(&self.deny);
+        if deny.iter().any(|range| range.contains(caller)) {
+            return false;
+        }
+        allow.is_empty() || allow.iter().any(|range| range.contains(caller))
+    }
+}
+
+pub struct IpFilterHandler {
+    pub(crate) config: Config<IpFilterHandlerConfig>,
+}
+
+impl NamedHandler for IpFilterHandler {
+    const NAME: &'static str = "IpFilterHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for IpFilterHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let (caller, admitted) = match exchange.input().await {
+            Ok(request) => {
+                let caller = config.caller(request);
+                let path = request.path.as_deref().unwrap_or("/");
+                (caller, caller.is_some_and(|caller| config.admits(path, caller)))
+            }
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+        if admitted {
+            return Ok(HandlerStatus::new(ExchangeState::OK));
+        }
+        let error = ERR10008_ADDRESS_FORBIDDEN.error();
+        let error = match caller {
+            Some(caller) => error.metadata("address", caller.to_string()),
+            None => error.detail("Caller address is unknown"),
+        };
+        Ok(error.raise(exchange))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::ip_filter::{IpFilterHandlerConfig, IpRange};
+    use crate::testkit::RequestBuilder;
+    use std::net::IpAddr;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ip_ranges() {
+        let range = IpRange::try_from("10.1.0.0/16".to_string()).unwrap();
+        assert!(range.contains(ip("10.1.200.3")));
+        assert!(!range.contains(ip("10.2.0.1")));
+        assert!(range.contains(ip("::ffff:10.1.0.9")));
+        let range = IpRange::try_from("2001:db8::/32".to_string()).unwrap();
+        assert!(range.contains(ip("2001:db8:1::1")));
+        assert!(IpRange::try_from("0.0.0.0/0".to_string()).unwrap().contains(ip("8.8.8.8")));
+        assert!(IpRange::try_from("10.0.0.0/33".to_string()).is_err());
+        assert!(IpRange::try_from("example.com".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let config: IpFilterHandlerConfig = serde_json::from_str(
+            r#"{ "enabled": true, "allow": ["10.0.0.0/8"], "deny": ["10.0.66.0/24"],
+                 "trusted_proxy_hops": 1,
+                 "path_prefix_ip_config": { "/public": { "allow": [] } } }"#,
+        )
+        .unwrap();
+        assert!(config.admits("/orders", ip("10.4.0.1")));
+        assert!(!config.admits("/orders", ip("10.0.66.7")));
+        assert!(!config.admits("/orders", ip("192.0.2.1")));
+        assert!(config.admits("/public/docs", ip("192.0.2.1")));
+        assert!(!config.admits("/public/docs", ip("10.0.66.7")));
+
+        let request = RequestBuilder::get("/orders")
+            .header("x-forwarded-for", "198.51.100.7, 10.4.0.1, 130.176.0.5")
+            .build();
+        assert_eq!(config.caller(&request), Some(ip("10.4.0.1")));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::config::secrets::{fetch_secrets, SecretRef};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+/* how long a secret read from Secrets Manager is used before it is read again */
+const SECRET_TTL: Duration = Duration::from_secs(300);
+
+/// The shared secret HS256, HS384 and HS512 tokens are signed with: the secret itself, or a
+/// Secrets Manager reference like `{"secretsManager": "arn:...", "jsonKey": "jwt"}`. Referenced
+/// secrets are read when first needed and again every few minutes, so rotations reach running
+/// containers.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum HmacSecret {
+    Secret(SecretRef),
+    Value(String),
+}
+
+impl HmacSecret {
+    pub async fn decoding_key(&self) -> Result<DecodingKey, String> {
+        let secret_ref = match self {
+            HmacSecret::Value(secret) => return Ok(DecodingKey::from_secret(secret.as_bytes())),
+            HmacSecret::Secret(secret_ref) => secret_ref,
+        };
+        static SECRETS: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+        let secrets = SECRETS.get_or_init(Mutex::default);
+        let now = Instant::now();
+        let cached = secrets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&secret_ref.secret_id)
+            .filter(|(_, expires)| *expires > now)
+            .map(|(secret_string, _)| secret_string.clone());
+        let secret_string = match cached {
+            Some(secret_string) => secret_string,
+            None => {
+                let secret_string = fetch_secrets(BTreeSet::from([secret_ref.secret_id.clone()]))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .remove(&secret_ref.secret_id)
+                    .ok_or_else(|| format!("Secret {} was not loaded", secret_ref.secret_id))?;
+                secrets.lock().unwrap_or_else(PoisonError::into_inner).insert(
+                    secret_ref.secret_id.clone(),
+                    (secret_string.clone(), now + SECRET_TTL),
+                );
+                secret_string
+            }
+        };
+        match secret_ref.resolve(&secret_string).map_err(|e| e.to_string())? {
+            Value::String(secret) => Ok(DecodingKey::from_secret(secret.as_bytes())),
+            _ => Err(format!("Secret {} is not a string", secret_ref.secret_id)),
+        }
+    }
+}
+
+pub fn is_hmac(algorithm: Algorithm) -> bool {
+    matches!(algorithm, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+/// The key to check a token signed with `algorithm` against, when the JWK is one for it.
+pub fn jwk_decoding_key(jwk: &Jwk, algorithm: Algorithm) -> Result<DecodingKey, &'static str> {
+    /* the key set may pin each key to one algorithm */
+    if jwk
+        .common
+        .key_algorithm
+        .is_some_and(|key_algorithm| {
+            Algorithm::from_str(&key_algorithm.to_string()).ok() != Some(algorithm)
+        })
+    {
+        return Err("JWK is for another algorithm");
+    }
+    let key = match (&jwk.algorithm, algorithm) {
+        (
+            AlgorithmParameters::RSA(rsa),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512,
+        ) => DecodingKey::from_rsa_components(&rsa.n, &rsa.e),
+        (AlgorithmParameters::EllipticCurve(ec), Algorithm::ES256)
+            if ec.curve == EllipticCurve::P256 =>
+        {
+            DecodingKey::from_ec_components(&ec.x, &ec.y)
+        }
+        (AlgorithmParameters::EllipticCurve(ec), Algorithm::ES384)
+            if ec.curve == EllipticCurve::P384 =>
+        {
+            DecodingKey::from_ec_components(&ec.x, &ec.y)
+        }
+        (AlgorithmParameters::OctetKeyPair(okp), Algorithm::EdDSA)
+            if okp.curve == EllipticCurve::Ed25519 =>
+        {
+            DecodingKey::from_ed_components(&okp.x)
+        }
+        /* a published key set is no place for a shared secret */
+        (AlgorithmParameters::OctetKey(_), _) => return Err("Symmetric JWKs are not accepted"),
+        _ => return Err("JWK doesn't match the token algorithm"),
+    };
+    key.map_err(|_| "Malformed JWK")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::jwt::keys::{jwk_decoding_key, HmacSecret};
+    use jsonwebtoken::jwk::Jwk;
+    use jsonwebtoken::{decode, encode, Algorithm, EncodingKey, Header, Validation};
+    use serde_json::{json, Value};
+
+    fn jwk(value: Value) -> Jwk {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_jwk_decoding_keys() {
+        let ec = jwk(json!({
+            "kty": "EC", "crv": "P-256",
+            "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"
+        }));
+        assert!(jwk_decoding_key(&ec, Algorithm::ES256).is_ok());
+        assert!(jwk_decoding_key(&ec, Algorithm::ES384).is_err());
+        assert!(jwk_decoding_key(&ec, Algorithm::RS256).is_err());
+
+        let okp = jwk(json!({
+            "kty": "OKP", "crv": "Ed25519", "alg": "EdDSA",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        }));
+        assert!(jwk_decoding_key(&okp, Algorithm::EdDSA).is_ok());
+
+        let pinned = jwk(json!({ "kty": "RSA", "alg": "RS384", "n": "AQAB", "e": "AQAB" }));
+        let rejected = jwk_decoding_key(&pinned, Algorithm::RS256).err();
+        assert_eq!(rejected, Some("JWK is for another algorithm"));
+        assert!(jwk_decoding_key(&pinned, Algorithm::RS384).is_ok());
+        let encryption = jwk(json!({ "kty": "RSA", "alg": "RSA-OAEP", "n": "AQAB", "e": "AQAB" }));
+        assert!(jwk_decoding_key(&encryption, Algorithm::RS256).is_err());
+
+        let octet = jwk(json!({ "kty": "oct", "k": "c2VjcmV0" }));
+        let rejected = jwk_decoding_key(&octet, Algorithm::HS256).err();
+        assert_eq!(rejected, Some("Symmetric JWKs are not accepted"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_hmac_secret() {
+        let secret: HmacSecret = serde_json::from_value(json!("s3cr3t")).unwrap();
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &json!({ "sub": "user123", "exp": 2000000000 }),
+            &EncodingKey::from_secret(b"s3cr3t"),
+        )
+        .unwrap();
+        let key = secret.decoding_key().await.unwrap();
+        assert!(decode::<Value>(&token, &key, &Validation::new(Algorithm::HS256)).is_ok());
+
+        let secret: HmacSecret =
+            serde_json::from_value(json!({ "secretsManager": "arn:jwt", "jsonKey": "key" }))
+                .unwrap();
+        assert!(matches!(secret, HmacSecret::Secret(_)));
+    }
+}
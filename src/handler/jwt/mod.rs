@@ -0,0 +1,1296 @@
+use std::convert::Infallible;
+use crate::audit::{record_audit_event, AuditEvent, AuditOutcome};
+use crate::config::units::deserialize_duration;
+use crate::flow::builder::NamedHandler;
+use crate::ROOT_CONFIG_PATH;
+use crate::error::{
+    handler_error, HandlerError, ERR10000_MISSING_TOKEN, ERR10001_INVALID_TOKEN,
+    ERR10002_INSUFFICIENT_SCOPE, ERR11000_INVALID_REQUEST, ERR13000_INTERNAL,
+    ERR13001_KEYS_UNAVAILABLE,
+};
+use crate::executor::authorizer::{add_authorizer_context, set_principal};
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::jwt::keys::{is_hmac, jwk_decoding_key, HmacSecret};
+use crate::handler::jwt::remote::RemoteJwkProvider;
+use crate::handler::LambdaExchange;
+use crate::metrics::{self, names};
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::exchange::Exchange;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::dangerous::insecure_decode;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation, decode};
+use lambda_http::Context;
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::{HeaderName, HeaderValue};
+use oasert::validator::{OpenApiPayloadValidator};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/* shared with other handlers, in the well-known namespace */
+const JWT_CLAIMS_ATTACHMENT_KEY: &str = "jwt_claims";
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct JwtValidationHandlerConfig {
+    pub enabled: bool,
+    pub jwk_provider: JwkProviders,
+    pub scope_verification: bool,
+    pub specification_name: String,
+    pub ignore_jwt_expiration: bool,
+    /// A single accepted audience, kept for older configs.
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// Accepted `aud` values on top of `audience`. When neither is set, any audience is.
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    /// Accepted `iss` values; any issuer is accepted when empty.
+    #[serde(default)]
+    pub issuers: Vec<String>,
+    /// Algorithms tokens may be signed with, like `["RS256", "ES256"]`. They are checked before
+    /// any key is looked up, so a token can't choose how its own signature is verified.
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+    /// The shared secret of HS256, HS384 and HS512 tokens.
+    #[serde(default)]
+    pub hmac_secret: Option<HmacSecret>,
+    /// Clock skew allowed when checking `exp` and `nbf`.
+    #[serde(default = "default_leeway", deserialize_with = "deserialize_duration")]
+    pub leeway: Duration,
+    /// Request headers set from the validated claims, keyed by claim, like
+    /// `{ "sub": "X-User-Id", "scope": "X-Scopes", "org.id": "X-Org-Id" }`. Nested claims are
+    /// reached by dotted path, and array claims are joined with spaces. Incoming headers of the
+    /// same names are always removed, single- and multi-value alike, so callers can't set them
+    /// themselves.
+    #[serde(default)]
+    pub claim_headers: BTreeMap<String, String>,
+    /// Issuers with their own keys, audiences and scope rules, picked by the token's `iss`.
+    /// When any are set, tokens from other issuers are rejected, and `jwk_provider`, `audience`,
+    /// `audiences`, `issuers`, `algorithms` and `hmac_secret` above are not used.
+    #[serde(default)]
+    pub trusted_issuers: Vec<JwtIssuerConfig>,
+}
+
+/// An issuer tokens are accepted from, for when they come from more than one identity provider,
+/// like Cognito and a partner's IdP:
+///
+/// ```json
+/// { "issuer": "https://cognito-idp.us-east-1.amazonaws.com/us-east-1_abc123",
+///   "jwk_provider": { "RemoteJwkProvider": { "jwk_server_url": "https://cognito-idp...",
+///     "jwk_server_path": "/us-east-1_abc123/.well-known/jwks.json" } },
+///   "audiences": ["orders"], "algorithms": ["RS256"] }
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct JwtIssuerConfig {
+    /// The `iss` of the issuer's tokens, matched exactly.
+    pub issuer: String,
+    pub jwk_provider: JwkProviders,
+    /// Accepted `aud` values; any audience is accepted when empty.
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+    #[serde(default)]
+    pub hmac_secret: Option<HmacSecret>,
+    /// Overrides the handler's `scope_verification` for this issuer's tokens.
+    #[serde(default)]
+    pub scope_verification: Option<bool>,
+    /// Overrides the handler's `specification_name` for this issuer's tokens.
+    #[serde(default)]
+    pub specification_name: Option<String>,
+}
+
+impl Default for JwtValidationHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            jwk_provider: JwkProviders::default(),
+            scope_verification: false,
+            ignore_jwt_expiration: false,
+            specification_name: "openapi.yaml".to_string(),
+            audience: None,
+            audiences: vec![],
+            issuers: vec![],
+            algorithms: default_algorithms(),
+            hmac_secret: None,
+            leeway: default_leeway(),
+            claim_headers: BTreeMap::new(),
+            trusted_issuers: vec![],
+        }
+    }
+}
+
+fn default_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::RS256]
+}
+
+fn default_leeway() -> Duration {
+    Duration::from_secs(60)
+}
+
+/* the claim by name, or by dotted path into nested claims */
+fn claim_header_value(claims: &Value, claim: &str) -> Option<String> {
+    let value = match claims.get(claim) {
+        Some(value) => value,
+        None => claim.split('.').try_fold(claims, |value, name| value.get(name))?,
+    };
+    let text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match value {
+        Value::Null => None,
+        Value::Array(items) => Some(items.iter().map(text).collect::<Vec<_>>().join(" ")),
+        other => Some(text(other)),
+    }
+}
+
+impl JwtValidationHandlerConfig {
+    /* the keys and rules of the token's issuer */
+    fn rules(&self, claims: &Value) -> Result<IssuerRules<'_>, &'static str> {
+        if self.trusted_issuers.is_empty() {
+            self.validate_iss(claims)?;
+            return Ok(IssuerRules {
+                jwk_provider: &self.jwk_provider,
+                audiences: self
+                    .audience
+                    .iter()
+                    .chain(&self.audiences)
+                    .map(String::as_str)
+                    .filter(|audience| !audience.is_empty())
+                    .collect(),
+                algorithms: &self.algorithms,
+                hmac_secret: self.hmac_secret.as_ref(),
+                scope_verification: self.scope_verification,
+                specification_name: &self.specification_name,
+            });
+        }
+        let issuer = match claims.get("iss").map(Value::as_str) {
+            None => return Err("Token has no issuer"),
+            Some(None) => return Err("Token issuer is malformed"),
+            Some(Some(issuer)) => self
+                .trusted_issuers
+                .iter()
+                .find(|trusted| trusted.issuer == issuer)
+                .ok_or("Token issuer is not accepted")?,
+        };
+        Ok(IssuerRules {
+            jwk_provider: &issuer.jwk_provider,
+            audiences: issuer.audiences.iter().map(String::as_str).collect(),
+            algorithms: &issuer.algorithms,
+            hmac_secret: issuer.hmac_secret.as_ref(),
+            scope_verification: issuer.scope_verification.unwrap_or(self.scope_verification),
+            specification_name: issuer
+                .specification_name
+                .as_deref()
+                .unwrap_or(&self.specification_name),
+        })
+    }
+
+    fn validate_iss(&self, claims: &Value) -> Result<(), &'static str> {
+        if self.issuers.is_empty() {
+            return Ok(());
+        }
+        match claims.get("iss").map(Value::as_str) {
+            None => Err("Token has no issuer"),
+            Some(None) => Err("Token issuer is malformed"),
+            Some(Some(issuer)) if self.issuers.iter().any(|accepted| accepted == issuer) => Ok(()),
+            Some(Some(_)) => Err("Token issuer is not accepted"),
+        }
+    }
+
+    /* `exp` and `nbf`, against `now` in seconds since the epoch */
+    fn validate_exp(&self, claims: &Value, now: u64) -> Result<(), &'static str> {
+        let leeway = self.leeway.as_secs();
+        /* whole, non-negative seconds; anything else is malformed rather than rounded */
+        let time = |name: &str| claims.get(name).map(Value::as_u64);
+        match time("nbf") {
+            Some(None) => return Err("Token not-before time is malformed"),
+            Some(Some(not_before)) if not_before > now.saturating_add(leeway) => {
+                return Err("Token is not valid yet");
+            }
+            _ => {}
+        }
+        if self.ignore_jwt_expiration {
+            return Ok(());
+        }
+        match time("exp") {
+            None => Err("Token has no expiration time"),
+            Some(None) => Err("Token expiration time is malformed"),
+            Some(Some(expires)) if expires.saturating_add(leeway) < now => Err("Token has expired"),
+            Some(Some(_)) => Ok(()),
+        }
+    }
+
+    fn forward_claims(&self, request: &mut ApiGatewayProxyRequest, claims: &Value) {
+        for (claim, header) in &self.claim_headers {
+            let Ok(header) = HeaderName::try_from(header.as_str()) else {
+                tracing::warn!("Invalid header name {} for claim {}", header, claim);
+                continue;
+            };
+            /* backends may read either map, so a spoofed value must not survive in one of them */
+            request.headers.remove(&header);
+            request.multi_value_headers.remove(&header);
+            if let Some(value) = claim_header_value(claims, claim)
+                .and_then(|value| HeaderValue::from_str(&value).ok())
+            {
+                request.multi_value_headers.insert(header.clone(), value.clone());
+                request.headers.insert(header, value);
+            }
+        }
+    }
+}
+
+/* the keys and rules a token is checked against, the handler's own or its issuer's */
+struct IssuerRules<'a> {
+    jwk_provider: &'a JwkProviders,
+    audiences: Vec<&'a str>,
+    algorithms: &'a [Algorithm],
+    hmac_secret: Option<&'a HmacSecret>,
+    scope_verification: bool,
+    specification_name: &'a str,
+}
+
+impl IssuerRules<'_> {
+    fn validate_aud(&self, claims: &Value) -> Result<(), &'static str> {
+        if self.audiences.is_empty() {
+            return Ok(());
+        }
+        /* a single audience or a list of them */
+        let audiences: Vec<&str> = match claims.get("aud") {
+            None => return Err("Token has no audience"),
+            Some(Value::String(audience)) => vec![audience.as_str()],
+            Some(Value::Array(audiences)) => audiences.iter().filter_map(Value::as_str).collect(),
+            Some(_) => return Err("Token audience is malformed"),
+        };
+        if !audiences.iter().any(|audience| self.audiences.contains(audience)) {
+            return Err("Token audience is not accepted");
+        }
+        Ok(())
+    }
+
+    /* the key for the token's algorithm, a shared secret or one from the key set */
+    async fn decoding_key(&self, header: &Header) -> Result<DecodingKey, HandlerError> {
+        if !self.algorithms.contains(&header.alg) {
+            return Err(ERR10001_INVALID_TOKEN
+                .error()
+                .detail(format!("Algorithm {:?} is not allowed", header.alg)));
+        }
+        if is_hmac(header.alg) {
+            let Some(secret) = self.hmac_secret else {
+                return Err(ERR13001_KEYS_UNAVAILABLE.error().detail("No HMAC secret configured"));
+            };
+            return secret.decoding_key().await.map_err(|e| {
+                ERR13001_KEYS_UNAVAILABLE
+                    .error()
+                    .detail(format!("Unable to read HMAC secret: {}", e))
+            });
+        }
+
+        let Some(kid) = &header.kid else {
+            return Err(ERR10001_INVALID_TOKEN.error().detail("JWT is missing kid"));
+        };
+        let mut jwk_set = self.jwk_provider.jwk().await.map_err(|_| {
+            ERR13001_KEYS_UNAVAILABLE
+                .error()
+                .detail("Unable to fetch JWKs")
+        })?;
+        /* the issuer may have rotated its keys since they were cached */
+        if jwk_set.find(kid).is_none() {
+            jwk_set = self.jwk_provider.refresh().await.unwrap_or(jwk_set);
+        }
+        let Some(jwk) = jwk_set.find(kid) else {
+            return Err(ERR10001_INVALID_TOKEN.error().detail("No matching JWK for kid"));
+        };
+        jwk_decoding_key(jwk, header.alg)
+            .map_err(|reason| ERR10001_INVALID_TOKEN.error().detail(reason))
+    }
+}
+
+/// The claims of the token the [`JwtValidationHandler`] validated.
+pub fn jwt_claims(exchange: &LambdaExchange) -> Option<&Value> {
+    exchange
+        .attachments()
+        .well_known()
+        .get::<Value>(JWT_CLAIMS_ATTACHMENT_KEY)
+}
+
+pub mod keys;
+pub mod remote;
+
+#[async_trait]
+pub trait JwkProvider {
+    async fn jwk(&self) -> Result<JwkSet, ()>;
+
+    /// The keys again, bypassing any cache, after a token named a key the last ones lacked.
+    async fn refresh(&self) -> Result<JwkSet, ()> {
+        self.jwk().await
+    }
+}
+
+/// Reads an issuer's keys from a JWKS file, like `/opt/config/jwks.json`, once per container.
+#[derive(Deserialize, Default, Debug)]
+pub struct LocalJwkProvider {
+    file_name: String,
+    file_path: String,
+}
+
+/* key sets by file path */
+fn local_key_sets() -> &'static Mutex<HashMap<String, JwkSet>> {
+    static KEY_SETS: OnceLock<Mutex<HashMap<String, JwkSet>>> = OnceLock::new();
+    KEY_SETS.get_or_init(Mutex::default)
+}
+
+#[async_trait]
+impl JwkProvider for LocalJwkProvider {
+    async fn jwk(&self) -> Result<JwkSet, ()> {
+        let path = format!("{}/{}", self.file_path.trim_end_matches('/'), self.file_name);
+        let mut key_sets = local_key_sets().lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(keys) = key_sets.get(&path) {
+            return Ok(keys.clone());
+        }
+        let keys: JwkSet = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| serde_json::from_str(&file).map_err(|e| e.to_string()))
+            .map_err(|e| tracing::error!("Unable to read JWKs from {}: {}", path, e))?;
+        key_sets.insert(path, keys.clone());
+        Ok(keys)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub enum JwkProviders {
+    RemoteJwkProvider(RemoteJwkProvider),
+    LocalJwkProvider(LocalJwkProvider),
+}
+
+impl Default for JwkProviders {
+    fn default() -> Self {
+        Self::LocalJwkProvider(LocalJwkProvider {
+            file_name: String::from("jwks.json"),
+            file_path: String::from("./config"),
+        })
+    }
+}
+
+#[async_trait]
+impl JwkProvider for JwkProviders {
+    async fn jwk(&self) -> Result<JwkSet, ()> {
+        match self {
+            JwkProviders::LocalJwkProvider(local) => local.jwk().await,
+
+            JwkProviders::RemoteJwkProvider(remote) => remote.jwk().await,
+        }
+    }
+
+    async fn refresh(&self) -> Result<JwkSet, ()> {
+        match self {
+            JwkProviders::LocalJwkProvider(local) => local.refresh().await,
+            JwkProviders::RemoteJwkProvider(remote) => remote.refresh().await,
+        }
+    }
+}
+
+//#[derive(ConfigurableHandler)]
+pub struct JwtValidationHandler {
+    pub(crate) config: Config<JwtValidationHandlerConfig>,
+    /* the specifications scopes are checked against, by file name, read when it's built */
+    specifications: HashMap<String, Result<OpenApiPayloadValidator, String>>,
+}
+
+impl JwtValidationHandler {
+    pub fn new(config: Config<JwtValidationHandlerConfig>) -> Self {
+        let settings = config.get();
+        let mut names = vec![];
+        if settings.trusted_issuers.is_empty() && settings.scope_verification {
+            names.push(settings.specification_name.as_str());
+        }
+        for issuer in &settings.trusted_issuers {
+            if issuer.scope_verification.unwrap_or(settings.scope_verification) {
+                let name = issuer.specification_name.as_ref();
+                names.push(name.unwrap_or(&settings.specification_name).as_str());
+            }
+        }
+        let specifications = names
+            .into_iter()
+            .map(|name| {
+                let specification = Self::load_specification(name);
+                if let Err(e) = &specification {
+                    tracing::error!("Unable to load specification {}: {}", name, e);
+                }
+                (name.to_string(), specification)
+            })
+            .collect();
+        Self {
+            config,
+            specifications,
+        }
+    }
+
+    fn load_specification(name: &str) -> Result<OpenApiPayloadValidator, String> {
+        let spec = std::fs::read_to_string(format!("{}/{}", ROOT_CONFIG_PATH, name))
+            .map_err(|e| e.to_string())?;
+        let spec = serde_json::from_str(&spec).map_err(|e| e.to_string())?;
+        OpenApiPayloadValidator::new(spec).map_err(|e| format!("{:?}", e))
+    }
+
+    #[cfg(test)]
+    fn validate_scope(spec: Value, request_path: &str, method: &str, claims: &Value) -> Result<(), ()> {
+        let validator = OpenApiPayloadValidator::new(spec).map_err(|_| ())?;
+        Self::check_scopes(&validator, request_path, method, claims)
+    }
+
+    fn check_scopes(
+        validator: &OpenApiPayloadValidator,
+        request_path: &str,
+        method: &str,
+        claims: &Value,
+    ) -> Result<(), ()> {
+        let token_scopes = match claims.get("scope") {
+            None => return Err(()),
+            Some(scope) => {
+                if let Some(scope) = scope.as_str() {
+                    scope.split(' ').map(String::from).collect::<Vec<String>>()
+                } else {
+                    return Err(());
+                }
+            }
+        };
+
+        let operation = match validator.traverser().get_operation_from_path_and_method(request_path, method) {
+            Ok(x) => x,
+            Err(_) => return Err(()),
+        };
+        if validator.validate_request_scopes(&operation, &token_scopes).is_err() {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    async fn authenticate(
+        &self,
+        exchange: &mut LambdaExchange,
+    ) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let request = match exchange.input().await {
+            Ok(req) => req,
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+
+        if let Some((_, auth_header_value)) = &request
+            .headers
+            .iter()
+            .find(|(header_key, _)| header_key.to_string().to_lowercase() == "authorization")
+        {
+            let Ok(auth_header_value) = auth_header_value.to_str() else {
+                return Ok(ERR10001_INVALID_TOKEN
+                    .error()
+                    .detail("Authorization header is not visible ASCII")
+                    .raise(exchange));
+            };
+            let auth_header_parts = auth_header_value.split(' ').collect::<Vec<&str>>();
+
+            if auth_header_parts.len() != 2 || auth_header_parts[0].to_lowercase() != "bearer" {
+                return Ok(ERR10000_MISSING_TOKEN
+                    .error()
+                    .detail("Missing client bearer token header")
+                    .raise(exchange));
+            }
+
+            let token = auth_header_parts[1];
+
+            /* read unverified, only to pick the issuer whose keys verify it */
+            let unverified = match insecure_decode::<Value>(token) {
+                Ok(unverified) => unverified,
+                Err(_) => {
+                    return Ok(ERR10001_INVALID_TOKEN
+                        .error()
+                        .detail("Malformed JWT")
+                        .raise(exchange));
+                }
+            };
+            let header = unverified.header;
+            let config = self.config.get();
+            let rules = match config.rules(&unverified.claims) {
+                Ok(rules) => rules,
+                Err(reason) => {
+                    return Ok(ERR10001_INVALID_TOKEN.error().detail(reason).raise(exchange));
+                }
+            };
+            let decoding_key = match rules.decoding_key(&header).await {
+                Ok(decoding_key) => decoding_key,
+                Err(error) => return Ok(error.raise(exchange)),
+            };
+
+            let mut validation = Validation::new(header.alg);
+            /* the registered claims are checked below, each with its own error */
+            validation.validate_exp = false;
+            validation.validate_aud = false;
+            validation.required_spec_claims.clear();
+            let token_data = match decode::<Value>(token, &decoding_key, &validation) {
+                Ok(token_data) => token_data,
+                Err(_) => {
+                    return Ok(ERR10001_INVALID_TOKEN.error().detail("Invalid JWT").raise(exchange));
+                }
+            };
+
+            let claims = token_data.claims;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let validated = rules
+                .validate_aud(&claims)
+                .and_then(|_| config.validate_exp(&claims, now));
+            if let Err(reason) = validated {
+                return Ok(ERR10001_INVALID_TOKEN.error().detail(reason).raise(exchange));
+            }
+
+            let (request_path, method) = match (&request.path, &request.http_method) {
+                (None, _) => {
+                    return Ok(ERR11000_INVALID_REQUEST
+                        .error()
+                        .detail("Missing request path")
+                        .raise(exchange));
+                }
+                (Some(path), method) => (path, method),
+            };
+
+            if rules.scope_verification {
+                let Some(Ok(validator)) = self.specifications.get(rules.specification_name) else {
+                    return Ok(ERR13000_INTERNAL
+                        .error()
+                        .detail("Unable to load the specification scopes are checked against")
+                        .raise(exchange));
+                };
+                let scopes = Self::check_scopes(validator, request_path, method.as_str(), &claims);
+                if scopes.is_err() {
+                    return Ok(ERR10002_INSUFFICIENT_SCOPE
+                        .error()
+                        .detail("Invalid scope for token")
+                        .raise(exchange));
+                }
+            }
+
+            /* for the authorizer mode; API Gateway passes the claims on to the integration */
+            if let Some(subject) = claims.get("sub").and_then(Value::as_str) {
+                set_principal(exchange, subject);
+            }
+            if let Some(claims) = claims.as_object() {
+                for (name, value) in claims {
+                    add_authorizer_context(exchange, name, value.clone());
+                }
+            }
+
+            /* for the backend, so it doesn't have to parse the token again */
+            if let Ok(request) = exchange.input_mut().await {
+                self.config.get().forward_claims(request, &claims);
+            }
+            exchange
+                .attachments_mut()
+                .well_known_mut()
+                .add::<Value>(JWT_CLAIMS_ATTACHMENT_KEY, claims);
+
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        } else {
+            Ok(ERR10000_MISSING_TOKEN.error().detail("Missing JWT").raise(exchange))
+        }
+    }
+}
+
+impl NamedHandler for JwtValidationHandler {
+    const NAME: &'static str = "JwtValidationHandler";
+}
+
+#[async_trait]
+impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for JwtValidationHandler {
+
+    async fn exec(
+        &self,
+        exchange: &mut LambdaExchange,
+    ) -> Result<HandlerStatus, Infallible> {
+        let status = self.authenticate(exchange).await?;
+        let outcome = if status.code().any_flags(ExchangeState::CLIENT_ERROR) {
+            metrics::counter(names::AUTH_REJECTIONS, 1, &[("handler", Self::NAME)]);
+            AuditOutcome::Denied
+        } else if status.code().is_error() {
+            AuditOutcome::Failure
+        } else if status.code() == ExchangeState::OK {
+            AuditOutcome::Success
+        } else {
+            return Ok(status);
+        };
+        let mut event = AuditEvent::new(Self::NAME, "authenticate", outcome);
+        if let Some(error) = handler_error(exchange) {
+            event = event.detail("error", error.code.code);
+            if let Some(detail) = &error.detail {
+                event = event.detail("reason", detail.as_str());
+            }
+        }
+        record_audit_event(exchange, event);
+        Ok(status)
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::handler_error;
+    use crate::handler::LambdaExchange;
+    use crate::handler::jwt::{JwkProvider, JwtValidationHandler, JwtValidationHandlerConfig};
+    use std::collections::BTreeMap;
+    use base64::Engine;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use lambda_http::http::HeaderValue;
+    use rsa::RsaPrivateKey;
+    use rsa::pkcs1::EncodeRsaPrivateKey;
+    use serde::{Deserialize, Serialize};
+    use std::error::Error;
+    use std::fs::File;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError, DefaultConfigProvider};
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::ExchangeState;
+    use serde_json::{json, Value};
+
+    fn b64_decode(s: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(BASE64_URL_SAFE_NO_PAD.decode(s)?)
+    }
+
+    fn rsa_private_key_from_jwk(jwk: &serde_json::Value) -> Result<RsaPrivateKey, Box<dyn Error>> {
+        let n = rsa::BigUint::from_bytes_be(&b64_decode(jwk["n"].as_str().unwrap())?);
+        let e = rsa::BigUint::from_bytes_be(&b64_decode(jwk["e"].as_str().unwrap())?);
+        let d = rsa::BigUint::from_bytes_be(&b64_decode(jwk["d"].as_str().unwrap())?);
+        let p = rsa::BigUint::from_bytes_be(&b64_decode(jwk["p"].as_str().unwrap())?);
+        let q = rsa::BigUint::from_bytes_be(&b64_decode(jwk["q"].as_str().unwrap())?);
+        Ok(RsaPrivateKey::from_components(n, e, d, vec![p, q]).unwrap())
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn get_test_key_gen() -> String {
+        let test_file = File::open("./test_resources/jwt/public_private_keypair.json");
+        let jwk: serde_json::Value = serde_json::from_reader(test_file.unwrap()).unwrap();
+        let private_key = rsa_private_key_from_jwk(&jwk).unwrap();
+        let der = private_key.to_pkcs1_der().unwrap().as_bytes().to_vec();
+        let encoding_key = EncodingKey::from_rsa_der(&der);
+        let claims = Claims {
+            sub: "user123".to_string(),
+            exp: 2000000000,
+        };
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = jwk
+            .get("kid")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        encode(&header, &claims, &encoding_key).unwrap()
+    }
+
+    #[test]
+    fn create_jwt_test() {
+        let token = get_test_key_gen();
+        println!("{}", token);
+        assert!(!token.is_empty())
+    }
+
+    #[test]
+    fn test_load_config() {
+        let config_string = r#"{
+          "enabled": true,
+          "jwk_provider": {
+            "LocalJwkProvider": {
+              "file_name": "jwks.json",
+              "file_path": "./config"
+            }
+          },
+          "scope_verification": true,
+          "ignore_jwt_expiration": true,
+          "specification_name": "openapi.json",
+          "audience": ""
+        }"#;
+        let config = serde_json::from_str::<JwtValidationHandlerConfig>(config_string);
+        println!("config: {:?}", config);
+        assert!(config.is_ok());
+
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_valid_jwt_validator_handler() {
+        // generate a valid token from a test pub private key set
+        let token = get_test_key_gen();
+        let complete_token_header = format!("{} {}", "Bearer", token);
+
+        // create a request containing our valid jwt and execute the handler
+        let mut test_request = ApiGatewayProxyRequest {
+            path: Some("/test".to_string()),
+            ..Default::default()
+        };
+        test_request.headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&complete_token_header).unwrap(),
+        );
+        let mut test_exchange: LambdaExchange = Exchange::new();
+        test_exchange.set_input(test_request);
+//        let jwt_validation_handler =
+//            JwtValidationHandler::init_handler(Config::new(DefaultConfigProvider).unwrap());
+
+        let jwt_validation_handler =
+            JwtValidationHandler::new(Config::new(DefaultConfigProvider).unwrap());
+
+
+        // make sure the result is OK
+        let result = jwt_validation_handler
+            .exec(&mut test_exchange)
+            .await
+            .unwrap();
+        let result_code = result.code();
+        assert!(
+            result_code.any_flags(ExchangeState::OK),
+            "Handler returned something other than OK status meaning validation did not pass"
+        );
+    }
+
+
+    struct StaticConfig(Value);
+
+    impl ConfigProvider<JwtValidationHandlerConfig> for StaticConfig {
+        fn load(&self) -> Result<JwtValidationHandlerConfig, ConfigProviderError> {
+            serde_json::from_value(self.0.clone()).map_err(|e| ConfigProviderError::Load {
+                message: e.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_unreadable_specification() {
+        let token = get_test_key_gen();
+        let mut test_request = ApiGatewayProxyRequest {
+            path: Some("/test".to_string()),
+            ..Default::default()
+        };
+        test_request.headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        );
+        let mut test_exchange: LambdaExchange = Exchange::new();
+        test_exchange.set_input(test_request);
+
+        /* read when the handler is built, and a server error rather than a panic per request */
+        let config = StaticConfig(json!({
+            "enabled": true,
+            "jwk_provider": {
+                "LocalJwkProvider": { "file_name": "jwks.json", "file_path": "./config" }
+            },
+            "scope_verification": true,
+            "specification_name": "missing.json",
+            "ignore_jwt_expiration": false
+        }));
+        let jwt_validation_handler = JwtValidationHandler::new(Config::new(config).unwrap());
+        assert!(matches!(jwt_validation_handler.specifications.get("missing.json"), Some(Err(_))));
+        let result = jwt_validation_handler.exec(&mut test_exchange).await.unwrap();
+        assert!(result.code().any_flags(ExchangeState::SERVER_ERROR));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_invalid_jwt_validator_handler() {
+        // An invalid/malformed JWT token
+        let invalid_token = "Bearer 389475983475893745invalid_jwt4789234789";
+
+        // Create an exchange containing the header with our invalid token.
+        let mut test_request = ApiGatewayProxyRequest {
+            path: Some("/test".to_string()),
+            ..Default::default()
+        };
+        test_request.headers.insert(
+            "Authorization",
+            HeaderValue::from_str(invalid_token).unwrap(),
+        );
+        let mut test_exchange: LambdaExchange = Exchange::new();
+        test_exchange.set_input(test_request);
+
+        // execute the validation and get the result
+//        let jwt_validation_handler =
+//            JwtValidationHandler::init_handler(Config::new(DefaultConfigProvider).unwrap());
+        let jwt_validation_handler =
+            JwtValidationHandler::new(Config::new(DefaultConfigProvider).unwrap());
+        let result = jwt_validation_handler
+            .exec(&mut test_exchange)
+            .await
+            .unwrap();
+
+        assert!(result.code().any_flags(ExchangeState::CLIENT_ERROR));
+
+        let mut test_request = ApiGatewayProxyRequest::default();
+        test_request.headers.insert(
+            "Authorization",
+            HeaderValue::from_bytes(b"Bearer \xfftoken").unwrap(),
+        );
+        let mut test_exchange: LambdaExchange = Exchange::new();
+        test_exchange.set_input(test_request);
+        let result = jwt_validation_handler.exec(&mut test_exchange).await.unwrap();
+        assert!(result.code().any_flags(ExchangeState::CLIENT_ERROR));
+        assert_eq!(handler_error(&test_exchange).unwrap().code.code, "ERR10001");
+
+        // make sure we returned the client error code with the Malformed 'JWT header message'
+//        let result_code = result.code();
+//        let result_message = result
+//        if result_code.any_flags(ExchangeState::CLIENT_ERROR) && result_message == "Malformed JWT header" {
+//            assert!(true)
+//        } else {
+//            assert!(false)
+//        }
+    }
+
+    #[test]
+    fn test_registered_claims() {
+        let config = JwtValidationHandlerConfig {
+            audiences: vec!["orders".to_string(), "billing".to_string()],
+            issuers: vec!["https://login.example.com".to_string()],
+            ..JwtValidationHandlerConfig::default()
+        };
+        let rules = config.rules(&json!({ "iss": "https://login.example.com" })).unwrap();
+        assert!(rules.validate_aud(&json!({ "aud": "orders" })).is_ok());
+        assert!(rules.validate_aud(&json!({ "aud": ["web", "billing"] })).is_ok());
+        let rejected = rules.validate_aud(&json!({ "aud": "web" }));
+        assert_eq!(rejected, Err("Token audience is not accepted"));
+        assert_eq!(rules.validate_aud(&json!({})), Err("Token has no audience"));
+
+        assert!(config.validate_iss(&json!({ "iss": "https://login.example.com" })).is_ok());
+        let rejected = config.validate_iss(&json!({ "iss": "https://evil.example.com" }));
+        assert_eq!(rejected, Err("Token issuer is not accepted"));
+        assert_eq!(config.validate_iss(&json!({})), Err("Token has no issuer"));
+
+        let claims = json!({ "exp": 1000, "nbf": 900 });
+        assert!(config.validate_exp(&claims, 1050).is_ok());
+        assert_eq!(config.validate_exp(&claims, 1061), Err("Token has expired"));
+        assert_eq!(config.validate_exp(&claims, 800), Err("Token is not valid yet"));
+        assert_eq!(config.validate_exp(&json!({}), 1000), Err("Token has no expiration time"));
+        /* the leeway can't overflow times at the end of the range */
+        assert!(config.validate_exp(&json!({ "exp": u64::MAX, "nbf": 0 }), u64::MAX).is_ok());
+        for exp in [json!(-1), json!(1000.5), json!("1000")] {
+            let rejected = config.validate_exp(&json!({ "exp": exp }), 1000);
+            assert_eq!(rejected, Err("Token expiration time is malformed"));
+        }
+        let rejected = config.validate_exp(&json!({ "exp": 1000, "nbf": -5 }), 1000);
+        assert_eq!(rejected, Err("Token not-before time is malformed"));
+
+        let config = JwtValidationHandlerConfig {
+            ignore_jwt_expiration: true,
+            ..JwtValidationHandlerConfig::default()
+        };
+        assert!(config.validate_exp(&claims, 5000).is_ok());
+        assert!(config.validate_exp(&json!({}), 5000).is_ok());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_trusted_issuers() {
+        let config: JwtValidationHandlerConfig = serde_json::from_value(json!({
+            "enabled": true,
+            "jwk_provider": { "LocalJwkProvider": { "file_name": "jwks.json", "file_path": "." } },
+            "scope_verification": false,
+            "specification_name": "openapi.json",
+            "ignore_jwt_expiration": false,
+            "audience": "",
+            "trusted_issuers": [
+                {
+                    "issuer": "https://cognito-idp.us-east-1.amazonaws.com/us-east-1_abc123",
+                    "jwk_provider": {
+                        "LocalJwkProvider": { "file_name": "cognito.json", "file_path": "." }
+                    },
+                    "audiences": ["orders"]
+                },
+                {
+                    "issuer": "https://partner.example.com",
+                    "jwk_provider": {
+                        "LocalJwkProvider": { "file_name": "partner.json", "file_path": "." }
+                    },
+                    "algorithms": ["HS256"],
+                    "hmac_secret": "s3cr3t",
+                    "scope_verification": true,
+                    "specification_name": "partner.json"
+                }
+            ]
+        }))
+        .unwrap();
+
+        let cognito = "https://cognito-idp.us-east-1.amazonaws.com/us-east-1_abc123";
+        let rules = config.rules(&json!({ "iss": cognito })).unwrap();
+        assert!(rules.validate_aud(&json!({ "aud": "orders" })).is_ok());
+        assert!(rules.validate_aud(&json!({ "aud": "billing" })).is_err());
+        assert!(!rules.scope_verification);
+        /* a token can't pick a shared secret for an issuer that signs with RSA */
+        let header = Header::new(Algorithm::HS256);
+        assert!(rules.decoding_key(&header).await.is_err());
+
+        let rules = config.rules(&json!({ "iss": "https://partner.example.com" })).unwrap();
+        assert!(rules.validate_aud(&json!({})).is_ok());
+        assert!(rules.scope_verification);
+        assert_eq!(rules.specification_name, "partner.json");
+        assert!(rules.decoding_key(&header).await.is_ok());
+
+        let rejected = config.rules(&json!({ "iss": "https://login.example.com" })).err();
+        assert_eq!(rejected, Some("Token issuer is not accepted"));
+        assert_eq!(config.rules(&json!({})).err(), Some("Token has no issuer"));
+    }
+
+    #[test]
+    fn test_forward_claims() {
+        let config = JwtValidationHandlerConfig {
+            claim_headers: BTreeMap::from([
+                ("sub".to_string(), "X-User-Id".to_string()),
+                ("scope".to_string(), "X-Scopes".to_string()),
+                ("roles".to_string(), "X-Roles".to_string()),
+                ("org.id".to_string(), "X-Org-Id".to_string()),
+                ("tenant".to_string(), "X-Tenant".to_string()),
+            ]),
+            ..JwtValidationHandlerConfig::default()
+        };
+        let claims = json!({
+            "sub": "user123",
+            "scope": "read:users write:users",
+            "roles": ["admin", "billing"],
+            "org": { "id": 42 }
+        });
+        let mut request = ApiGatewayProxyRequest::default();
+        request.headers.insert("x-user-id", HeaderValue::from_static("spoofed"));
+        request.headers.insert("x-tenant", HeaderValue::from_static("spoofed"));
+        config.forward_claims(&mut request, &claims);
+        let headers = &request.headers;
+        assert_eq!(headers["x-user-id"], "user123");
+        assert_eq!(headers["x-scopes"], "read:users write:users");
+        assert_eq!(headers["x-roles"], "admin billing");
+        assert_eq!(headers["x-org-id"], "42");
+        assert!(headers.get("x-tenant").is_none());
+        assert_eq!(request.multi_value_headers["x-user-id"], "user123");
+    }
+
+    #[test]
+    fn test_forward_claims_multi_value_spoof() {
+        let config = JwtValidationHandlerConfig {
+            claim_headers: BTreeMap::from([
+                ("sub".to_string(), "X-User-Id".to_string()),
+                ("tenant".to_string(), "X-Tenant".to_string()),
+            ]),
+            ..JwtValidationHandlerConfig::default()
+        };
+        let mut request = ApiGatewayProxyRequest::default();
+        let spoofed = &mut request.multi_value_headers;
+        spoofed.append("x-user-id", HeaderValue::from_static("admin"));
+        spoofed.append("x-user-id", HeaderValue::from_static("root"));
+        spoofed.insert("x-tenant", HeaderValue::from_static("other-tenant"));
+        config.forward_claims(&mut request, &json!({ "sub": "user123" }));
+
+        let user_ids: Vec<_> = request.multi_value_headers.get_all("x-user-id").iter().collect();
+        assert_eq!(user_ids, ["user123"]);
+        assert_eq!(request.headers["x-user-id"], "user123");
+        assert!(request.multi_value_headers.get("x-tenant").is_none());
+        assert!(request.headers.get("x-tenant").is_none());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn load_jwk_file_test() {
+        let file = r#"
+        {
+            "enabled": true,
+            "scope_verification": false,
+            "ignore_jwt_expiration": false,
+            "specification_name": "openapi.yaml",
+            "jwk_provider": {
+                "LocalJwkProvider": {
+                    "file_name": "jwks.json",
+                    "file_path": "./config"
+                 }
+            },
+            "audience": "https://issuer.example.com"
+        }
+        "#;
+        let jwt_config: JwtValidationHandlerConfig = serde_json::from_str(file).unwrap();
+        assert!(jwt_config.enabled);
+        let jwk_set = jwt_config.jwk_provider.jwk().await.unwrap();
+        assert!(
+            jwk_set
+                .keys
+                .iter()
+                .any(|jwk| jwk.clone().common.key_id.unwrap()
+                    == "DDbt045YVtnjCkzHUv-rFN4wPfGD3Upk9_da_yweZ1c")
+        );
+    }
+
+    fn create_test_spec() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "info": {
+                "title": "Test API",
+                "version": "1.0.0"
+            },
+            "paths": {
+                "/users": {
+                    "get": {
+                        "security": [
+                            {
+                                "oauth2": ["read:users"]
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "OK"
+                            }
+                        }
+                    },
+                    "post": {
+                        "security": [
+                            {
+                                "oauth2": ["write:users"]
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "OK"
+                            }
+                        }
+                    }
+                },
+                "/admin": {
+                    "get": {
+                        "security": [
+                            {
+                                "oauth2": ["admin:read", "admin:users"]
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "OK"
+                            }
+                        }
+                    }
+                },
+                "/public": {
+                    "get": {
+                        "responses": {
+                            "200": {
+                                "description": "OK"
+                            }
+                        }
+                    }
+                },
+                "/alternate-auth": {
+                    "get": {
+                        "security": [
+                            {
+                                "oauth2": ["read:resource"]
+                            },
+                            {
+                                "oauth2": ["admin:all"]
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "OK"
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "securitySchemes": {
+                    "oauth2": {
+                        "type": "oauth2",
+                        "flows": {
+                            "implicit": {
+                                "authorizationUrl": "https://example.com/oauth/authorize",
+                                "scopes": {
+                                    "read:users": "Read user data",
+                                    "write:users": "Write user data",
+                                    "admin:read": "Admin read access",
+                                    "admin:users": "Admin user access",
+                                    "read:resource": "Read resource",
+                                    "admin:all": "Admin all access"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_scope_with_valid_single_scope() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "read:users"});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_ok(), "Token with correct scope should be valid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_missing_required_scope() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "write:users"});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_err(), "Token with wrong scope should be invalid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_multiple_scopes_including_required() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "read:users write:users admin:read"});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_ok(), "Token with multiple scopes including required one should be valid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_different_http_method() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "write:users"});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "POST", &claims);
+
+        assert!(result.is_ok(), "Token with correct scope for POST should be valid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_endpoint_requiring_multiple_scopes() {
+        let spec = create_test_spec();
+
+        // Test with all required scopes
+        let claims_complete = json!({"scope": "admin:read admin:users"});
+        let result_complete = JwtValidationHandler::validate_scope(spec.clone(), "/admin", "GET", &claims_complete);
+        assert!(result_complete.is_ok(), "Token with all required scopes should be valid");
+
+        // Test with only one of the required scopes
+        let claims_partial = json!({"scope": "admin:read"});
+        let result_partial = JwtValidationHandler::validate_scope(spec, "/admin", "GET", &claims_partial);
+        assert!(result_partial.is_err(), "Token with only some required scopes should be invalid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_missing_scope_claim() {
+        let spec = create_test_spec();
+        let claims = json!({});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_err(), "Token without scope claim should be invalid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_non_string_scope() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": 123});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_err(), "Token with non-string scope should be invalid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_public_endpoint() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": ""});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/public", "GET", &claims);
+
+        assert!(result.is_ok(), "Public endpoint should not require scopes");
+    }
+
+    #[test]
+    fn test_validate_scope_with_nonexistent_path() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "read:users"});
+
+        // This test will verify the error handling in the validator.traverser().get_operation call
+        let result = JwtValidationHandler::validate_scope(spec, "/nonexistent", "GET", &claims);
+
+        // The exact result depends on the implementation of the error handling in get_operation,
+        // but we expect an error since the path doesn't exist
+        assert!(result.is_err(), "Nonexistent path should result in an error");
+    }
+
+    #[test]
+    fn test_validate_scope_with_invalid_method() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": "read:users"});
+
+        // This test will verify the error handling in the validator.traverser().get_operation call
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "INVALID_METHOD", &claims);
+
+        // The exact result depends on the implementation of the error handling in get_operation,
+        // but we expect an error since the method doesn't exist
+        assert!(result.is_err(), "Invalid method should result in an error");
+    }
+
+    #[test]
+    fn test_validate_scope_with_alternate_security_requirements() {
+        let spec = create_test_spec();
+
+        // Test with first security requirement
+        let claims_first = json!({"scope": "read:resource"});
+        let result_first = JwtValidationHandler::validate_scope(spec.clone(), "/alternate-auth", "GET", &claims_first);
+        assert!(result_first.is_ok(), "Token with first alternate scope should be valid");
+
+        // Test with second security requirement
+        let claims_second = json!({"scope": "admin:all"});
+        let result_second = JwtValidationHandler::validate_scope(spec, "/alternate-auth", "GET", &claims_second);
+        assert!(result_second.is_ok(), "Token with second alternate scope should be valid");
+    }
+
+    #[test]
+    fn test_validate_scope_with_empty_scope() {
+        let spec = create_test_spec();
+        let claims = json!({"scope": ""});
+
+        let result = JwtValidationHandler::validate_scope(spec, "/users", "GET", &claims);
+
+        assert!(result.is_err(), "Empty scope should be invalid for protected endpoint");
+    }
+
+    #[test]
+    fn test_validate_scope_with_malformed_spec() {
+        let malformed_spec = json!({
+            "openapi": "3.0.0",
+            "info": {
+                "title": "Malformed API",
+                "version": "1.0.0"
+            }
+            // Missing required fields
+        });
+
+        let claims = json!({"scope": "read:users"});
+
+        // This test will verify the error handling in the OpenApiPayloadValidator::new call
+        let result = JwtValidationHandler::validate_scope(malformed_spec, "/users", "GET", &claims);
+
+        // The exact result depends on the implementation of the error handling in OpenApiPayloadValidator::new,
+        // but we expect an error since the spec is malformed
+        assert!(result.is_err(), "Malformed spec should result in an error");
+    }
+
+}
@@ -0,0 +1,367 @@
+use crate::config::units::deserialize_duration;
+use crate::handler::http_backend::{http_client, HttpClientConfig};
+use crate::handler::jwt::JwkProvider;
+use crate::metrics::{self, names};
+use async_trait::async_trait;
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use jsonwebtoken::jwk::JwkSet;
+use reqwest::header::{HeaderMap, CACHE_CONTROL};
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Fetches an issuer's keys from its JWKS endpoint over HTTPS. Keys are cached per container,
+/// per endpoint, for as long as the response's `Cache-Control: max-age` allows, or `cache_ttl`
+/// when it doesn't say:
+///
+/// ```json
+/// { "RemoteJwkProvider": { "jwk_server_url": "https://login.example.com",
+///   "jwk_server_path": "/.well-known/jwks.json", "cache_ttl": "10m", "max_stale": "1h" } }
+/// ```
+///
+/// Expired keys are still served for up to `max_stale` while they are fetched again in the
+/// background, and when that fails, so an outage of the identity provider doesn't fail every
+/// request. A token naming a key the cached ones don't have makes them refresh, at most once
+/// per `min_refresh_interval`.
+///
+/// Endpoints other than `https` ones are rejected when the config is loaded. Only with
+/// `"allow_insecure_localhost": true` may an endpoint on a loopback host, like a test identity
+/// provider, be plain `http`.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "RemoteJwkProviderConfig")]
+pub struct RemoteJwkProvider {
+    jwk_server_url: String,
+    jwk_server_path: String,
+    cache_ttl: Duration,
+    max_stale: Duration,
+    min_refresh_interval: Duration,
+    timeout: Duration,
+}
+
+#[derive(Deserialize)]
+struct RemoteJwkProviderConfig {
+    jwk_server_url: String,
+    jwk_server_path: String,
+    #[serde(default = "default_cache_ttl", deserialize_with = "deserialize_duration")]
+    cache_ttl: Duration,
+    #[serde(default = "default_max_stale", deserialize_with = "deserialize_duration")]
+    max_stale: Duration,
+    #[serde(default = "default_min_refresh_interval", deserialize_with = "deserialize_duration")]
+    min_refresh_interval: Duration,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+    #[serde(default)]
+    allow_insecure_localhost: bool,
+}
+
+impl TryFrom<RemoteJwkProviderConfig> for RemoteJwkProvider {
+    type Error = String;
+
+    fn try_from(config: RemoteJwkProviderConfig) -> Result<Self, Self::Error> {
+        check_endpoint(&config.jwk_server_url, config.allow_insecure_localhost)?;
+        Ok(Self {
+            jwk_server_url: config.jwk_server_url,
+            jwk_server_path: config.jwk_server_path,
+            cache_ttl: config.cache_ttl,
+            max_stale: config.max_stale,
+            min_refresh_interval: config.min_refresh_interval,
+            timeout: config.timeout,
+        })
+    }
+}
+
+/* keys only come over TLS, unless plain HTTP is allowed for a loopback endpoint */
+fn check_endpoint(url: &str, allow_insecure_localhost: bool) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid jwk_server_url {}: {}", url, e))?;
+    let loopback = parsed.host_str().is_some_and(|host| {
+        matches!(host, "localhost" | "[::1]")
+            || host.parse::<Ipv4Addr>().is_ok_and(|ip| ip.is_loopback())
+    });
+    match parsed.scheme() {
+        "https" => Ok(()),
+        "http" if allow_insecure_localhost && loopback => Ok(()),
+        _ => Err(format!("jwk_server_url {} must use https", url)),
+    }
+}
+
+impl Default for RemoteJwkProvider {
+    fn default() -> Self {
+        Self {
+            jwk_server_url: String::new(),
+            jwk_server_path: String::new(),
+            cache_ttl: default_cache_ttl(),
+            max_stale: default_max_stale(),
+            min_refresh_interval: default_min_refresh_interval(),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+fn default_cache_ttl() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_max_stale() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_min_refresh_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+struct CachedKeys {
+    keys: JwkSet,
+    expires: Instant,
+    /* no fetch before then, so outages and unknown kids don't flood the identity provider */
+    next_fetch: Instant,
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CachedKeys>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CachedKeys>>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+/* what a lookup does with the cache entry it found */
+enum Lookup {
+    Cached(JwkSet),
+    /* serve the stale keys and fetch new ones in the background */
+    Revalidate(JwkSet),
+    /* fetch now, falling back to the stale keys if there are any */
+    Fetch(Option<JwkSet>),
+    Unavailable,
+}
+
+/* the lifetime `Cache-Control` gives the response, if it gives one */
+fn max_age(headers: &HeaderMap) -> Option<Duration> {
+    let directives: Vec<String> = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_lowercase())
+        .collect();
+    if directives.iter().any(|directive| directive == "no-cache" || directive == "no-store") {
+        return Some(Duration::ZERO);
+    }
+    directives
+        .iter()
+        .filter_map(|directive| directive.strip_prefix("max-age="))
+        .find_map(|seconds| seconds.trim_matches('"').parse().ok())
+        .map(Duration::from_secs)
+}
+
+/* fetches the keys and caches them */
+async fn fetch(
+    url: &str,
+    timeout: Duration,
+    cache_ttl: Duration,
+    min_refresh_interval: Duration,
+) -> Result<JwkSet, String> {
+    metrics::counter(names::JWKS_REFRESHES, 1, &[]);
+    let response = http_client(&HttpClientConfig::default())?
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let ttl = max_age(response.headers()).unwrap_or(cache_ttl);
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    let keys: JwkSet = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+
+    let now = Instant::now();
+    cache().lock().unwrap_or_else(PoisonError::into_inner).insert(
+        url.to_string(),
+        CachedKeys {
+            keys: keys.clone(),
+            expires: now + ttl,
+            next_fetch: now + min_refresh_interval,
+        },
+    );
+    Ok(keys)
+}
+
+type SharedFetch = Shared<BoxFuture<'static, Result<JwkSet, String>>>;
+
+/* fetches under way by URL, so requests arriving together share one */
+fn in_flight() -> &'static Mutex<HashMap<String, SharedFetch>> {
+    static IN_FLIGHT: OnceLock<Mutex<HashMap<String, SharedFetch>>> = OnceLock::new();
+    IN_FLIGHT.get_or_init(Mutex::default)
+}
+
+/* fetches the keys, or waits for the fetch another request already started */
+async fn fetch_once(
+    url: &str,
+    timeout: Duration,
+    cache_ttl: Duration,
+    min_refresh_interval: Duration,
+) -> Result<JwkSet, String> {
+    let fetching = in_flight()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(url.to_string())
+        .or_insert_with(|| {
+            let url = url.to_string();
+            async move {
+                let fetched = fetch(&url, timeout, cache_ttl, min_refresh_interval).await;
+                in_flight().lock().unwrap_or_else(PoisonError::into_inner).remove(&url);
+                fetched
+            }
+            .boxed()
+            .shared()
+        })
+        .clone();
+    fetching.await
+}
+
+impl RemoteJwkProvider {
+    fn url(&self) -> String {
+        format!(
+            "{}/{}",
+            self.jwk_server_url.trim_end_matches('/'),
+            self.jwk_server_path.trim_start_matches('/')
+        )
+    }
+
+    fn lookup(&self, url: &str, refresh: bool, now: Instant) -> Lookup {
+        let mut cache = cache().lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(cached) = cache.get_mut(url) else {
+            return Lookup::Fetch(None);
+        };
+        if now < cached.expires && !refresh {
+            return Lookup::Cached(cached.keys.clone());
+        }
+        let usable = (now < cached.expires + self.max_stale).then(|| cached.keys.clone());
+        if now < cached.next_fetch {
+            return usable.map_or(Lookup::Unavailable, Lookup::Cached);
+        }
+        cached.next_fetch = now + self.min_refresh_interval;
+        match usable {
+            Some(keys) if !refresh => Lookup::Revalidate(keys),
+            usable => Lookup::Fetch(usable),
+        }
+    }
+
+    async fn keys(&self, refresh: bool) -> Result<JwkSet, ()> {
+        let url = self.url();
+        match self.lookup(&url, refresh, Instant::now()) {
+            Lookup::Cached(keys) => Ok(keys),
+            Lookup::Revalidate(keys) => {
+                let (timeout, cache_ttl) = (self.timeout, self.cache_ttl);
+                let min_refresh_interval = self.min_refresh_interval;
+                tokio::spawn(async move {
+                    let fetched = fetch_once(&url, timeout, cache_ttl, min_refresh_interval).await;
+                    if let Err(e) = fetched {
+                        tracing::warn!("Unable to refresh JWKs from {}: {}", url, e);
+                    }
+                });
+                Ok(keys)
+            }
+            Lookup::Fetch(stale) => {
+                let fetched =
+                    fetch_once(&url, self.timeout, self.cache_ttl, self.min_refresh_interval)
+                        .await;
+                match (fetched, stale) {
+                    (Ok(keys), _) => Ok(keys),
+                    (Err(e), Some(keys)) => {
+                        tracing::warn!("Unable to fetch JWKs from {}, using old ones: {}", url, e);
+                        Ok(keys)
+                    }
+                    (Err(e), None) => {
+                        tracing::error!("Unable to fetch JWKs from {}: {}", url, e);
+                        Err(())
+                    }
+                }
+            }
+            Lookup::Unavailable => Err(()),
+        }
+    }
+}
+
+#[async_trait]
+impl JwkProvider for RemoteJwkProvider {
+    async fn jwk(&self) -> Result<JwkSet, ()> {
+        self.keys(false).await
+    }
+
+    async fn refresh(&self) -> Result<JwkSet, ()> {
+        self.keys(true).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::jwt::remote::{
+        cache, check_endpoint, max_age, CachedKeys, Lookup, RemoteJwkProvider,
+    };
+    use jsonwebtoken::jwk::JwkSet;
+    use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_cache_control_max_age() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(max_age(&headers), None);
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("public, max-age=600"));
+        assert_eq!(max_age(&headers), Some(Duration::from_secs(600)));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        assert_eq!(max_age(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_cache_lookup() {
+        let provider = RemoteJwkProvider::default();
+        let url = "https://lookup.test/jwks.json";
+        let start = Instant::now();
+        assert!(matches!(provider.lookup(url, false, start), Lookup::Fetch(None)));
+
+        cache().lock().unwrap().insert(
+            url.to_string(),
+            CachedKeys {
+                keys: JwkSet { keys: vec![] },
+                expires: start + Duration::from_secs(300),
+                next_fetch: start + Duration::from_secs(30),
+            },
+        );
+        assert!(matches!(provider.lookup(url, false, start), Lookup::Cached(_)));
+        /* an unknown kid right after a fetch waits for the refresh interval */
+        assert!(matches!(provider.lookup(url, true, start), Lookup::Cached(_)));
+        let later = start + Duration::from_secs(60);
+        assert!(matches!(provider.lookup(url, true, later), Lookup::Fetch(Some(_))));
+
+        let expired = start + Duration::from_secs(400);
+        assert!(matches!(provider.lookup(url, false, expired), Lookup::Revalidate(_)));
+        /* the background fetch is already under way */
+        assert!(matches!(provider.lookup(url, false, expired), Lookup::Cached(_)));
+        let gone = start + Duration::from_secs(300 + 3600 + 60);
+        assert!(matches!(provider.lookup(url, false, gone), Lookup::Fetch(None)));
+        assert!(matches!(provider.lookup(url, false, gone), Lookup::Unavailable));
+    }
+
+    #[test]
+    fn test_endpoint_must_use_https() {
+        let config = r#"{ "jwk_server_url": "https://login.example.com", "jwk_server_path": "/" }"#;
+        assert!(serde_json::from_str::<RemoteJwkProvider>(config).is_ok());
+        let config = r#"{ "jwk_server_url": "http://localhost:8080", "jwk_server_path": "/",
+            "allow_insecure_localhost": true }"#;
+        assert!(serde_json::from_str::<RemoteJwkProvider>(config).is_ok());
+        let config = r#"{ "jwk_server_url": "http://login.example.com", "jwk_server_path": "/" }"#;
+        assert!(serde_json::from_str::<RemoteJwkProvider>(config).is_err());
+
+        assert!(check_endpoint("http://localhost:8080", false).is_err());
+        assert!(check_endpoint("http://localhost:8080", true).is_ok());
+        assert!(check_endpoint("http://127.0.0.1:8080", true).is_ok());
+        assert!(check_endpoint("http://[::1]:8080", true).is_ok());
+        assert!(check_endpoint("http://login.example.com", true).is_err());
+        assert!(check_endpoint("ftp://localhost", true).is_err());
+        assert!(check_endpoint("login.example.com", false).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use aws_config::timeout::TimeoutConfig;
+use aws_config::{BehaviorVersion, Region, SdkConfig};
+use aws_sdk_lambda::Client as LambdaClient;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use crate::config::units::deserialize_optional_duration;
+
+/// How a handler's Lambda client reaches the service, on top of the SDK's default config chain
+/// (environment, profile, container credentials):
+///
+/// ```json
+/// { "region": "eu-west-1", "endpoint_override": "http://localhost:4566",
+///   "api_call_timeout": "5s" }
+/// ```
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct LambdaClientConfig {
+    pub region: Option<String>,
+    /* e.g. a LocalStack endpoint */
+    pub endpoint_override: Option<String>,
+    /* for the whole operation, retries included */
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub api_call_timeout: Option<Duration>,
+}
+
+/* one per distinct config and container, the SDK client pools its connections */
+static CLIENTS: Mutex<Option<HashMap<LambdaClientConfig, LambdaClient>>> = Mutex::const_new(None);
+
+static SDK_CONFIGS: Mutex<Option<HashMap<LambdaClientConfig, SdkConfig>>> = Mutex::const_new(None);
+
+async fn load(config: &LambdaClientConfig) -> SdkConfig {
+    let mut loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(region) = &config.region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    if let Some(endpoint) = &config.endpoint_override {
+        loader = loader.endpoint_url(endpoint);
+    }
+    if let Some(timeout) = config.api_call_timeout {
+        loader = loader.timeout_config(TimeoutConfig::builder().operation_timeout(timeout).build());
+    }
+    loader.load().await
+}
+
+/// The SDK config for the settings, shared by every AWS client built with the same ones, like
+/// the DynamoDB client of the replay handler. It is loaded on first use and kept for the
+/// lifetime of the container.
+pub async fn sdk_config(config: &LambdaClientConfig) -> SdkConfig {
+    let mut configs = SDK_CONFIGS.lock().await;
+    let configs = configs.get_or_insert_with(HashMap::new);
+    if let Some(sdk_config) = configs.get(config) {
+        return sdk_config.clone();
+    }
+    let sdk_config = load(config).await;
+    configs.insert(config.clone(), sdk_config.clone());
+    sdk_config
+}
+
+/// The Lambda client for the config, shared by every handler that invokes functions with the
+/// same one. It is built on first use and kept for the lifetime of the container.
+pub async fn lambda_client(config: &LambdaClientConfig) -> LambdaClient {
+    let mut clients = CLIENTS.lock().await;
+    let clients = clients.get_or_insert_with(HashMap::new);
+    if let Some(client) = clients.get(config) {
+        return client.clone();
+    }
+    let client = LambdaClient::new(&sdk_config(config).await);
+    clients.insert(config.clone(), client.clone());
+    client
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::lambda::LambdaClientConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lambda_client_config() {
+        let config: LambdaClientConfig = serde_json::from_str(
+            r#"{ "endpoint_override": "http://localhost:4566", "api_call_timeout": "2s" }"#,
+        )
+        .unwrap();
+        assert_eq!(config.region, None);
+        assert_eq!(config.endpoint_override.as_deref(), Some("http://localhost:4566"));
+        assert_eq!(config.api_call_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(
+            serde_json::from_str::<LambdaClientConfig>("{}").unwrap(),
+            LambdaClientConfig::default()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::config::units::deserialize_optional_byte_size;
+use crate::error::{
+    HandlerError, ERR11000_INVALID_REQUEST, ERR11001_PAYLOAD_TOO_LARGE, ERR13000_INTERNAL,
+};
+use crate::flow::builder::NamedHandler;
+use crate::handler::body::BodyAccess;
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::header::CONTENT_TYPE;
+use serde::Deserialize;
+use std::convert::Infallible;
+
+/// Caps on what a request may carry, checked before expensive handlers like validation or
+/// sanitization see it. Unset limits don't apply:
+///
+/// ```json
+/// { "enabled": true, "max_body_size": "256KiB", "max_header_count": 50,
+///   "max_header_size": "8KiB", "max_json_depth": 20, "max_json_array_length": 1000 }
+/// ```
+///
+/// A body over its size fails with 413; the other limits fail with 400. The JSON limits apply
+/// to bodies with a JSON content type, and are checked without parsing the body.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct LimitHandlerConfig {
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_optional_byte_size")]
+    pub max_body_size: Option<usize>,
+    pub max_header_count: Option<usize>,
+    /* of the largest header, name and value */
+    #[serde(deserialize_with = "deserialize_optional_byte_size")]
+    pub max_header_size: Option<usize>,
+    pub max_json_depth: Option<usize>,
+    pub max_json_array_length: Option<usize>,
+}
+
+/* the decoded size of the body, without decoding it */
+fn body_size(request: &ApiGatewayProxyRequest) -> usize {
+    match &request.body {
+        None => 0,
+        Some(body) if request.is_base64_encoded => {
+            let padding = body.bytes().rev().take_while(|byte| *byte == b'=').count();
+            (body.len() / 4 * 3).saturating_sub(padding)
+        }
+        Some(body) => body.len(),
+    }
+}
+
+fn is_json(request: &ApiGatewayProxyRequest) -> bool {
+    request
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|media_type| media_type.trim().to_lowercase())
+        .is_some_and(|media_type| media_type == "application/json" || media_type.ends_with("+json"))
+}
+
+#[derive(Debug, PartialEq)]
+enum JsonViolation {
+    Depth,
+    ArrayLength,
+}
+
+/* walks the structure of a JSON document, counting nesting and array elements */
+fn check_json(
+    bytes: &[u8],
+    max_depth: Option<usize>,
+    max_array_length: Option<usize>,
+) -> Result<(), JsonViolation> {
+    /* per open container: whether it is an array, and the commas seen in it */
+    let mut open: Vec<(bool, usize)> = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                open.push((*byte == b'[', 0));
+                if max_depth.is_some_and(|max_depth| open.len() > max_depth) {
+                    return Err(JsonViolation::Depth);
+                }
+            }
+            b'}' | b']' => {
+                open.pop();
+            }
+            b',' => {
+                if let Some((true, commas)) = open.last_mut() {
+                    *commas += 1;
+                    if max_array_length.is_some_and(|max_length| *commas + 1 > max_length) {
+                        return Err(JsonViolation::ArrayLength);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl LimitHandlerConfig {
+    fn check_size_and_headers(&self, request: &ApiGatewayProxyRequest) -> Result<(), HandlerError> {
+        let body_size = body_size(request);
+        if let Some(max_body_size) = self.max_body_size.filter(|max| body_size > *max) {
+            return Err(ERR11001_PAYLOAD_TOO_LARGE
+                .error()
+                .detail(format!(
+                    "Request body of {} bytes is over the {} byte limit",
+                    body_size, max_body_size
+                ))
+                .metadata("limit", max_body_size.to_string()));
+        }
+        let header_count = request.headers.len();
+        if let Some(max_count) = self.max_header_count.filter(|max| header_count > *max) {
+            return Err(ERR11000_INVALID_REQUEST
+                .error()
+                .detail(format!("{} headers are over the limit of {}", header_count, max_count))
+                .metadata("limit", max_count.to_string()));
+        }
+        let oversized = request
+            .headers
+            .iter()
+            .find(|(name, value)| {
+                let size = name.as_str().len() + value.len();
+                self.max_header_size.is_some_and(|max_size| size > max_size)
+            })
+            .map(|(name, _)| name.as_str());
+        if let (Some(name), Some(max_size)) = (oversized, self.max_header_size) {
+            return Err(ERR11000_INVALID_REQUEST
+                .error()
+                .detail(format!("Header {} is over the {} byte limit", name, max_size))
+                .metadata("limit", max_size.to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub struct LimitHandler {
+    pub(crate) config: Config<LimitHandlerConfig>,
+}
+
+impl NamedHandler for LimitHandler {
+    const NAME: &'static str = "LimitHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for LimitHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let checked = match exchange.input().await {
+            Ok(request) => config.check_size_and_headers(request).map(|_| is_json(request)),
+            Err(_) => Err(ERR13000_INTERNAL.error().detail("Unable to get request")),
+        };
+        let json = match checked {
+            Ok(json) => json,
+            Err(error) => return Ok(error.raise(exchange)),
+        };
+        if !json || (config.max_json_depth.is_none() && config.max_json_array_length.is_none()) {
+            return Ok(HandlerStatus::new(ExchangeState::OK));
+        }
+
+        /* decoded once, for the handlers after this one too */
+        let checked = match BodyAccess::bytes(exchange).await {
+            Ok(Some(bytes)) => {
+                check_json(bytes, config.max_json_depth, config.max_json_array_length)
+            }
+            Ok(None) | Err(_) => Ok(()),
+        };
+        match checked {
+            Ok(()) => Ok(HandlerStatus::new(ExchangeState::OK)),
+            Err(JsonViolation::Depth) => Ok(ERR11000_INVALID_REQUEST
+                .error()
+                .detail("JSON body is nested too deeply")
+                .metadata("limit", config.max_json_depth.unwrap_or_default().to_string())
+                .raise(exchange)),
+            Err(JsonViolation::ArrayLength) => Ok(ERR11000_INVALID_REQUEST
+                .error()
+                .detail("JSON body has an array over the length limit")
+                .metadata("limit", config.max_json_array_length.unwrap_or_default().to_string())
+                .raise(exchange)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::limit::{check_json, JsonViolation, LimitHandlerConfig};
+    use crate::testkit::RequestBuilder;
+
+    #[test]
+    fn test_json_limits() {
+        let document = br#"{"a": [1, 2, {"b": "[[[,,,]]]"}], "c": {"d": {}}}"#;
+        assert_eq!(check_json(document, Some(3), Some(3)), Ok(()));
+        assert_eq!(check_json(document, Some(2), None), Err(JsonViolation::Depth));
+        assert_eq!(check_json(document, None, Some(2)), Err(JsonViolation::ArrayLength));
+        assert_eq!(check_json(br#"["\"]]]]", 1]"#, Some(1), Some(2)), Ok(()));
+    }
+
+    #[test]
+    fn test_size_and_header_limits() {
+        let config: LimitHandlerConfig = serde_json::from_str(
+            r#"{ "enabled": true, "max_body_size": "1KiB", "max_header_count": 2,
+                 "max_header_size": 32 }"#,
+        )
+        .unwrap();
+        let request = RequestBuilder::get("/pets").header("accept", "*/*").build();
+        assert!(config.check_size_and_headers(&request).is_ok());
+
+        let request = RequestBuilder::post("/pets").body("x".repeat(1025)).build();
+        let error = config.check_size_and_headers(&request).unwrap_err();
+        assert_eq!(error.code.status_code, 413);
+
+        let request = RequestBuilder::get("/pets").header("x-long", &"y".repeat(30)).build();
+        assert_eq!(config.check_size_and_headers(&request).unwrap_err().code.status_code, 400);
+
+        let request = RequestBuilder::get("/pets")
+            .header("a", "1")
+            .header("b", "2")
+            .header("c", "3")
+            .build();
+        assert!(config.check_size_and_headers(&request).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::ERR13000_INTERNAL;
+use crate::flow::builder::NamedHandler;
+use crate::handler::LambdaExchange;
+use crate::metrics::registry::Registry;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::header::{ACCEPT, CONTENT_TYPE};
+use lambda_http::Body;
+use serde::Deserialize;
+use std::convert::Infallible;
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsFormat {
+    #[default]
+    Prometheus,
+    Json,
+}
+
+impl MetricsFormat {
+    /// The format a `?format=json` query or an `Accept: application/json` header asks for.
+    fn requested(request: &ApiGatewayProxyRequest) -> Option<Self> {
+        match request.query_string_parameters.first("format") {
+            Some("json") => return Some(MetricsFormat::Json),
+            Some("prometheus") => return Some(MetricsFormat::Prometheus),
+            _ => {}
+        }
+        let accept = request.headers.get(ACCEPT)?.to_str().ok()?;
+        match accept.contains(JSON_CONTENT_TYPE) {
+            true => Some(MetricsFormat::Json),
+            false => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MetricsHandlerConfig {
+    pub enabled: bool,
+    /* used when the request doesn't ask for a format */
+    #[serde(default)]
+    pub format: MetricsFormat,
+}
+
+impl Default for MetricsHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            format: MetricsFormat::default(),
+        }
+    }
+}
+
+/// Reports the gateway's own metrics, everything recorded since the container started, in the
+/// Prometheus text format or as JSON. It terminates a route like `/metrics`, after the chain's
+/// usual auth handlers:
+///
+/// ```json
+/// { "/metrics": { "method": "GET", "exec": ["secured", "MetricsHandler"] } }
+/// ```
+pub struct MetricsHandler {
+    pub(crate) config: Config<MetricsHandlerConfig>,
+}
+
+impl NamedHandler for MetricsHandler {
+    const NAME: &'static str = "MetricsHandler";
+}
+
+pub(crate) fn metrics_response(
+    registry: &Registry,
+    format: MetricsFormat,
+) -> ApiGatewayProxyResponse {
+    let (content_type, body) = match format {
+        MetricsFormat::Prometheus => (PROMETHEUS_CONTENT_TYPE, registry.to_prometheus()),
+        MetricsFormat::Json => (JSON_CONTENT_TYPE, registry.to_json().to_string()),
+    };
+    let mut response = ApiGatewayProxyResponse {
+        status_code: 200,
+        body: Some(Body::Text(body)),
+        ..Default::default()
+    };
+    response
+        .headers
+        .insert(CONTENT_TYPE, content_type.parse().unwrap());
+    response
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for MetricsHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let format = match exchange.input().await {
+            Ok(request) => MetricsFormat::requested(request).unwrap_or(self.config.get().format),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+        exchange.set_output(metrics_response(Registry::global(), format));
+        Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::metrics::{metrics_response, MetricsFormat};
+    use crate::metrics::registry::Registry;
+    use crate::testkit::RequestBuilder;
+    use lambda_http::Body;
+
+    #[test]
+    fn test_metrics_format() {
+        let json = RequestBuilder::get("/metrics").header("accept", "application/json").build();
+        assert_eq!(MetricsFormat::requested(&json), Some(MetricsFormat::Json));
+        let query = RequestBuilder::get("/metrics").query("format", "prometheus").build();
+        assert_eq!(MetricsFormat::requested(&query), Some(MetricsFormat::Prometheus));
+        assert_eq!(MetricsFormat::requested(&RequestBuilder::get("/metrics").build()), None);
+
+        let registry = Registry::default();
+        registry.counter("JwksRefreshes", 2, &[]);
+        let response = metrics_response(&registry, MetricsFormat::Json);
+        assert_eq!(response.headers["content-type"], "application/json");
+        let expected = r#"{"JwksRefreshes":[{"count":2}]}"#;
+        assert!(matches!(response.body, Some(Body::Text(body)) if body == expected));
+    }
+}
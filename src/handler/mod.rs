@@ -1,10 +1,30 @@
+pub mod adapter;
+pub mod admin;
+pub mod api_key;
+pub mod attachments;
+pub mod body;
+pub mod context;
 pub mod cors;
 pub mod echo;
+pub mod function_routes;
 pub mod header;
 pub mod health;
+pub mod http_backend;
+pub mod ip_filter;
 pub mod jwt;
+pub mod lambda;
+pub mod limit;
+pub mod metrics;
+pub mod parallel;
+pub mod pii;
 pub mod proxy;
+pub mod rate_limit;
+pub mod replay;
+pub mod response_template;
 pub mod traceability;
+pub mod webhook;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 mod validator;
 mod sanitizer;
 use idemio::exchange::Exchange;
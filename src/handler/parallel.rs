@@ -0,0 +1,251 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use async_trait::async_trait;
+use futures::future::join_all;
+use idemio::exchange::Exchange;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::Context;
+use crate::error::handler_error;
+use crate::executor::deadline::{deadline, set_deadline};
+use crate::flow::matcher::{
+    matched_route, path_parameters, route_metadata, set_path_parameters, set_route,
+    set_route_metadata,
+};
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::traceability::{CORRELATION_ID_ATTACHMENT_KEY, TRACEABILITY_ID_ATTACHMENT_KEY};
+use crate::handler::LambdaExchange;
+
+/// Runs independent, read-only handlers (traceability lookups, metrics, geo lookups, validation)
+/// concurrently as one stage of the chain.
+///
+/// Each handler runs against its own copy of the request, seeing the route, path parameters,
+/// deadline and trace ids of the exchange, and anything else it writes to the request,
+/// attachments or output listeners is discarded. The stage reports the first handler, in
+/// declaration order, that stopped the chain, along with the error it raised and the response it
+/// wrote, if any. Handlers that mutate the exchange must not be grouped here.
+pub struct ParallelStageHandler {
+    name: String,
+    handlers: Vec<Arc<dyn Handler<LambdaExchange>>>,
+}
+
+impl ParallelStageHandler {
+    pub fn new(name: impl Into<String>, handlers: Vec<Arc<dyn Handler<LambdaExchange>>>) -> Self {
+        Self {
+            name: name.into(),
+            handlers,
+        }
+    }
+
+    /* an exchange for one handler, with the request and what the chain so far learned about it */
+    fn scratch(exchange: &LambdaExchange, request: &ApiGatewayProxyRequest) -> LambdaExchange {
+        let mut scratch: LambdaExchange = Exchange::new();
+        scratch.set_input(request.clone());
+        if let Ok(metadata) = exchange.metadata() {
+            scratch.set_metadata(metadata.clone());
+        }
+        if let Some(route) = matched_route(exchange) {
+            set_route(&mut scratch, route);
+        }
+        if let Some(parameters) = path_parameters(exchange) {
+            set_path_parameters(&mut scratch, parameters.clone());
+        }
+        if let Some(metadata) = route_metadata(exchange) {
+            set_route_metadata(&mut scratch, metadata.clone());
+        }
+        if let Some(deadline) = deadline(exchange) {
+            set_deadline(&mut scratch, deadline);
+        }
+        for key in [CORRELATION_ID_ATTACHMENT_KEY, TRACEABILITY_ID_ATTACHMENT_KEY] {
+            if let Some(id) = exchange.attachments().well_known().get::<String>(key) {
+                let id = id.clone();
+                scratch.attachments_mut().well_known_mut().add::<String>(key, id);
+            }
+        }
+        scratch
+    }
+}
+
+#[async_trait]
+impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for ParallelStageHandler {
+    async fn exec(
+        &self,
+        exchange: &mut LambdaExchange,
+    ) -> Result<HandlerStatus, Infallible> {
+        let request = match exchange.input().await {
+            Ok(request) => request.clone(),
+            Err(_) => {
+                return Ok(
+                    HandlerStatus::new(ExchangeState::SERVER_ERROR).message("Unable to get request")
+                );
+            }
+        };
+
+        let runs = self.handlers.iter().map(|handler| {
+            let mut scratch = Self::scratch(exchange, &request);
+            async move {
+                let status = match handler.exec(&mut scratch).await {
+                    Ok(status) => status,
+                    Err(never) => match never {},
+                };
+                (status, scratch)
+            }
+        });
+
+        for (status, mut scratch) in join_all(runs).await {
+            let code = status.code();
+            if code.is_in_flight() || code == ExchangeState::DISABLED {
+                continue;
+            }
+            if let Some(error) = handler_error(&scratch) {
+                error.clone().raise(exchange);
+            }
+            if let Ok(output) = scratch.take_output().await {
+                exchange.set_output(output);
+            }
+            return Ok(status);
+        }
+        Ok(HandlerStatus::new(ExchangeState::OK))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{handler_error, ERR10008_ADDRESS_FORBIDDEN, ERR13000_INTERNAL};
+    use crate::executor::deadline::{deadline, set_deadline, Deadline};
+    use crate::flow::matcher::{matched_route, path_parameters, set_route};
+    use crate::flow::registry::HandlerFactories;
+    use crate::flow::ExecutionFlowConfig;
+    use crate::handler::parallel::ParallelStageHandler;
+    use crate::handler::LambdaExchange;
+    use crate::testkit::{RequestBuilder, TestGateway};
+    use async_trait::async_trait;
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::{ExchangeState, HandlerStatus};
+    use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct FixedStatusHandler(ExchangeState, Option<i64>);
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for FixedStatusHandler {
+        async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            if let Some(status_code) = self.1 {
+                exchange.set_output(ApiGatewayProxyResponse {
+                    status_code,
+                    ..Default::default()
+                });
+            }
+            Ok(HandlerStatus::new(self.0))
+        }
+
+        fn name(&self) -> &str {
+            "FixedStatusHandler"
+        }
+    }
+
+    /* forbids `/private/{id}`, and fails when the stage hid the route or deadline */
+    struct RouteGuardHandler;
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for RouteGuardHandler {
+        async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            let has_id = path_parameters(exchange).is_some_and(|p| p.contains_key("id"));
+            match matched_route(exchange) {
+                Some("/private/{id}") => Ok(ERR10008_ADDRESS_FORBIDDEN.raise(exchange)),
+                Some(_) if has_id && deadline(exchange).is_some() => {
+                    Ok(HandlerStatus::new(ExchangeState::OK))
+                }
+                _ => Ok(ERR13000_INTERNAL.raise(exchange)),
+            }
+        }
+
+        fn name(&self) -> &str {
+            "RouteGuardHandler"
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_parallel_stage_reports_first_stop() {
+        let stage = ParallelStageHandler::new(
+            "checks",
+            vec![
+                Arc::new(FixedStatusHandler(ExchangeState::OK, None)),
+                Arc::new(FixedStatusHandler(ExchangeState::DISABLED, None)),
+                Arc::new(FixedStatusHandler(ExchangeState::CLIENT_ERROR, Some(403))),
+                Arc::new(FixedStatusHandler(ExchangeState::SERVER_ERROR, Some(500))),
+            ],
+        );
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(ApiGatewayProxyRequest::default());
+        let status = stage.exec(&mut exchange).await.unwrap();
+        assert!(status.code().any_flags(ExchangeState::CLIENT_ERROR));
+        assert_eq!(exchange.output().await.unwrap().status_code, 403);
+
+        let passing = ParallelStageHandler::new(
+            "passing",
+            vec![Arc::new(FixedStatusHandler(ExchangeState::OK, None))],
+        );
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(ApiGatewayProxyRequest::default());
+        assert!(passing.exec(&mut exchange).await.unwrap().code().is_in_flight());
+    }
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_parallel_stage_raises_handler_error() {
+        let stage = ParallelStageHandler::new(
+            "checks",
+            vec![
+                Arc::new(FixedStatusHandler(ExchangeState::OK, None)),
+                Arc::new(RouteGuardHandler),
+            ],
+        );
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(ApiGatewayProxyRequest::default());
+        set_route(&mut exchange, "/private/{id}");
+        set_deadline(&mut exchange, Deadline::after(Duration::from_secs(5)));
+        let status = stage.exec(&mut exchange).await.unwrap();
+        assert!(status.code().any_flags(ExchangeState::CLIENT_ERROR));
+        assert_eq!(handler_error(&exchange).unwrap().code.code, "ERR10008");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_parallel_stage_in_flow() {
+        let flow: ExecutionFlowConfig = serde_json::from_str(
+            r#"{
+                "handlers": ["RouteGuardHandler", "CheckHandler", "ReplyHandler"],
+                "parallel": { "checks": ["RouteGuardHandler", "CheckHandler"] },
+                "paths": {
+                    "/pets/{id}": { "method": "GET", "exec": ["checks", "ReplyHandler"] },
+                    "/private/{id}": { "method": "GET", "exec": ["checks", "ReplyHandler"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut factories = HandlerFactories::new();
+        factories.register("RouteGuardHandler", || Box::new(RouteGuardHandler));
+        factories.register("CheckHandler", || {
+            Box::new(FixedStatusHandler(ExchangeState::OK, None))
+        });
+        factories.register("ReplyHandler", || {
+            Box::new(FixedStatusHandler(ExchangeState::OK, Some(200)))
+        });
+        let gateway = TestGateway::new(&flow, &factories).unwrap();
+
+        gateway.send(RequestBuilder::get("/pets/1").build()).await.assert_status(200);
+        let response = gateway.send(RequestBuilder::get("/private/1").build()).await;
+        response.assert_status(403);
+        assert_eq!(response.body_json()["code"], "ERR10008");
+
+        let mut unlisted = flow.clone();
+        unlisted.handlers.retain(|handler| handler != "CheckHandler");
+        assert!(TestGateway::new(&unlisted, &factories).is_err());
+    }
+}
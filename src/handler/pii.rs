@@ -0,0 +1,467 @@
+use crate::error::{ERR11000_INVALID_REQUEST, ERR13000_INTERNAL};
+use crate::flow::builder::NamedHandler;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::body::BodyAccess;
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::http::{HeaderMap, HeaderName, HeaderValue};
+use lambda_http::Body;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::sync::OnceLock;
+
+const RESPONSE_CONFIG_ATTACHMENT_KEY: &str = "response_config";
+/* hex characters of a hash kept in the masked value */
+const HASH_LENGTH: usize = 16;
+
+/// A regex for a custom kind of PII, compiled when the config is loaded.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "String")]
+pub struct PiiPattern(Regex);
+
+impl TryFrom<String> for PiiPattern {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Regex::new(&value)
+            .map(PiiPattern)
+            .map_err(|e| format!("Invalid PII pattern '{}': {}", value, e))
+    }
+}
+
+/// How a detected value is masked:
+///
+/// - `full`: replaced by `[REDACTED:<kind>]`.
+/// - `partial`: all but the last four characters starred, like `****-****-****-1111`; emails
+///   keep their first character and domain, like `j***@example.com`.
+/// - `hash`: replaced by `[<kind>:<hash>]`, so the same value can still be correlated.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingStrategy {
+    #[default]
+    Full,
+    Partial,
+    Hash,
+}
+
+/// A kind of PII: `email`, `credit_card` (numbers passing the Luhn check), `ssn`, or a
+/// `pattern` with a `name` and a `regex`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    CreditCard,
+    Ssn,
+    Pattern { name: String, regex: PiiPattern },
+}
+
+impl PiiKind {
+    fn name(&self) -> &str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::CreditCard => "credit_card",
+            PiiKind::Ssn => "ssn",
+            PiiKind::Pattern { name, .. } => name,
+        }
+    }
+
+    fn regex(&self) -> &Regex {
+        static EMAIL: OnceLock<Regex> = OnceLock::new();
+        static CREDIT_CARD: OnceLock<Regex> = OnceLock::new();
+        static SSN: OnceLock<Regex> = OnceLock::new();
+        let builtin = |regex: &'static OnceLock<Regex>, pattern: &str| {
+            regex.get_or_init(|| Regex::new(pattern).unwrap())
+        };
+        match self {
+            PiiKind::Email => builtin(
+                &EMAIL,
+                r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            ),
+            PiiKind::CreditCard => builtin(&CREDIT_CARD, r"\b\d(?:[ -]?\d){12,18}\b"),
+            PiiKind::Ssn => builtin(&SSN, r"\b\d{3}-\d{2}-\d{4}\b"),
+            PiiKind::Pattern { regex, .. } => &regex.0,
+        }
+    }
+
+    /* rules out the matches the regex alone can't */
+    fn confirms(&self, found: &str) -> bool {
+        match self {
+            PiiKind::CreditCard => luhn(found),
+            PiiKind::Ssn => {
+                let mut parts = found.split('-');
+                let (area, group, serial) = (parts.next(), parts.next(), parts.next());
+                !matches!(area, Some("000" | "666"))
+                    && !area.is_some_and(|area| area.starts_with('9'))
+                    && group != Some("00")
+                    && serial != Some("0000")
+            }
+            _ => true,
+        }
+    }
+}
+
+fn luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match i % 2 {
+            0 => *digit,
+            _ if *digit * 2 > 9 => *digit * 2 - 9,
+            _ => *digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// A kind of PII to look for, masked with the handler's strategy unless it has its own.
+#[derive(Deserialize, Clone, Debug)]
+pub struct PiiDetector {
+    #[serde(flatten)]
+    pub kind: PiiKind,
+    #[serde(default)]
+    pub masking: Option<MaskingStrategy>,
+}
+
+/// Masks PII in request and response bodies, and in the listed headers, before later handlers,
+/// logs and the backend see it:
+///
+/// ```json
+/// { "enabled": true, "masking": "partial",
+///   "detectors": [{ "type": "email" }, { "type": "credit_card", "masking": "hash" },
+///                 { "type": "pattern", "name": "member_id", "regex": "M-\\d{6}" }],
+///   "headers": ["x-customer-email"], "allow_fields": ["support_email", "billing.contact"] }
+/// ```
+///
+/// JSON bodies are masked value by value. The fields in `allow_fields`, by name or by dotted
+/// path from the root, are left as they are. Other text bodies are masked as a whole. Hashes
+/// are HMACs under `hash_key` when one is set, so guessable values like SSNs can't be recovered
+/// by hashing candidates.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PiiRedactionHandlerConfig {
+    pub enabled: bool,
+    pub detectors: Vec<PiiDetector>,
+    pub masking: MaskingStrategy,
+    pub hash_key: Option<String>,
+    pub request_body: bool,
+    pub response_body: bool,
+    pub headers: Vec<String>,
+    pub allow_fields: Vec<String>,
+}
+
+impl Default for PiiRedactionHandlerConfig {
+    fn default() -> Self {
+        let detector = |kind| PiiDetector {
+            kind,
+            masking: None,
+        };
+        Self {
+            enabled: true,
+            detectors: vec![
+                detector(PiiKind::Email),
+                detector(PiiKind::CreditCard),
+                detector(PiiKind::Ssn),
+            ],
+            masking: MaskingStrategy::default(),
+            hash_key: None,
+            request_body: true,
+            response_body: true,
+            headers: vec![],
+            allow_fields: vec![],
+        }
+    }
+}
+
+fn partial(kind: &PiiKind, found: &str) -> String {
+    if let (PiiKind::Email, Some((local, domain))) = (kind, found.split_once('@')) {
+        let first = local.chars().next().unwrap_or('*');
+        return format!("{}***@{}", first, domain);
+    }
+    let total = found.chars().filter(|c| c.is_alphanumeric()).count();
+    let mut seen = 0;
+    found
+        .chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if total > 4 && seen > total - 4 { c } else { '*' }
+        })
+        .collect()
+}
+
+impl PiiRedactionHandlerConfig {
+    fn hash(&self, found: &str) -> String {
+        let digest = match &self.hash_key {
+            Some(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+                    .expect("HMAC takes keys of any length");
+                mac.update(found.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            None => Sha256::digest(found.as_bytes()).to_vec(),
+        };
+        digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()[..HASH_LENGTH]
+            .to_string()
+    }
+
+    fn mask(&self, detector: &PiiDetector, found: &str) -> String {
+        match detector.masking.unwrap_or(self.masking) {
+            MaskingStrategy::Full => format!("[REDACTED:{}]", detector.kind.name()),
+            MaskingStrategy::Partial => partial(&detector.kind, found),
+            MaskingStrategy::Hash => format!("[{}:{}]", detector.kind.name(), self.hash(found)),
+        }
+    }
+
+    /// The text with the PII it contains masked; borrowed when there was none.
+    pub fn redact_text<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let mut text = Cow::Borrowed(text);
+        for detector in &self.detectors {
+            let replaced = detector.kind.regex().replace_all(&text, |captures: &Captures| {
+                let found = &captures[0];
+                if detector.kind.confirms(found) {
+                    self.mask(detector, found)
+                } else {
+                    found.to_string()
+                }
+            });
+            let replaced = match replaced {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(replaced) = replaced {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+
+    /* masks the string values outside allowed fields, returns whether any changed */
+    fn redact_json(&self, value: &mut Value, path: &str) -> bool {
+        match value {
+            Value::String(text) => {
+                let redacted = match self.redact_text(text) {
+                    Cow::Owned(redacted) if redacted != *text => Some(redacted),
+                    _ => None,
+                };
+                redacted.map(|redacted| *text = redacted).is_some()
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .fold(false, |changed, item| self.redact_json(item, path) | changed),
+            Value::Object(fields) => fields.iter_mut().fold(false, |changed, (name, field)| {
+                let path = match path {
+                    "" => name.clone(),
+                    parent => format!("{}.{}", parent, name),
+                };
+                let allowed = self
+                    .allow_fields
+                    .iter()
+                    .any(|allowed| *allowed == *name || *allowed == path);
+                (!allowed && self.redact_json(field, &path)) | changed
+            }),
+            _ => false,
+        }
+    }
+
+    fn redact_headers(&self, headers: &mut HeaderMap) {
+        for name in &self.headers {
+            let values: Vec<HeaderValue> = headers
+                .get_all(name.as_str())
+                .iter()
+                .map(|value| match value.to_str() {
+                    Ok(text) => HeaderValue::from_str(&self.redact_text(text))
+                        .unwrap_or_else(|_| value.clone()),
+                    Err(_) => value.clone(),
+                })
+                .collect();
+            let Ok(name) = name.parse::<HeaderName>() else {
+                continue;
+            };
+            headers.remove(&name);
+            for value in values {
+                headers.append(&name, value);
+            }
+        }
+    }
+
+    fn redact_body(&self, body: &mut Option<Body>) {
+        let Some(Body::Text(text)) = body else {
+            return;
+        };
+        let redacted = match serde_json::from_str::<Value>(text) {
+            Ok(mut json) => self.redact_json(&mut json, "").then(|| json.to_string()),
+            Err(_) => match self.redact_text(text) {
+                Cow::Owned(redacted) => Some(redacted),
+                Cow::Borrowed(_) => None,
+            },
+        };
+        if let Some(redacted) = redacted {
+            *body = Some(Body::Text(redacted));
+        }
+    }
+}
+
+pub struct PiiRedactionHandler {
+    pub(crate) config: Config<PiiRedactionHandlerConfig>,
+}
+
+impl PiiRedactionHandler {
+    async fn redact_request_body(
+        config: &PiiRedactionHandlerConfig,
+        exchange: &mut LambdaExchange,
+    ) -> Result<(), ()> {
+        if let Some(json) = BodyAccess::json(exchange).await? {
+            let mut json = json.clone();
+            if config.redact_json(&mut json, "") {
+                BodyAccess::set_json(exchange, json).await?;
+            }
+            return Ok(());
+        }
+        let redacted = match BodyAccess::bytes(exchange).await? {
+            Some(bytes) => match std::str::from_utf8(bytes).map(|text| config.redact_text(text)) {
+                Ok(Cow::Owned(redacted)) => Some(redacted),
+                _ => None,
+            },
+            None => None,
+        };
+        match redacted {
+            Some(redacted) => BodyAccess::set_text(exchange, redacted).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl NamedHandler for PiiRedactionHandler {
+    const NAME: &'static str = "PiiRedactionHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for PiiRedactionHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        match exchange.input_mut().await {
+            Ok(request) => config.redact_headers(&mut request.headers),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        }
+        if config.request_body && Self::redact_request_body(config, exchange).await.is_err() {
+            return Ok(ERR11000_INVALID_REQUEST
+                .error()
+                .detail("Request body is not valid base64")
+                .raise(exchange));
+        }
+
+        if config.response_body || !config.headers.is_empty() {
+            exchange
+                .attachments_mut()
+                .scoped_mut(Self::NAME)
+                .add::<PiiRedactionHandlerConfig>(RESPONSE_CONFIG_ATTACHMENT_KEY, config.clone());
+            exchange.add_output_listener(|response, attachments| {
+                let attachments = attachments.scoped(Self::NAME);
+                let Some(config) =
+                    attachments.get::<PiiRedactionHandlerConfig>(RESPONSE_CONFIG_ATTACHMENT_KEY)
+                else {
+                    return;
+                };
+                config.redact_headers(&mut response.headers);
+                if config.response_body {
+                    config.redact_body(&mut response.body);
+                }
+            });
+        }
+        Ok(HandlerStatus::new(ExchangeState::OK))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::pii::{luhn, PiiRedactionHandlerConfig};
+    use serde_json::json;
+
+    #[test]
+    fn test_detectors_and_masking() {
+        assert!(luhn("4111 1111 1111 1111"));
+        assert!(!luhn("4111 1111 1111 1112"));
+
+        let config = PiiRedactionHandlerConfig::default();
+        let text = "mail jane.doe@example.com, card 4111-1111-1111-1111, \
+                    order 4111-1111-1111-1112, ssn 123-45-6789, not 000-12-3456";
+        assert_eq!(
+            config.redact_text(text),
+            "mail [REDACTED:email], card [REDACTED:credit_card], \
+             order 4111-1111-1111-1112, ssn [REDACTED:ssn], not 000-12-3456"
+        );
+        assert!(matches!(config.redact_text("nothing here"), std::borrow::Cow::Borrowed(_)));
+
+        let config: PiiRedactionHandlerConfig = serde_json::from_value(json!({
+            "masking": "partial",
+            "detectors": [
+                { "type": "email" },
+                { "type": "credit_card" },
+                { "type": "ssn", "masking": "hash" },
+                { "type": "pattern", "name": "member", "regex": "M-\\d{6}" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(config.redact_text("jane@example.com"), "j***@example.com");
+        assert_eq!(config.redact_text("4111-1111-1111-1111"), "****-****-****-1111");
+        assert_eq!(config.redact_text("M-123456"), "*-**3456");
+        let hashed = config.redact_text("123-45-6789");
+        assert!(hashed.starts_with("[ssn:") && hashed.len() == "[ssn:]".len() + 16);
+        assert_eq!(config.redact_text("123-45-6789"), hashed);
+    }
+
+    #[test]
+    fn test_json_allowlist() {
+        let config: PiiRedactionHandlerConfig =
+            serde_json::from_value(json!({ "allow_fields": ["support", "billing.contact"] }))
+                .unwrap();
+        let mut body = json!({
+            "support": "help@example.com",
+            "customer": { "contact": "jane@example.com", "cards": ["4111111111111111"] },
+            "billing": { "contact": "ap@example.com" },
+            "count": 4111111111111111u64
+        });
+        assert!(config.redact_json(&mut body, ""));
+        assert_eq!(
+            body,
+            json!({
+                "support": "help@example.com",
+                "customer": { "contact": "[REDACTED:email]", "cards": ["[REDACTED:credit_card]"] },
+                "billing": { "contact": "ap@example.com" },
+                "count": 4111111111111111u64
+            })
+        );
+        assert!(!config.redact_json(&mut json!({ "name": "Jane" }), ""));
+    }
+}
@@ -1,34 +1,368 @@
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use serde::{Deserialize};
-use std::ops::Add;
+use std::time::{Duration, Instant, SystemTime};
 use async_trait::async_trait;
-use aws_config::BehaviorVersion;
+use aws_sdk_lambda::error::ProvideErrorMetadata;
 use aws_sdk_lambda::primitives::Blob;
-use aws_sdk_lambda::Client as LambdaClient;
 use idemio::config::Config;
 use idemio::exchange::Exchange;
 use idemio::handler::Handler;
 use idemio::status::{ExchangeState, HandlerStatus};
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::{HeaderName, HeaderValue};
 use lambda_http::Context;
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use crate::executor::exception::{correlation_id, ErrorContext, ErrorHandler, StatusClass};
+use crate::executor::pool::HandlerLifecycle;
+use crate::executor::response_mapper::{ResponseMapper, ResponseMapping};
+use crate::executor::sampling;
+use crate::executor::xray::{is_sampled, record_subsegment, Subsegment};
+use crate::flow::builder::NamedHandler;
+use crate::config::units::{deserialize_optional_byte_size, deserialize_optional_duration};
+use crate::error::{
+    ERR11000_INVALID_REQUEST, ERR11001_PAYLOAD_TOO_LARGE, ERR11002_NO_ROUTE,
+    ERR12000_UPSTREAM_FAILED, ERR12001_UPSTREAM_TIMEOUT, ERR12002_UPSTREAM_RESPONSE_TOO_LARGE,
+    ERR12003_UPSTREAM_BUSY, ERR13000_INTERNAL,
+};
+use crate::flow::matcher::request_host;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::function_routes::FunctionRoutes;
+use crate::handler::http_backend::{HttpBackendError, HttpTarget};
+use crate::handler::lambda::{lambda_client, LambdaClientConfig};
+use crate::handler::response_template::ResponseTemplate;
+use crate::handler::traceability::{CORRELATION_ID_ATTACHMENT_KEY, TRACEABILITY_ID_ATTACHMENT_KEY};
 use crate::handler::LambdaExchange;
+use crate::metrics::{self, names, Unit};
 
 #[derive(Deserialize, Default)]
+#[serde(default)]
 pub(crate) struct LambdaProxyHandlerConfig {
     pub enabled: bool,
-    pub functions: HashMap<String, String>
+    /* functions by "/path@METHOD", "host/path@METHOD" for a single host, or path templates
+    and globs like "/pets/{id}@GET" */
+    pub functions: FunctionRoutes<FunctionTarget>,
+    /* alias or version for functions that don't name one, e.g. "prod" */
+    #[serde(default)]
+    pub default_qualifier: Option<String>,
+    /* e.g. "3s" or "500ms", no timeout when unset */
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub timeout: Option<Duration>,
+    /* e.g. "1MB", capped at the synchronous invoke payload limit */
+    #[serde(default, deserialize_with = "deserialize_optional_byte_size")]
+    pub max_payload_size: Option<usize>,
+    /* e.g. "2MB", capped at API Gateway's response limit */
+    #[serde(default, deserialize_with = "deserialize_optional_byte_size")]
+    pub max_response_size: Option<usize>,
+    /* in-flight invokes of any one function in this container, unlimited when unset */
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /* how long a request waits for a free slot, forever when unset */
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub queue_timeout: Option<Duration>,
+    /* headers the correlation and traceability ids are passed to the function in */
+    #[serde(default)]
+    pub correlation_header_name: Option<String>,
+    #[serde(default)]
+    pub traceability_header_name: Option<String>,
+    /* region, endpoint_override and api_call_timeout */
+    #[serde(flatten)]
+    pub client: LambdaClientConfig,
 }
 
+impl LambdaProxyHandlerConfig {
+    fn payload_limit(&self) -> usize {
+        self.max_payload_size
+            .map_or(LAMBDA_PAYLOAD_LIMIT, |limit| limit.min(LAMBDA_PAYLOAD_LIMIT))
+    }
+
+    fn response_limit(&self) -> usize {
+        self.max_response_size
+            .map_or(API_GATEWAY_RESPONSE_LIMIT, |limit| limit.min(API_GATEWAY_RESPONSE_LIMIT))
+    }
+
+    /* the size of what the gateway function would return to API Gateway, when over the limit */
+    fn oversized(&self, response: &ApiGatewayProxyResponse) -> Option<usize> {
+        let size = serde_json::to_vec(response).map_or(0, |response| response.len());
+        (size > self.response_limit()).then_some(size)
+    }
+}
+
+/// A function and how to invoke it:
+///
+/// ```json
+/// { "name": "pets", "qualifier": "beta", "response": { "body_path": "/result" },
+///   "timeout": "2s", "errors": { "PetNotFound": { "status_code": 404 },
+///   "Timeout": { "status_code": 503, "headers": { "retry-after": "5" } } } }
+/// ```
+///
+/// Errors are looked up by the `errorType` the function reports, then its `FunctionError`
+/// (`Unhandled` for crashes), and by the SDK error code when the invoke itself fails. Unmapped
+/// errors keep the generic upstream responses.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct FunctionSpec {
+    pub name: String,
+    /* alias or version */
+    #[serde(default)]
+    pub qualifier: Option<String>,
+    /* for functions returning plain JSON instead of a proxy response */
+    #[serde(default)]
+    pub response: Option<ResponseTemplate>,
+    /* overrides the handler's timeout */
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub timeout: Option<Duration>,
+    /* overrides the handler's max_concurrency */
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /* responses by error type, function error or SDK error code, or "Timeout" */
+    #[serde(default)]
+    pub errors: HashMap<String, ResponseMapping>,
+}
+
+/// What a route is proxied to: a function, by name or as a [`FunctionSpec`], or an HTTP backend
+/// (`"type": "http"`, see [`HttpTarget`]).
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub(crate) enum FunctionTarget {
+    Name(String),
+    Http(HttpTarget),
+    Spec(FunctionSpec),
+}
+
+impl FunctionTarget {
+    /// The function name, or the URL of an HTTP backend.
+    pub fn name(&self) -> &str {
+        match self {
+            FunctionTarget::Name(name) => name,
+            FunctionTarget::Http(http) => &http.url,
+            FunctionTarget::Spec(spec) => &spec.name,
+        }
+    }
 
+    /// The target's own qualifier, or the default one when it has none.
+    pub fn qualifier<'a>(&'a self, default: Option<&'a str>) -> Option<&'a str> {
+        match self {
+            FunctionTarget::Spec(FunctionSpec {
+                qualifier: Some(qualifier),
+                ..
+            }) => Some(qualifier),
+            FunctionTarget::Http(_) => None,
+            _ => default,
+        }
+    }
 
-const FUNCTION_NAME_SEPARATOR: &str = "@";
+    pub fn response_template(&self) -> Option<&ResponseTemplate> {
+        match self {
+            FunctionTarget::Spec(spec) => spec.response.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The target's own timeout, or the default one when it has none.
+    pub fn timeout(&self, default: Option<Duration>) -> Option<Duration> {
+        match self {
+            FunctionTarget::Spec(FunctionSpec {
+                timeout: Some(timeout),
+                ..
+            })
+            | FunctionTarget::Http(HttpTarget {
+                timeout: Some(timeout),
+                ..
+            }) => Some(*timeout),
+            _ => default,
+        }
+    }
+
+    /// The target's own concurrency limit, or the default one when it has none.
+    pub fn max_concurrency(&self, default: Option<usize>) -> Option<usize> {
+        match self {
+            FunctionTarget::Spec(FunctionSpec {
+                max_concurrency: Some(limit),
+                ..
+            })
+            | FunctionTarget::Http(HttpTarget {
+                max_concurrency: Some(limit),
+                ..
+            }) => Some(*limit),
+            _ => default,
+        }
+    }
+
+    /// The response for the first of the error keys that has one.
+    pub fn error_mapping(&self, keys: &[&str]) -> Option<&ResponseMapping> {
+        let FunctionTarget::Spec(spec) = self else {
+            return None;
+        };
+        keys.iter().find_map(|key| spec.errors.get(*key))
+    }
+}
+
+/* Execution slots by function and limit, shared by every pooled instance of the handler; a
+reloaded limit gets fresh slots. */
+fn function_slots(function_name: &str, limit: usize) -> Arc<Semaphore> {
+    type Slots = HashMap<(String, usize), Arc<Semaphore>>;
+    static SLOTS: OnceLock<Mutex<Slots>> = OnceLock::new();
+    let mut slots = SLOTS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    slots
+        .entry((function_name.to_string(), limit))
+        .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+        .clone()
+}
+
+/* the `errorType` of a function's error payload, e.g. `{"errorType":"PetNotFound",...}` */
+fn error_type(payload: Option<&Blob>) -> Option<String> {
+    let payload: Value = serde_json::from_slice(payload?.as_ref()).ok()?;
+    payload.get("errorType")?.as_str().map(str::to_string)
+}
+
+/* the synchronous invoke limit on the request payload */
+const LAMBDA_PAYLOAD_LIMIT: usize = 6 * 1024 * 1024;
+/* the limit on the response API Gateway takes from the gateway function */
+const API_GATEWAY_RESPONSE_LIMIT: usize = 10 * 1024 * 1024;
+/* the error mapping used when the invoke takes longer than the timeout */
+const TIMEOUT_ERROR_KEY: &str = "Timeout";
+/* the TraceabilityHandler's default header names */
+const DEFAULT_CORRELATION_HEADER: &str = "x-correlation";
+const DEFAULT_TRACEABILITY_HEADER: &str = "x-trace";
 
 //#[derive(ConfigurableHandler)]
 pub struct LambdaProxyHandler {
     pub(crate) config: Config<LambdaProxyHandlerConfig>,
 }
 
+impl LambdaProxyHandler {
+    pub(crate) fn new(config: Config<LambdaProxyHandlerConfig>) -> Self {
+        Self { config }
+    }
+
+    async fn forward_http(
+        &self,
+        exchange: &mut LambdaExchange,
+        http: &HttpTarget,
+        request: &ApiGatewayProxyRequest,
+        path: &str,
+        timeout: Option<Duration>,
+    ) -> HandlerStatus {
+        let started = Instant::now();
+        let result = http.forward(request, path, timeout).await;
+        metrics::histogram(
+            names::PROXY_LATENCY,
+            started.elapsed().as_secs_f64() * 1000.0,
+            Unit::Milliseconds,
+            &[("function", &http.url)],
+        );
+        let correlation_id = correlation_id(exchange);
+        match result {
+            Ok(response) => {
+                if let Some(response_size) = self.config.get().oversized(&response) {
+                    tracing::warn!(
+                        backend = %http.url,
+                        correlation_id = %correlation_id,
+                        "HTTP backend response of {} bytes is over the limit",
+                        response_size
+                    );
+                    return ERR12002_UPSTREAM_RESPONSE_TOO_LARGE
+                        .error()
+                        .detail("HTTP backend response too large.")
+                        .raise(exchange);
+                }
+                exchange.set_output(response);
+                HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED)
+            }
+            Err(HttpBackendError::Timeout) => {
+                tracing::warn!(
+                    backend = %http.url,
+                    correlation_id = %correlation_id,
+                    "HTTP backend timed out"
+                );
+                ERR12001_UPSTREAM_TIMEOUT
+                    .error()
+                    .detail("HTTP backend timed out.")
+                    .raise(exchange)
+            }
+            Err(HttpBackendError::Failed(e)) => {
+                tracing::warn!(
+                    backend = %http.url,
+                    correlation_id = %correlation_id,
+                    "HTTP backend could not be reached: {}",
+                    e
+                );
+                ERR12000_UPSTREAM_FAILED
+                    .error()
+                    .detail("Failed to reach HTTP backend.")
+                    .raise(exchange)
+            }
+        }
+    }
+
+    /* Answers with the function's mapped error response, when it has one for the keys. */
+    fn mapped_error(
+        &self,
+        exchange: &mut LambdaExchange,
+        target: &FunctionTarget,
+        keys: &[&str],
+        status_class: StatusClass,
+    ) -> Option<HandlerStatus> {
+        let mapping = target.error_mapping(keys)?;
+        let correlation_id = correlation_id(exchange);
+        let response = ResponseMapper::new(mapping.clone()).handle(&ErrorContext {
+            status_class: Some(status_class),
+            handler_name: Some(Self::NAME),
+            correlation_id: &correlation_id,
+            error: None,
+        });
+        exchange.set_output(response);
+        Some(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED))
+    }
+
+    /// Passes the correlation and traceability ids of the exchange on to the function in the
+    /// request headers, so its logs and traces connect to the gateway's.
+    fn propagate_ids(&self, exchange: &LambdaExchange, request: &mut ApiGatewayProxyRequest) {
+        let config = self.config.get();
+        let well_known = exchange.attachments().well_known();
+        let ids = [
+            (
+                config.correlation_header_name.as_deref().unwrap_or(DEFAULT_CORRELATION_HEADER),
+                well_known.get::<String>(CORRELATION_ID_ATTACHMENT_KEY),
+            ),
+            (
+                config.traceability_header_name.as_deref().unwrap_or(DEFAULT_TRACEABILITY_HEADER),
+                well_known.get::<String>(TRACEABILITY_ID_ATTACHMENT_KEY),
+            ),
+        ];
+        for (header, id) in ids {
+            let (Ok(name), Some(Ok(value))) =
+                (HeaderName::try_from(header), id.map(|id| HeaderValue::from_str(id)))
+            else {
+                continue;
+            };
+            /* the function reads the multi-value headers when there are any */
+            if !request.multi_value_headers.is_empty() {
+                request.multi_value_headers.insert(name.clone(), value.clone());
+            }
+            request.headers.insert(name, value);
+        }
+    }
+}
+
+impl NamedHandler for LambdaProxyHandler {
+    const NAME: &'static str = "LambdaProxyHandler";
+}
+
+#[async_trait]
+impl HandlerLifecycle for LambdaProxyHandler {
+    async fn on_init(&self) -> Result<(), String> {
+        /* loading the SDK config is the slow part of the first invoke */
+        lambda_client(&self.config.get().client).await;
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for LambdaProxyHandler {
 
@@ -38,70 +372,356 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     ) -> Result<HandlerStatus, Infallible>
 
     {
-        let client =
-            LambdaClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await);
         if !self.config.get().enabled {
             return Ok(HandlerStatus::new(ExchangeState::DISABLED));
         }
+        let client = lambda_client(&self.config.get().client).await;
 
         match exchange.take_input().await {
-            Ok(request) => {
+            Ok(mut request) => {
+                self.propagate_ids(exchange, &mut request);
+                let host = request_host(&request);
                 let payload = serde_json::to_string(&request).unwrap();
-                let path = match request.path {
+                let payload_limit = self.config.get().payload_limit();
+                if payload.len() > payload_limit {
+                    return Ok(ERR11001_PAYLOAD_TOO_LARGE
+                        .error()
+                        .detail(format!(
+                            "Request payload of {} bytes is over the {} byte limit.",
+                            payload.len(),
+                            payload_limit
+                        ))
+                        .metadata("limit", payload_limit.to_string())
+                        .raise(exchange));
+                }
+                let path = match request.path.clone() {
                     Some(path) => path,
                     _ => {
-                        return Ok(HandlerStatus::new(ExchangeState::CLIENT_ERROR)
-                            .message("Missing path in request."))
+                        return Ok(ERR11000_INVALID_REQUEST
+                            .error()
+                            .detail("Missing path in request.")
+                            .raise(exchange))
                     }
                 };
-                let method = request.http_method;
-                let function_key = path.add(FUNCTION_NAME_SEPARATOR).add(method.as_str());
-                let function_name = match self.config.get().functions.get(&function_key) {
+                let functions = &self.config.get().functions;
+                let method = request.http_method.as_str();
+                let target = match functions.find(host.as_deref(), &path, method) {
                     None => {
-                        return Ok(HandlerStatus::new(ExchangeState::CLIENT_ERROR)
-                            .message("No function found for path and method combination."))
+                        return Ok(ERR11002_NO_ROUTE
+                            .error()
+                            .detail("No function found for path and method combination.")
+                            .raise(exchange))
+                    }
+                    Some(target) => target,
+                };
+                let function_name = target.name().to_string();
+                let qualifier = target
+                    .qualifier(self.config.get().default_qualifier.as_deref())
+                    .map(str::to_string);
+                let correlation_id = correlation_id(exchange);
+                tracing::debug!(
+                    function = %function_name,
+                    qualifier = ?qualifier,
+                    correlation_id = %correlation_id,
+                    "Invoking Lambda function"
+                );
+                let max_concurrency = target.max_concurrency(self.config.get().max_concurrency);
+                let _permit = match max_concurrency {
+                    Some(limit) => {
+                        let acquire = function_slots(&function_name, limit).acquire_owned();
+                        let permit = match self.config.get().queue_timeout {
+                            Some(queue_timeout) => {
+                                tokio::time::timeout(queue_timeout, acquire).await.ok()
+                            }
+                            None => Some(acquire.await),
+                        };
+                        match permit {
+                            Some(Ok(permit)) => Some(permit),
+                            _ => {
+                                tracing::warn!(
+                                    function = %function_name,
+                                    correlation_id = %correlation_id,
+                                    "No free slot for the Lambda function"
+                                );
+                                return Ok(ERR12003_UPSTREAM_BUSY
+                                    .error()
+                                    .detail(format!(
+                                        "{} already has {} requests in flight.",
+                                        function_name, limit
+                                    ))
+                                    .raise(exchange));
+                            }
+                        }
                     }
-                    Some(function) => function.clone(),
+                    None => None,
                 };
+                if let FunctionTarget::Http(http) = target {
+                    let timeout = self.config.get().timeout;
+                    return Ok(self.forward_http(exchange, http, &request, &path, timeout).await);
+                }
                 let proxy_blob = Blob::new(payload);
-                match client
+                let invoked_at = SystemTime::now();
+                let invoke_started = Instant::now();
+                let invoke = client
                     .invoke()
                     .function_name(&function_name)
+                    .set_qualifier(qualifier)
                     .payload(proxy_blob)
-                    .send()
-                    .await
-                {
+                    .send();
+                let invoke_result = match target.timeout(self.config.get().timeout) {
+                    Some(timeout) => match tokio::time::timeout(timeout, invoke).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            tracing::warn!(
+                                function = %function_name,
+                                correlation_id = %correlation_id,
+                                "Lambda function timed out"
+                            );
+                            let keys = [TIMEOUT_ERROR_KEY];
+                            if let Some(status) =
+                                self.mapped_error(exchange, target, &keys, StatusClass::Timeout)
+                            {
+                                return Ok(status);
+                            }
+                            return Ok(ERR12001_UPSTREAM_TIMEOUT
+                                .error()
+                                .detail("Lambda function timed out.")
+                                .raise(exchange))
+                        }
+                    },
+                    None => invoke.await,
+                };
+                metrics::histogram(
+                    names::PROXY_LATENCY,
+                    invoke_started.elapsed().as_secs_f64() * 1000.0,
+                    Unit::Milliseconds,
+                    &[("function", &function_name)],
+                );
+                let failed = invoke_result
+                    .as_ref()
+                    .map_or(true, |response| response.function_error().is_some());
+                if is_sampled(exchange) && sampling::is_recorded(exchange, failed) {
+                    let mut subsegment =
+                        Subsegment::for_exchange(exchange, &function_name, invoked_at);
+                    subsegment.namespace = Some("aws");
+                    subsegment.fault = failed;
+                    record_subsegment(exchange, &subsegment);
+                }
+                match invoke_result {
                     Ok(response) => {
-                        if response.function_error().is_some() {
-
-                            return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
-                                .message("Lambda function returned an error."));
+                        if let Some(function_error) = response.function_error() {
+                            tracing::warn!(
+                                function = %function_name,
+                                correlation_id = %correlation_id,
+                                "Lambda function returned {}",
+                                function_error
+                            );
+                            let error_type = error_type(response.payload());
+                            let keys: Vec<&str> =
+                                error_type.as_deref().into_iter().chain([function_error]).collect();
+                            if let Some(status) =
+                                self.mapped_error(exchange, target, &keys, StatusClass::ServerError)
+                            {
+                                return Ok(status);
+                            }
+                            return Ok(ERR12000_UPSTREAM_FAILED
+                                .error()
+                                .detail("Lambda function returned an error.")
+                                .raise(exchange));
                         }
 
-                        let response_payload_bytes = response.payload.unwrap().into_inner();
-                        let lambda_response: ApiGatewayProxyResponse =
-                            match serde_json::from_slice(&response_payload_bytes) {
-                                Ok(response) => response,
-                                Err(_) => {
-                                    return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
-                                        .message(
-                                            "Failed to parse response from Lambda function.",
-                                        ));
-                                }
-                            };
+                        let Some(response_payload) = response.payload else {
+                            tracing::warn!(
+                                function = %function_name,
+                                correlation_id = %correlation_id,
+                                "Lambda function returned no payload"
+                            );
+                            return Ok(ERR12000_UPSTREAM_FAILED
+                                .error()
+                                .detail("Lambda function returned no response.")
+                                .raise(exchange));
+                        };
+                        let response_payload_bytes = response_payload.into_inner();
+                        let lambda_response = match target.response_template() {
+                            Some(template) => template.apply(&response_payload_bytes),
+                            None => serde_json::from_slice::<ApiGatewayProxyResponse>(
+                                &response_payload_bytes,
+                            )
+                            .map_err(|e| e.to_string()),
+                        };
+                        let lambda_response = match lambda_response {
+                            Ok(response) => response,
+                            Err(e) => {
+                                tracing::warn!(
+                                    function = %function_name,
+                                    correlation_id = %correlation_id,
+                                    "Lambda function response could not be mapped: {}",
+                                    e
+                                );
+                                return Ok(ERR12000_UPSTREAM_FAILED
+                                    .error()
+                                    .detail("Failed to parse response from Lambda function.")
+                                    .raise(exchange));
+                            }
+                        };
+                        if let Some(response_size) = self.config.get().oversized(&lambda_response)
+                        {
+                            tracing::warn!(
+                                function = %function_name,
+                                correlation_id = %correlation_id,
+                                "Lambda function response of {} bytes is over the limit",
+                                response_size
+                            );
+                            return Ok(ERR12002_UPSTREAM_RESPONSE_TOO_LARGE
+                                .error()
+                                .detail("Lambda function response too large.")
+                                .raise(exchange));
+                        }
                         exchange.set_output(lambda_response);
                         Ok(HandlerStatus::new(ExchangeState::EXCHANGE_COMPLETED))
                     }
-                    Err(_) => Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
-                        .message("Failed to invoke Lambda function.")),
+                    Err(e) => {
+                        tracing::warn!(
+                            function = %function_name,
+                            correlation_id = %correlation_id,
+                            "Lambda function could not be invoked: {}",
+                            e
+                        );
+                        let keys: Vec<&str> = e.code().into_iter().collect();
+                        if let Some(status) =
+                            self.mapped_error(exchange, target, &keys, StatusClass::ServerError)
+                        {
+                            return Ok(status);
+                        }
+                        Ok(ERR12000_UPSTREAM_FAILED
+                            .error()
+                            .detail("Failed to invoke Lambda function.")
+                            .raise(exchange))
+                    }
                 }
             }
-            Err(_) => Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
-                .message("Failed to consume request.")),
+            Err(_) => Ok(ERR13000_INTERNAL
+                .error()
+                .detail("Failed to consume request.")
+                .raise(exchange)),
         }
     }
 
     fn name(&self) -> &str {
-        "LambdaProxyHandler"
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::attachments::ScopedAttachmentsExt;
+    use crate::handler::proxy::{
+        error_type, function_slots, FunctionTarget, LambdaProxyHandler, LambdaProxyHandlerConfig,
+    };
+    use crate::handler::traceability::{
+        CORRELATION_ID_ATTACHMENT_KEY, TRACEABILITY_ID_ATTACHMENT_KEY,
+    };
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use aws_sdk_lambda::primitives::Blob;
+    use idemio::config::{Config, DefaultConfigProvider};
+    use idemio::exchange::Exchange;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::Body;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_propagate_ids() {
+        let handler = LambdaProxyHandler::new(Config::new(DefaultConfigProvider).unwrap());
+        let mut exchange: LambdaExchange = Exchange::new();
+        let mut well_known = exchange.attachments_mut().well_known_mut();
+        well_known.add::<String>(CORRELATION_ID_ATTACHMENT_KEY, "cid-1".to_string());
+        well_known.add::<String>(TRACEABILITY_ID_ATTACHMENT_KEY, "tid-1".to_string());
+
+        let mut request = RequestBuilder::get("/pets").header("x-trace", "stale").build();
+        request.multi_value_headers = request.headers.clone();
+        handler.propagate_ids(&exchange, &mut request);
+        assert_eq!(request.headers["x-correlation"], "cid-1");
+        assert_eq!(request.headers["x-trace"], "tid-1");
+        assert_eq!(request.multi_value_headers["x-trace"], "tid-1");
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let config = LambdaProxyHandlerConfig::default();
+        assert_eq!(config.payload_limit(), 6 * 1024 * 1024);
+        assert_eq!(config.response_limit(), 10 * 1024 * 1024);
+
+        let config: LambdaProxyHandlerConfig = serde_json::from_str(
+            r#"{ "enabled": true, "functions": {}, "max_payload_size": "1MB",
+                 "max_response_size": "20MB" }"#,
+        )
+        .unwrap();
+        assert_eq!(config.payload_limit(), 1_000_000);
+        assert_eq!(config.response_limit(), 10 * 1024 * 1024);
+
+        let config: LambdaProxyHandlerConfig = serde_json::from_str(
+            r#"{ "enabled": true, "functions": {}, "max_response_size": "1KB" }"#,
+        )
+        .unwrap();
+        let mut response = ApiGatewayProxyResponse {
+            status_code: 200,
+            body: Some(Body::Text("x".repeat(100))),
+            ..Default::default()
+        };
+        assert_eq!(config.oversized(&response), None);
+        response.body = Some(Body::Text("x".repeat(1000)));
+        assert!(config.oversized(&response).unwrap() > 1000);
+    }
+
+    #[test]
+    fn test_function_slots() {
+        let target: FunctionTarget =
+            serde_json::from_str(r#"{ "name": "slow", "max_concurrency": 1 }"#).unwrap();
+        let limit = target.max_concurrency(Some(10)).unwrap();
+        assert_eq!(limit, 1);
+        let held = function_slots("slow", limit).try_acquire_owned().unwrap();
+        assert!(function_slots("slow", limit).try_acquire_owned().is_err());
+        assert!(function_slots("fast", 10).try_acquire_owned().is_ok());
+        drop(held);
+        assert!(function_slots("slow", limit).try_acquire_owned().is_ok());
+    }
+
+    #[test]
+    fn test_function_target_qualifier() {
+        let functions: HashMap<String, FunctionTarget> = serde_json::from_str(
+            r#"{ "/pets@GET": "pets", "/pets@POST": { "name": "pets", "qualifier": "beta" } }"#,
+        )
+        .unwrap();
+        let get = &functions["/pets@GET"];
+        let post = &functions["/pets@POST"];
+        assert_eq!(get.name(), "pets");
+        assert_eq!(post.name(), "pets");
+        assert_eq!(get.qualifier(None), None);
+        assert_eq!(get.qualifier(Some("prod")), Some("prod"));
+        assert_eq!(post.qualifier(Some("prod")), Some("beta"));
+    }
+
+    #[test]
+    fn test_function_error_mapping() {
+        let target: FunctionTarget = serde_json::from_str(
+            r#"{ "name": "pets", "timeout": "2s", "errors": {
+                "PetNotFound": { "status_code": 404 },
+                "Unhandled": { "status_code": 503 } } }"#,
+        )
+        .unwrap();
+        let default = Some(Duration::from_secs(10));
+        assert_eq!(target.timeout(default), Some(Duration::from_secs(2)));
+        assert_eq!(FunctionTarget::Name("pets".to_string()).timeout(default), default);
+
+        let payload = Blob::new(r#"{"errorType":"PetNotFound","errorMessage":"no pet 7"}"#);
+        let error_type = error_type(Some(&payload));
+        assert_eq!(error_type.as_deref(), Some("PetNotFound"));
+        let mapping = target.error_mapping(&["PetNotFound", "Unhandled"]).unwrap();
+        assert_eq!(mapping.status_code, 404);
+        let mapping = target.error_mapping(&["OutOfMemory", "Unhandled"]).unwrap();
+        assert_eq!(mapping.status_code, 503);
+        assert!(target.error_mapping(&["Timeout"]).is_none());
     }
 }
@@ -0,0 +1,344 @@
+use crate::config::units::deserialize_duration;
+use crate::error::{ERR11004_RATE_LIMITED, ERR13000_INTERNAL};
+use crate::executor::authorizer::principal;
+use crate::flow::builder::NamedHandler;
+use crate::flow::matcher::matched_route;
+use crate::handler::api_key::client_identity;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::rate_limit::store::{counter_store, CounterState, CounterStoreConfig, Decision};
+use crate::handler::LambdaExchange;
+use crate::metrics::{self, names};
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_http::http::header::RETRY_AFTER;
+use lambda_http::http::HeaderValue;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod store;
+
+const RETRY_AFTER_ATTACHMENT_KEY: &str = "retry_after";
+const HEADER_KEY_PREFIX: &str = "header:";
+/* stands in for attributes a request doesn't have */
+const MISSING_KEY_PART: &str = "-";
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /* `limit` tokens per `window` trickle into a bucket of `burst` */
+    #[default]
+    TokenBucket,
+    /* `limit` requests per `window`, counted from the start of each window */
+    FixedWindow,
+}
+
+/// A request attribute clients are told apart by: `ip`, `api_key`, `jwt_sub`, `route` or
+/// `header:<name>`. `api_key` is the client the [`ApiKeyHandler`] validated the key of; requests
+/// it didn't validate are counted by IP, so made-up keys don't get buckets of their own.
+///
+/// [`ApiKeyHandler`]: crate::handler::api_key::ApiKeyHandler
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub enum RateLimitKey {
+    Ip,
+    ApiKey,
+    JwtSub,
+    Route,
+    Header(String),
+}
+
+impl TryFrom<String> for RateLimitKey {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Some(header) = value.strip_prefix(HEADER_KEY_PREFIX) {
+            return Ok(RateLimitKey::Header(header.to_lowercase()));
+        }
+        match value.as_str() {
+            "ip" => Ok(RateLimitKey::Ip),
+            "api_key" => Ok(RateLimitKey::ApiKey),
+            "jwt_sub" => Ok(RateLimitKey::JwtSub),
+            "route" => Ok(RateLimitKey::Route),
+            _ => Err(format!("Unknown rate limit key '{}'", value)),
+        }
+    }
+}
+
+impl RateLimitKey {
+    fn value(&self, exchange: &LambdaExchange, request: &ApiGatewayProxyRequest) -> Option<String> {
+        let header = |name: &str| {
+            request
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        match self {
+            RateLimitKey::Ip => request.request_context.identity.source_ip.clone(),
+            RateLimitKey::ApiKey => match client_identity(exchange) {
+                Some(identity) => Some(identity.client_id.clone()),
+                None => request.request_context.identity.source_ip.clone(),
+            },
+            RateLimitKey::JwtSub => principal(exchange).map(str::to_string),
+            RateLimitKey::Route => matched_route(exchange).map(str::to_string),
+            RateLimitKey::Header(name) => header(name),
+        }
+    }
+}
+
+/// Limits how often each client may call. With the default in-memory store every container
+/// counts on its own; a `dynamodb` or `elasticache` store makes the limit hold across all of
+/// them:
+///
+/// ```json
+/// { "enabled": true, "algorithm": "fixed_window", "limit": 100, "window": "1m",
+///   "key_by": ["api_key", "route"], "store": { "type": "dynamodb", "table": "rate-limits" } }
+/// ```
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RateLimitHandlerConfig {
+    pub enabled: bool,
+    pub algorithm: RateLimitAlgorithm,
+    pub limit: u64,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub window: Duration,
+    /* the most tokens a bucket holds, `limit` when unset */
+    pub burst: Option<u64>,
+    pub key_by: Vec<RateLimitKey>,
+    pub store: CounterStoreConfig,
+    /* let requests through while the store can't be reached */
+    pub fail_open: bool,
+}
+
+impl Default for RateLimitHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            algorithm: RateLimitAlgorithm::default(),
+            limit: 100,
+            window: Duration::from_secs(1),
+            burst: None,
+            key_by: vec![RateLimitKey::Ip],
+            store: CounterStoreConfig::default(),
+            fail_open: true,
+        }
+    }
+}
+
+fn epoch_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl RateLimitHandlerConfig {
+    fn window_millis(&self) -> u64 {
+        (self.window.as_millis() as u64).max(1)
+    }
+
+    /* tokens added per millisecond */
+    fn refill_rate(&self) -> f64 {
+        self.limit as f64 / self.window_millis() as f64
+    }
+
+    fn burst(&self) -> f64 {
+        self.burst.unwrap_or(self.limit) as f64
+    }
+
+    /* how long a key has to be kept for its state to matter */
+    fn ttl(&self) -> Duration {
+        match self.algorithm {
+            RateLimitAlgorithm::FixedWindow => self.window,
+            RateLimitAlgorithm::TokenBucket if self.limit == 0 => self.window,
+            RateLimitAlgorithm::TokenBucket => {
+                let refill = (self.burst() / self.refill_rate()).ceil() as u64;
+                Duration::from_millis(refill).max(self.window)
+            }
+        }
+    }
+
+    fn client_key(&self, exchange: &LambdaExchange, request: &ApiGatewayProxyRequest) -> String {
+        let parts: Vec<String> = self
+            .key_by
+            .iter()
+            .map(|key| key.value(exchange, request))
+            .map(|value| value.unwrap_or_else(|| MISSING_KEY_PART.to_string()))
+            .collect();
+        format!("{}:{}", RateLimitHandler::NAME, parts.join("|"))
+    }
+
+    /// Applies the limit to the state of a key at `now`, in epoch milliseconds.
+    pub fn decide(&self, current: Option<CounterState>, now: u64) -> Decision {
+        match self.algorithm {
+            RateLimitAlgorithm::FixedWindow => {
+                let window = self.window_millis();
+                let start = now - now % window;
+                let count = current
+                    .filter(|state| state.stamp == start)
+                    .map_or(0.0, |state| state.value);
+                let allowed = count < self.limit as f64;
+                let count = if allowed { count + 1.0 } else { count };
+                Decision {
+                    state: CounterState { value: count, stamp: start },
+                    allowed,
+                    remaining: (self.limit as f64 - count).max(0.0) as u64,
+                    retry_after: match allowed {
+                        true => Duration::ZERO,
+                        false => Duration::from_millis(start + window - now),
+                    },
+                }
+            }
+            RateLimitAlgorithm::TokenBucket => {
+                let rate = self.refill_rate();
+                let tokens = match current {
+                    Some(state) => {
+                        let refill = now.saturating_sub(state.stamp) as f64 * rate;
+                        (state.value + refill).min(self.burst())
+                    }
+                    None => self.burst(),
+                };
+                let allowed = tokens >= 1.0;
+                let tokens = if allowed { tokens - 1.0 } else { tokens };
+                let retry_after = match (allowed, rate > 0.0) {
+                    (true, _) => Duration::ZERO,
+                    (false, true) => Duration::from_millis(((1.0 - tokens) / rate).ceil() as u64),
+                    (false, false) => self.window,
+                };
+                Decision {
+                    state: CounterState { value: tokens, stamp: now },
+                    allowed,
+                    remaining: tokens.floor() as u64,
+                    retry_after,
+                }
+            }
+        }
+    }
+}
+
+pub struct RateLimitHandler {
+    pub(crate) config: Config<RateLimitHandlerConfig>,
+}
+
+impl NamedHandler for RateLimitHandler {
+    const NAME: &'static str = "RateLimitHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for RateLimitHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let key = match exchange.input().await {
+            Ok(request) => config.client_key(exchange, request),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+        let now = epoch_millis();
+        let update = |current: Option<CounterState>| config.decide(current, now);
+        let decision = match counter_store(&config.store).apply(&key, config.ttl(), &update).await {
+            Ok(decision) => decision,
+            Err(e) if config.fail_open => {
+                tracing::warn!("Rate limit store unavailable, letting request through: {}", e);
+                return Ok(HandlerStatus::new(ExchangeState::OK));
+            }
+            Err(e) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail(format!("Rate limit store unavailable: {}", e))
+                    .raise(exchange));
+            }
+        };
+        if decision.allowed {
+            return Ok(HandlerStatus::new(ExchangeState::OK));
+        }
+
+        let route = matched_route(exchange).unwrap_or("").to_string();
+        metrics::counter(names::RATE_LIMITED, 1, &[("route", route.as_str())]);
+        /* whole seconds, rounded up so clients don't come back too early */
+        let retry_after = decision.retry_after.as_millis().div_ceil(1000) as u64;
+        exchange
+            .attachments_mut()
+            .scoped_mut(Self::NAME)
+            .add::<u64>(RETRY_AFTER_ATTACHMENT_KEY, retry_after);
+        exchange.add_output_listener(|response, attachments| {
+            let attachments = attachments.scoped(Self::NAME);
+            if let Some(retry_after) = attachments.get::<u64>(RETRY_AFTER_ATTACHMENT_KEY) {
+                response.headers.insert(RETRY_AFTER, HeaderValue::from(*retry_after));
+            }
+        });
+        Ok(ERR11004_RATE_LIMITED
+            .error()
+            .metadata("retry_after", retry_after.to_string())
+            .raise(exchange))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::rate_limit::{RateLimitAlgorithm, RateLimitHandlerConfig, RateLimitKey};
+    use crate::handler::LambdaExchange;
+    use idemio::exchange::Exchange;
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+    use lambda_http::http::HeaderValue;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limit_algorithms() {
+        let config: RateLimitHandlerConfig = serde_json::from_str(
+            r#"{ "algorithm": "fixed_window", "limit": 2, "window": "1s",
+                 "key_by": ["ip", "header:X-Tenant"] }"#,
+        )
+        .unwrap();
+        assert_eq!(config.key_by[1], RateLimitKey::Header("x-tenant".to_string()));
+        let first = config.decide(None, 10_200);
+        let second = config.decide(Some(first.state), 10_400);
+        let third = config.decide(Some(second.state), 10_600);
+        assert!(first.allowed && second.allowed && !third.allowed);
+        assert_eq!(third.retry_after, Duration::from_millis(400));
+        /* a new window starts over */
+        assert!(config.decide(Some(third.state), 11_000).allowed);
+
+        let config = RateLimitHandlerConfig {
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            limit: 1,
+            burst: Some(2),
+            ..config
+        };
+        let first = config.decide(None, 0);
+        let second = config.decide(Some(first.state), 0);
+        let third = config.decide(Some(second.state), 0);
+        assert!(first.allowed && second.allowed && !third.allowed);
+        assert_eq!(third.retry_after, Duration::from_secs(1));
+        assert!(config.decide(Some(third.state), 1_000).allowed);
+        assert_eq!(config.ttl(), Duration::from_secs(2));
+
+        assert!(serde_json::from_str::<RateLimitHandlerConfig>(r#"{ "key_by": ["cookie"] }"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_unvalidated_api_key_is_keyed_by_ip() {
+        let mut request = ApiGatewayProxyRequest::default();
+        request.request_context.identity.source_ip = Some("203.0.113.7".to_string());
+        request.headers.insert("x-api-key", HeaderValue::from_static("made-up-key"));
+        let exchange: LambdaExchange = Exchange::new();
+        let key = RateLimitKey::ApiKey.value(&exchange, &request);
+        assert_eq!(key.as_deref(), Some("203.0.113.7"));
+    }
+}
@@ -0,0 +1,315 @@
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+
+/* how often a shared store retries when another container changed the key first */
+const MAX_ATTEMPTS: usize = 5;
+/* in-memory keys kept before the expired ones are dropped */
+const MEMORY_PRUNE_SIZE: usize = 10_000;
+
+const KEY_ATTRIBUTE: &str = "key";
+const STATE_ATTRIBUTE: &str = "state";
+/* epoch seconds, for the table's TTL setting */
+const EXPIRES_ATTRIBUTE: &str = "expires_at";
+
+/* sets the key only when it still holds what the decision was based on */
+const COMPARE_AND_SET: &str = r#"
+if (redis.call('GET', KEYS[1]) or '') ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+return 1
+"#;
+
+/// What a limit keeps per key: a request count or a token level, and when it was last set in
+/// epoch milliseconds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CounterState {
+    pub value: f64,
+    pub stamp: u64,
+}
+
+/// The outcome of applying a limit to the state of a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decision {
+    pub state: CounterState,
+    pub allowed: bool,
+    pub remaining: u64,
+    pub retry_after: Duration,
+}
+
+pub type Update<'a> = &'a (dyn Fn(Option<CounterState>) -> Decision + Send + Sync);
+
+/// Keeps the counters of a rate limit.
+#[async_trait]
+pub trait CounterStore: Send + Sync {
+    /// Decides on the current state of the key and keeps the new state of an allowed request,
+    /// atomically across every container sharing the store. Untouched keys expire after `ttl`.
+    async fn apply(&self, key: &str, ttl: Duration, update: Update<'_>) -> Result<Decision, String>;
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CounterStoreConfig {
+    /* per container, so the limit applies to each container on its own */
+    #[default]
+    Memory,
+    /* a table with a string partition key named `key` */
+    #[serde(rename = "dynamodb")]
+    DynamoDb { table: String },
+    /* a Redis or Valkey cluster, `rediss://` for in-transit encryption */
+    #[serde(rename = "elasticache")]
+    ElastiCache { url: String },
+}
+
+impl CounterStoreConfig {
+    pub fn build(&self) -> Arc<dyn CounterStore> {
+        match self.clone() {
+            CounterStoreConfig::Memory => Arc::new(MemoryStore::default()),
+            CounterStoreConfig::DynamoDb { table } => Arc::new(DynamoDbStore {
+                table,
+                client: OnceCell::new(),
+            }),
+            CounterStoreConfig::ElastiCache { url } => Arc::new(ElastiCacheStore {
+                url,
+                connection: OnceCell::new(),
+            }),
+        }
+    }
+}
+
+/// The store for the config, shared by every handler with the same one. It is built on first
+/// use and kept for the lifetime of the container, in-memory counters included.
+pub fn counter_store(config: &CounterStoreConfig) -> Arc<dyn CounterStore> {
+    static STORES: OnceLock<Mutex<HashMap<CounterStoreConfig, Arc<dyn CounterStore>>>> =
+        OnceLock::new();
+    STORES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(config.clone())
+        .or_insert_with(|| config.build())
+        .clone()
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    counters: Mutex<HashMap<String, (CounterState, Instant)>>,
+}
+
+#[async_trait]
+impl CounterStore for MemoryStore {
+    async fn apply(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: Update<'_>,
+    ) -> Result<Decision, String> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        if counters.len() >= MEMORY_PRUNE_SIZE {
+            counters.retain(|_, (_, expires)| *expires > now);
+        }
+        let current = counters
+            .get(key)
+            .filter(|(_, expires)| *expires > now)
+            .map(|(state, _)| *state);
+        let decision = update(current);
+        if decision.allowed {
+            counters.insert(key.to_string(), (decision.state, now + ttl));
+        }
+        Ok(decision)
+    }
+}
+
+pub struct DynamoDbStore {
+    table: String,
+    client: OnceCell<DynamoDbClient>,
+}
+
+impl DynamoDbStore {
+    async fn client(&self) -> &DynamoDbClient {
+        self.client
+            .get_or_init(|| async {
+                DynamoDbClient::new(&aws_config::load_defaults(BehaviorVersion::latest()).await)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl CounterStore for DynamoDbStore {
+    async fn apply(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: Update<'_>,
+    ) -> Result<Decision, String> {
+        let client = self.client().await;
+        for _ in 0..MAX_ATTEMPTS {
+            let output = client
+                .get_item()
+                .table_name(&self.table)
+                .key(KEY_ATTRIBUTE, AttributeValue::S(key.to_string()))
+                .consistent_read(true)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            let item = output.item();
+            let stored = item
+                .and_then(|item| item.get(STATE_ATTRIBUTE))
+                .and_then(|state| state.as_s().ok())
+                .cloned();
+            /* expired items linger until DynamoDB gets around to deleting them */
+            let expired = item
+                .and_then(|item| item.get(EXPIRES_ATTRIBUTE))
+                .and_then(|expires| expires.as_n().ok())
+                .and_then(|expires| expires.parse::<u64>().ok())
+                .is_some_and(|expires| expires <= epoch_seconds());
+            let current = stored
+                .as_deref()
+                .filter(|_| !expired)
+                .and_then(|state| serde_json::from_str(state).ok());
+            let decision = update(current);
+            if !decision.allowed {
+                return Ok(decision);
+            }
+
+            let state = serde_json::to_string(&decision.state).map_err(|e| e.to_string())?;
+            let expires = epoch_seconds() + ttl.as_secs().max(1);
+            let put = client
+                .put_item()
+                .table_name(&self.table)
+                .item(KEY_ATTRIBUTE, AttributeValue::S(key.to_string()))
+                .item(STATE_ATTRIBUTE, AttributeValue::S(state))
+                .item(EXPIRES_ATTRIBUTE, AttributeValue::N(expires.to_string()));
+            let put = match stored {
+                Some(stored) => put
+                    .condition_expression("#state = :stored")
+                    .expression_attribute_names("#state", STATE_ATTRIBUTE)
+                    .expression_attribute_values(":stored", AttributeValue::S(stored)),
+                None => put
+                    .condition_expression("attribute_not_exists(#key)")
+                    .expression_attribute_names("#key", KEY_ATTRIBUTE),
+            };
+            match put.send().await {
+                Ok(_) => return Ok(decision),
+                Err(e)
+                    if e.as_service_error()
+                        .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Err(format!("{} stayed contended after {} attempts", key, MAX_ATTEMPTS))
+    }
+}
+
+pub struct ElastiCacheStore {
+    url: String,
+    /* reconnects on its own after connection errors */
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl ElastiCacheStore {
+    async fn connection(&self) -> Result<ConnectionManager, String> {
+        self.connection
+            .get_or_try_init(|| async {
+                let client = redis::Client::open(self.url.as_str()).map_err(|e| e.to_string())?;
+                ConnectionManager::new(client).await.map_err(|e| e.to_string())
+            })
+            .await
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl CounterStore for ElastiCacheStore {
+    async fn apply(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: Update<'_>,
+    ) -> Result<Decision, String> {
+        let mut connection = self.connection().await?;
+        let script = Script::new(COMPARE_AND_SET);
+        for _ in 0..MAX_ATTEMPTS {
+            let stored: Option<String> = connection.get(key).await.map_err(|e| e.to_string())?;
+            let current = stored
+                .as_deref()
+                .and_then(|state| serde_json::from_str(state).ok());
+            let decision = update(current);
+            if !decision.allowed {
+                return Ok(decision);
+            }
+
+            let state = serde_json::to_string(&decision.state).map_err(|e| e.to_string())?;
+            let set: i64 = script
+                .key(key)
+                .arg(stored.unwrap_or_default())
+                .arg(state)
+                .arg(ttl.as_millis().max(1) as u64)
+                .invoke_async(&mut connection)
+                .await
+                .map_err(|e| e.to_string())?;
+            if set == 1 {
+                return Ok(decision);
+            }
+        }
+        Err(format!("{} stayed contended after {} attempts", key, MAX_ATTEMPTS))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::rate_limit::store::{
+        CounterState, CounterStore, CounterStoreConfig, Decision, MemoryStore,
+    };
+    use std::time::Duration;
+
+    fn count(current: Option<CounterState>) -> Decision {
+        let value = current.map_or(0.0, |state| state.value);
+        Decision {
+            state: CounterState { value: value + 1.0, stamp: 0 },
+            allowed: value < 2.0,
+            remaining: 0,
+            retry_after: Duration::ZERO,
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_memory_store() {
+        let store = MemoryStore::default();
+        let ttl = Duration::from_secs(60);
+        assert!(store.apply("a", ttl, &count).await.unwrap().allowed);
+        assert!(store.apply("a", ttl, &count).await.unwrap().allowed);
+        assert!(!store.apply("a", ttl, &count).await.unwrap().allowed);
+        assert!(store.apply("b", ttl, &count).await.unwrap().allowed);
+
+        /* expired keys start over */
+        assert!(store.apply("c", Duration::ZERO, &count).await.unwrap().allowed);
+        assert!(store.apply("c", Duration::ZERO, &count).await.unwrap().allowed);
+        assert!(store.apply("c", Duration::ZERO, &count).await.unwrap().allowed);
+
+        let config: CounterStoreConfig =
+            serde_json::from_str(r#"{ "type": "dynamodb", "table": "rate-limits" }"#).unwrap();
+        assert_eq!(config, CounterStoreConfig::DynamoDb { table: "rate-limits".to_string() });
+    }
+}
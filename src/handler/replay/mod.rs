@@ -0,0 +1,214 @@
+use crate::config::units::deserialize_duration;
+use crate::error::{
+    HandlerError, ERR10009_REPLAYED_REQUEST, ERR10010_STALE_REQUEST, ERR11000_INVALID_REQUEST,
+    ERR13000_INTERNAL,
+};
+use crate::executor::authorizer::principal;
+use crate::flow::builder::NamedHandler;
+use crate::handler::api_key::client_identity;
+use crate::handler::replay::store::{nonce_store, NonceStoreConfig};
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod store;
+
+/// Blocks replays of signed requests. Every request carries a unique nonce and the time it was
+/// signed, in seconds since the epoch:
+///
+/// ```json
+/// { "enabled": true, "nonce_header": "x-nonce", "timestamp_header": "x-timestamp",
+///   "max_skew": "5m", "store": { "type": "dynamodb", "table": "nonces" } }
+/// ```
+///
+/// Requests signed more than `max_skew` away from now are rejected, and the nonces of the rest
+/// are remembered until their timestamp leaves that window, so each can be used once. Nonces
+/// are per client when an earlier handler identified one. The in-memory store only sees the
+/// requests of its own container; the `dynamodb` store sees them all.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ReplayHandlerConfig {
+    pub enabled: bool,
+    pub nonce_header: String,
+    pub timestamp_header: String,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_skew: Duration,
+    pub store: NonceStoreConfig,
+}
+
+impl Default for ReplayHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            nonce_header: "x-nonce".to_string(),
+            timestamp_header: "x-timestamp".to_string(),
+            max_skew: Duration::from_secs(300),
+            store: NonceStoreConfig::default(),
+        }
+    }
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn header<'a>(request: &'a ApiGatewayProxyRequest, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+impl ReplayHandlerConfig {
+    /* the nonce and how long to remember it for */
+    fn check(
+        &self,
+        request: &ApiGatewayProxyRequest,
+        now: u64,
+    ) -> Result<(String, Duration), HandlerError> {
+        let nonce = header(request, &self.nonce_header).ok_or_else(|| {
+            ERR11000_INVALID_REQUEST
+                .error()
+                .detail(format!("Missing {} header", self.nonce_header))
+        })?;
+        let timestamp = header(request, &self.timestamp_header)
+            .ok_or_else(|| {
+                ERR11000_INVALID_REQUEST
+                    .error()
+                    .detail(format!("Missing {} header", self.timestamp_header))
+            })?
+            .parse::<u64>()
+            .map_err(|_| {
+                ERR11000_INVALID_REQUEST
+                    .error()
+                    .detail(format!("Invalid {} header", self.timestamp_header))
+            })?;
+        let skew = self.max_skew.as_secs();
+        if timestamp.abs_diff(now) > skew {
+            return Err(ERR10010_STALE_REQUEST
+                .error()
+                .metadata("max_skew", skew.to_string()));
+        }
+        let remaining = (timestamp + skew).saturating_sub(now).max(1);
+        Ok((nonce.to_string(), Duration::from_secs(remaining)))
+    }
+}
+
+pub struct ReplayHandler {
+    pub(crate) config: Config<ReplayHandlerConfig>,
+}
+
+impl NamedHandler for ReplayHandler {
+    const NAME: &'static str = "ReplayHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for ReplayHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+
+        let checked = match exchange.input().await {
+            Ok(request) => config.check(request, epoch_seconds()),
+            Err(_) => Err(ERR13000_INTERNAL.error().detail("Unable to get request")),
+        };
+        let (nonce, ttl) = match checked {
+            Ok(checked) => checked,
+            Err(error) => return Ok(error.raise(exchange)),
+        };
+        let client = client_identity(exchange)
+            .map(|identity| identity.client_id.as_str())
+            .or_else(|| principal(exchange))
+            .unwrap_or("");
+        let key = format!("{}:{}", client, nonce);
+        match nonce_store(&config.store).insert(&key, ttl).await {
+            Ok(true) => Ok(HandlerStatus::new(ExchangeState::OK)),
+            Ok(false) => Ok(ERR10009_REPLAYED_REQUEST.raise(exchange)),
+            Err(e) => Ok(ERR13000_INTERNAL
+                .error()
+                .detail(format!("Nonce store unavailable: {}", e))
+                .raise(exchange)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::replay::{ReplayHandler, ReplayHandlerConfig};
+    use crate::handler::LambdaExchange;
+    use crate::testkit::RequestBuilder;
+    use idemio::config::{Config, ConfigProvider, ConfigProviderError};
+    use idemio::exchange::Exchange;
+    use idemio::handler::Handler;
+    use idemio::status::ExchangeState;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    struct StaticConfig;
+
+    impl ConfigProvider<ReplayHandlerConfig> for StaticConfig {
+        fn load(&self) -> Result<ReplayHandlerConfig, ConfigProviderError> {
+            Ok(serde_json::from_str(r#"{ "max_skew": "1m" }"#).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_timestamp_window() {
+        let config: ReplayHandlerConfig = serde_json::from_str(r#"{ "max_skew": "1m" }"#).unwrap();
+        let request = |timestamp: &str| {
+            RequestBuilder::post("/payments")
+                .header("x-nonce", "n-1")
+                .header("x-timestamp", timestamp)
+                .build()
+        };
+        let (nonce, ttl) = config.check(&request("1000"), 1000).unwrap();
+        assert_eq!(nonce, "n-1");
+        assert_eq!(ttl, Duration::from_secs(60));
+        assert_eq!(config.check(&request("950"), 1000).unwrap().1, Duration::from_secs(10));
+        assert_eq!(config.check(&request("1060"), 1000).unwrap().1, Duration::from_secs(120));
+
+        let error = config.check(&request("939"), 1000).unwrap_err();
+        assert_eq!(error.code.code, "ERR10010");
+        assert!(config.check(&request("1061"), 1000).is_err());
+        assert_eq!(config.check(&request("soon"), 1000).unwrap_err().code.status_code, 400);
+        let unsigned = RequestBuilder::post("/payments").header("x-timestamp", "1000").build();
+        assert_eq!(config.check(&unsigned, 1000).unwrap_err().code.status_code, 400);
+    }
+
+    async fn run(handler: &ReplayHandler, nonce: &str) -> ExchangeState {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let request = RequestBuilder::post("/payments")
+            .header("x-nonce", nonce)
+            .header("x-timestamp", &now.to_string())
+            .build();
+        let mut exchange: LambdaExchange = Exchange::new();
+        exchange.set_input(request);
+        handler.exec(&mut exchange).await.unwrap().code()
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_replayed_nonce() {
+        let handler = ReplayHandler {
+            config: Config::new(StaticConfig).unwrap(),
+        };
+        assert_eq!(run(&handler, "replay-test-1").await, ExchangeState::OK);
+        assert!(run(&handler, "replay-test-1").await.any_flags(ExchangeState::CLIENT_ERROR));
+        assert_eq!(run(&handler, "replay-test-2").await, ExchangeState::OK);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::handler::lambda::{sdk_config, LambdaClientConfig};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+
+const NONCE_ATTRIBUTE: &str = "nonce";
+/* epoch seconds, for the table's TTL setting */
+const EXPIRES_ATTRIBUTE: &str = "expires_at";
+
+/// Remembers the nonces of signed requests for as long as they could be replayed.
+#[async_trait]
+pub trait NonceStore: Send + Sync {
+    /// Records the nonce for `ttl`, `false` when it was already recorded and hasn't expired.
+    async fn insert(&self, nonce: &str, ttl: Duration) -> Result<bool, String>;
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NonceStoreConfig {
+    /* per container; once full, the nonces closest to expiring are forgotten first */
+    Memory {
+        #[serde(default = "default_capacity")]
+        capacity: usize,
+    },
+    /* a table with a string partition key named `nonce` */
+    #[serde(rename = "dynamodb")]
+    DynamoDb { table: String },
+}
+
+impl Default for NonceStoreConfig {
+    fn default() -> Self {
+        NonceStoreConfig::Memory {
+            capacity: default_capacity(),
+        }
+    }
+}
+
+fn default_capacity() -> usize {
+    100_000
+}
+
+impl NonceStoreConfig {
+    pub fn build(&self) -> Arc<dyn NonceStore> {
+        match self.clone() {
+            NonceStoreConfig::Memory { capacity } => Arc::new(MemoryNonceStore {
+                capacity,
+                nonces: Mutex::default(),
+            }),
+            NonceStoreConfig::DynamoDb { table } => Arc::new(DynamoDbNonceStore {
+                table,
+                client: OnceCell::new(),
+            }),
+        }
+    }
+}
+
+/// The store for the config, shared by every handler with the same one for the lifetime of
+/// the container.
+pub fn nonce_store(config: &NonceStoreConfig) -> Arc<dyn NonceStore> {
+    static STORES: OnceLock<Mutex<HashMap<NonceStoreConfig, Arc<dyn NonceStore>>>> =
+        OnceLock::new();
+    STORES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(config.clone())
+        .or_insert_with(|| config.build())
+        .clone()
+}
+
+#[derive(Default)]
+struct RecentNonces {
+    expiries: HashMap<String, Instant>,
+    /* the same nonces, the first to expire first */
+    by_expiry: BTreeSet<(Instant, String)>,
+}
+
+impl RecentNonces {
+    fn remove_expired(&mut self, now: Instant) {
+        while let Some((expires, nonce)) = self.by_expiry.first().cloned() {
+            if expires > now {
+                break;
+            }
+            self.by_expiry.pop_first();
+            self.expiries.remove(&nonce);
+        }
+    }
+}
+
+pub struct MemoryNonceStore {
+    capacity: usize,
+    nonces: Mutex<RecentNonces>,
+}
+
+#[async_trait]
+impl NonceStore for MemoryNonceStore {
+    async fn insert(&self, nonce: &str, ttl: Duration) -> Result<bool, String> {
+        let now = Instant::now();
+        let mut nonces = self.nonces.lock().unwrap_or_else(PoisonError::into_inner);
+        nonces.remove_expired(now);
+        if nonces.expiries.contains_key(nonce) {
+            return Ok(false);
+        }
+        /* the oldest nonce of a ttl is the first to expire, and the least likely to be replayed */
+        while nonces.expiries.len() >= self.capacity.max(1) {
+            let Some((_, oldest)) = nonces.by_expiry.pop_first() else {
+                break;
+            };
+            nonces.expiries.remove(&oldest);
+            tracing::warn!("Nonce store is full, forgetting a nonce before it expires");
+        }
+        let expires = now + ttl;
+        nonces.expiries.insert(nonce.to_string(), expires);
+        nonces.by_expiry.insert((expires, nonce.to_string()));
+        Ok(true)
+    }
+}
+
+pub struct DynamoDbNonceStore {
+    table: String,
+    client: OnceCell<DynamoDbClient>,
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[async_trait]
+impl NonceStore for DynamoDbNonceStore {
+    async fn insert(&self, nonce: &str, ttl: Duration) -> Result<bool, String> {
+        let client = self
+            .client
+            .get_or_init(|| async {
+                DynamoDbClient::new(&sdk_config(&LambdaClientConfig::default()).await)
+            })
+            .await;
+        let now = epoch_seconds();
+        let result = client
+            .put_item()
+            .table_name(&self.table)
+            .item(NONCE_ATTRIBUTE, AttributeValue::S(nonce.to_string()))
+            .item(EXPIRES_ATTRIBUTE, AttributeValue::N((now + ttl.as_secs().max(1)).to_string()))
+            /* expired items linger until DynamoDB gets around to deleting them */
+            .condition_expression("attribute_not_exists(#nonce) OR #expires <= :now")
+            .expression_attribute_names("#nonce", NONCE_ATTRIBUTE)
+            .expression_attribute_names("#expires", EXPIRES_ATTRIBUTE)
+            .expression_attribute_values(":now", AttributeValue::N(now.to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::replay::store::NonceStoreConfig;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_memory_nonce_store() {
+        let store = NonceStoreConfig::Memory { capacity: 2 }.build();
+        let ttl = Duration::from_secs(60);
+        assert!(store.insert("a", ttl).await.unwrap());
+        assert!(!store.insert("a", ttl).await.unwrap());
+        assert!(store.insert("b", Duration::ZERO).await.unwrap());
+        /* expired nonces make room first, then the oldest live ones */
+        assert!(store.insert("c", ttl).await.unwrap());
+        assert!(store.insert("d", Duration::from_secs(120)).await.unwrap());
+        assert!(store.insert("a", ttl).await.unwrap());
+        assert!(!store.insert("d", ttl).await.unwrap());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+use lambda_http::http::header::CONTENT_TYPE;
+use lambda_http::http::{HeaderName, HeaderValue};
+use lambda_http::Body;
+use serde::Deserialize;
+use serde_json::Value;
+
+const DEFAULT_STATUS_CODE: i64 = 200;
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+fn default_status_code() -> i64 {
+    DEFAULT_STATUS_CODE
+}
+
+/// Turns the plain JSON a function returns into a response, for functions that don't return an
+/// API Gateway proxy response. Paths are JSON pointers into the payload:
+///
+/// ```json
+/// { "status_code": 201, "status_code_path": "/status", "body_path": "/result",
+///   "headers": { "cache-control": "no-store" } }
+/// ```
+///
+/// The status code at `status_code_path` wins over `status_code` when the payload has one. The
+/// body is the value at `body_path`, or the whole payload; strings are used as they are and
+/// anything else as JSON.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ResponseTemplate {
+    #[serde(default = "default_status_code")]
+    pub status_code: i64,
+    #[serde(default)]
+    pub status_code_path: Option<String>,
+    #[serde(default)]
+    pub body_path: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl ResponseTemplate {
+    pub fn apply(&self, payload: &[u8]) -> Result<ApiGatewayProxyResponse, String> {
+        let payload: Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+        let status_code = self
+            .status_code_path
+            .as_deref()
+            .and_then(|path| payload.pointer(path))
+            .and_then(Value::as_i64)
+            .unwrap_or(self.status_code);
+        let body = match &self.body_path {
+            Some(path) => payload.pointer(path).cloned().unwrap_or(Value::Null),
+            None => payload,
+        };
+        let body = match body {
+            Value::Null => None,
+            Value::String(text) => Some(Body::Text(text)),
+            other => Some(Body::Text(other.to_string())),
+        };
+
+        let mut response = ApiGatewayProxyResponse {
+            status_code,
+            body,
+            ..Default::default()
+        };
+        response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+        for (name, value) in &self.headers {
+            let name = HeaderName::try_from(name.as_str()).map_err(|e| e.to_string())?;
+            let value = HeaderValue::from_str(value).map_err(|e| e.to_string())?;
+            response.headers.insert(name, value);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::response_template::ResponseTemplate;
+    use lambda_http::Body;
+
+    #[test]
+    fn test_response_template() {
+        let template: ResponseTemplate = serde_json::from_str(
+            r#"{ "status_code_path": "/status", "body_path": "/result",
+                 "headers": { "content-type": "text/plain" } }"#,
+        )
+        .unwrap();
+        let response = template.apply(br#"{ "status": 202, "result": "queued" }"#).unwrap();
+        assert_eq!(response.status_code, 202);
+        assert_eq!(response.headers["content-type"], "text/plain");
+        assert!(matches!(response.body, Some(Body::Text(body)) if body == "queued"));
+
+        let response = template.apply(br#"{ "result": { "id": 7 } }"#).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert!(matches!(response.body, Some(Body::Text(body)) if body == r#"{"id":7}"#));
+
+        assert!(template.apply(b"not json").is_err());
+    }
+}
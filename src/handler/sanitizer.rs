@@ -1,8 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::convert::Infallible;
 use async_trait::async_trait;
-use http::HeaderMap;
 use idemio::config::Config;
 use idemio::exchange::Exchange;
 use idemio::handler::Handler;
@@ -10,8 +9,12 @@ use idemio::status::{ExchangeState, HandlerStatus};
 use lambda_http::Context;
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use lambda_http::http::HeaderValue;
-use serde_json::{Map, Value};
+use serde_json::Value;
 use tiny_clean::{java_script_encoder::{JavaScriptEncoder, JavaScriptEncoderMode}, xml_encoder::{XmlEncoder, XmlEncoderMode}, uri_encoder::{UriEncoder, UriEncoderMode}};
+use crate::flow::builder::NamedHandler;
+use crate::config::units::deserialize_optional_byte_size;
+use crate::encoder::{encode_bounded, encode_json_value, Encoder, EncoderChain, NormalizationForm, NormalizingEncoder, OutputOverflowPolicy};
+use crate::handler::body::BodyAccess;
 use crate::handler::LambdaExchange;
 
 // TODO - change tiny-clean to allow serialization of mode enums
@@ -20,7 +23,8 @@ use crate::handler::LambdaExchange;
 pub enum SanitizerMode {
     JavaScript(u64, bool),
     Uri(u64),
-    Xml(u64)
+    Xml(u64),
+    Chain(Vec<SanitizerMode>)
 }
 
 impl Default for SanitizerMode {
@@ -29,6 +33,58 @@ impl Default for SanitizerMode {
     }
 }
 
+impl SanitizerMode {
+    pub fn encoder(&self) -> Result<Box<dyn Encoder>, ()> {
+        match self {
+            SanitizerMode::JavaScript(mode, ascii_only) => {
+                let mode = match mode {
+                    1u64 => JavaScriptEncoderMode::Block,
+                    2u64 => JavaScriptEncoderMode::Attribute,
+                    3u64 => JavaScriptEncoderMode::Html,
+                    4u64 => JavaScriptEncoderMode::Source,
+                    _ => return Err(()),
+                };
+                Ok(Box::new(JavaScriptEncoder::new(mode, *ascii_only)))
+            }
+            SanitizerMode::Uri(mode) => {
+                let mode = match mode {
+                    1u64 => UriEncoderMode::Component,
+                    2u64 => UriEncoderMode::FullUri,
+                    _ => return Err(()),
+                };
+                Ok(Box::new(UriEncoder::new(mode)))
+            }
+            SanitizerMode::Xml(mode) => {
+                let mode = match mode {
+                    1u64 => XmlEncoderMode::All,
+                    2u64 => XmlEncoderMode::Content,
+                    3u64 => XmlEncoderMode::Attribute,
+                    4u64 => XmlEncoderMode::SingleQuotedAttribute,
+                    5u64 => XmlEncoderMode::DoubleQuotedAttribute,
+                    _ => return Err(()),
+                };
+                Ok(Box::new(XmlEncoder::new(mode)))
+            }
+            SanitizerMode::Chain(modes) => {
+                let mut encoders = Vec::with_capacity(modes.len());
+                for mode in modes {
+                    encoders.push(mode.encoder()?);
+                }
+                Ok(Box::new(EncoderChain::new(encoders)))
+            }
+        }
+    }
+}
+
+/// What to do with header bytes that are not valid UTF-8 before they reach the encoder.
+#[derive(Deserialize, Serialize, Default, Clone, Copy)]
+pub enum InvalidSequencePolicy {
+    #[default]
+    ReplaceWithInvalidChar,
+    Strip,
+    Error,
+}
+
 #[derive(Deserialize, Serialize, Default, Clone)]
 pub enum SanitizerSettings {
 
@@ -37,7 +93,16 @@ pub enum SanitizerSettings {
     Enabled {
         mode: SanitizerMode,
         ignore_list: Option<Vec<String>>,
-        encode_list: Option<Vec<String>>
+        encode_list: Option<Vec<String>>,
+        #[serde(default)]
+        normalization: Option<NormalizationForm>,
+        #[serde(default)]
+        invalid_sequence_policy: InvalidSequencePolicy,
+        /// Upper bound for each encoded header value, e.g. `8KiB`.
+        #[serde(default, deserialize_with = "deserialize_optional_byte_size")]
+        max_output_len: Option<usize>,
+        #[serde(default)]
+        output_overflow_policy: OutputOverflowPolicy
     }
 }
 #[derive(Deserialize, Serialize, Default, Clone)]
@@ -56,138 +121,74 @@ pub struct SanitizerHandler {
 
 impl SanitizerHandler {
 
-    fn java_script_encoder_for_mode(mode: u64, ascii_only: bool) -> Result<JavaScriptEncoder, ()> {
-        if mode == 1u64 {
-            Ok(JavaScriptEncoder::new(JavaScriptEncoderMode::Block, ascii_only))
-        } else if mode == 2u64 {
-            Ok(JavaScriptEncoder::new(JavaScriptEncoderMode::Attribute, ascii_only))
-        } else if mode == 3u64 {
-            Ok(JavaScriptEncoder::new(JavaScriptEncoderMode::Html, ascii_only))
-        } else if mode == 4u64 {
-            Ok(JavaScriptEncoder::new(JavaScriptEncoderMode::Source, ascii_only))
-        } else {
-            return Err(())
+    fn build_encoder(mode: &SanitizerMode, normalization: &Option<NormalizationForm>) -> Result<Box<dyn Encoder>, ()> {
+        let encoder = mode.encoder()?;
+        match normalization {
+            None => Ok(encoder),
+            Some(form) => Ok(Box::new(NormalizingEncoder::new(*form, encoder))),
         }
     }
 
-    async fn sanitize_headers(exchange: &mut LambdaExchange, mode: &SanitizerMode, ignore_list: &Option<Vec<String>>, encode_list: &Option<Vec<String>>) -> Result<(), ()> {
-
-        // TODO - add input_mut
-        let headers = match exchange.input_mut().await {
-            Ok(input) => {
-                &mut input.headers
-            }
-            Err(_) => return Err(())
-        };
-
-        match mode {
-            SanitizerMode::JavaScript(mode, ascii_only) => {
-
-                let encoder = match Self::java_script_encoder_for_mode(*mode, *ascii_only) {
-                    Ok(encoder) => encoder,
-                    Err(_) => return Err(())
-                };
-
-                for (header_name, header_value) in headers {
-                    if ignore_list.as_ref().is_some_and(|list| list.contains(&header_name.to_string())) {
-                        continue;
-                    } else if encode_list.as_ref().is_some_and(|list| list.contains(&header_name.to_string())) {
-                        *header_value = HeaderValue::from_str(&*encoder.encode(header_value.to_str().unwrap())).unwrap();
-                    } else if encode_list.as_ref().is_none() {
-                        *header_value = HeaderValue::from_str(&*encoder.encode(header_value.to_str().unwrap())).unwrap();
+    fn decode_header_value(value: &[u8], policy: InvalidSequencePolicy) -> Result<Cow<'_, str>, ()> {
+        match std::str::from_utf8(value) {
+            Ok(value) => Ok(Cow::Borrowed(value)),
+            Err(_) => match policy {
+                InvalidSequencePolicy::ReplaceWithInvalidChar => Ok(String::from_utf8_lossy(value)),
+                InvalidSequencePolicy::Strip => {
+                    let mut stripped = String::with_capacity(value.len());
+                    for chunk in value.utf8_chunks() {
+                        stripped.push_str(chunk.valid());
                     }
+                    Ok(Cow::Owned(stripped))
                 }
-                Ok(())
-            }
-            _ => todo!("Implement header sanitizer for modes")
+                InvalidSequencePolicy::Error => Err(()),
+            },
         }
     }
 
-   async fn sanitize_body(exchange: &mut LambdaExchange, mode: &SanitizerMode, ignore_list: &Option<Vec<String>>, encode_list: &Option<Vec<String>>) -> Result<(), ()> {
-        let body: Value = match exchange.input().await {
-            Ok(input) => {
-                match &input.body {
-                    None => return Ok(()),
-                    Some(body) => {
-                        match serde_json::from_str(&body) {
-                            Ok(val) => val,
-                            Err(_) => return Err(())
-                        }
-                    }
-                }
+    async fn sanitize_headers(exchange: &mut LambdaExchange, encoder: &dyn Encoder, ignore_list: &Option<Vec<String>>, encode_list: &Option<Vec<String>>, invalid_sequence_policy: InvalidSequencePolicy, max_output_len: Option<usize>, output_overflow_policy: OutputOverflowPolicy) -> Result<(), ()> {
 
+        // TODO - add input_mut
+        let headers = match exchange.input_mut().await {
+            Ok(input) => {
+                &mut input.headers
             }
             Err(_) => return Err(())
         };
-        let mut body = match body.as_object() {
-            None => return Ok(()),
-            Some(body) => body
-        };
-        let sanitized_body = match mode {
-            SanitizerMode::JavaScript(mode, ascii_only) => {
-                let encoder = match Self::java_script_encoder_for_mode(*mode, *ascii_only) {
-                    Ok(encoder) => encoder,
-                    Err(_) => return Err(())
-                };
 
-                let mut sanitized_body: Map<String, Value> = Map::new();
-                for (key, value) in body {
-                    if ignore_list.as_ref().is_some_and(|list| list.contains(&key)) {
-                        sanitized_body.insert(key.clone(), value.clone());
-                    } else if encode_list.as_ref().is_some_and(|list| list.contains(&key)) {
-                        sanitized_body.insert(key.clone(), Self::sanitize_value(value, ignore_list, encode_list, &encoder));
-                    } else if encode_list.as_ref().is_none() {
-                        sanitized_body.insert(key.clone(), Self::sanitize_value(value, ignore_list, encode_list, &encoder));
-                    }
-                }
-                sanitized_body
-            }
-            SanitizerMode::Uri(mode) => {
-                todo!("Implement URI encoder for body")
-            }
-            SanitizerMode::Xml(mode) => {
-                todo!("Implement XML encoder for body")
-            }
-        };
-        if let Ok(input) = exchange.input_mut().await {
-            if let Ok(value) = serde_json::to_string(&Value::Object(sanitized_body)) {
-                input.body = Some(value);
-                return Ok(())
+        for (header_name, header_value) in headers {
+            if ignore_list.as_ref().is_some_and(|list| list.contains(&header_name.to_string()))
+                || encode_list.as_ref().is_some_and(|list| !list.contains(&header_name.to_string())) {
+                continue;
             }
+            let decoded = Self::decode_header_value(header_value.as_bytes(), invalid_sequence_policy)?;
+            let encoded = match max_output_len {
+                Some(max_output_len) => encode_bounded(encoder, &decoded, max_output_len, output_overflow_policy)?,
+                None => encoder.encode(&decoded),
+            };
+            *header_value = match HeaderValue::from_str(&encoded) {
+                Ok(encoded) => encoded,
+                Err(_) => return Err(()),
+            };
         }
-        Err(())
+        Ok(())
     }
 
-    fn sanitize_value(current_value: &Value, ignore_list: &Option<Vec<String>>, encode_list: &Option<Vec<String>>, encoder: &JavaScriptEncoder) -> Value {
-        if let Some(value) = current_value.as_object() {
-            let mut map_value: Map<String, Value> = Map::new();
-            for (key, value) in value {
-                if ignore_list.as_ref().is_some_and(|list| list.contains(&key)) {
-                    map_value.insert(key.clone(), value.clone());
-                } else if encode_list.as_ref().is_some_and(|list| list.contains(&key)) {
-                    map_value.insert(key.clone(), Self::sanitize_value(value, ignore_list, encode_list, &encoder));
-                } else if encode_list.as_ref().is_none() {
-                    map_value.insert(key.clone(), Self::sanitize_value(value, ignore_list, encode_list, &encoder));
-                }
-            }
-            Value::Object(map_value)
-
-        } else if let Some(value) = current_value.as_array() {
-            let capacity = value.len();
-            let mut array_value: Vec<Value> = Vec::with_capacity(capacity);
-            for item in value {
-                array_value.push(Self::sanitize_value(item, ignore_list, encode_list, &encoder));
-            }
-            Value::Array(array_value)
-        } else if let Some(value) = current_value.as_str() {
-            let string_value = encoder.encode(value);
-            Value::String(string_value)
-        } else {
-            current_value.clone()
+    async fn sanitize_body(exchange: &mut LambdaExchange, encoder: &dyn Encoder, ignore_list: &Option<Vec<String>>, encode_list: &Option<Vec<String>>) -> Result<(), ()> {
+        if BodyAccess::bytes(exchange).await?.is_none() {
+            return Ok(())
         }
+        let mut body: Value = match BodyAccess::json(exchange).await? {
+            Some(body) => body.clone(),
+            None => return Err(())
+        };
+        encode_json_value(&mut body, encoder, encode_list.as_deref(), ignore_list.as_deref());
+        BodyAccess::set_json(exchange, body).await
     }
+}
 
-
+impl NamedHandler for SanitizerHandler {
+    const NAME: &'static str = "SanitizerHandler";
 }
 
 #[async_trait]
@@ -206,9 +207,15 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             SanitizerSettings::Enabled {
                 mode,
                 ignore_list,
-                encode_list
+                encode_list,
+                normalization,
+                ..
             } => {
-                if let Err(_) = Self::sanitize_body(exchange, mode, ignore_list, encode_list).await {
+                let encoder = match Self::build_encoder(mode, normalization) {
+                    Ok(encoder) => encoder,
+                    Err(_) => return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)),
+                };
+                if Self::sanitize_body(exchange, &*encoder, ignore_list, encode_list).await.is_err() {
                     return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR));
                 }
             }
@@ -221,10 +228,19 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
             SanitizerSettings::Enabled {
                 mode,
                 ignore_list,
-                encode_list
+                encode_list,
+                normalization,
+                invalid_sequence_policy,
+                max_output_len,
+                output_overflow_policy
             } => {
-                if let Err(_) = Self::sanitize_headers(exchange, mode, ignore_list, encode_list).await {
-                    return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR));
+                let encoder = match Self::build_encoder(mode, normalization) {
+                    Ok(encoder) => encoder,
+                    Err(_) => return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)),
+                };
+                if Self::sanitize_headers(exchange, &*encoder, ignore_list, encode_list, *invalid_sequence_policy, *max_output_len, *output_overflow_policy).await.is_err() {
+                    return Ok(HandlerStatus::new(ExchangeState::CLIENT_ERROR)
+                        .message("Request header could not be sanitized"));
                 }
             }
         }
@@ -232,6 +248,28 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     }
 
     fn name(&self) -> &str {
-        "SanitizerHandler"
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::sanitizer::{InvalidSequencePolicy, SanitizerHandler};
+
+    #[test]
+    fn test_invalid_sequence_policies() {
+        let value = b"abc\xffdef";
+
+        let replaced = SanitizerHandler::decode_header_value(value, InvalidSequencePolicy::ReplaceWithInvalidChar).unwrap();
+        assert_eq!(replaced, "abc\u{FFFD}def");
+
+        let stripped = SanitizerHandler::decode_header_value(value, InvalidSequencePolicy::Strip).unwrap();
+        assert_eq!(stripped, "abcdef");
+
+        let error = SanitizerHandler::decode_header_value(value, InvalidSequencePolicy::Error);
+        assert!(error.is_err());
+
+        let valid = SanitizerHandler::decode_header_value(b"caf\xc3\xa9", InvalidSequencePolicy::Error).unwrap();
+        assert_eq!(valid, "caf\u{e9}");
     }
 }
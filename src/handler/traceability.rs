@@ -6,11 +6,14 @@ use idemio::handler::Handler;
 use idemio::status::{ExchangeState, HandlerStatus};
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use lambda_http::http::{HeaderMap, HeaderName, HeaderValue};
-use lambda_http::{Context, tracing};
+use lambda_http::Context;
 use serde::Deserialize;
+use crate::flow::builder::NamedHandler;
+use crate::handler::attachments::ScopedAttachmentsExt;
 use crate::handler::LambdaExchange;
 
 #[derive(Deserialize)]
+#[serde(default)]
 pub struct TraceabilityHandlerConfig {
     pub enabled: bool,
     pub autogen_correlation_id: bool,
@@ -33,7 +36,7 @@ impl Default for TraceabilityHandlerConfig {
 
 //#[derive(ConfigurableHandler)]
 pub struct TraceabilityHandler {
-    config: Config<TraceabilityHandlerConfig>,
+    pub(crate) config: Config<TraceabilityHandlerConfig>,
 }
 
 impl TraceabilityHandler {
@@ -61,10 +64,16 @@ impl TraceabilityHandler {
     }
 }
 
-const TRACE_V_ATTACHMENT_KEY: &'static str = "trace_v";
-const CORR_V_ATTACHMENT_KEY: &'static str = "corr_v";
-const CORR_H_ATTACHMENT_KEY: &'static str = "corr_h";
-const TRACE_H_ATTACHMENT_KEY: &'static str = "trace_h";
+const TRACE_V_ATTACHMENT_KEY: &str = "trace_v";
+/* shared with the executor, in the well-known namespace */
+pub(crate) const CORRELATION_ID_ATTACHMENT_KEY: &str = "correlation_id";
+pub(crate) const TRACEABILITY_ID_ATTACHMENT_KEY: &str = "traceability_id";
+const CORR_H_ATTACHMENT_KEY: &str = "corr_h";
+const TRACE_H_ATTACHMENT_KEY: &str = "trace_h";
+
+impl NamedHandler for TraceabilityHandler {
+    const NAME: &'static str = "TraceabilityHandler";
+}
 
 #[async_trait]
 impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for TraceabilityHandler {
@@ -72,7 +81,6 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
         &self,
         exchange: &mut LambdaExchange,
     ) -> Result<HandlerStatus, Infallible> {
-        tracing::debug!("Traceability handler starts");
         if !self.config.get().enabled {
             return Ok(HandlerStatus::new(ExchangeState::DISABLED));
         }
@@ -88,35 +96,28 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
         let tid_header_name = self.config.get().traceability_header_name.clone();
         let tid = Self::find_or_create_uuid(&request.headers, &tid_header_name, false);
 
-        if cid.is_some() {
-            let cid = cid.unwrap();
-            if tid.is_some() {
-                let tid = tid.unwrap();
-                tracing::info!(
-                    "Associate traceability Id {} with correlation Id {}",
-                    &tid,
-                    &cid
-                );
+        if let Some(cid) = cid {
+            exchange
+                .attachments_mut()
+                .well_known_mut()
+                .add::<String>(CORRELATION_ID_ATTACHMENT_KEY, cid.clone());
+            if let Some(tid) = tid {
+                exchange
+                    .attachments_mut()
+                    .well_known_mut()
+                    .add::<String>(TRACEABILITY_ID_ATTACHMENT_KEY, tid.clone());
 
                 if self.config.get().add_trace_to_response {
-                    exchange
-                        .attachments_mut()
-                        .add::<String>(TRACE_V_ATTACHMENT_KEY, tid);
-                    exchange
-                        .attachments_mut()
-                        .add::<String>(CORR_V_ATTACHMENT_KEY, cid.clone());
-                    exchange.attachments_mut().add::<String>(
-                        CORR_H_ATTACHMENT_KEY,
-                        cid_header_name.clone(),
-                    );
-                    exchange.attachments_mut().add::<String>(
-                        TRACE_H_ATTACHMENT_KEY,
-                        tid_header_name,
-                    );
+                    let mut attachments = exchange.attachments_mut().scoped_mut(Self::NAME);
+                    attachments.add::<String>(TRACE_V_ATTACHMENT_KEY, tid);
+                    attachments.add::<String>(CORR_H_ATTACHMENT_KEY, cid_header_name.clone());
+                    attachments.add::<String>(TRACE_H_ATTACHMENT_KEY, tid_header_name);
                     exchange.add_output_listener(|response, attachments| {
+                        let well_known = attachments.well_known();
+                        let attachments = attachments.scoped(Self::NAME);
                         if let (Some(cid_header), Some(cid_value)) = (
                             attachments.get::<String>(CORR_H_ATTACHMENT_KEY),
-                            attachments.get::<String>(CORR_V_ATTACHMENT_KEY),
+                            well_known.get::<String>(CORRELATION_ID_ATTACHMENT_KEY),
                         ) {
                             response.headers.insert(
                                 HeaderName::from_bytes(cid_header.as_bytes()).unwrap(),
@@ -154,7 +155,7 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     }
 
     fn name(&self) -> &str {
-        "TraceabilityHandler"
+        Self::NAME
     }
 }
 
@@ -1,8 +1,11 @@
 use std::convert::Infallible;
+use crate::flow::builder::NamedHandler;
 use crate::ROOT_CONFIG_PATH;
+use crate::handler::body::BodyAccess;
 use crate::handler::LambdaExchange;
+use crate::metrics::{self, names};
 use async_trait::async_trait;
-use http::{HeaderMap, Method, Request};
+use http::{HeaderMap, Method};
 use idemio::config::Config;
 use idemio::exchange::Exchange;
 use idemio::handler::Handler;
@@ -51,6 +54,10 @@ pub struct ValidatorHandler {
     config: Config<ValidatorHandlerConfig>,
 }
 
+impl NamedHandler for ValidatorHandler {
+    const NAME: &'static str = "ValidatorHandler";
+}
+
 #[async_trait]
 impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for ValidatorHandler {
     async fn exec(
@@ -63,10 +70,15 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
 
         if self.config.get().loaded_openapi_specification.is_some() {
             let validator = self.config.get().loaded_openapi_specification.as_ref().unwrap();
+            let body = match BodyAccess::json(exchange).await {
+                Ok(body) => body.cloned(),
+                Err(_) => None,
+            };
             let request = exchange.input().await.unwrap();
-            let request = ApiGatewayProxyRequestWrapper::new(request);
+            let request = ApiGatewayProxyRequestWrapper::new(request, body);
             let result = validator.validate_request(&request, None);
             if result.is_err() {
+                metrics::counter(names::VALIDATION_FAILURES, 1, &[("handler", Self::NAME)]);
                 return Ok(HandlerStatus::new(ExchangeState::CLIENT_ERROR)
                     .message("Request validation failed"));
             }
@@ -77,7 +89,7 @@ impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>>
     }
 
     fn name(&self) -> &str {
-        "ValidatorHandler"
+        Self::NAME
     }
 }
 
@@ -89,7 +101,7 @@ struct ApiGatewayProxyRequestWrapper<'a> {
 }
 
 impl<'a> ApiGatewayProxyRequestWrapper<'a> {
-    pub fn new(request: &'a ApiGatewayProxyRequest) -> Self {
+    pub fn new(request: &'a ApiGatewayProxyRequest, body: Option<Value>) -> Self {
         let path = request.path.clone().unwrap_or("/".to_string());
         let query_params: Option<String> = if !request.query_string_parameters.is_empty() {
             Some(request.query_string_parameters.to_query_string())   
@@ -97,16 +109,6 @@ impl<'a> ApiGatewayProxyRequestWrapper<'a> {
             None
         };
         
-        let body: Option<Value> = match request.body.as_ref() {
-            None => None,
-            Some(found) => {
-                match serde_json::from_str(found) {
-                    Ok(x) => Some(x),
-                    Err(_) => None,
-                }
-            }
-        };
-        
         Self {
             request,
             body,
@@ -134,10 +136,7 @@ impl HttpLike<String> for ApiGatewayProxyRequestWrapper<'_>
 
     fn body(&self) -> Option<Value> {
         // TODO - change to ref of value to reduce clone.
-        match &self.body {
-            None => None,
-            Some(body) => Some(body.clone())
-        }
+        self.body.clone()
     }
 
     fn query(&self) -> Option<&str> {
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::Path;
+use async_trait::async_trait;
+use idemio::config::Config;
+use idemio::exchange::Exchange;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::{HeaderName, HeaderValue};
+use lambda_http::{Body, Context};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+use wasmtime::{Engine, Instance, Module, Store};
+use crate::executor::pool::HandlerLifecycle;
+use crate::flow::builder::NamedHandler;
+use crate::handler::LambdaExchange;
+use crate::ROOT_CONFIG_PATH;
+
+/* Guest ABI: the module exports its memory plus
+ *   idem_alloc(len: i32) -> i32             reserves len bytes for the request
+ *   idem_handle(ptr: i32, len: i32) -> i64  handles a WasmRequest, returns (ptr << 32) | len of a WasmResponse
+ * Both messages are JSON. */
+const GUEST_MEMORY_EXPORT: &str = "memory";
+const GUEST_ALLOC_EXPORT: &str = "idem_alloc";
+const GUEST_HANDLE_EXPORT: &str = "idem_handle";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct WasmHandlerConfig {
+    pub enabled: bool,
+    /* relative paths are resolved against the config layer */
+    pub module_path: String,
+}
+
+#[derive(Serialize)]
+struct WasmRequest<'a> {
+    method: &'a str,
+    path: Option<&'a str>,
+    headers: HashMap<&'a str, &'a str>,
+    body: Option<&'a str>,
+    is_base64_encoded: bool,
+}
+
+#[derive(Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum WasmDisposition {
+    #[default]
+    Continue,
+    Complete,
+    ClientError,
+    ServerError,
+}
+
+/// What the guest returns. On `continue` the headers are added to the request, otherwise they
+/// are part of the response the guest produced.
+#[derive(Deserialize, Default)]
+struct WasmResponse {
+    #[serde(default)]
+    status: WasmDisposition,
+    #[serde(default)]
+    status_code: Option<i64>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+pub struct WasmHandler {
+    pub(crate) config: Config<WasmHandlerConfig>,
+    engine: Engine,
+    /* compiled once per container, instantiated per request */
+    module: OnceCell<Module>,
+}
+
+impl WasmHandler {
+    pub(crate) fn new(config: Config<WasmHandlerConfig>) -> Self {
+        Self {
+            config,
+            engine: Engine::default(),
+            module: OnceCell::new(),
+        }
+    }
+
+    async fn module(&self) -> Result<&Module, String> {
+        self.module
+            .get_or_try_init(|| async {
+                let module_path = Path::new(ROOT_CONFIG_PATH).join(&self.config.get().module_path);
+                Module::from_file(&self.engine, &module_path)
+                    .map_err(|e| format!("Could not load {}: {}", module_path.display(), e))
+            })
+            .await
+    }
+
+    fn call_guest(&self, module: &Module, input: &[u8]) -> Result<WasmResponse, String> {
+        let mut store = Store::new(&self.engine, ());
+        let instance = Instance::new(&mut store, module, &[]).map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&mut store, GUEST_MEMORY_EXPORT)
+            .ok_or("Module does not export its memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, GUEST_ALLOC_EXPORT)
+            .map_err(|e| e.to_string())?;
+        let handle = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, GUEST_HANDLE_EXPORT)
+            .map_err(|e| e.to_string())?;
+
+        let input_len = i32::try_from(input.len()).map_err(|e| e.to_string())?;
+        let input_ptr = alloc.call(&mut store, input_len).map_err(|e| e.to_string())?;
+        memory
+            .write(&mut store, input_ptr as u32 as usize, input)
+            .map_err(|e| e.to_string())?;
+        let packed = handle
+            .call(&mut store, (input_ptr, input_len))
+            .map_err(|e| e.to_string())? as u64;
+        let output_ptr = (packed >> 32) as usize;
+        let mut output = vec![0u8; (packed & 0xFFFF_FFFF) as usize];
+        memory
+            .read(&store, output_ptr, &mut output)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&output).map_err(|e| e.to_string())
+    }
+
+    fn parse_headers(headers: HashMap<String, String>) -> Vec<(HeaderName, HeaderValue)> {
+        headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                Some((HeaderName::try_from(name).ok()?, HeaderValue::try_from(value).ok()?))
+            })
+            .collect()
+    }
+}
+
+impl NamedHandler for WasmHandler {
+    const NAME: &'static str = "WasmHandler";
+}
+
+#[async_trait]
+impl HandlerLifecycle for WasmHandler {
+    async fn on_init(&self) -> Result<(), String> {
+        if !self.config.get().enabled {
+            return Ok(());
+        }
+        self.module().await.map(|_| ())
+    }
+}
+
+#[async_trait]
+impl Handler<Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>> for WasmHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        if !self.config.get().enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+        let module = match self.module().await {
+            Ok(module) => module,
+            Err(e) => return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR).message(e)),
+        };
+
+        let request = match exchange.input().await {
+            Ok(request) => request,
+            Err(_) => {
+                return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
+                    .message("Failed to read request."));
+            }
+        };
+        let input = serde_json::to_vec(&WasmRequest {
+            method: request.http_method.as_str(),
+            path: request.path.as_deref(),
+            headers: request
+                .headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+                .collect(),
+            body: request.body.as_deref(),
+            is_base64_encoded: request.is_base64_encoded,
+        });
+        let response = match input
+            .map_err(|e| e.to_string())
+            .and_then(|input| self.call_guest(module, &input))
+        {
+            Ok(response) => response,
+            Err(e) => {
+                return Ok(HandlerStatus::new(ExchangeState::SERVER_ERROR)
+                    .message(format!("WASM module failed: {}", e)));
+            }
+        };
+
+        let state = match response.status {
+            WasmDisposition::Continue => {
+                if let Ok(request) = exchange.input_mut().await {
+                    request.headers.extend(Self::parse_headers(response.headers));
+                }
+                return Ok(HandlerStatus::new(ExchangeState::OK));
+            }
+            WasmDisposition::Complete => ExchangeState::EXCHANGE_COMPLETED,
+            WasmDisposition::ClientError => ExchangeState::CLIENT_ERROR,
+            WasmDisposition::ServerError => ExchangeState::SERVER_ERROR,
+        };
+        let mut output = ApiGatewayProxyResponse {
+            status_code: response.status_code.unwrap_or(match response.status {
+                WasmDisposition::ClientError => 400,
+                WasmDisposition::ServerError => 500,
+                _ => 200,
+            }),
+            body: response.body.map(Body::Text),
+            ..Default::default()
+        };
+        output.headers.extend(Self::parse_headers(response.headers));
+        exchange.set_output(output);
+        Ok(HandlerStatus::new(state))
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::wasm::{WasmDisposition, WasmResponse};
+
+    #[test]
+    fn test_parse_guest_response() {
+        let response: WasmResponse =
+            serde_json::from_str(r#"{"headers":{"x-tenant":"a"}}"#).unwrap();
+        assert_eq!(response.status, WasmDisposition::Continue);
+        assert_eq!(response.headers["x-tenant"], "a");
+
+        let response: WasmResponse =
+            serde_json::from_str(r#"{"status":"client_error","status_code":403,"body":"denied"}"#)
+                .unwrap();
+        assert_eq!(response.status, WasmDisposition::ClientError);
+        assert_eq!(response.status_code, Some(403));
+    }
+}
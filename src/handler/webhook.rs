@@ -0,0 +1,294 @@
+use crate::config::units::deserialize_duration;
+use crate::error::{
+    HandlerError, ERR10010_STALE_REQUEST, ERR10011_INVALID_SIGNATURE, ERR11000_INVALID_REQUEST,
+    ERR13000_INTERNAL,
+};
+use crate::flow::builder::NamedHandler;
+use crate::flow::matcher::matched_route;
+use crate::handler::body::BodyAccess;
+use crate::handler::LambdaExchange;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use idemio::config::Config;
+use idemio::handler::Handler;
+use idemio::status::{ExchangeState, HandlerStatus};
+use lambda_http::http::HeaderMap;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const GITHUB_SIGNATURE_HEADER: &str = "x-hub-signature-256";
+const STRIPE_SIGNATURE_HEADER: &str = "stripe-signature";
+const SLACK_SIGNATURE_HEADER: &str = "x-slack-signature";
+const SLACK_TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+
+/// How a provider signs its webhooks, each an HMAC-SHA256 of the raw body under the shared
+/// `secret`:
+///
+/// - `github`: `X-Hub-Signature-256: sha256=<hex>`.
+/// - `stripe`: `Stripe-Signature: t=<unix time>,v1=<hex>`, signing `<t>.<body>`.
+/// - `slack`: `X-Slack-Signature: v0=<hex>` and `X-Slack-Request-Timestamp`, signing
+///   `v0:<timestamp>:<body>`.
+///
+/// Stripe and Slack signatures older or newer than `tolerance` are rejected.
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(tag = "scheme", rename_all = "snake_case")]
+pub enum WebhookScheme {
+    Github {
+        secret: String,
+    },
+    Stripe {
+        secret: String,
+        #[serde(default = "default_tolerance", deserialize_with = "deserialize_duration")]
+        tolerance: Duration,
+    },
+    Slack {
+        secret: String,
+        #[serde(default = "default_tolerance", deserialize_with = "deserialize_duration")]
+        tolerance: Duration,
+    },
+}
+
+fn default_tolerance() -> Duration {
+    Duration::from_secs(300)
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/* keeps the shared secrets out of logged configs */
+impl fmt::Debug for WebhookScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookScheme::Github { .. } => {
+                f.debug_struct("Github").field("secret", &REDACTED).finish()
+            }
+            WebhookScheme::Stripe { tolerance, .. } => f
+                .debug_struct("Stripe")
+                .field("secret", &REDACTED)
+                .field("tolerance", tolerance)
+                .finish(),
+            WebhookScheme::Slack { tolerance, .. } => f
+                .debug_struct("Slack")
+                .field("secret", &REDACTED)
+                .field("tolerance", tolerance)
+                .finish(),
+        }
+    }
+}
+
+/// Verifies the signatures of inbound webhooks, so the functions receiving them don't have to.
+/// Routes are keyed by route template, and requests to routes without a scheme pass through:
+///
+/// ```json
+/// { "enabled": true, "routes": {
+///     "/hooks/github": { "scheme": "github", "secret": "..." },
+///     "/hooks/stripe": { "scheme": "stripe", "secret": "whsec_...", "tolerance": "5m" } } }
+/// ```
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WebhookHandlerConfig {
+    pub enabled: bool,
+    pub routes: HashMap<String, WebhookScheme>,
+}
+
+impl Default for WebhookHandlerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, HandlerError> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| {
+            ERR10011_INVALID_SIGNATURE
+                .error()
+                .detail(format!("Missing {} header", name))
+        })
+}
+
+/* whether the hex signature is the HMAC of the parts, compared in constant time */
+fn signed(secret: &str, parts: &[&[u8]], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    parts.iter().for_each(|part| mac.update(part));
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn check_timestamp(timestamp: &str, tolerance: Duration, now: u64) -> Result<(), HandlerError> {
+    let timestamp = timestamp.parse::<u64>().map_err(|_| {
+        ERR10011_INVALID_SIGNATURE
+            .error()
+            .detail("Invalid signature timestamp")
+    })?;
+    if timestamp.abs_diff(now) > tolerance.as_secs() {
+        return Err(ERR10010_STALE_REQUEST
+            .error()
+            .metadata("tolerance", tolerance.as_secs().to_string()));
+    }
+    Ok(())
+}
+
+impl WebhookScheme {
+    fn verify(&self, headers: &HeaderMap, body: &[u8], now: u64) -> Result<(), HandlerError> {
+        let verified = match self {
+            WebhookScheme::Github { secret } => header(headers, GITHUB_SIGNATURE_HEADER)?
+                .strip_prefix("sha256=")
+                .is_some_and(|signature| signed(secret, &[body], signature)),
+            WebhookScheme::Stripe { secret, tolerance } => {
+                let fields: Vec<(&str, &str)> = header(headers, STRIPE_SIGNATURE_HEADER)?
+                    .split(',')
+                    .filter_map(|field| field.trim().split_once('='))
+                    .collect();
+                let timestamp = fields
+                    .iter()
+                    .find(|(name, _)| *name == "t")
+                    .map(|(_, timestamp)| *timestamp)
+                    .unwrap_or_default();
+                check_timestamp(timestamp, *tolerance, now)?;
+                /* more than one while the secret is being rolled */
+                fields.iter().filter(|(name, _)| *name == "v1").any(|(_, signature)| {
+                    signed(secret, &[timestamp.as_bytes(), b".", body], signature)
+                })
+            }
+            WebhookScheme::Slack { secret, tolerance } => {
+                let timestamp = header(headers, SLACK_TIMESTAMP_HEADER)?;
+                check_timestamp(timestamp, *tolerance, now)?;
+                header(headers, SLACK_SIGNATURE_HEADER)?
+                    .strip_prefix("v0=")
+                    .is_some_and(|signature| {
+                        signed(secret, &[b"v0:", timestamp.as_bytes(), b":", body], signature)
+                    })
+            }
+        };
+        if !verified {
+            return Err(ERR10011_INVALID_SIGNATURE.error());
+        }
+        Ok(())
+    }
+}
+
+pub struct WebhookHandler {
+    pub(crate) config: Config<WebhookHandlerConfig>,
+}
+
+impl NamedHandler for WebhookHandler {
+    const NAME: &'static str = "WebhookHandler";
+}
+
+#[async_trait]
+impl Handler<LambdaExchange> for WebhookHandler {
+    async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+        let config = self.config.get();
+        if !config.enabled {
+            return Ok(HandlerStatus::new(ExchangeState::DISABLED));
+        }
+        let Some(scheme) = matched_route(exchange).and_then(|route| config.routes.get(route))
+        else {
+            return Ok(HandlerStatus::new(ExchangeState::OK));
+        };
+
+        let headers = match exchange.input().await {
+            Ok(request) => request.headers.clone(),
+            Err(_) => {
+                return Ok(ERR13000_INTERNAL
+                    .error()
+                    .detail("Unable to get request")
+                    .raise(exchange));
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let verified = match BodyAccess::bytes(exchange).await {
+            Ok(body) => scheme.verify(&headers, body.unwrap_or_default(), now),
+            Err(_) => Err(ERR11000_INVALID_REQUEST
+                .error()
+                .detail("Request body is not valid base64")),
+        };
+        match verified {
+            Ok(()) => Ok(HandlerStatus::new(ExchangeState::OK)),
+            Err(error) => Ok(error.raise(exchange)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handler::webhook::WebhookScheme;
+    use hmac::{Hmac, Mac};
+    use lambda_http::http::{HeaderMap, HeaderValue};
+    use sha2::Sha256;
+    use std::time::Duration;
+
+    fn hex_hmac(secret: &str, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(payload.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_webhook_schemes() {
+        let body = br#"{"event":"push"}"#;
+        let payload = r#"{"event":"push"}"#;
+        let github = WebhookScheme::Github {
+            secret: "gh".to_string(),
+        };
+        let signature = format!("sha256={}", hex_hmac("gh", payload));
+        let signed = headers(&[("x-hub-signature-256", signature)]);
+        assert!(github.verify(&signed, body, 0).is_ok());
+        assert!(github.verify(&signed, b"{}", 0).is_err());
+        assert!(github.verify(&HeaderMap::new(), body, 0).is_err());
+        assert_eq!(format!("{:?}", github), r#"Github { secret: "[REDACTED]" }"#);
+
+        let stripe = WebhookScheme::Stripe {
+            secret: "whsec".to_string(),
+            tolerance: Duration::from_secs(300),
+        };
+        let signature = hex_hmac("whsec", &format!("1000.{}", payload));
+        let value = format!("t=1000,v1=00ff,v1={},v0=abc", signature);
+        let signed = headers(&[("stripe-signature", value)]);
+        assert!(stripe.verify(&signed, body, 1200).is_ok());
+        let error = stripe.verify(&signed, body, 1301).unwrap_err();
+        assert_eq!(error.code.code, "ERR10010");
+
+        let slack = WebhookScheme::Slack {
+            secret: "sl".to_string(),
+            tolerance: Duration::from_secs(300),
+        };
+        let signature = format!("v0={}", hex_hmac("sl", &format!("v0:1000:{}", payload)));
+        let signed = headers(&[
+            ("x-slack-signature", signature),
+            ("x-slack-request-timestamp", "1000".to_string()),
+        ]);
+        assert!(slack.verify(&signed, body, 1000).is_ok());
+        let error = slack.verify(&signed, b"tampered", 1000).unwrap_err();
+        assert_eq!(error.code.code, "ERR10011");
+    }
+}
@@ -1,25 +1,75 @@
+/* handlers, adapters and builder methods the built-in flow doesn't use are kept for flows and
+   embedding code that do */
+#![allow(dead_code)]
+
 use async_trait::async_trait;
+use aws_lambda_events::eventbridge::EventBridgeEvent;
+use aws_lambda_events::sns::SnsEvent;
+use aws_lambda_events::sqs::SqsEvent;
 use core::result::Result;
-use idemio::config::{Config, DefaultConfigProvider};
+use idemio::config::{Config, ConfigProvider, DefaultConfigProvider, FileConfigProvider};
 use idemio::exchange::Exchange;
-use idemio::handler::registry::HandlerRegistry;
-use idemio::handler::HandlerId;
-use idemio::router::config::builder::{
-    MethodBuilder, RouteBuilder, ServiceBuilder, SingleServiceConfigBuilder,
-};
-use idemio::router::executor::DefaultExecutor;
 use idemio::router::factory::{ExchangeFactory, ExchangeFactoryError, RouteInfo};
-use idemio::router::path::http::HttpPathMethodMatcher;
 use idemio::router::path::PathMatcher;
 use idemio::router::{RequestRouter, Router, RouterBuilder};
 use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::http::header::ALLOW;
+use lambda_http::http::HeaderValue;
 use lambda_http::tracing::init_default_subscriber;
-use lambda_http::{lambda_runtime, service_fn, Body, Context, Error, LambdaEvent};
-use std::marker::PhantomData;
-use std::sync::Arc;
+use lambda_http::{lambda_runtime, service_fn, Context, Error, LambdaEvent};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
+pub(crate) mod audit;
+pub(crate) mod config;
+pub(crate) mod encoder;
+pub(crate) mod error;
+pub(crate) mod event;
+pub(crate) mod executor;
+pub(crate) mod flow;
 pub(crate) mod handler;
+pub(crate) mod metrics;
+#[cfg(feature = "otel")]
+pub(crate) mod telemetry;
+#[cfg(test)]
+pub(crate) mod testkit;
 
+use crate::audit::AuditConfig;
+use crate::config::file::CachedFileConfigProvider;
+use crate::config::registry::ConfigRegistry;
+use crate::config::reload::{FileVersion, RefreshPolicy, ReloadableConfig};
+use crate::config::units::parse_duration;
+use crate::event::authorizer::AuthorizerRequest;
+use crate::event::eventbridge::run_event;
+use crate::event::sns::run_notifications;
+use crate::event::sqs::run_batch;
+use crate::event::warmup::WarmupConfig;
+use crate::event::{
+    EventKind, EventResponse, EventRoutes, GatewayRequest, GatewayResponse, PayloadFormat,
+};
+use crate::executor::authorizer::set_authorize_only;
+use crate::executor::deadline::{set_deadline, Deadline};
+use crate::error::{ERR11002_NO_ROUTE, ERR11003_METHOD_NOT_ALLOWED};
+use crate::executor::exception::{ErrorContext, ExceptionHandlers, StatusClass};
+use crate::executor::pool::HandlerPool;
+use crate::executor::response_mapper::ResponseMapperConfig;
+use crate::executor::flags::StatusFlags;
+use crate::executor::access_log::AccessLogConfig;
+use crate::executor::trace::ChainTraceConfig;
+use crate::executor::LambdaExecutor;
+use crate::flow::base_path::BasePathConfig;
+use crate::flow::builder::ChainBuilder;
+use crate::flow::matcher::{
+    request_host, set_path_parameters, set_route, set_route_metadata, PathTemplates, RouteMatch,
+    TemplatePathMatcher, REQUEST_HOST,
+};
+use crate::flow::openapi::flow_from_openapi;
+use crate::flow::registry::HandlerFactories;
+use crate::flow::{ExecutionFlowConfig, RouteMetadata};
 use crate::handler::header::HeaderHandler;
 use crate::handler::jwt::JwtValidationHandler;
 use crate::handler::proxy::LambdaProxyHandler;
@@ -27,10 +77,29 @@ use crate::handler::proxy::LambdaProxyHandler;
 pub const ROOT_CONFIG_PATH: &str = "/opt/config";
 
 type LambdaExchange = Exchange<ApiGatewayProxyRequest, ApiGatewayProxyResponse, Context>;
-type LambdaPathRouter = HttpPathMethodMatcher<LambdaExchange>;
+type LambdaPathRouter = TemplatePathMatcher<LambdaExchange>;
 type IncomingLambdaRequest = ApiGatewayProxyRequest;
-type OutgoingLambdaResponse = ApiGatewayProxyResponse;
-struct LambdaExchangeFactory;
+struct LambdaExchangeFactory {
+    templates: Arc<PathTemplates>,
+    /* metadata by route, then method */
+    route_metadata: Arc<BTreeMap<String, BTreeMap<String, RouteMetadata>>>,
+}
+
+impl LambdaExchangeFactory {
+    fn new(parts: &RouterParts) -> Self {
+        Self {
+            templates: parts.matcher.templates(),
+            route_metadata: parts.route_metadata.clone(),
+        }
+    }
+}
+
+tokio::task_local! {
+    /* the context of the invocation being routed, for the exchange factory */
+    static INVOCATION_CONTEXT: Context;
+    /* set while running a chain as an API Gateway authorizer */
+    static AUTHORIZE_ONLY: bool;
+}
 
 #[async_trait]
 impl ExchangeFactory<IncomingLambdaRequest, LambdaExchange> for LambdaExchangeFactory {
@@ -38,10 +107,7 @@ impl ExchangeFactory<IncomingLambdaRequest, LambdaExchange> for LambdaExchangeFa
         &self,
         request: &'a IncomingLambdaRequest,
     ) -> Result<RouteInfo<'a>, ExchangeFactoryError> {
-        let path = match request.path.as_ref() {
-            None => None,
-            Some(val) => Some(val.as_str()),
-        };
+        let path = request.path.as_deref();
         let method = Some(request.http_method.as_str());
         Ok(RouteInfo { path, method })
     }
@@ -50,8 +116,38 @@ impl ExchangeFactory<IncomingLambdaRequest, LambdaExchange> for LambdaExchangeFa
         &self,
         request: IncomingLambdaRequest,
     ) -> Result<LambdaExchange, ExchangeFactoryError> {
+        let host = request_host(&request);
+        let method = request.http_method.as_str();
+        let (route, path_parameters) = match request.path.as_deref() {
+            Some(path) => (
+                self.templates.route(host.as_deref(), path, method).map(str::to_string),
+                self.templates.path_parameters(host.as_deref(), path, method),
+            ),
+            None => (None, None),
+        };
+        let metadata = route
+            .as_ref()
+            .and_then(|route| self.route_metadata.get(route))
+            .and_then(|by_method| by_method.get(method))
+            .cloned();
         let mut exchange = Exchange::new();
         exchange.set_input(request);
+        if let Some(route) = route {
+            set_route(&mut exchange, &route);
+        }
+        if let Some(path_parameters) = path_parameters {
+            set_path_parameters(&mut exchange, path_parameters);
+        }
+        if let Some(metadata) = metadata {
+            set_route_metadata(&mut exchange, metadata);
+        }
+        if AUTHORIZE_ONLY.try_with(|authorize_only| *authorize_only).unwrap_or(false) {
+            set_authorize_only(&mut exchange);
+        }
+        let _ = INVOCATION_CONTEXT.try_with(|context| {
+            set_deadline(&mut exchange, Deadline::from_context(context));
+            exchange.set_metadata(context.clone());
+        });
         Ok(exchange)
     }
 }
@@ -60,78 +156,471 @@ type AwsLambdaRouter = RequestRouter<
     IncomingLambdaRequest,
     LambdaExchange,
     LambdaExchangeFactory,
-    DefaultExecutor<OutgoingLambdaResponse>,
+    LambdaExecutor,
     LambdaPathRouter,
 >;
 
-// TODO - these will be changed to be configurable, for now we just use the default config for all handlers and statically set our endpoints.
-fn create_router() -> AwsLambdaRouter {
-    let mut handler_registry = HandlerRegistry::new();
-    // only use the header handler, jwt handler, and proxy handler for now.
-    let header_handler = HeaderHandler {
-        config: Config::new(DefaultConfigProvider).unwrap(),
-    };
-    handler_registry
-        .register_handler(HandlerId::new("HeaderHandler"), header_handler)
-        .unwrap();
-    let jwt_handler = JwtValidationHandler {
-        config: Config::new(DefaultConfigProvider).unwrap(),
-    };
-    handler_registry
-        .register_handler(HandlerId::new("JwtValidationHandler"), jwt_handler)
-        .unwrap();
-    let proxy_handler = LambdaProxyHandler {
-        config: Config::new(DefaultConfigProvider).unwrap(),
-    };
-    handler_registry
-        .register_handler(HandlerId::new("LambdaProxyHandler"), proxy_handler)
-        .unwrap();
-    let router_config = SingleServiceConfigBuilder::new()
+const HANDLERS_CONFIG_NAME: &str = "handlers.json";
+
+/* Used when no handlers.json is deployed with the function. */
+fn default_flow() -> ExecutionFlowConfig {
+    ChainBuilder::new()
         .route("/test")
         .get()
-        .request_handler("JwtValidationHandler")
-        .request_handler("HeaderHandler")
-        .termination_handler("LambdaProxyHandler")
+        .request_handler::<JwtValidationHandler>()
+        .request_handler::<HeaderHandler>()
+        .termination_handler::<LambdaProxyHandler>()
         .end_method()
         .end_route()
-        .build();
+        .build()
+}
 
-    let matcher = HttpPathMethodMatcher::new(&router_config, &handler_registry).unwrap();
-    let executor: DefaultExecutor<OutgoingLambdaResponse> = DefaultExecutor {
-        _phantom: PhantomData::default(),
+/* Set to a duration (e.g. "5m") to check handlers.json and the handler configs at that interval
+   and rebuild on change. */
+const FLOW_REFRESH_ENV_VAR: &str = "IDEM_FLOW_REFRESH";
+
+/* Set to the name of an OpenAPI spec (JSON) in the config directory to take the routes from it. */
+const FLOW_OPENAPI_ENV_VAR: &str = "IDEM_FLOW_OPENAPI";
+
+/// What a router is assembled from. The test kit runs chains on these directly.
+struct RouterParts {
+    matcher: LambdaPathRouter,
+    executor: LambdaExecutor,
+    handler_pool: Arc<HandlerPool>,
+    exception_handlers: ExceptionHandlers,
+    base_path: BasePathConfig,
+    route_metadata: Arc<BTreeMap<String, BTreeMap<String, RouteMetadata>>>,
+}
+
+fn create_router_parts(
+    flow: &ExecutionFlowConfig,
+    spec: Option<&Value>,
+    factories: &HandlerFactories,
+    status_flags: &StatusFlags,
+) -> Result<RouterParts, String> {
+    let generated;
+    let flow = match spec {
+        Some(spec) => {
+            generated = flow_from_openapi(spec, flow)?;
+            &generated
+        }
+        None => flow,
     };
-    let factory = LambdaExchangeFactory;
-    RouterBuilder::new()
+    let (handler_registry, handler_pool) = factories.build(flow)?;
+    let handler_pool = Arc::new(handler_pool);
+    let router_config = flow.router_config()?;
+
+    let mut matcher =
+        TemplatePathMatcher::new(&router_config, &handler_registry).map_err(|e| e.to_string())?;
+    matcher.prioritize(&flow.route_priorities()?);
+    let response_mapper: Config<ResponseMapperConfig> = Config::new(DefaultConfigProvider).unwrap();
+    let chain_trace: Config<ChainTraceConfig> = Config::new(DefaultConfigProvider).unwrap();
+    let access_log: Config<AccessLogConfig> = Config::new(DefaultConfigProvider).unwrap();
+    let mut executor = LambdaExecutor::new(response_mapper.get().exception_handlers())
+        .handler_pool(handler_pool.clone())
+        .chain_trace(chain_trace.get().clone())
+        .access_log(access_log.get().clone())
+        .sampling(flow.sampling.clone())
+        .latency_budgets(flow.latency_budgets()?)
+        .complete_on(status_flags.parse(&flow.complete_on)?)
+        .status_flags(status_flags.clone());
+    for (handler_name, policy) in &flow.retry {
+        executor = executor.retry_policy(handler_name, policy.clone());
+    }
+    for (handler_name, limit) in &flow.max_concurrency {
+        executor = executor.max_concurrency(handler_name, *limit);
+    }
+    for (handler_name, timeout) in &flow.timeouts {
+        executor = executor.handler_timeout(handler_name, *timeout);
+    }
+    if let Some(timeout) = flow.default_timeout {
+        executor = executor.default_handler_timeout(timeout);
+    }
+    Ok(RouterParts {
+        matcher,
+        executor,
+        handler_pool,
+        exception_handlers: response_mapper.get().exception_handlers(),
+        base_path: flow.base_path.clone(),
+        route_metadata: Arc::new(flow.route_metadata()?),
+    })
+}
+
+fn create_router(
+    flow: &ExecutionFlowConfig,
+    spec: Option<&Value>,
+    factories: &HandlerFactories,
+    status_flags: &StatusFlags,
+) -> Result<(LambdaGateway, Arc<HandlerPool>), String> {
+    let parts = create_router_parts(flow, spec, factories, status_flags)?;
+    let factory = LambdaExchangeFactory::new(&parts);
+    let templates = factory.templates.clone();
+    let router = RouterBuilder::new()
         .factory(factory)
-        .executor(executor)
-        .matcher(matcher)
-        .build()
+        .executor(parts.executor)
+        .matcher(parts.matcher)
+        .build();
+    let gateway = LambdaGateway {
+        router,
+        templates,
+        exception_handlers: parts.exception_handlers,
+        base_path: parts.base_path,
+    };
+    Ok((gateway, parts.handler_pool))
+}
+
+/// The router, with what is needed to answer the requests it has no chain for.
+struct LambdaGateway {
+    router: AwsLambdaRouter,
+    templates: Arc<PathTemplates>,
+    exception_handlers: ExceptionHandlers,
+    base_path: BasePathConfig,
+}
+
+impl LambdaGateway {
+    fn unmatched(
+        &self,
+        request: &IncomingLambdaRequest,
+        host: Option<&str>,
+        context: &Context,
+    ) -> Option<ApiGatewayProxyResponse> {
+        unmatched_response(&self.templates, &self.exception_handlers, request, host, context)
+    }
+}
+
+/* the router can't be asked for a route it doesn't have, so those are answered here */
+fn unmatched_response(
+    templates: &PathTemplates,
+    exception_handlers: &ExceptionHandlers,
+    request: &IncomingLambdaRequest,
+    host: Option<&str>,
+    context: &Context,
+) -> Option<ApiGatewayProxyResponse> {
+    let method = request.http_method.as_str();
+    let route_match = match request.path.as_deref() {
+        Some(path) => templates.route_match(host, path, method),
+        None => RouteMatch::NotFound,
+    };
+    let (status_class, error, allowed) = match route_match {
+        RouteMatch::Found => return None,
+        RouteMatch::NotFound => (StatusClass::NotFound, ERR11002_NO_ROUTE.error(), None),
+        RouteMatch::MethodNotAllowed(allowed) => (
+            StatusClass::MethodNotAllowed,
+            ERR11003_METHOD_NOT_ALLOWED.error(),
+            Some(allowed),
+        ),
+    };
+    let mut response = exception_handlers.handle(&ErrorContext {
+        status_class: Some(status_class),
+        handler_name: None,
+        correlation_id: &context.request_id,
+        error: Some(&error),
+    });
+    let allow = allowed.and_then(|allowed| HeaderValue::from_str(&allowed.join(", ")).ok());
+    if let Some(allow) = allow {
+        response.headers.insert(ALLOW, allow);
+    }
+    Some(response)
+}
+
+struct BuiltRouter {
+    flow: Arc<ExecutionFlowConfig>,
+    router: Arc<LambdaGateway>,
+    /* the event routes and warm-up markers of the flow `router` was built from */
+    events: EventRoutes,
+    warmup: WarmupConfig,
+    handler_pool: Arc<HandlerPool>,
+}
+
+/// The execution flow, its handlers and the router built from them, created once at init and
+/// reused by every warm invocation. When a refresh interval is configured, `handlers.json` and
+/// the handler configs in the global [`ConfigRegistry`] are checked for changes at that interval
+/// and a new router replaces the old one; a flow that fails to build keeps the current router.
+/// A handler config that is pinned or rolled back in the registry rebuilds the router as well.
+struct RouterCache {
+    factories: HandlerFactories,
+    status_flags: StatusFlags,
+    /* the OpenAPI spec the routes are generated from, instead of the flow's paths */
+    spec: Option<Arc<Value>>,
+    flow: Option<ReloadableConfig<ExecutionFlowConfig>>,
+    /* how often the handler configs are reloaded, and when they last were */
+    config_refresh: Option<(Duration, Mutex<Instant>)>,
+    /* set by the registry when a handler config changed since the router was built */
+    configs_changed: Arc<AtomicBool>,
+    current: RwLock<BuiltRouter>,
+}
+
+impl RouterCache {
+    fn new(factories: HandlerFactories, status_flags: StatusFlags) -> Result<Self, String> {
+        let config_path = Path::new(ROOT_CONFIG_PATH).join(HANDLERS_CONFIG_NAME);
+        let refresh = match std::env::var(FLOW_REFRESH_ENV_VAR) {
+            Ok(interval) => Some(parse_duration(&interval)?),
+            Err(_) => None,
+        };
+        let spec = match std::env::var(FLOW_OPENAPI_ENV_VAR) {
+            Ok(spec_name) => Some(
+                CachedFileConfigProvider::new(ROOT_CONFIG_PATH, spec_name)
+                    .load_value()
+                    .map_err(|e| e.to_string())?,
+            ),
+            Err(_) => None,
+        };
+        let (flow, reloadable) = match (config_path.exists(), refresh) {
+            (false, _) if spec.is_some() => (Arc::new(ExecutionFlowConfig::default()), None),
+            (false, _) => (Arc::new(default_flow()), None),
+            (true, None) => {
+                let provider =
+                    CachedFileConfigProvider::new(ROOT_CONFIG_PATH, HANDLERS_CONFIG_NAME);
+                let flow: ExecutionFlowConfig =
+                    ConfigProvider::load(&provider).map_err(|e| e.to_string())?;
+                (Arc::new(flow), None)
+            }
+            (true, Some(interval)) => {
+                let provider = FileConfigProvider {
+                    base_path: ROOT_CONFIG_PATH.to_string(),
+                    config_name: HANDLERS_CONFIG_NAME.to_string(),
+                };
+                let policy = RefreshPolicy::VersionCheck {
+                    interval,
+                    version: Box::new(FileVersion { path: config_path }),
+                };
+                let reloadable =
+                    ReloadableConfig::new(provider, policy).map_err(|e| e.to_string())?;
+                (reloadable.get(), Some(reloadable))
+            }
+        };
+        let (router, handler_pool) =
+            create_router(&flow, spec.as_deref(), &factories, &status_flags)?;
+        let configs_changed = Arc::new(AtomicBool::new(false));
+        let changed = configs_changed.clone();
+        ConfigRegistry::global().subscribe(None, move |_| changed.store(true, Ordering::Release));
+        Ok(Self {
+            factories,
+            status_flags,
+            spec,
+            flow: reloadable,
+            config_refresh: refresh.map(|interval| (interval, Mutex::new(Instant::now()))),
+            configs_changed,
+            current: RwLock::new(BuiltRouter {
+                events: flow.events.clone(),
+                warmup: flow.warmup.clone(),
+                flow,
+                router: Arc::new(router),
+                handler_pool,
+            }),
+        })
+    }
+
+    fn handler_pool(&self) -> Arc<HandlerPool> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handler_pool
+            .clone()
+    }
+
+    fn event_routes(&self) -> EventRoutes {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .events
+            .clone()
+    }
+
+    fn warmup(&self) -> WarmupConfig {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .warmup
+            .clone()
+    }
+
+    /* reloads the handler configs once the refresh interval has passed since they last were */
+    fn reload_configs(&self) {
+        let Some((interval, reloaded_at)) = &self.config_refresh else {
+            return;
+        };
+        {
+            let mut reloaded_at = reloaded_at.lock().unwrap_or_else(PoisonError::into_inner);
+            if reloaded_at.elapsed() < *interval {
+                return;
+            }
+            *reloaded_at = Instant::now();
+        }
+        /* the files were cached when the configs were first loaded */
+        CachedFileConfigProvider::clear_cache();
+        for (name, e) in ConfigRegistry::global().reload_all() {
+            tracing::error!("Config {} could not be reloaded, keeping the old one: {}", name, e);
+        }
+    }
+
+    async fn router(&self) -> Arc<LambdaGateway> {
+        self.reload_configs();
+        let flow = {
+            let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+            let flow = match &self.flow {
+                Some(flow) => flow.get(),
+                None => current.flow.clone(),
+            };
+            let configs_changed = self.configs_changed.swap(false, Ordering::AcqRel);
+            if Arc::ptr_eq(&flow, &current.flow) && !configs_changed {
+                return current.router.clone();
+            }
+            flow
+        };
+
+        let built = create_router(
+            &flow,
+            self.spec.as_deref(),
+            &self.factories,
+            &self.status_flags,
+        );
+        let (router, handler_pool) = match built {
+            Ok(built) => built,
+            Err(e) => {
+                tracing::error!("Changed execution flow or configs could not be built: {}", e);
+                let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+                /* don't try the same flow again on every invocation */
+                current.flow = flow;
+                return current.router.clone();
+            }
+        };
+        if let Err(e) = handler_pool.init().await {
+            tracing::error!("Handlers of the rebuilt router failed to start: {}", e);
+        }
+        let router = Arc::new(router);
+        let previous = std::mem::replace(
+            &mut *self.current.write().unwrap_or_else(PoisonError::into_inner),
+            BuiltRouter {
+                events: flow.events.clone(),
+                warmup: flow.warmup.clone(),
+                flow,
+                router: router.clone(),
+                handler_pool,
+            },
+        );
+        tracing::info!("Execution flow or handler configs changed, router rebuilt");
+        previous.handler_pool.shutdown().await;
+        router
+    }
+}
+
+/* routes one request, without its base path, answering with 404 or 405 when it has no route */
+async fn route(
+    gateway: &LambdaGateway,
+    mut request: IncomingLambdaRequest,
+    context: &Context,
+) -> ApiGatewayProxyResponse {
+    gateway.base_path.strip_request(&mut request);
+    let host = request_host(&request);
+    if let Some(response) = gateway.unmatched(&request, host.as_deref(), context) {
+        return response;
+    }
+    let routed = INVOCATION_CONTEXT.scope(context.clone(), gateway.router.route(request));
+    match REQUEST_HOST.scope(host, routed).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Request could not be routed: {}", e);
+            gateway.exception_handlers.handle(&ErrorContext {
+                status_class: None,
+                handler_name: None,
+                correlation_id: &context.request_id,
+                error: None,
+            })
+        }
+    }
 }
 
 async fn entry(
-    event: LambdaEvent<ApiGatewayProxyRequest>,
-    router: Arc<AwsLambdaRouter>,
-) -> Result<ApiGatewayProxyResponse, Error> {
-    let request = event.payload;
+    event: LambdaEvent<Value>,
+    routers: Arc<RouterCache>,
+    payload_format: PayloadFormat,
+) -> Result<EventResponse, Error> {
     let context = event.context;
-    match router.route(request).await {
-        Ok(response) => Ok(response),
-        Err(e) => {
-            let mut response = ApiGatewayProxyResponse::default();
-            response.body = Some(Body::Text(format!("Error: {}", e)));
-            Ok(response)
+    let warmup = routers.warmup();
+    if let Some(ping) = warmup.detect(&event.payload) {
+        if warmup.prewarm
+            && let Err(e) = routers.handler_pool().warm_up().await
+        {
+            tracing::error!("Warm-up failed: {}", e);
+        }
+        return Ok(ping.response(&event.payload, payload_format));
+    }
+    let router = routers.router().await;
+    let process = |request| route(&router, request, &context);
+    match EventKind::detect(&event.payload) {
+        EventKind::Gateway => {}
+        EventKind::Authorizer => {
+            let (request, result) = AuthorizerRequest::parse(event.payload)?.into_proxy_request();
+            let response = AUTHORIZE_ONLY.scope(true, process(request)).await;
+            return Ok(EventResponse::Authorizer(result.respond(response)?));
+        }
+        EventKind::Sqs => {
+            let batch: SqsEvent = serde_json::from_value(event.payload)?;
+            let response = run_batch(batch, &routers.event_routes(), process).await;
+            return Ok(EventResponse::Sqs(response));
+        }
+        EventKind::Sns => {
+            let notifications: SnsEvent = serde_json::from_value(event.payload)?;
+            run_notifications(notifications, &routers.event_routes(), process).await?;
+            return Ok(EventResponse::Empty);
         }
+        EventKind::EventBridge => {
+            let event: EventBridgeEvent<Value> = serde_json::from_value(event.payload)?;
+            run_event(event, &routers.event_routes(), process).await?;
+            return Ok(EventResponse::Empty);
+        }
+    }
+
+    let (request, payload_format) =
+        GatewayRequest::parse(event.payload, payload_format)?.into_proxy_request();
+    let response = route(&router, request, &context).await;
+    Ok(EventResponse::Gateway(Box::new(GatewayResponse::new(response, payload_format))))
+}
+
+fn init_subscriber() {
+    #[cfg(feature = "otel")]
+    match telemetry::init_subscriber() {
+        Ok(()) => return,
+        Err(e) => eprintln!("OpenTelemetry could not be set up, spans are not exported: {}", e),
     }
+    init_default_subscriber();
 }
 
 fn main() -> Result<(), Error> {
-    let router = Arc::new(create_router());
+    let routers = Arc::new(
+        RouterCache::new(HandlerFactories::with_builtin(), StatusFlags::new()).unwrap(),
+    );
+    let payload_format = PayloadFormat::from_env().unwrap();
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
         .block_on(async {
-            init_default_subscriber();
-            lambda_runtime::run(service_fn(|event| entry(event, router.clone()))).await
+            init_subscriber();
+            if let Err(e) = metrics::init_from_env() {
+                tracing::error!("Metrics are not recorded: {}", e);
+            }
+            let audit_config: Config<AuditConfig> = Config::new(DefaultConfigProvider).unwrap();
+            audit::init(audit_config.get());
+            if let Err(e) = routers.handler_pool().init().await {
+                tracing::error!("{}", e);
+            }
+            let result =
+                lambda_runtime::run(service_fn(|event| {
+                    let routers = routers.clone();
+                    async move {
+                        let response = entry(event, routers, payload_format).await;
+                        #[cfg(feature = "otel")]
+                        telemetry::flush();
+                        response
+                    }
+                }))
+                .await;
+            routers.handler_pool().shutdown().await;
+            audit::shutdown().await;
+            #[cfg(feature = "otel")]
+            telemetry::shutdown();
+            result
         })
 }
@@ -0,0 +1,98 @@
+use serde_json::{json, Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::metrics::{Dimensions, MetricsBackend, Unit};
+
+/// Writes every metric as a CloudWatch embedded metric format (EMF) line to stdout, where the
+/// Lambda log agent turns it into a CloudWatch metric.
+pub struct EmfMetrics {
+    namespace: String,
+}
+
+impl EmfMetrics {
+    pub fn new(namespace: impl Into<String>) -> Self {
+        Self {
+            namespace: namespace.into(),
+        }
+    }
+
+    fn document(
+        &self,
+        name: &str,
+        value: Value,
+        unit: Unit,
+        dimensions: Dimensions<'_>,
+        timestamp: u128,
+    ) -> Value {
+        let dimension_names: Vec<&str> = dimensions.iter().map(|(name, _)| *name).collect();
+        let mut document = Map::new();
+        document.insert(
+            "_aws".to_string(),
+            json!({
+                "Timestamp": timestamp as u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": self.namespace,
+                    "Dimensions": [dimension_names],
+                    "Metrics": [{ "Name": name, "Unit": unit.as_str() }]
+                }]
+            }),
+        );
+        for (dimension, dimension_value) in dimensions {
+            document.insert(dimension.to_string(), json!(dimension_value));
+        }
+        document.insert(name.to_string(), value);
+        Value::Object(document)
+    }
+
+    fn write(&self, name: &str, value: Value, unit: Unit, dimensions: Dimensions<'_>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        println!("{}", self.document(name, value, unit, dimensions, timestamp));
+    }
+}
+
+impl MetricsBackend for EmfMetrics {
+    fn counter(&self, name: &str, value: u64, dimensions: Dimensions<'_>) {
+        self.write(name, json!(value), Unit::Count, dimensions);
+    }
+
+    fn histogram(&self, name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>) {
+        self.write(name, json!(value), unit, dimensions);
+    }
+
+    fn gauge(&self, name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>) {
+        self.write(name, json!(value), unit, dimensions);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::emf::EmfMetrics;
+    use crate::metrics::Unit;
+    use serde_json::json;
+
+    #[test]
+    fn test_emf_document() {
+        let metrics = EmfMetrics::new("gateway");
+        let dimensions = [("route", "/pets/{id}"), ("status_code", "200")];
+        let document =
+            metrics.document("RequestDuration", json!(12.5), Unit::Milliseconds, &dimensions, 1000);
+        assert_eq!(
+            document,
+            json!({
+                "_aws": {
+                    "Timestamp": 1000,
+                    "CloudWatchMetrics": [{
+                        "Namespace": "gateway",
+                        "Dimensions": [["route", "status_code"]],
+                        "Metrics": [{ "Name": "RequestDuration", "Unit": "Milliseconds" }]
+                    }]
+                },
+                "route": "/pets/{id}",
+                "status_code": "200",
+                "RequestDuration": 12.5
+            })
+        );
+    }
+}
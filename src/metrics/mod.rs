@@ -0,0 +1,122 @@
+use std::sync::OnceLock;
+use crate::metrics::registry::Registry;
+
+pub mod emf;
+pub mod registry;
+
+/* "emf" to write CloudWatch embedded metrics, "none" (the default) to drop them */
+pub const METRICS_BACKEND_ENV_VAR: &str = "IDEM_METRICS";
+/* the CloudWatch namespace of the embedded metrics */
+pub const METRICS_NAMESPACE_ENV_VAR: &str = "IDEM_METRICS_NAMESPACE";
+const DEFAULT_NAMESPACE: &str = "idem-serverless";
+
+/// Names of the metrics the gateway records.
+pub mod names {
+    /* every request that ran a chain, by route and status code */
+    pub const REQUESTS: &str = "Requests";
+    pub const REQUEST_DURATION: &str = "RequestDuration";
+    /* requests over their latency budget, by route and what went over */
+    pub const SLOW_REQUESTS: &str = "SlowRequests";
+    /* by handler */
+    pub const HANDLER_DURATION: &str = "HandlerDuration";
+    pub const HANDLER_ERRORS: &str = "HandlerErrors";
+    /* by target function */
+    pub const PROXY_LATENCY: &str = "ProxyLatency";
+    pub const VALIDATION_FAILURES: &str = "ValidationFailures";
+    pub const AUTH_REJECTIONS: &str = "AuthRejections";
+    /* requests over their client's rate limit, by route */
+    pub const RATE_LIMITED: &str = "RateLimited";
+    pub const JWKS_REFRESHES: &str = "JwksRefreshes";
+    /* the process-wide cache of config files */
+    pub const CONFIG_CACHE_HITS: &str = "ConfigCacheHits";
+    pub const CONFIG_CACHE_MISSES: &str = "ConfigCacheMisses";
+    pub const CONFIG_CACHE_SIZE: &str = "ConfigCacheSize";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Count,
+    Milliseconds,
+    Bytes,
+    None,
+}
+
+impl Unit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Count => "Count",
+            Unit::Milliseconds => "Milliseconds",
+            Unit::Bytes => "Bytes",
+            Unit::None => "None",
+        }
+    }
+}
+
+/// Dimensions of a metric, as name and value.
+pub type Dimensions<'a> = &'a [(&'a str, &'a str)];
+
+/// Where metrics end up. Counters add up, histograms record one observation each, gauges
+/// record the current value.
+pub trait MetricsBackend: Send + Sync {
+    fn counter(&self, name: &str, value: u64, dimensions: Dimensions<'_>);
+
+    fn histogram(&self, name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>);
+
+    fn gauge(&self, name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>);
+}
+
+/// Drops every metric.
+pub struct NoopMetrics;
+
+impl MetricsBackend for NoopMetrics {
+    fn counter(&self, _: &str, _: u64, _: Dimensions<'_>) {}
+
+    fn histogram(&self, _: &str, _: f64, _: Unit, _: Dimensions<'_>) {}
+
+    fn gauge(&self, _: &str, _: f64, _: Unit, _: Dimensions<'_>) {}
+}
+
+static BACKEND: OnceLock<Box<dyn MetricsBackend>> = OnceLock::new();
+
+/// Sets the backend every metric goes to. Only the first call has an effect; until then
+/// metrics are dropped.
+pub fn set_backend(backend: Box<dyn MetricsBackend>) {
+    let _ = BACKEND.set(backend);
+}
+
+/// Sets the backend named by `IDEM_METRICS`.
+pub fn init_from_env() -> Result<(), String> {
+    match std::env::var(METRICS_BACKEND_ENV_VAR).as_deref() {
+        Err(_) | Ok("none") => set_backend(Box::new(NoopMetrics)),
+        Ok("emf") => {
+            let namespace = std::env::var(METRICS_NAMESPACE_ENV_VAR)
+                .unwrap_or_else(|_| DEFAULT_NAMESPACE.to_string());
+            set_backend(Box::new(emf::EmfMetrics::new(namespace)));
+        }
+        Ok(other) => return Err(format!("Unknown metrics backend {}", other)),
+    }
+    Ok(())
+}
+
+fn backend() -> &'static dyn MetricsBackend {
+    match BACKEND.get() {
+        Some(backend) => backend.as_ref(),
+        None => &NoopMetrics,
+    }
+}
+
+/* every metric also goes to the registry, which the MetricsHandler reports */
+pub fn counter(name: &str, value: u64, dimensions: Dimensions<'_>) {
+    Registry::global().counter(name, value, dimensions);
+    backend().counter(name, value, dimensions);
+}
+
+pub fn histogram(name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>) {
+    Registry::global().histogram(name, value, dimensions);
+    backend().histogram(name, value, unit, dimensions);
+}
+
+pub fn gauge(name: &str, value: f64, unit: Unit, dimensions: Dimensions<'_>) {
+    Registry::global().gauge(name, value, dimensions);
+    backend().gauge(name, value, unit, dimensions);
+}
@@ -0,0 +1,187 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use crate::metrics::Dimensions;
+
+/// The current value of one metric with one set of dimensions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Series {
+    Counter(u64),
+    Gauge(f64),
+    /* observations and their total */
+    Histogram { count: u64, sum: f64 },
+}
+
+type SeriesKey = (String, Vec<(String, String)>);
+
+/// Every metric recorded since the container started, whatever the backend does with them, for
+/// the gateway to report on itself.
+#[derive(Default)]
+pub struct Registry {
+    series: Mutex<BTreeMap<SeriesKey, Series>>,
+}
+
+fn key(name: &str, dimensions: Dimensions<'_>) -> SeriesKey {
+    let mut dimensions: Vec<(String, String)> = dimensions
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    dimensions.sort();
+    (name.to_string(), dimensions)
+}
+
+impl Registry {
+    pub fn global() -> &'static Registry {
+        static REGISTRY: OnceLock<Registry> = OnceLock::new();
+        REGISTRY.get_or_init(Registry::default)
+    }
+
+    fn update(&self, name: &str, dimensions: Dimensions<'_>, update: impl FnOnce(&mut Series)) {
+        let mut series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = series.entry(key(name, dimensions));
+        update(entry.or_insert(Series::Counter(0)));
+    }
+
+    pub fn counter(&self, name: &str, value: u64, dimensions: Dimensions<'_>) {
+        self.update(name, dimensions, |series| match series {
+            Series::Counter(count) => *count += value,
+            other => *other = Series::Counter(value),
+        });
+    }
+
+    pub fn gauge(&self, name: &str, value: f64, dimensions: Dimensions<'_>) {
+        self.update(name, dimensions, |series| *series = Series::Gauge(value));
+    }
+
+    pub fn histogram(&self, name: &str, value: f64, dimensions: Dimensions<'_>) {
+        self.update(name, dimensions, |series| match series {
+            Series::Histogram { count, sum } => {
+                *count += 1;
+                *sum += value;
+            }
+            other => {
+                *other = Series::Histogram {
+                    count: 1,
+                    sum: value,
+                }
+            }
+        });
+    }
+
+    fn snapshot(&self) -> BTreeMap<SeriesKey, Series> {
+        self.series
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The Prometheus text exposition of every series, e.g. `requests_total{route="/pets"} 3`.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let mut last_name = None;
+        for ((name, dimensions), series) in self.snapshot() {
+            let metric = snake_case(&name);
+            let labels = labels(&dimensions);
+            if last_name.as_ref() != Some(&name) {
+                let kind = match series {
+                    Series::Counter(_) => "counter",
+                    Series::Gauge(_) => "gauge",
+                    Series::Histogram { .. } => "summary",
+                };
+                let _ = writeln!(text, "# TYPE {} {}", metric, kind);
+                last_name = Some(name.clone());
+            }
+            let _ = match series {
+                Series::Counter(count) => writeln!(text, "{}_total{} {}", metric, labels, count),
+                Series::Gauge(value) => writeln!(text, "{}{} {}", metric, labels, value),
+                Series::Histogram { count, sum } => {
+                    let _ = writeln!(text, "{}_count{} {}", metric, labels, count);
+                    writeln!(text, "{}_sum{} {}", metric, labels, sum)
+                }
+            };
+        }
+        text
+    }
+
+    /// Every series by metric name, with its dimensions next to its value.
+    pub fn to_json(&self) -> Value {
+        let mut metrics = Map::new();
+        for ((name, dimensions), series) in self.snapshot() {
+            let mut entry: Map<String, Value> = dimensions
+                .into_iter()
+                .map(|(name, value)| (name, json!(value)))
+                .collect();
+            match series {
+                Series::Counter(count) => entry.insert("count".into(), json!(count)),
+                Series::Gauge(value) => entry.insert("value".into(), json!(value)),
+                Series::Histogram { count, sum } => {
+                    entry.insert("count".into(), json!(count));
+                    entry.insert("sum".into(), json!(sum))
+                }
+            };
+            let values = metrics.entry(name).or_insert_with(|| json!([]));
+            if let Value::Array(values) = values {
+                values.push(Value::Object(entry));
+            }
+        }
+        Value::Object(metrics)
+    }
+}
+
+/* `RequestDuration` becomes `request_duration` */
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+fn labels(dimensions: &[(String, String)]) -> String {
+    if dimensions.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = dimensions
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::registry::Registry;
+    use serde_json::json;
+
+    #[test]
+    fn test_registry_exposition() {
+        let registry = Registry::default();
+        registry.counter("Requests", 1, &[("status_code", "200"), ("route", "/pets")]);
+        registry.counter("Requests", 2, &[("route", "/pets"), ("status_code", "200")]);
+        registry.histogram("ProxyLatency", 10.0, &[("function", "pets")]);
+        registry.histogram("ProxyLatency", 30.0, &[("function", "pets")]);
+        registry.gauge("ConfigCacheSize", 4.0, &[]);
+
+        assert_eq!(
+            registry.to_prometheus(),
+            "# TYPE config_cache_size gauge\n\
+             config_cache_size 4\n\
+             # TYPE proxy_latency summary\n\
+             proxy_latency_count{function=\"pets\"} 2\n\
+             proxy_latency_sum{function=\"pets\"} 40\n\
+             # TYPE requests counter\n\
+             requests_total{route=\"/pets\",status_code=\"200\"} 3\n"
+        );
+        assert_eq!(
+            registry.to_json()["Requests"],
+            json!([{ "route": "/pets", "status_code": "200", "count": 3 }])
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use lambda_http::http::HeaderMap;
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/* the standard variable, the service is called after the function when it isn't set */
+const SERVICE_NAME_ENV_VAR: &str = "OTEL_SERVICE_NAME";
+const FUNCTION_NAME_ENV_VAR: &str = "AWS_LAMBDA_FUNCTION_NAME";
+const TRACER_NAME: &str = "idem-serverless";
+
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Installs a subscriber that logs like the default one and exports spans over OTLP. The
+/// exporter is configured with the standard `OTEL_EXPORTER_OTLP_*` variables, e.g.
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` for the collector and `OTEL_EXPORTER_OTLP_HEADERS` for its
+/// credentials.
+pub fn init_subscriber() -> Result<(), String> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let service_name = std::env::var(SERVICE_NAME_ENV_VAR)
+        .or_else(|_| std::env::var(FUNCTION_NAME_ENV_VAR))
+        .unwrap_or_else(|_| TRACER_NAME.to_string());
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+    let tracer = provider.tracer(TRACER_NAME);
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer().without_time())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    let _ = TRACER_PROVIDER.set(provider);
+    Ok(())
+}
+
+/// Exports the spans of the invocation; the environment may be frozen right after it returns.
+pub fn flush() {
+    if let Some(provider) = TRACER_PROVIDER.get()
+        && let Err(e) = provider.force_flush()
+    {
+        tracing::warn!("Spans could not be exported: {}", e);
+    }
+}
+
+pub fn shutdown() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        let _ = provider.shutdown();
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Continues the trace of the caller, given by the `traceparent` header, in `span`.
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+    let parent =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    let _ = span.set_parent(parent);
+}
@@ -0,0 +1,358 @@
+use idemio::config::{ConfigProvider, FileConfigProvider};
+use idemio::exchange::Attachments;
+use idemio::router::executor::HandlerExecutor;
+use idemio::router::factory::{ExchangeFactory, RouteInfo};
+use idemio::router::path::PathMatcher;
+use lambda_http::aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use lambda_http::http::header::CONTENT_TYPE;
+use lambda_http::http::{HeaderName, HeaderValue, Method};
+use lambda_http::{Body, Context};
+use serde_json::Value;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::executor::flags::StatusFlags;
+use crate::flow::matcher::{request_host, REQUEST_HOST};
+use crate::flow::registry::HandlerFactories;
+use crate::flow::ExecutionFlowConfig;
+use crate::handler::attachments::ScopedAttachmentsExt;
+use crate::handler::LambdaExchange;
+use crate::{
+    create_router_parts, unmatched_response, LambdaExchangeFactory, RouterParts,
+    HANDLERS_CONFIG_NAME, INVOCATION_CONTEXT,
+};
+
+/* the invocation time a test request gets before its handlers time out */
+const TEST_INVOCATION_TIMEOUT: Duration = Duration::from_secs(30);
+const TEST_REQUEST_ID: &str = "test-request";
+
+/// Builds the API Gateway events test requests are made of.
+pub struct RequestBuilder {
+    request: ApiGatewayProxyRequest,
+}
+
+impl RequestBuilder {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        let mut request = ApiGatewayProxyRequest {
+            path: Some(path.into()),
+            http_method: method.clone(),
+            ..Default::default()
+        };
+        request.request_context.http_method = method;
+        request.request_context.request_id = Some(TEST_REQUEST_ID.to_string());
+        Self { request }
+    }
+
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new(Method::GET, path)
+    }
+
+    pub fn post(path: impl Into<String>) -> Self {
+        Self::new(Method::POST, path)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.request.headers.insert(
+            HeaderName::try_from(name).expect("invalid header name"),
+            HeaderValue::from_str(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    pub fn host(self, host: &str) -> Self {
+        self.header("host", host)
+    }
+
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        let mut query = self.request.multi_value_query_string_parameters.to_query_string();
+        if !query.is_empty() {
+            query.push('&');
+        }
+        query.push_str(&format!("{}={}", name, value));
+        let query: QueryMap = query.parse().unwrap_or_default();
+        self.request.query_string_parameters = query.clone();
+        self.request.multi_value_query_string_parameters = query;
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.request.body = Some(body.into());
+        self
+    }
+
+    pub fn json(self, body: &Value) -> Self {
+        self.header(CONTENT_TYPE.as_str(), "application/json")
+            .body(body.to_string())
+    }
+
+    pub fn build(self) -> ApiGatewayProxyRequest {
+        self.request
+    }
+}
+
+/// Runs requests through the chains of an execution flow in-process, the way the function
+/// does, without AWS. Handlers are built by the given factories, so tests can swap in their own.
+pub struct TestGateway {
+    parts: RouterParts,
+    factory: LambdaExchangeFactory,
+}
+
+impl TestGateway {
+    pub fn new(flow: &ExecutionFlowConfig, factories: &HandlerFactories) -> Result<Self, String> {
+        let parts = create_router_parts(flow, None, factories, &StatusFlags::new())?;
+        let factory = LambdaExchangeFactory::new(&parts);
+        Ok(Self { parts, factory })
+    }
+
+    /// Loads `handlers.json` from `config_dir`, like the function does from its config layer.
+    pub fn from_dir(
+        config_dir: impl AsRef<Path>,
+        factories: &HandlerFactories,
+    ) -> Result<Self, String> {
+        let provider = FileConfigProvider {
+            base_path: config_dir.as_ref().display().to_string(),
+            config_name: HANDLERS_CONFIG_NAME.to_string(),
+        };
+        let flow: ExecutionFlowConfig =
+            ConfigProvider::load(&provider).map_err(|e| e.to_string())?;
+        Self::new(&flow, factories)
+    }
+
+    fn context() -> Context {
+        let deadline = SystemTime::now() + TEST_INVOCATION_TIMEOUT;
+        let mut context = Context::default();
+        context.request_id = TEST_REQUEST_ID.to_string();
+        context.deadline = deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        context
+    }
+
+    pub async fn send(&self, mut request: ApiGatewayProxyRequest) -> TestResponse {
+        let context = Self::context();
+        self.parts.base_path.strip_request(&mut request);
+        let host = request_host(&request);
+        let unmatched = unmatched_response(
+            &self.factory.templates,
+            &self.parts.exception_handlers,
+            &request,
+            host.as_deref(),
+            &context,
+        );
+        if let Some(response) = unmatched {
+            return TestResponse {
+                response,
+                exchange: None,
+            };
+        }
+
+        let route_info = RouteInfo {
+            path: request.path.as_deref(),
+            method: Some(request.http_method.as_str()),
+        };
+        let chain = REQUEST_HOST
+            .sync_scope(host, || self.parts.matcher.lookup(route_info))
+            .expect("a matched request has a chain");
+        let mut exchange = INVOCATION_CONTEXT
+            .scope(context, self.factory.create_exchange(request))
+            .await
+            .expect("exchange could not be created");
+        let response = self
+            .parts
+            .executor
+            .execute_handlers(chain, &mut exchange)
+            .await
+            .expect("handler chain failed");
+        TestResponse {
+            response,
+            exchange: Some(exchange),
+        }
+    }
+}
+
+/// The response of a test request, with the exchange it was produced by when a chain ran.
+pub struct TestResponse {
+    pub response: ApiGatewayProxyResponse,
+    pub exchange: Option<LambdaExchange>,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> i64 {
+        self.response.status_code
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    pub fn body_text(&self) -> Option<String> {
+        match &self.response.body {
+            Some(Body::Text(body)) => Some(body.clone()),
+            Some(Body::Binary(body)) => String::from_utf8(body.clone()).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn body_json(&self) -> Value {
+        let body = self.body_text().expect("response has no body");
+        serde_json::from_str(&body).expect("response body is not JSON")
+    }
+
+    pub fn attachments(&self) -> &Attachments {
+        self.exchange
+            .as_ref()
+            .expect("no chain ran for the request")
+            .attachments()
+    }
+
+    pub fn well_known<T: Send + 'static>(&self, key: &str) -> Option<&T> {
+        self.attachments().well_known().get::<T>(key)
+    }
+
+    pub fn assert_status(&self, expected: i64) -> &Self {
+        assert_eq!(
+            self.status(),
+            expected,
+            "unexpected status, response body: {:?}",
+            self.body_text()
+        );
+        self
+    }
+
+    pub fn assert_header(&self, name: &str, expected: &str) -> &Self {
+        assert_eq!(self.header(name), Some(expected), "unexpected {} header", name);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::registry::ConfigRegistry;
+    use crate::flow::builder::{ChainBuilder, NamedHandler};
+    use crate::flow::matcher::route_metadata;
+    use crate::flow::registry::HandlerFactories;
+    use crate::handler::attachments::ScopedAttachmentsExt;
+    use crate::handler::traceability::TraceabilityHandlerConfig;
+    use crate::handler::LambdaExchange;
+    use crate::testkit::{RequestBuilder, TestGateway};
+    use async_trait::async_trait;
+    use idemio::handler::Handler;
+    use idemio::status::{ExchangeState, HandlerStatus};
+    use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_http::Body;
+    use serde_json::json;
+    use std::convert::Infallible;
+
+    struct GreetingHandler;
+
+    impl NamedHandler for GreetingHandler {
+        const NAME: &'static str = "GreetingHandler";
+    }
+
+    #[async_trait]
+    impl Handler<LambdaExchange> for GreetingHandler {
+        async fn exec(&self, exchange: &mut LambdaExchange) -> Result<HandlerStatus, Infallible> {
+            let name = exchange
+                .input()
+                .await
+                .ok()
+                .and_then(|request| request.query_string_parameters.first("name"))
+                .unwrap_or("world")
+                .to_string();
+            exchange
+                .attachments_mut()
+                .well_known_mut()
+                .add::<String>("greeted", name.clone());
+            exchange.set_output(ApiGatewayProxyResponse {
+                status_code: 200,
+                body: Some(Body::Text(json!({ "hello": name }).to_string())),
+                ..Default::default()
+            });
+            Ok(HandlerStatus::new(ExchangeState::OK))
+        }
+
+        fn name(&self) -> &str {
+            Self::NAME
+        }
+    }
+
+    fn factories() -> HandlerFactories {
+        let mut factories = HandlerFactories::new();
+        factories.register(GreetingHandler::NAME, || Box::new(GreetingHandler));
+        factories
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_gateway_runs_chains() {
+        let flow = ChainBuilder::new()
+            .route("/greet")
+            .get()
+            .termination_handler::<GreetingHandler>()
+            .end_method()
+            .end_route()
+            .build();
+        let gateway = TestGateway::new(&flow, &factories()).unwrap();
+
+        let request = RequestBuilder::get("/greet")
+            .host("api.example.com")
+            .query("name", "idem")
+            .build();
+        let response = gateway.send(request).await;
+        response.assert_status(200);
+        assert_eq!(response.body_json()["hello"], "idem");
+        assert_eq!(response.well_known::<String>("greeted").unwrap(), "idem");
+
+        let request = RequestBuilder::post("/greet").json(&json!({})).build();
+        gateway
+            .send(request)
+            .await
+            .assert_status(405)
+            .assert_header("allow", "GET");
+        gateway.send(RequestBuilder::get("/missing").build()).await.assert_status(404);
+    }
+
+    #[test]
+    fn test_builtin_configs_registered() {
+        assert!(TestGateway::from_dir("./config", &HandlerFactories::with_builtin()).is_ok());
+        let registry = ConfigRegistry::global();
+        for handler in [
+            "TraceabilityHandler",
+            "JwtValidationHandler",
+            "LambdaProxyHandler",
+            "HealthCheckHandler",
+        ] {
+            assert!(registry.version(handler).is_some(), "{} is not registered", handler);
+        }
+        let traceability = registry.get_named::<TraceabilityHandlerConfig>("TraceabilityHandler");
+        assert!(traceability.is_some());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_gateway_from_dir() {
+        let config_dir = std::env::temp_dir().join("idem-testkit-from-dir");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        let flow = json!({
+            "handlers": ["GreetingHandler"],
+            "paths": {
+                "/greet": {
+                    "method": "GET",
+                    "exec": ["GreetingHandler"],
+                    "metadata": { "team": "core", "tier": 1 }
+                }
+            }
+        });
+        std::fs::write(config_dir.join("handlers.json"), flow.to_string()).unwrap();
+
+        let gateway = TestGateway::from_dir(&config_dir, &factories()).unwrap();
+        let response = gateway.send(RequestBuilder::get("/greet").build()).await;
+        response.assert_status(200);
+        assert_eq!(response.body_json()["hello"], "world");
+        let metadata = route_metadata(response.exchange.as_ref().unwrap()).unwrap();
+        assert_eq!(metadata["team"], "core");
+        assert_eq!(metadata["tier"], 1);
+    }
+}